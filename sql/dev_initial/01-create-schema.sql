@@ -1,3 +1,14 @@
+-- Fuzzy (trigram) and accent insensitive search.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE EXTENSION IF NOT EXISTS unaccent;
+
+-- unaccent() is only STABLE (its dictionary could change), pinning the
+-- dictionary makes it usable in indexes.
+CREATE OR REPLACE FUNCTION public.f_unaccent(text)
+RETURNS text
+LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT AS
+$$ SELECT public.unaccent('public.unaccent', $1) $$;
+
 DROP SCHEMA IF EXISTS consts;
 CREATE SCHEMA IF NOT EXISTS consts;
 
@@ -83,7 +94,10 @@ CREATE TABLE IF NOT EXISTS
         pwd VARCHAR(256),
         pwd_salt uuid NOT NULL DEFAULT gen_random_uuid(),
         token_salt uuid NOT NULL DEFAULT gen_random_uuid(),
+        -- When the pwd was last changed, for the pwd expiry.
+        pwd_mtime timestamp with time zone NOT NULL default now(),
         assigned_role VARCHAR(50),
+        is_service BOOLEAN NOT NULL DEFAULT FALSE,
         is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
         cid bigint NOT NULL,
         ctime timestamp with time zone NOT NULL default now(),
@@ -125,22 +139,6 @@ CREATE TABLE IF NOT EXISTS
         FOREIGN KEY (user_id) REFERENCES "user" (id)
     );
 
-DROP TABLE IF EXISTS public.document_comment cascade;
-CREATE TABLE IF NOT EXISTS
-    public.document_comment (
-        id BIGSERIAL PRIMARY KEY,
-        document_id BIGINT NOT NULL,
-        text VARCHAR(250) NOT NULL,
-        user_id BIGINT NOT NULL,
-        is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
-        cid bigint NOT NULL,
-        ctime timestamp with time zone NOT NULL default now(),
-        mid bigint NOT NULL,
-        mtime timestamp with time zone NOT NULL default now(),
-        FOREIGN KEY (document_id) REFERENCES document(id),
-        FOREIGN KEY (user_id) REFERENCES "user" (id)
-    );
-
 DROP TABLE IF EXISTS public.separator cascade;
 CREATE TABLE IF NOT EXISTS
     public.separator (
@@ -177,6 +175,9 @@ CREATE TABLE IF NOT EXISTS
         FOREIGN KEY (last_edit_user) REFERENCES "user" (id)
     );
 
+CREATE INDEX IF NOT EXISTS value_value_trgm_idx
+    ON public.value USING gin (f_unaccent(lower(value)) gin_trgm_ops);
+
 DROP TABLE IF EXISTS public.document cascade;
 CREATE TABLE IF NOT EXISTS
     public.document (
@@ -199,11 +200,33 @@ CREATE TABLE IF NOT EXISTS
         FOREIGN KEY (last_edit_user) REFERENCES "user" (id)
     );
 
+CREATE INDEX IF NOT EXISTS document_name_trgm_idx
+    ON public.document USING gin (f_unaccent(lower(name)) gin_trgm_ops);
+
+DROP TABLE IF EXISTS public.document_comment cascade;
+CREATE TABLE IF NOT EXISTS
+    public.document_comment (
+        id BIGSERIAL PRIMARY KEY,
+        document_id BIGINT NOT NULL,
+        text VARCHAR(250) NOT NULL,
+        user_id BIGINT NOT NULL,
+        is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
+        cid bigint NOT NULL,
+        ctime timestamp with time zone NOT NULL default now(),
+        mid bigint NOT NULL,
+        mtime timestamp with time zone NOT NULL default now(),
+        FOREIGN KEY (document_id) REFERENCES document(id),
+        FOREIGN KEY (user_id) REFERENCES "user" (id)
+    );
+
+-- The access to the structures: a grant of a role to all its users, or an
+-- override of a user (which replaces the grant of its role).
 DROP TABLE IF EXISTS public.structure_privilege cascade;
 CREATE TABLE IF NOT EXISTS
     public.structure_privilege (
         id BIGSERIAL PRIMARY KEY,
-        project_id BIGINT,
+        project_id BIGINT NOT NULL,
+        user_id BIGINT,
         role_name VARCHAR(50),
         is_enabled BOOLEAN NOT NULL DEFAULT TRUE,
         is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
@@ -212,7 +235,11 @@ CREATE TABLE IF NOT EXISTS
         mid bigint NOT NULL,
         mtime timestamp with time zone NOT NULL  default now(),
         FOREIGN KEY (project_id) REFERENCES structure(id),
-        FOREIGN KEY (role_name) REFERENCES role(role_name)
+        FOREIGN KEY (user_id) REFERENCES "user"(id),
+        FOREIGN KEY (role_name) REFERENCES role(role_name),
+        CHECK ((user_id IS NULL) <> (role_name IS NULL)),
+        UNIQUE (project_id, user_id),
+        UNIQUE (project_id, role_name)
 );
 
 DROP TABLE IF EXISTS public.separator_privilege cascade;
@@ -231,6 +258,203 @@ CREATE TABLE IF NOT EXISTS
         FOREIGN KEY (role_name) REFERENCES role(role_name)
 );
 
+DROP TABLE IF EXISTS public.saved_search cascade;
+CREATE TABLE IF NOT EXISTS
+    public.saved_search (
+        id BIGSERIAL PRIMARY KEY,
+        name VARCHAR(50) NOT NULL,
+        owner BIGINT NOT NULL,
+        shared_role VARCHAR(50),
+        filters JSONB NOT NULL,
+        list_options JSONB,
+        is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
+        cid bigint NOT NULL,
+        ctime timestamp with time zone NOT NULL default now(),
+        mid bigint NOT NULL,
+        mtime timestamp with time zone NOT NULL  default now(),
+        FOREIGN KEY (owner) REFERENCES "user" (id),
+        FOREIGN KEY (shared_role) REFERENCES role(role_name)
+);
+
+DROP TABLE IF EXISTS public.api_key cascade;
+CREATE TABLE IF NOT EXISTS
+    public.api_key (
+        id BIGSERIAL PRIMARY KEY,
+        user_id BIGINT NOT NULL,
+        name VARCHAR(50) NOT NULL,
+        prefix VARCHAR(16) UNIQUE NOT NULL,
+        key_hash VARCHAR(256) NOT NULL,
+        key_salt uuid NOT NULL DEFAULT gen_random_uuid(),
+        role_name VARCHAR(50) NOT NULL,
+        project_ids BIGINT[],
+        expires_at timestamp with time zone NOT NULL,
+        revoked_at timestamp with time zone,
+        last_used_at timestamp with time zone,
+        -- Keys are revoked, not deleted (the column is for the audit trigger).
+        is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
+        cid bigint NOT NULL,
+        ctime timestamp with time zone NOT NULL default now(),
+        mid bigint NOT NULL,
+        mtime timestamp with time zone NOT NULL  default now(),
+        FOREIGN KEY (user_id) REFERENCES "user" (id),
+        FOREIGN KEY (role_name) REFERENCES role(role_name)
+);
+
+DROP TABLE IF EXISTS public.ip_allowlist cascade;
+CREATE TABLE IF NOT EXISTS
+    public.ip_allowlist (
+        id BIGSERIAL PRIMARY KEY,
+        -- Of a role or of a user, the users of a listed role (or listed
+        -- themselves) are only allowed from one of their ranges.
+        role_name VARCHAR(50),
+        user_id BIGINT,
+        cidr VARCHAR(50) NOT NULL,
+        description VARCHAR(256),
+        is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
+        cid bigint NOT NULL,
+        ctime timestamp with time zone NOT NULL default now(),
+        mid bigint NOT NULL,
+        mtime timestamp with time zone NOT NULL  default now(),
+        FOREIGN KEY (role_name) REFERENCES role(role_name),
+        FOREIGN KEY (user_id) REFERENCES "user" (id),
+        CHECK ((role_name IS NULL) <> (user_id IS NULL))
+);
+
+-- Login sessions, `token_id` is the ident of their web tokens, and
+-- `token_kind` the transport of those (`cookie` or `header`).
+DROP TABLE IF EXISTS public.session cascade;
+CREATE TABLE IF NOT EXISTS
+    public.session (
+        id BIGSERIAL PRIMARY KEY,
+        token_id uuid UNIQUE NOT NULL DEFAULT gen_random_uuid(),
+        token_kind VARCHAR(16) NOT NULL,
+        user_id BIGINT NOT NULL,
+        ip VARCHAR(64),
+        user_agent VARCHAR(512),
+        ctime timestamp with time zone NOT NULL default now(),
+        last_seen timestamp with time zone NOT NULL default now(),
+        revoked_at timestamp with time zone,
+        FOREIGN KEY (user_id) REFERENCES "user" (id)
+);
+
+-- Recent failed logins per username and per ip, for the login throttling.
+DROP TABLE IF EXISTS public.login_attempt cascade;
+CREATE TABLE IF NOT EXISTS
+    public.login_attempt (
+        id BIGSERIAL PRIMARY KEY,
+        scope VARCHAR(16) NOT NULL,
+        key VARCHAR(256) NOT NULL,
+        failures INT NOT NULL,
+        last_failure timestamp with time zone NOT NULL,
+        locked_until timestamp with time zone,
+        UNIQUE (scope, key)
+);
+
+-- The logins (failed ones too), logoffs, pwd and 2FA changes and lockouts of
+-- the users, with where they came from. `user_id` is null for a failed login
+-- of an unknown `username`.
+DROP TABLE IF EXISTS public.login_history cascade;
+CREATE TABLE IF NOT EXISTS
+    public.login_history (
+        id BIGSERIAL PRIMARY KEY,
+        user_id BIGINT,
+        username VARCHAR(50) NOT NULL,
+        kind VARCHAR(32) NOT NULL,
+        ip VARCHAR(64),
+        user_agent VARCHAR(512),
+        detail JSONB,
+        ctime timestamp with time zone NOT NULL default now(),
+        FOREIGN KEY (user_id) REFERENCES "user" (id)
+);
+
+-- Pwd reset requests, `token_id` is the ident of their (single use) tokens.
+DROP TABLE IF EXISTS public.password_reset cascade;
+CREATE TABLE IF NOT EXISTS
+    public.password_reset (
+        id BIGSERIAL PRIMARY KEY,
+        token_id uuid UNIQUE NOT NULL DEFAULT gen_random_uuid(),
+        user_id BIGINT NOT NULL,
+        ctime timestamp with time zone NOT NULL default now(),
+        used_at timestamp with time zone,
+        FOREIGN KEY (user_id) REFERENCES "user" (id)
+);
+
+-- The TOTP second factor of the users, enabled once a first code is verified.
+DROP TABLE IF EXISTS public.user_totp cascade;
+CREATE TABLE IF NOT EXISTS
+    public.user_totp (
+        user_id BIGINT PRIMARY KEY,
+        -- Encrypted with the TOTP_KEY.
+        secret_enc VARCHAR(256) NOT NULL,
+        -- Hashes of the not yet used recovery codes.
+        recovery_codes TEXT[] NOT NULL DEFAULT '{}',
+        last_used_step BIGINT,
+        enabled_at timestamp with time zone,
+        ctime timestamp with time zone NOT NULL default now(),
+        FOREIGN KEY (user_id) REFERENCES "user" (id)
+);
+
+-- The pending OpenID Connect logins, until the callback of the IdP.
+DROP TABLE IF EXISTS public.oidc_login cascade;
+CREATE TABLE IF NOT EXISTS
+    public.oidc_login (
+        state VARCHAR(64) PRIMARY KEY,
+        nonce VARCHAR(64) NOT NULL,
+        -- The PKCE verifier of the code exchange.
+        code_verifier VARCHAR(128) NOT NULL,
+        ctime timestamp with time zone NOT NULL default now()
+);
+
+-- The IdP accounts (OpenID Connect `iss` and `sub`) of the users.
+DROP TABLE IF EXISTS public.user_identity cascade;
+CREATE TABLE IF NOT EXISTS
+    public.user_identity (
+        id BIGSERIAL PRIMARY KEY,
+        user_id BIGINT NOT NULL,
+        issuer VARCHAR(256) NOT NULL,
+        subject VARCHAR(256) NOT NULL,
+        ctime timestamp with time zone NOT NULL default now(),
+        UNIQUE (issuer, subject),
+        FOREIGN KEY (user_id) REFERENCES "user" (id)
+);
+
+-- The rolled signing keys of the tokens (encrypted), in the order of their
+-- activation. A retired key keeps its row, without its secret.
+DROP TABLE IF EXISTS public.token_key cascade;
+CREATE TABLE IF NOT EXISTS
+    public.token_key (
+        id BIGSERIAL PRIMARY KEY,
+        kid VARCHAR(32) NOT NULL UNIQUE,
+        key_enc TEXT,
+        active_from timestamp with time zone NOT NULL,
+        ctime timestamp with time zone NOT NULL default now()
+);
+
+-- The last pwds of the users (hashed), which a new pwd cannot reuse.
+DROP TABLE IF EXISTS public.pwd_history cascade;
+CREATE TABLE IF NOT EXISTS
+    public.pwd_history (
+        id BIGSERIAL PRIMARY KEY,
+        user_id BIGINT NOT NULL,
+        pwd VARCHAR(256) NOT NULL,
+        ctime timestamp with time zone NOT NULL default now(),
+        FOREIGN KEY (user_id) REFERENCES "user" (id)
+);
+
+-- Emails to send, delivered by the smtp sender.
+DROP TABLE IF EXISTS public.email_outbox cascade;
+CREATE TABLE IF NOT EXISTS
+    public.email_outbox (
+        id BIGSERIAL PRIMARY KEY,
+        recipient VARCHAR(256) NOT NULL,
+        subject VARCHAR(256) NOT NULL,
+        body TEXT NOT NULL,
+        attempts INT NOT NULL DEFAULT 0,
+        last_error TEXT,
+        ctime timestamp with time zone NOT NULL default now(),
+        sent_at timestamp with time zone
+);
+
 DROP TABLE IF EXISTS public.event cascade;
 CREATE TABLE IF NOT EXISTS public.event (
     id BIGSERIAL PRIMARY KEY,
@@ -320,7 +544,16 @@ BEGIN
                 WHERE table_schema = 'public' AND table_type = 'BASE TABLE'
     LOOP
         -- Exclude the 'event' table and any other tables you don't want to audit
-        IF tbl.table_name NOT IN ('event') THEN
+        -- ('session' is updated on every request, its last_seen, and
+        -- 'login_attempt' on every failed login, 'password_reset' and
+        -- 'email_outbox' hold the reset tokens, 'pwd_history' the pwds and
+        -- 'user_totp' the TOTP secrets, 'oidc_login' the PKCE verifiers;
+        -- 'user_identity' links are logged as OIDC_LINK events, 'token_key'
+        -- rolls as TOKEN_KEY_ROLL events, and 'login_history' is a log itself).
+        IF tbl.table_name NOT IN ('event', 'session', 'login_attempt',
+                'password_reset', 'email_outbox', 'pwd_history', 'user_totp',
+                'oidc_login', 'user_identity', 'token_key',
+                'login_history') THEN
             trigger_name := tbl.table_name || '_audit_trigger';
             EXECUTE format('
                 CREATE TRIGGER %I
@@ -377,49 +610,49 @@ AFTER INSERT ON consts.privilege
 FOR EACH ROW
 EXECUTE FUNCTION associate_privilege_with_roles();
 
-CREATE OR REPLACE FUNCTION associate_user_with_structures()
+CREATE OR REPLACE FUNCTION associate_role_with_structures()
 RETURNS TRIGGER AS $$
 BEGIN
-    -- Insert an entry in structure_privilege for each existing structure when a new user is created,
-    -- only if the association does not already exist
-    INSERT INTO public.structure_privilege (project_id, user_id, is_enabled, is_deleted, cid, ctime, mid, mtime)
-    SELECT s.id, NEW.id, TRUE, FALSE, NEW.id, now(), NEW.id, now()
+    -- Insert a grant in structure_privilege of each existing structure when a new role is created,
+    -- only if the grant does not already exist (the users of the role inherit it)
+    INSERT INTO public.structure_privilege (project_id, role_name, is_enabled, is_deleted, cid, ctime, mid, mtime)
+    SELECT s.id, NEW.role_name, TRUE, FALSE, NEW.cid, now(), NEW.cid, now()
     FROM public.structure s
     WHERE NOT EXISTS (
         SELECT 1 FROM public.structure_privilege sp
-        WHERE sp.project_id = s.id AND sp.user_id = NEW.id
+        WHERE sp.project_id = s.id AND sp.role_name = NEW.role_name
     );
 
     RETURN NEW;
 END;
 $$ LANGUAGE plpgsql;
 
-CREATE TRIGGER user_structure_association
-AFTER INSERT ON public."user"
+CREATE TRIGGER role_structure_association
+AFTER INSERT ON public.role
 FOR EACH ROW
-EXECUTE FUNCTION associate_user_with_structures();
+EXECUTE FUNCTION associate_role_with_structures();
 
-CREATE OR REPLACE FUNCTION associate_structure_with_users()
+CREATE OR REPLACE FUNCTION associate_structure_with_roles()
 RETURNS TRIGGER AS $$
 BEGIN
-    -- Insert an entry in structure_privilege for each existing user when a new structure is created,
-    -- only if the association does not already exist
-    INSERT INTO public.structure_privilege (project_id, user_id, is_enabled, is_deleted,  cid, ctime, mid, mtime)
-    SELECT NEW.id, u.id, TRUE, FALSE, NEW.cid, now(), NEW.cid, now()
-    FROM public."user" u
+    -- Insert a grant in structure_privilege for each existing role when a new structure is created,
+    -- only if the grant does not already exist
+    INSERT INTO public.structure_privilege (project_id, role_name, is_enabled, is_deleted,  cid, ctime, mid, mtime)
+    SELECT NEW.id, r.role_name, TRUE, FALSE, NEW.cid, now(), NEW.cid, now()
+    FROM public.role r
     WHERE NOT EXISTS (
         SELECT 1 FROM public.structure_privilege sp
-        WHERE sp.project_id = NEW.id AND sp.user_id = u.id
+        WHERE sp.project_id = NEW.id AND sp.role_name = r.role_name
     );
 
     RETURN NEW;
 END;
 $$ LANGUAGE plpgsql;
 
-CREATE TRIGGER structure_user_association
+CREATE TRIGGER structure_role_association
 AFTER INSERT ON public.structure
 FOR EACH ROW
-EXECUTE FUNCTION associate_structure_with_users();
+EXECUTE FUNCTION associate_structure_with_roles();
 
 CREATE OR REPLACE FUNCTION enforce_admin_privilege()
 RETURNS TRIGGER AS $$
@@ -442,8 +675,9 @@ EXECUTE FUNCTION enforce_admin_privilege();
 CREATE OR REPLACE FUNCTION enforce_admin_structure_privilege()
 RETURNS TRIGGER AS $$
 BEGIN
-    -- Check if the user being inserted/updated has the "ADMIN" role
-    IF (SELECT assigned_role FROM public."user" WHERE id = NEW.user_id) = 'ADMIN' THEN
+    -- Check if the grant is of the "ADMIN" role, or the override of a user with it
+    IF NEW.role_name = 'ADMIN'
+        OR (SELECT assigned_role FROM public."user" WHERE id = NEW.user_id) = 'ADMIN' THEN
         NEW.is_enabled := TRUE;
     END IF;
 
@@ -1 +1,12 @@
-insert into "user" (username, correo) values ('demo1', 'demo1@uvg.edu.gt');
+-- Root user (the `Ctx::root_ctx()` user, at id 0)
+insert into "user" (id, username, email, cid, mid) values (0, 'root', 'root@uvg.edu.gt', 0, 0);
+
+-- Demo role
+insert into role (role_name, description, cid, mid) values ('demo', 'Demo role', 0, 0);
+update "user" set assigned_role = 'demo' where id = 0;
+
+-- User demo1
+insert into "user" (username, email, assigned_role, cid, mid) values ('demo1', 'demo1@uvg.edu.gt', 'demo', 0, 0);
+
+-- Index datatypes (the ids of the search `SearchDatatype`)
+insert into consts.datatype (id, datatype_name) values (1, 'TEXT'), (2, 'NUMERIC'), (3, 'DATE');
@@ -0,0 +1,128 @@
+-- Moves an existing database from the structure_privilege rows of the roles
+-- (and, when the table already has a user_id, of the users) to the role
+-- grants with per-user overrides of sql/dev_initial/01-create-schema.sql.
+-- Run once, as the app user, in a transaction.
+
+BEGIN;
+
+-- The old triggers of the users (on a user_id the table may not have yet).
+DROP TRIGGER IF EXISTS user_structure_association ON public."user";
+DROP FUNCTION IF EXISTS associate_user_with_structures();
+DROP TRIGGER IF EXISTS structure_user_association ON public.structure;
+DROP FUNCTION IF EXISTS associate_structure_with_users();
+
+ALTER TABLE public.structure_privilege
+    ADD COLUMN IF NOT EXISTS user_id BIGINT REFERENCES "user"(id);
+
+CREATE OR REPLACE FUNCTION enforce_admin_structure_privilege()
+RETURNS TRIGGER AS $$
+BEGIN
+    -- Check if the grant is of the "ADMIN" role, or the override of a user with it
+    IF NEW.role_name = 'ADMIN'
+        OR (SELECT assigned_role FROM public."user" WHERE id = NEW.user_id) = 'ADMIN' THEN
+        NEW.is_enabled := TRUE;
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Rows of neither a user nor a role (or of both) can not be resolved.
+DELETE FROM public.structure_privilege
+WHERE project_id IS NULL OR (user_id IS NULL) = (role_name IS NULL);
+
+-- At most one row per structure and role (and per structure and user), the
+-- last modified.
+DELETE FROM public.structure_privilege sp
+USING public.structure_privilege newer
+WHERE sp.project_id = newer.project_id
+    AND sp.role_name = newer.role_name
+    AND (sp.mtime, sp.id) < (newer.mtime, newer.id);
+
+DELETE FROM public.structure_privilege sp
+USING public.structure_privilege newer
+WHERE sp.project_id = newer.project_id
+    AND sp.user_id = newer.user_id
+    AND (sp.mtime, sp.id) < (newer.mtime, newer.id);
+
+ALTER TABLE public.structure_privilege
+    ALTER COLUMN project_id SET NOT NULL,
+    ADD CONSTRAINT structure_privilege_check
+        CHECK ((user_id IS NULL) <> (role_name IS NULL)),
+    ADD CONSTRAINT structure_privilege_project_id_user_id_key
+        UNIQUE (project_id, user_id),
+    ADD CONSTRAINT structure_privilege_project_id_role_name_key
+        UNIQUE (project_id, role_name);
+
+-- A grant of each role on each structure it has none of, enabled when all
+-- the users of the role had it enabled (or when they had no row of it).
+INSERT INTO public.structure_privilege (project_id, role_name, is_enabled, is_deleted, cid, ctime, mid, mtime)
+SELECT s.id, r.role_name,
+    COALESCE(bool_and(sp.is_enabled), TRUE), FALSE, s.cid, now(), s.cid, now()
+FROM public.structure s
+CROSS JOIN public.role r
+LEFT JOIN public."user" u ON u.assigned_role = r.role_name
+LEFT JOIN public.structure_privilege sp
+    ON sp.project_id = s.id AND sp.user_id = u.id AND sp.is_deleted = FALSE
+GROUP BY s.id, s.cid, r.role_name
+ON CONFLICT (project_id, role_name) DO NOTHING;
+
+-- As enforced from now on, the "ADMIN" grants are enabled.
+UPDATE public.structure_privilege SET is_enabled = TRUE
+WHERE role_name = 'ADMIN' AND is_enabled = FALSE;
+
+-- The user rows the same as their role grant are inherited from now on, the
+-- others stay as overrides (as do those of the users without a role).
+DELETE FROM public.structure_privilege sp
+USING public."user" u, public.structure_privilege g
+WHERE sp.user_id = u.id
+    AND g.project_id = sp.project_id
+    AND g.role_name = u.assigned_role
+    AND g.is_enabled = sp.is_enabled;
+
+-- The users inherit the grants of their role, instead of getting their own rows.
+CREATE OR REPLACE FUNCTION associate_role_with_structures()
+RETURNS TRIGGER AS $$
+BEGIN
+    -- Insert a grant in structure_privilege of each existing structure when a new role is created,
+    -- only if the grant does not already exist (the users of the role inherit it)
+    INSERT INTO public.structure_privilege (project_id, role_name, is_enabled, is_deleted, cid, ctime, mid, mtime)
+    SELECT s.id, NEW.role_name, TRUE, FALSE, NEW.cid, now(), NEW.cid, now()
+    FROM public.structure s
+    WHERE NOT EXISTS (
+        SELECT 1 FROM public.structure_privilege sp
+        WHERE sp.project_id = s.id AND sp.role_name = NEW.role_name
+    );
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER role_structure_association
+AFTER INSERT ON public.role
+FOR EACH ROW
+EXECUTE FUNCTION associate_role_with_structures();
+
+CREATE OR REPLACE FUNCTION associate_structure_with_roles()
+RETURNS TRIGGER AS $$
+BEGIN
+    -- Insert a grant in structure_privilege for each existing role when a new structure is created,
+    -- only if the grant does not already exist
+    INSERT INTO public.structure_privilege (project_id, role_name, is_enabled, is_deleted,  cid, ctime, mid, mtime)
+    SELECT NEW.id, r.role_name, TRUE, FALSE, NEW.cid, now(), NEW.cid, now()
+    FROM public.role r
+    WHERE NOT EXISTS (
+        SELECT 1 FROM public.structure_privilege sp
+        WHERE sp.project_id = NEW.id AND sp.role_name = r.role_name
+    );
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER structure_role_association
+AFTER INSERT ON public.structure
+FOR EACH ROW
+EXECUTE FUNCTION associate_structure_with_roles();
+
+COMMIT;
@@ -1,4 +1,6 @@
-use crate::utils::envs::{get_env_b64u_as_u8s, get_env_parse};
+use crate::auth::pwd::policy::PwdCharClass;
+use crate::utils::b64::b64u_decode;
+use crate::utils::envs::{get_env, get_env_b64u_as_u8s, get_env_parse, Error};
 use std::sync::OnceLock;
 
 pub fn auth_config() -> &'static AuthConfig {
@@ -18,6 +20,67 @@ pub struct AuthConfig {
 
 	pub TOKEN_KEY: Vec<u8>,
 	pub TOKEN_DURATION_SEC: f64,
+	/// For the `Authorization: Bearer` tokens (not refreshed).
+	pub HEADER_TOKEN_DURATION_SEC: f64,
+	/// Of the (single use) pwd reset tokens, sent by email.
+	pub PWD_RESET_TOKEN_DURATION_SEC: f64,
+	/// Of the challenge tokens, between the pwd and the TOTP code of a login.
+	pub LOGIN_CHALLENGE_DURATION_SEC: f64,
+
+	// -- Token key ring
+	/// AES-256-GCM key (32 bytes) of the rolled token keys at rest.
+	pub TOKEN_RING_KEY: Vec<u8>,
+	/// Age of the active key before a new one is rolled (0 for no scheduled
+	/// rolling, only the `roll_token_key` rpc).
+	pub TOKEN_KEY_ROTATION_SEC: f64,
+	/// Reload of the ring from the db, also the delay before a rolled key
+	/// signs (so that all the instances know it by then).
+	pub TOKEN_KEY_REFRESH_SEC: f64,
+	/// A replaced key still validates its tokens this long (at least the
+	/// longest token duration).
+	pub TOKEN_KEY_GRACE_SEC: f64,
+
+	// -- Totp
+	/// AES-256-GCM key (32 bytes) of the TOTP secrets at rest.
+	pub TOTP_KEY: Vec<u8>,
+	/// The account issuer shown by the authenticator apps.
+	pub TOTP_ISSUER: String,
+
+	// -- Login
+	/// From a comma separated list of `pwd` and `ldap`, tried in this order
+	/// until one knows the username.
+	pub LOGIN_AUTHENTICATORS: Vec<String>,
+
+	// -- Login throttling
+	/// Failures (of a username or an ip) locking the login out.
+	pub LOGIN_MAX_FAILURES: i32,
+	/// Wait after the first failure, doubled on each next one.
+	pub LOGIN_BACKOFF_BASE_SEC: f64,
+	pub LOGIN_LOCKOUT_SEC: f64,
+
+	// -- Pwd policy
+	pub PWD_MIN_LENGTH: usize,
+	/// From a comma separated list of `lowercase`, `uppercase`, `digit` and
+	/// `symbol` (empty for none).
+	pub PWD_REQUIRED_CLASSES: Vec<PwdCharClass>,
+	/// Previous pwds a new one cannot reuse (0 for no history).
+	pub PWD_HISTORY_SIZE: usize,
+	/// Days before a pwd must be changed at login (0 for no expiry).
+	pub PWD_MAX_AGE_DAYS: i64,
+
+	// -- Pwd scheme 03
+	/// From a comma separated list of `kid=key_b64u` (kid of 1 to 8 bytes), the
+	/// first peppering the new hashes, the others still validating theirs.
+	pub PWD_PEPPERS: Vec<PwdPepper>,
+	/// Argon2id memory (KiB), iterations and parallelism of the new hashes.
+	pub PWD_ARGON2_M_COST: u32,
+	pub PWD_ARGON2_T_COST: u32,
+	pub PWD_ARGON2_P_COST: u32,
+}
+
+pub struct PwdPepper {
+	pub kid: String,
+	pub key: Vec<u8>,
 }
 
 impl AuthConfig {
@@ -28,6 +91,81 @@ impl AuthConfig {
 
 			TOKEN_KEY: get_env_b64u_as_u8s("SERVICE_TOKEN_KEY")?,
 			TOKEN_DURATION_SEC: get_env_parse("SERVICE_TOKEN_DURATION_SEC")?,
+			HEADER_TOKEN_DURATION_SEC: get_env_parse(
+				"SERVICE_HEADER_TOKEN_DURATION_SEC",
+			)?,
+			PWD_RESET_TOKEN_DURATION_SEC: get_env_parse(
+				"SERVICE_PWD_RESET_TOKEN_DURATION_SEC",
+			)?,
+			LOGIN_CHALLENGE_DURATION_SEC: get_env_parse(
+				"SERVICE_LOGIN_CHALLENGE_DURATION_SEC",
+			)?,
+
+			// -- Token key ring
+			TOKEN_RING_KEY: get_env_b64u_as_u8s("SERVICE_TOKEN_RING_KEY")?,
+			TOKEN_KEY_ROTATION_SEC: get_env_parse("SERVICE_TOKEN_KEY_ROTATION_SEC")?,
+			TOKEN_KEY_REFRESH_SEC: get_env_parse("SERVICE_TOKEN_KEY_REFRESH_SEC")?,
+			TOKEN_KEY_GRACE_SEC: get_env_parse("SERVICE_TOKEN_KEY_GRACE_SEC")?,
+
+			// -- Totp
+			TOTP_KEY: get_env_b64u_as_u8s("SERVICE_TOTP_KEY")?,
+			TOTP_ISSUER: get_env("SERVICE_TOTP_ISSUER")?,
+
+			// -- Login
+			LOGIN_AUTHENTICATORS: get_env("SERVICE_LOGIN_AUTHENTICATORS")?
+				.split(',')
+				.map(str::trim)
+				.filter(|name| !name.is_empty())
+				.map(str::to_string)
+				.collect(),
+
+			// -- Login throttling
+			LOGIN_MAX_FAILURES: get_env_parse("SERVICE_LOGIN_MAX_FAILURES")?,
+			LOGIN_BACKOFF_BASE_SEC: get_env_parse("SERVICE_LOGIN_BACKOFF_BASE_SEC")?,
+			LOGIN_LOCKOUT_SEC: get_env_parse("SERVICE_LOGIN_LOCKOUT_SEC")?,
+
+			// -- Pwd policy
+			PWD_MIN_LENGTH: get_env_parse("SERVICE_PWD_MIN_LENGTH")?,
+			PWD_REQUIRED_CLASSES: get_env("SERVICE_PWD_REQUIRED_CLASSES")?
+				.split(',')
+				.map(str::trim)
+				.filter(|class| !class.is_empty())
+				.map(|class| {
+					class.parse().map_err(|_| {
+						Error::WrongFormat("SERVICE_PWD_REQUIRED_CLASSES")
+					})
+				})
+				.collect::<Result<_, _>>()?,
+			PWD_HISTORY_SIZE: get_env_parse("SERVICE_PWD_HISTORY_SIZE")?,
+			PWD_MAX_AGE_DAYS: get_env_parse("SERVICE_PWD_MAX_AGE_DAYS")?,
+
+			// -- Pwd scheme 03
+			PWD_PEPPERS: parse_pwd_peppers(&get_env("SERVICE_PWD_PEPPERS")?)
+				.ok_or(Error::WrongFormat("SERVICE_PWD_PEPPERS"))?,
+			PWD_ARGON2_M_COST: get_env_parse("SERVICE_PWD_ARGON2_M_COST")?,
+			PWD_ARGON2_T_COST: get_env_parse("SERVICE_PWD_ARGON2_T_COST")?,
+			PWD_ARGON2_P_COST: get_env_parse("SERVICE_PWD_ARGON2_P_COST")?,
 		})
 	}
 }
+
+/// None when empty, or of a kid not of 1 to 8 bytes (the argon2 `keyid`), a
+/// duplicate kid or a key not in base64url.
+fn parse_pwd_peppers(peppers: &str) -> Option<Vec<PwdPepper>> {
+	let mut pwd_peppers: Vec<PwdPepper> = Vec::new();
+	for pepper in peppers.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+		let (kid, key_b64u) = pepper.split_once('=')?;
+		let kid = kid.trim();
+		if !(1..=8).contains(&kid.len())
+			|| pwd_peppers.iter().any(|pepper| pepper.kid == kid)
+		{
+			return None;
+		}
+		pwd_peppers.push(PwdPepper {
+			kid: kid.to_string(),
+			key: b64u_decode(key_b64u.trim()).ok()?,
+		});
+	}
+
+	(!pwd_peppers.is_empty()).then_some(pwd_peppers)
+}
@@ -0,0 +1,99 @@
+use crate::utils::envs::{get_env, get_env_parse, Error};
+use std::sync::OnceLock;
+
+/// None when `SERVICE_LDAP_URL` is not set (no LDAP login).
+pub fn ldap_config() -> Option<&'static LdapConfig> {
+	static INSTANCE: OnceLock<Option<LdapConfig>> = OnceLock::new();
+
+	INSTANCE
+		.get_or_init(|| {
+			get_env("SERVICE_LDAP_URL").ok().map(|url| {
+				LdapConfig::load_from_env(url).unwrap_or_else(|ex| {
+					panic!("FATAL - WHILE LOADING CONF - Cause: {ex:?}")
+				})
+			})
+		})
+		.as_ref()
+}
+
+/// A group of the directory and the role of its members.
+#[derive(Clone, Debug)]
+pub struct GroupRole {
+	pub group_dn: String,
+	pub role: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone)]
+pub struct LdapConfig {
+	// -- Server
+	/// `ldap://` or `ldaps://` url of the directory.
+	pub URL: String,
+	/// Upgrades an `ldap://` connection with StartTLS.
+	pub STARTTLS: bool,
+	pub CONN_TIMEOUT_SEC: u64,
+
+	// -- Search
+	/// The account searching the users, none for an anonymous search.
+	pub BIND_DN: Option<String>,
+	pub BIND_PWD: Option<String>,
+	pub USER_BASE_DN: String,
+	/// With a `{username}` placeholder (escaped), e.g.
+	/// `(&(objectClass=person)(uid={username}))` or, for Active Directory,
+	/// `(sAMAccountName={username})`.
+	pub USER_FILTER: String,
+	pub EMAIL_ATTR: String,
+	/// The groups of the user entry (`memberOf`).
+	pub GROUP_ATTR: String,
+
+	// -- Roles
+	/// From a `;` separated list of `<group dn>=><role>`, the first group of
+	/// the user in this order giving its role.
+	pub GROUP_ROLES: Vec<GroupRole>,
+	/// The role of the users in none of the `GROUP_ROLES`, none for no login.
+	pub DEFAULT_ROLE: Option<String>,
+}
+
+impl LdapConfig {
+	fn load_from_env(url: String) -> crate::utils::envs::Result<LdapConfig> {
+		Ok(LdapConfig {
+			// -- Server
+			URL: url,
+			STARTTLS: get_env_parse("SERVICE_LDAP_STARTTLS")?,
+			CONN_TIMEOUT_SEC: get_env_parse("SERVICE_LDAP_CONN_TIMEOUT_SEC")?,
+
+			// -- Search
+			BIND_DN: get_env("SERVICE_LDAP_BIND_DN").ok(),
+			BIND_PWD: get_env("SERVICE_LDAP_BIND_PWD").ok(),
+			USER_BASE_DN: get_env("SERVICE_LDAP_USER_BASE_DN")?,
+			USER_FILTER: get_env("SERVICE_LDAP_USER_FILTER")?,
+			EMAIL_ATTR: get_env("SERVICE_LDAP_EMAIL_ATTR")?,
+			GROUP_ATTR: get_env("SERVICE_LDAP_GROUP_ATTR")?,
+
+			// -- Roles
+			GROUP_ROLES: parse_group_roles(
+				"SERVICE_LDAP_GROUP_ROLES",
+				&get_env("SERVICE_LDAP_GROUP_ROLES")?,
+			)?,
+			DEFAULT_ROLE: get_env("SERVICE_LDAP_DEFAULT_ROLE").ok(),
+		})
+	}
+}
+
+fn parse_group_roles(
+	name: &'static str,
+	val: &str,
+) -> crate::utils::envs::Result<Vec<GroupRole>> {
+	val.split(';')
+		.map(str::trim)
+		.filter(|mapping| !mapping.is_empty())
+		.map(|mapping| {
+			let (group_dn, role) =
+				mapping.split_once("=>").ok_or(Error::WrongFormat(name))?;
+			Ok(GroupRole {
+				group_dn: group_dn.trim().to_string(),
+				role: role.trim().to_string(),
+			})
+		})
+		.collect()
+}
@@ -0,0 +1,37 @@
+use derive_more::From;
+use serde::Serialize;
+use serde_with::{serde_as, DisplayFromStr};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[serde_as]
+#[derive(Debug, Serialize, From)]
+pub enum Error {
+	// -- Search
+	UserNotUnique {
+		username: String,
+	},
+
+	// -- Bind
+	/// Also for an empty pwd, which would be an (anonymous) unauthenticated bind.
+	PwdNotMatching {
+		dn: String,
+	},
+
+	// -- Externals
+	#[from]
+	Ldap(#[serde_as(as = "DisplayFromStr")] ldap3::LdapError),
+}
+
+// region:    --- Error Boilerplate
+impl core::fmt::Display for Error {
+	fn fmt(
+		&self,
+		fmt: &mut core::fmt::Formatter,
+	) -> core::result::Result<(), core::fmt::Error> {
+		write!(fmt, "{self:?}")
+	}
+}
+
+impl std::error::Error for Error {}
+// endregion: --- Error Boilerplate
@@ -0,0 +1,308 @@
+//! A local LDAP server of the tests, with a `reader` search account and three
+//! users, speaking just the bind, search and unbind of the protocol.
+
+use crate::auth::ldap::config::GroupRole;
+use crate::auth::ldap::{LdapClient, LdapConfig};
+use ldap3::asn1::{parse_tag, StructureTag, TagClass, PL};
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// (username, pwd) of the users.
+pub const FX_ALICE: (&str, &str) = ("alice", "fx-alice-pwd");
+pub const FX_BOB: (&str, &str) = ("bob", "fx-bob-pwd");
+pub const FX_CAROL: (&str, &str) = ("carol", "fx-carol-pwd");
+
+const FX_READER_DN: &str = "cn=reader,dc=example,dc=org";
+const FX_READER_PWD: &str = "fx-reader-pwd";
+const FX_ADMINS_DN: &str = "cn=admins,ou=groups,dc=example,dc=org";
+const FX_STAFF_DN: &str = "cn=staff,ou=groups,dc=example,dc=org";
+
+// -- Result codes
+const RC_SUCCESS: u8 = 0;
+const RC_INSUFFICIENT_ACCESS_RIGHTS: u8 = 50;
+const RC_INVALID_CREDENTIALS: u8 = 49;
+
+struct FxEntry {
+	dn: &'static str,
+	pwd: &'static str,
+	attrs: Vec<(&'static str, Vec<&'static str>)>,
+}
+
+fn fx_entries() -> Vec<FxEntry> {
+	let person = |uid: &'static str, pwd, mail, groups| FxEntry {
+		dn: match uid {
+			"alice" => "uid=alice,ou=people,dc=example,dc=org",
+			"bob" => "uid=bob,ou=people,dc=example,dc=org",
+			_ => "uid=carol,ou=people,dc=example,dc=org",
+		},
+		pwd,
+		attrs: vec![
+			("objectClass", vec!["person"]),
+			("uid", vec![uid]),
+			("mail", vec![mail]),
+			("memberOf", groups),
+		],
+	};
+
+	vec![
+		// Of both groups, in the case of the directory (not the config's).
+		person(
+			FX_ALICE.0,
+			FX_ALICE.1,
+			"alice@example.org",
+			vec![FX_STAFF_DN, "CN=Admins,OU=Groups,DC=example,DC=org"],
+		),
+		person(FX_BOB.0, FX_BOB.1, "bob@example.org", vec![FX_STAFF_DN]),
+		person(FX_CAROL.0, FX_CAROL.1, "carol@example.org", vec![]),
+	]
+}
+
+/// A client of a new local server, the admins being `fx-admin` and the staff
+/// `fx-staff` (in this order).
+pub async fn fx_client(default_role: Option<&str>) -> io::Result<LdapClient> {
+	let url = fx_server().await?;
+
+	Ok(LdapClient::new(LdapConfig {
+		URL: url,
+		STARTTLS: false,
+		CONN_TIMEOUT_SEC: 5,
+		BIND_DN: Some(FX_READER_DN.to_string()),
+		BIND_PWD: Some(FX_READER_PWD.to_string()),
+		USER_BASE_DN: "ou=people,dc=example,dc=org".to_string(),
+		USER_FILTER: "(&(objectClass=person)(uid={username}))".to_string(),
+		EMAIL_ATTR: "mail".to_string(),
+		GROUP_ATTR: "memberOf".to_string(),
+		GROUP_ROLES: vec![
+			GroupRole {
+				group_dn: FX_ADMINS_DN.to_string(),
+				role: "fx-admin".to_string(),
+			},
+			GroupRole {
+				group_dn: FX_STAFF_DN.to_string(),
+				role: "fx-staff".to_string(),
+			},
+		],
+		DEFAULT_ROLE: default_role.map(str::to_string),
+	}))
+}
+
+/// The `ldap://` url of a new local server.
+async fn fx_server() -> io::Result<String> {
+	let listener = TcpListener::bind("127.0.0.1:0").await?;
+	let url = format!("ldap://{}", listener.local_addr()?);
+
+	tokio::spawn(async move {
+		while let Ok((stream, _)) = listener.accept().await {
+			tokio::spawn(serve(stream));
+		}
+	});
+
+	Ok(url)
+}
+
+// region:    --- Protocol
+
+async fn serve(mut stream: TcpStream) -> io::Result<()> {
+	let entries = fx_entries();
+	let mut bound_dn: Option<String> = None;
+
+	while let Some(message) = read_message(&mut stream).await? {
+		let (_, message) = parse_tag(&message)
+			.map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "not BER"))?;
+		let [message_id, op] = constructed(message).try_into().map_err(|_| {
+			io::Error::new(io::ErrorKind::InvalidData, "not an LDAPMessage")
+		})?;
+
+		let responses = match (op.class, op.id) {
+			// -- Bind
+			(TagClass::Application, 0) => {
+				let [_version, name, pwd] = constructed(op).try_into().unwrap();
+				let (dn, pwd) = (string(&name), string(&pwd));
+
+				let rc = if dn.is_empty() && pwd.is_empty() {
+					bound_dn = None;
+					RC_SUCCESS
+				} else if (dn == FX_READER_DN && pwd == FX_READER_PWD)
+					|| entries.iter().any(|e| e.dn == dn && e.pwd == pwd)
+				{
+					bound_dn = Some(dn);
+					RC_SUCCESS
+				} else {
+					RC_INVALID_CREDENTIALS
+				};
+
+				vec![result_op(1, rc)]
+			}
+
+			// -- Search
+			(TagClass::Application, 3) => {
+				let parts = constructed(op);
+				let (filter, attrs) = (&parts[6], constructed(parts[7].clone()));
+				let attrs: Vec<String> = attrs.iter().map(string).collect();
+
+				if bound_dn.as_deref() != Some(FX_READER_DN) {
+					vec![result_op(5, RC_INSUFFICIENT_ACCESS_RIGHTS)]
+				} else {
+					let mut responses: Vec<StructureTag> = entries
+						.iter()
+						.filter(|entry| matches_filter(filter, entry))
+						.map(|entry| entry_op(entry, &attrs))
+						.collect();
+					responses.push(result_op(5, RC_SUCCESS));
+					responses
+				}
+			}
+
+			// -- Unbind (or anything else)
+			_ => return Ok(()),
+		};
+
+		for op in responses {
+			let response =
+				tag(TagClass::Universal, 16, PL::C(vec![message_id.clone(), op]));
+			stream.write_all(&encode(&response)).await?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Just the `and`, `or`, `equalityMatch` and `present` filters.
+fn matches_filter(filter: &StructureTag, entry: &FxEntry) -> bool {
+	let values = |attr: &str| {
+		entry
+			.attrs
+			.iter()
+			.find(|(name, _)| name.eq_ignore_ascii_case(attr))
+			.map(|(_, values)| values.clone())
+	};
+
+	match (filter.id, &filter.payload) {
+		(0, PL::C(filters)) => filters.iter().all(|f| matches_filter(f, entry)),
+		(1, PL::C(filters)) => filters.iter().any(|f| matches_filter(f, entry)),
+		(3, PL::C(ava)) => values(&string(&ava[0]))
+			.is_some_and(|values| values.contains(&string(&ava[1]).as_str())),
+		(7, PL::P(attr)) => values(&String::from_utf8_lossy(attr)).is_some(),
+		_ => false,
+	}
+}
+
+/// A SearchResultEntry, of the requested attributes only (all when none).
+fn entry_op(entry: &FxEntry, attrs: &[String]) -> StructureTag {
+	let attributes = entry
+		.attrs
+		.iter()
+		.filter(|(name, _)| {
+			attrs.is_empty() || attrs.iter().any(|a| a.eq_ignore_ascii_case(name))
+		})
+		.map(|(name, values)| {
+			let values = values.iter().map(|v| octet_string(v)).collect();
+			tag(
+				TagClass::Universal,
+				16,
+				PL::C(vec![
+					octet_string(name),
+					tag(TagClass::Universal, 17, PL::C(values)),
+				]),
+			)
+		})
+		.collect();
+
+	tag(
+		TagClass::Application,
+		4,
+		PL::C(vec![
+			octet_string(entry.dn),
+			tag(TagClass::Universal, 16, PL::C(attributes)),
+		]),
+	)
+}
+
+/// A BindResponse (1) or SearchResultDone (5).
+fn result_op(id: u64, rc: u8) -> StructureTag {
+	tag(
+		TagClass::Application,
+		id,
+		PL::C(vec![
+			tag(TagClass::Universal, 10, PL::P(vec![rc])),
+			octet_string(""),
+			octet_string(""),
+		]),
+	)
+}
+
+fn tag(class: TagClass, id: u64, payload: PL) -> StructureTag {
+	StructureTag { class, id, payload }
+}
+
+fn octet_string(val: &str) -> StructureTag {
+	tag(TagClass::Universal, 4, PL::P(val.as_bytes().to_vec()))
+}
+
+fn constructed(tag: StructureTag) -> Vec<StructureTag> {
+	match tag.payload {
+		PL::C(tags) => tags,
+		PL::P(_) => Vec::new(),
+	}
+}
+
+fn string(tag: &StructureTag) -> String {
+	match &tag.payload {
+		PL::P(bytes) => String::from_utf8_lossy(bytes).to_string(),
+		PL::C(_) => String::new(),
+	}
+}
+
+/// BER, with the ids of LDAP (all below 31).
+fn encode(tag: &StructureTag) -> Vec<u8> {
+	let (constructed, content) = match &tag.payload {
+		PL::P(bytes) => (0, bytes.clone()),
+		PL::C(tags) => (0x20, tags.iter().flat_map(encode).collect()),
+	};
+	let class = match tag.class {
+		TagClass::Universal => 0x00,
+		TagClass::Application => 0x40,
+		TagClass::Context => 0x80,
+		TagClass::Private => 0xc0,
+	};
+
+	let mut bytes = vec![class | constructed | tag.id as u8];
+	if content.len() < 0x80 {
+		bytes.push(content.len() as u8);
+	} else {
+		let len = content.len().to_be_bytes();
+		let len: Vec<u8> = len.into_iter().skip_while(|b| *b == 0).collect();
+		bytes.push(0x80 | len.len() as u8);
+		bytes.extend(len);
+	}
+	bytes.extend(content);
+
+	bytes
+}
+
+/// The bytes of the next message, none when the client closed the connection.
+async fn read_message(stream: &mut TcpStream) -> io::Result<Option<Vec<u8>>> {
+	let mut header = vec![0u8; 2];
+	if stream.read_exact(&mut header).await.is_err() {
+		return Ok(None);
+	}
+
+	let len = if header[1] & 0x80 == 0 {
+		header[1] as usize
+	} else {
+		let mut len_bytes = vec![0u8; (header[1] & 0x7f) as usize];
+		stream.read_exact(&mut len_bytes).await?;
+		header.extend(&len_bytes);
+		len_bytes.iter().fold(0, |len, b| (len << 8) | *b as usize)
+	};
+
+	let mut message = header;
+	let start = message.len();
+	message.resize(start + len, 0);
+	stream.read_exact(&mut message[start..]).await?;
+
+	Ok(Some(message))
+}
+
+// endregion: --- Protocol
@@ -0,0 +1,264 @@
+//! LDAP (or Active Directory) login: the user entry is searched (as the
+//! `BIND_DN` account), then bound with the pwd of the login, its groups giving
+//! the role of the user.
+
+// region:    --- Modules
+
+mod config;
+mod error;
+#[cfg(test)]
+pub mod fixture;
+
+pub use self::config::{ldap_config, LdapConfig};
+pub use self::error::{Error, Result};
+
+use ldap3::{
+	drive, ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, LdapError, Scope,
+	SearchEntry,
+};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// endregion: --- Modules
+
+/// `invalidCredentials` result code of a bind.
+const RC_INVALID_CREDENTIALS: u32 = 49;
+
+/// None when no directory is configured.
+pub fn ldap_client() -> Option<&'static LdapClient> {
+	static INSTANCE: OnceLock<Option<LdapClient>> = OnceLock::new();
+
+	INSTANCE
+		.get_or_init(|| ldap_config().map(|config| LdapClient::new(config.clone())))
+		.as_ref()
+}
+
+/// The directory entry of an authenticated user.
+#[derive(Debug)]
+pub struct LdapUser {
+	pub dn: String,
+	pub email: Option<String>,
+	/// From the `GROUP_ROLES`, otherwise the `DEFAULT_ROLE`.
+	pub role: Option<String>,
+}
+
+pub struct LdapClient {
+	config: LdapConfig,
+}
+
+impl LdapClient {
+	pub fn new(config: LdapConfig) -> Self {
+		LdapClient { config }
+	}
+
+	/// The entry of the `username`, bound with its `pwd`. None when the
+	/// directory has no such user.
+	pub async fn authenticate(
+		&self,
+		username: &str,
+		pwd: &str,
+	) -> Result<Option<LdapUser>> {
+		let mut ldap = self.connect().await?;
+
+		let res = self.authenticate_with(&mut ldap, username, pwd).await;
+		// Best effort, the connection is dropped anyway.
+		let _ = ldap.unbind().await;
+
+		res
+	}
+
+	/// The role of the members of the `groups` (DNs compared case
+	/// insensitively).
+	pub fn role_of(&self, groups: &[String]) -> Option<String> {
+		self.config
+			.GROUP_ROLES
+			.iter()
+			.find(|group_role| {
+				groups.iter().any(|group| {
+					group.trim().eq_ignore_ascii_case(&group_role.group_dn)
+				})
+			})
+			.map(|group_role| group_role.role.clone())
+			.or_else(|| self.config.DEFAULT_ROLE.clone())
+	}
+}
+
+// region:    --- Privates
+
+impl LdapClient {
+	async fn connect(&self) -> Result<Ldap> {
+		let settings = LdapConnSettings::new()
+			.set_conn_timeout(Duration::from_secs(self.config.CONN_TIMEOUT_SEC))
+			.set_starttls(self.config.STARTTLS);
+		let (conn, ldap) =
+			LdapConnAsync::with_settings(settings, &self.config.URL).await?;
+		drive!(conn);
+
+		Ok(ldap)
+	}
+
+	async fn authenticate_with(
+		&self,
+		ldap: &mut Ldap,
+		username: &str,
+		pwd: &str,
+	) -> Result<Option<LdapUser>> {
+		// -- Search the entry of the user.
+		if let Some(bind_dn) = &self.config.BIND_DN {
+			let bind_pwd = self.config.BIND_PWD.as_deref().unwrap_or_default();
+			ldap.simple_bind(bind_dn, bind_pwd).await?.success()?;
+		}
+
+		let filter = self
+			.config
+			.USER_FILTER
+			.replace("{username}", &ldap_escape(username));
+		let attrs = [
+			self.config.EMAIL_ATTR.as_str(),
+			self.config.GROUP_ATTR.as_str(),
+		];
+		let (mut entries, _) = ldap
+			.search(&self.config.USER_BASE_DN, Scope::Subtree, &filter, attrs)
+			.await?
+			.success()?;
+
+		let entry = match entries.len() {
+			0 => return Ok(None),
+			1 => SearchEntry::construct(entries.remove(0)),
+			_ => {
+				return Err(Error::UserNotUnique {
+					username: username.to_string(),
+				})
+			}
+		};
+
+		// -- Bind as the user.
+		if pwd.is_empty() {
+			return Err(Error::PwdNotMatching { dn: entry.dn });
+		}
+		match ldap.simple_bind(&entry.dn, pwd).await?.success() {
+			Ok(_) => (),
+			Err(LdapError::LdapResult { result })
+				if result.rc == RC_INVALID_CREDENTIALS =>
+			{
+				return Err(Error::PwdNotMatching { dn: entry.dn });
+			}
+			Err(ex) => return Err(ex.into()),
+		}
+
+		let email = attr_values(&entry, &self.config.EMAIL_ATTR)
+			.into_iter()
+			.next();
+		let groups = attr_values(&entry, &self.config.GROUP_ATTR);
+		let role = self.role_of(&groups);
+
+		Ok(Some(LdapUser {
+			dn: entry.dn,
+			email,
+			role,
+		}))
+	}
+}
+
+/// The attribute names of the entries keep the case of the directory.
+fn attr_values(entry: &SearchEntry, attr: &str) -> Vec<String> {
+	entry
+		.attrs
+		.iter()
+		.find(|(name, _)| name.eq_ignore_ascii_case(attr))
+		.map(|(_, values)| values.clone())
+		.unwrap_or_default()
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::auth::ldap::fixture::{fx_client, FX_ALICE, FX_BOB, FX_CAROL};
+	use anyhow::Result;
+
+	#[tokio::test]
+	async fn test_authenticate_ok_group_role() -> Result<()> {
+		// -- Setup & Fixtures
+		let client = fx_client(Some("demo")).await?;
+
+		// -- Exec
+		let alice = client.authenticate(FX_ALICE.0, FX_ALICE.1).await?;
+		let bob = client.authenticate(FX_BOB.0, FX_BOB.1).await?;
+
+		// -- Check
+		let alice = alice.ok_or(anyhow::anyhow!("alice should be found"))?;
+		assert_eq!(alice.dn, "uid=alice,ou=people,dc=example,dc=org");
+		assert_eq!(alice.email.as_deref(), Some("alice@example.org"));
+		// The first mapped group in the order of the config.
+		assert_eq!(alice.role.as_deref(), Some("fx-admin"));
+		let bob = bob.ok_or(anyhow::anyhow!("bob should be found"))?;
+		assert_eq!(bob.role.as_deref(), Some("fx-staff"));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_authenticate_ok_default_role() -> Result<()> {
+		// -- Setup & Fixtures
+		let client = fx_client(Some("demo")).await?;
+		let client_no_default = fx_client(None).await?;
+
+		// -- Exec
+		let carol = client.authenticate(FX_CAROL.0, FX_CAROL.1).await?;
+		let carol_no_default = client_no_default
+			.authenticate(FX_CAROL.0, FX_CAROL.1)
+			.await?;
+
+		// -- Check
+		let carol = carol.ok_or(anyhow::anyhow!("carol should be found"))?;
+		assert_eq!(carol.role.as_deref(), Some("demo"));
+		let carol_no_default =
+			carol_no_default.ok_or(anyhow::anyhow!("carol should be found"))?;
+		assert_eq!(carol_no_default.role, None);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_authenticate_err_pwd_not_matching() -> Result<()> {
+		// -- Setup & Fixtures
+		let client = fx_client(Some("demo")).await?;
+
+		// -- Exec
+		let res_wrong = client.authenticate(FX_ALICE.0, "fx-wrong-pwd").await;
+		let res_empty = client.authenticate(FX_ALICE.0, "").await;
+
+		// -- Check
+		for res in [res_wrong, res_empty] {
+			assert!(
+				matches!(res, Err(Error::PwdNotMatching { .. })),
+				"Should have matched `Err(Error::PwdNotMatching)` but was `{res:?}`"
+			);
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_authenticate_unknown_user_none() -> Result<()> {
+		// -- Setup & Fixtures
+		let client = fx_client(Some("demo")).await?;
+
+		// -- Exec
+		// Escaped, so not a wildcard matching all the users.
+		let unknown = client.authenticate("fx-unknown", "fx-pwd").await?;
+		let wildcard = client.authenticate("*", "fx-pwd").await?;
+
+		// -- Check
+		assert!(unknown.is_none());
+		assert!(wildcard.is_none());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -1,6 +1,9 @@
-mod config;
+pub mod config;
+pub mod ldap;
+pub mod oidc;
 pub mod pwd;
 //pub mod pwd_legacy;
 pub mod token;
+pub mod totp;
 
 use config::auth_config;
@@ -0,0 +1,57 @@
+use crate::utils::envs::{get_env, get_env_parse};
+use std::sync::OnceLock;
+
+/// None when `SERVICE_OIDC_ISSUER` is not set (no OpenID Connect login).
+pub fn oidc_config() -> Option<&'static OidcConfig> {
+	static INSTANCE: OnceLock<Option<OidcConfig>> = OnceLock::new();
+
+	INSTANCE
+		.get_or_init(|| {
+			get_env("SERVICE_OIDC_ISSUER").ok().map(|issuer| {
+				OidcConfig::load_from_env(issuer).unwrap_or_else(|ex| {
+					panic!("FATAL - WHILE LOADING CONF - Cause: {ex:?}")
+				})
+			})
+		})
+		.as_ref()
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone)]
+pub struct OidcConfig {
+	// -- IdP
+	/// The `iss` of the IdP, its discovery being under
+	/// `/.well-known/openid-configuration`.
+	pub ISSUER: String,
+	pub CLIENT_ID: String,
+	/// None for a public client (PKCE only).
+	pub CLIENT_SECRET: Option<String>,
+	/// The callback url (`/api/oidc/callback`) registered at the IdP.
+	pub REDIRECT_URL: String,
+
+	// -- Login
+	/// The page the browser is sent to once logged in.
+	pub POST_LOGIN_URL: String,
+	/// Of the pending logins, between the redirect to the IdP and its callback.
+	pub LOGIN_DURATION_SEC: i64,
+	/// The role of the auto-provisioned users, none for no auto-provisioning
+	/// (only the existing users log in).
+	pub DEFAULT_ROLE: Option<String>,
+}
+
+impl OidcConfig {
+	fn load_from_env(issuer: String) -> crate::utils::envs::Result<OidcConfig> {
+		Ok(OidcConfig {
+			// -- IdP
+			ISSUER: issuer,
+			CLIENT_ID: get_env("SERVICE_OIDC_CLIENT_ID")?,
+			CLIENT_SECRET: get_env("SERVICE_OIDC_CLIENT_SECRET").ok(),
+			REDIRECT_URL: get_env("SERVICE_OIDC_REDIRECT_URL")?,
+
+			// -- Login
+			POST_LOGIN_URL: get_env("SERVICE_OIDC_POST_LOGIN_URL")?,
+			LOGIN_DURATION_SEC: get_env_parse("SERVICE_OIDC_LOGIN_DURATION_SEC")?,
+			DEFAULT_ROLE: get_env("SERVICE_OIDC_DEFAULT_ROLE").ok(),
+		})
+	}
+}
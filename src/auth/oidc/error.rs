@@ -0,0 +1,52 @@
+use derive_more::From;
+use serde::Serialize;
+use serde_with::{serde_as, DisplayFromStr};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[serde_as]
+#[derive(Debug, Serialize, From)]
+pub enum Error {
+	// -- Discovery
+	IssuerNotMatching {
+		expected: String,
+		actual: String,
+	},
+	AuthorizationEndpointNotValid {
+		url: String,
+	},
+
+	// -- Code exchange
+	TokenRequestFail {
+		status: u16,
+		body: String,
+	},
+
+	// -- Id token
+	IdTokenAlgNotSupported {
+		alg: String,
+	},
+	IdTokenKeyNotFound {
+		kid: Option<String>,
+	},
+	IdTokenNonceNotMatching,
+
+	// -- Externals
+	#[from]
+	Http(#[serde_as(as = "DisplayFromStr")] reqwest::Error),
+	#[from]
+	Jwt(#[serde_as(as = "DisplayFromStr")] jsonwebtoken::errors::Error),
+}
+
+// region:    --- Error Boilerplate
+impl core::fmt::Display for Error {
+	fn fmt(
+		&self,
+		fmt: &mut core::fmt::Formatter,
+	) -> core::result::Result<(), core::fmt::Error> {
+		write!(fmt, "{self:?}")
+	}
+}
+
+impl std::error::Error for Error {}
+// endregion: --- Error Boilerplate
@@ -0,0 +1,464 @@
+//! OpenID Connect login (authorization code flow with PKCE): the redirect to
+//! the IdP, then the code exchange and the verification of its id token with
+//! the keys (JWKS) of the IdP.
+
+// region:    --- Modules
+
+mod config;
+mod error;
+
+pub use self::config::{oidc_config, OidcConfig};
+pub use self::error::{Error, Result};
+
+use crate::utils::b64::b64u_encode;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use rand::RngCore;
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+use tokio::sync::{OnceCell, RwLock};
+
+// endregion: --- Modules
+
+const SCOPES: &str = "openid email profile";
+
+/// Random bytes of the state, nonce and PKCE verifier.
+const RANDOM_LEN: usize = 32;
+
+/// None when no IdP is configured.
+pub fn oidc_client() -> Option<&'static OidcClient> {
+	static INSTANCE: OnceLock<Option<OidcClient>> = OnceLock::new();
+
+	INSTANCE
+		.get_or_init(|| oidc_config().map(|config| OidcClient::new(config.clone())))
+		.as_ref()
+}
+
+/// To redirect the browser to (`url`), the rest being kept until the callback.
+pub struct AuthorizationRequest {
+	pub url: String,
+	pub state: String,
+	pub nonce: String,
+	pub code_verifier: String,
+}
+
+/// The claims of the verified id token used for the login.
+#[derive(Debug, Deserialize)]
+pub struct IdClaims {
+	pub iss: String,
+	pub sub: String,
+	pub email: Option<String>,
+	pub email_verified: Option<bool>,
+	pub preferred_username: Option<String>,
+	nonce: Option<String>,
+}
+
+/// Of the `/.well-known/openid-configuration` of the IdP.
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+	issuer: String,
+	authorization_endpoint: String,
+	token_endpoint: String,
+	jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+	id_token: String,
+}
+
+pub struct OidcClient {
+	config: OidcConfig,
+	http: reqwest::Client,
+	/// Discovered at the first login.
+	metadata: OnceCell<ProviderMetadata>,
+	/// Fetched again when an id token has an unknown `kid` (key rotation).
+	jwks: RwLock<Option<JwkSet>>,
+}
+
+impl OidcClient {
+	pub fn new(config: OidcConfig) -> Self {
+		OidcClient {
+			config,
+			http: reqwest::Client::new(),
+			metadata: OnceCell::new(),
+			jwks: RwLock::new(None),
+		}
+	}
+
+	pub fn config(&self) -> &OidcConfig {
+		&self.config
+	}
+
+	/// A new login at the IdP, with its state, nonce and PKCE (S256) verifier.
+	pub async fn authorization_request(&self) -> Result<AuthorizationRequest> {
+		let metadata = self.metadata().await?;
+
+		let state = random_b64u();
+		let nonce = random_b64u();
+		let code_verifier = random_b64u();
+		let code_challenge = b64u_encode(Sha256::digest(code_verifier.as_bytes()));
+
+		let url = Url::parse_with_params(
+			&metadata.authorization_endpoint,
+			[
+				("response_type", "code"),
+				("client_id", &self.config.CLIENT_ID),
+				("redirect_uri", &self.config.REDIRECT_URL),
+				("scope", SCOPES),
+				("state", &state),
+				("nonce", &nonce),
+				("code_challenge", &code_challenge),
+				("code_challenge_method", "S256"),
+			],
+		)
+		.map_err(|_| Error::AuthorizationEndpointNotValid {
+			url: metadata.authorization_endpoint.clone(),
+		})?;
+
+		Ok(AuthorizationRequest {
+			url: url.to_string(),
+			state,
+			nonce,
+			code_verifier,
+		})
+	}
+
+	/// Exchanges the code of the callback for the claims of its (verified)
+	/// id token, which must have the `nonce` of the login.
+	pub async fn exchange_code(
+		&self,
+		code: &str,
+		code_verifier: &str,
+		nonce: &str,
+	) -> Result<IdClaims> {
+		let metadata = self.metadata().await?;
+
+		let mut form = vec![
+			("grant_type", "authorization_code"),
+			("code", code),
+			("redirect_uri", &self.config.REDIRECT_URL),
+			("client_id", &self.config.CLIENT_ID),
+			("code_verifier", code_verifier),
+		];
+		if let Some(client_secret) = &self.config.CLIENT_SECRET {
+			form.push(("client_secret", client_secret));
+		}
+
+		let res = self
+			.http
+			.post(&metadata.token_endpoint)
+			.form(&form)
+			.send()
+			.await?;
+		if !res.status().is_success() {
+			return Err(Error::TokenRequestFail {
+				status: res.status().as_u16(),
+				body: res.text().await.unwrap_or_default(),
+			});
+		}
+		let TokenResponse { id_token } = res.json().await?;
+
+		let claims = self.verify_id_token(&id_token, &metadata.issuer).await?;
+		if claims.nonce.as_deref() != Some(nonce) {
+			return Err(Error::IdTokenNonceNotMatching);
+		}
+
+		Ok(claims)
+	}
+}
+
+// region:    --- Privates
+
+impl OidcClient {
+	async fn metadata(&self) -> Result<&ProviderMetadata> {
+		self.metadata
+			.get_or_try_init(|| async {
+				let url = format!(
+					"{}/.well-known/openid-configuration",
+					self.config.ISSUER.trim_end_matches('/')
+				);
+				let metadata: ProviderMetadata = self
+					.http
+					.get(url)
+					.send()
+					.await?
+					.error_for_status()?
+					.json()
+					.await?;
+
+				if metadata.issuer != self.config.ISSUER {
+					return Err(Error::IssuerNotMatching {
+						expected: self.config.ISSUER.clone(),
+						actual: metadata.issuer,
+					});
+				}
+
+				Ok(metadata)
+			})
+			.await
+	}
+
+	/// The signature (with an asymmetric key of the IdP), expiration, issuer
+	/// and audience (the client id) of the id token.
+	async fn verify_id_token(
+		&self,
+		id_token: &str,
+		issuer: &str,
+	) -> Result<IdClaims> {
+		let header = decode_header(id_token)?;
+		if matches!(
+			header.alg,
+			Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+		) {
+			return Err(Error::IdTokenAlgNotSupported {
+				alg: format!("{:?}", header.alg),
+			});
+		}
+
+		let key = match self.decoding_key(header.kid.as_deref()).await? {
+			Some(key) => key,
+			None => {
+				self.refresh_jwks().await?;
+				self.decoding_key(header.kid.as_deref())
+					.await?
+					.ok_or(Error::IdTokenKeyNotFound { kid: header.kid })?
+			}
+		};
+
+		let mut validation = Validation::new(header.alg);
+		validation.set_issuer(&[issuer]);
+		validation.set_audience(&[&self.config.CLIENT_ID]);
+		validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+		let token_data = decode::<IdClaims>(id_token, &key, &validation)?;
+
+		Ok(token_data.claims)
+	}
+
+	/// The key of the `kid`, or the only key of the IdP when none.
+	async fn decoding_key(&self, kid: Option<&str>) -> Result<Option<DecodingKey>> {
+		let jwks = self.jwks.read().await;
+		let Some(jwks) = jwks.as_ref() else {
+			return Ok(None);
+		};
+
+		let jwk = match kid {
+			Some(kid) => jwks.find(kid),
+			None if jwks.keys.len() == 1 => jwks.keys.first(),
+			None => None,
+		};
+
+		jwk.map(DecodingKey::from_jwk)
+			.transpose()
+			.map_err(Error::from)
+	}
+
+	async fn refresh_jwks(&self) -> Result<()> {
+		let metadata = self.metadata().await?;
+
+		let jwks: JwkSet = self
+			.http
+			.get(&metadata.jwks_uri)
+			.send()
+			.await?
+			.error_for_status()?
+			.json()
+			.await?;
+
+		*self.jwks.write().await = Some(jwks);
+
+		Ok(())
+	}
+}
+
+fn random_b64u() -> String {
+	let mut random = [0u8; RANDOM_LEN];
+	rand::thread_rng().fill_bytes(&mut random);
+	b64u_encode(random)
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use anyhow::Result;
+	use axum::extract::{Form, State};
+	use axum::http::StatusCode;
+	use axum::routing::{get, post};
+	use axum::{Json, Router};
+	use jsonwebtoken::{encode, EncodingKey, Header};
+	use serde_json::{json, Value};
+	use std::collections::HashMap;
+	use std::sync::{Arc, Mutex};
+	use tokio::net::TcpListener;
+
+	const FX_CLIENT_ID: &str = "fx-client";
+	const FX_CODE: &str = "fx-code";
+	const FX_KID: &str = "fx-key-01";
+
+	/// An IdP with a static key, which issues an id token for the `FX_CODE`
+	/// with the PKCE challenge and nonce of the last authorization url.
+	struct MockIdp {
+		issuer: String,
+		authorization: Mutex<HashMap<String, String>>,
+	}
+
+	impl MockIdp {
+		/// The browser going to the authorization url.
+		fn authorize(&self, url: &str) -> Result<()> {
+			let params = Url::parse(url)?.query_pairs().into_owned().collect();
+			*self.authorization.lock().unwrap() = params;
+
+			Ok(())
+		}
+	}
+
+	async fn mock_discovery(State(idp): State<Arc<MockIdp>>) -> Json<Value> {
+		Json(json!({
+			"issuer": idp.issuer,
+			"authorization_endpoint": format!("{}/authorize", idp.issuer),
+			"token_endpoint": format!("{}/token", idp.issuer),
+			"jwks_uri": format!("{}/jwks", idp.issuer),
+		}))
+	}
+
+	async fn mock_jwks() -> Json<Value> {
+		Json(serde_json::from_str(include_str!("fixtures/idp_jwks.json")).unwrap())
+	}
+
+	async fn mock_token(
+		State(idp): State<Arc<MockIdp>>,
+		Form(form): Form<HashMap<String, String>>,
+	) -> (StatusCode, Json<Value>) {
+		let authorization = idp.authorization.lock().unwrap().clone();
+		let code_challenge = form
+			.get("code_verifier")
+			.map(|verifier| b64u_encode(Sha256::digest(verifier.as_bytes())));
+
+		if form.get("code").map(String::as_str) != Some(FX_CODE)
+			|| code_challenge.as_ref() != authorization.get("code_challenge")
+		{
+			return (
+				StatusCode::BAD_REQUEST,
+				Json(json!({ "error": "invalid_grant" })),
+			);
+		}
+
+		let now = crate::utils::time::now_utc().unix_timestamp();
+		let claims = json!({
+			"iss": idp.issuer,
+			"sub": "fx-sub",
+			"aud": FX_CLIENT_ID,
+			"iat": now,
+			"exp": now + 300,
+			"nonce": authorization.get("nonce"),
+			"email": "fx-user@example.com",
+			"email_verified": true,
+		});
+		let mut header = Header::new(Algorithm::RS256);
+		header.kid = Some(FX_KID.to_string());
+		let key = EncodingKey::from_rsa_pem(include_bytes!("fixtures/idp_key.pem"))
+			.unwrap();
+		let id_token = encode(&header, &claims, &key).unwrap();
+
+		(StatusCode::OK, Json(json!({ "id_token": id_token })))
+	}
+
+	async fn fx_client_and_idp() -> Result<(OidcClient, Arc<MockIdp>)> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let issuer = format!("http://{}", listener.local_addr()?);
+		let idp = Arc::new(MockIdp {
+			issuer: issuer.clone(),
+			authorization: Mutex::new(HashMap::new()),
+		});
+
+		let app = Router::new()
+			.route("/.well-known/openid-configuration", get(mock_discovery))
+			.route("/jwks", get(mock_jwks))
+			.route("/token", post(mock_token))
+			.with_state(idp.clone());
+		tokio::spawn(async move { axum::serve(listener, app).await });
+
+		let client = OidcClient::new(OidcConfig {
+			ISSUER: issuer,
+			CLIENT_ID: FX_CLIENT_ID.to_string(),
+			CLIENT_SECRET: None,
+			REDIRECT_URL: "http://localhost:8080/api/oidc/callback".to_string(),
+			POST_LOGIN_URL: "/".to_string(),
+			LOGIN_DURATION_SEC: 300,
+			DEFAULT_ROLE: None,
+		});
+
+		Ok((client, idp))
+	}
+
+	#[tokio::test]
+	async fn test_exchange_code_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let (client, idp) = fx_client_and_idp().await?;
+		let request = client.authorization_request().await?;
+		idp.authorize(&request.url)?;
+
+		// -- Exec
+		let claims = client
+			.exchange_code(FX_CODE, &request.code_verifier, &request.nonce)
+			.await?;
+
+		// -- Check
+		assert_eq!(claims.sub, "fx-sub");
+		assert_eq!(claims.email.as_deref(), Some("fx-user@example.com"));
+		assert_eq!(claims.email_verified, Some(true));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_exchange_code_err_code_verifier() -> Result<()> {
+		// -- Setup & Fixtures
+		let (client, idp) = fx_client_and_idp().await?;
+		let request = client.authorization_request().await?;
+		idp.authorize(&request.url)?;
+
+		// -- Exec
+		let res = client
+			.exchange_code(FX_CODE, "fx-other-verifier", &request.nonce)
+			.await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::TokenRequestFail { status: 400, .. })),
+			"Should have matched `Err(Error::TokenRequestFail)` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_exchange_code_err_nonce_not_matching() -> Result<()> {
+		// -- Setup & Fixtures
+		let (client, idp) = fx_client_and_idp().await?;
+		let request = client.authorization_request().await?;
+		idp.authorize(&request.url)?;
+
+		// -- Exec
+		let res = client
+			.exchange_code(FX_CODE, &request.code_verifier, "fx-other-nonce")
+			.await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::IdTokenNonceNotMatching)),
+			"Should have matched `Err(Error::IdTokenNonceNotMatching)` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
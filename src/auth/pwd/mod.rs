@@ -1,6 +1,7 @@
 // region:    --- Modules
 
 mod error;
+pub mod policy;
 mod scheme;
 
 pub use self::error::{Error, Result};
@@ -42,18 +43,11 @@ pub async fn validate_pwd(
 		hashed,
 	} = pwd_ref.parse()?;
 
-	let scheme_status = if scheme_name == DEFAULT_SCHEME {
-		SchemeStatus::Ok
-	} else {
-		SchemeStatus::Outdated
-	};
 	tokio::task::spawn_blocking(move || {
 		validate_for_scheme(&scheme_name, to_hash, hashed)
 	})
 	.await
-	.map_err(|_| Error::FailSpawnBlockForValidate)??;
-
-	Ok(scheme_status)
+	.map_err(|_| Error::FailSpawnBlockForValidate)?
 }
 // endregion: --- Public Functions
 
@@ -65,13 +59,20 @@ fn hash_for_scheme(scheme_name: &str, to_hash: ContentToHash) -> Result<String>
 	Ok(format!("#{scheme_name}#{pwd_hashed}"))
 }
 
+/// Outdated when not of the default scheme, or not of its current settings.
 fn validate_for_scheme(
 	scheme_name: &str,
 	to_hash: ContentToHash,
 	pwd_ref: String,
-) -> Result<()> {
-	get_scheme(scheme_name)?.validate(&to_hash, &pwd_ref)?;
-	Ok(())
+) -> Result<SchemeStatus> {
+	let scheme = get_scheme(scheme_name)?;
+	scheme.validate(&to_hash, &pwd_ref)?;
+
+	if scheme_name == DEFAULT_SCHEME {
+		Ok(scheme.status(&pwd_ref)?)
+	} else {
+		Ok(SchemeStatus::Outdated)
+	}
 }
 
 struct PwdParts {
@@ -0,0 +1,193 @@
+//! The rules of the new pwds (length, character classes, common pwds). The
+//! reuse of the previous pwds is checked by the model (`UserBmc`), with the
+//! `PwdRule::NotReused` of this module.
+
+use crate::auth::config::auth_config;
+use crate::utils::time::now_utc;
+use serde::Serialize;
+use sqlx::types::time::OffsetDateTime;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use time::Duration;
+
+/// The bundled list of the most common pwds, one (lowercase) per line.
+const COMMON_PWDS: &str = include_str!("common_pwds.txt");
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PwdCharClass {
+	Lowercase,
+	Uppercase,
+	Digit,
+	Symbol,
+}
+
+impl PwdCharClass {
+	fn matches(self, c: char) -> bool {
+		match self {
+			Self::Lowercase => c.is_lowercase(),
+			Self::Uppercase => c.is_uppercase(),
+			Self::Digit => c.is_numeric(),
+			Self::Symbol => !c.is_alphanumeric(),
+		}
+	}
+}
+
+impl FromStr for PwdCharClass {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"lowercase" => Ok(Self::Lowercase),
+			"uppercase" => Ok(Self::Uppercase),
+			"digit" => Ok(Self::Digit),
+			"symbol" => Ok(Self::Symbol),
+			_ => Err(()),
+		}
+	}
+}
+
+/// A rule failed by a new pwd, returned to the client.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(tag = "rule")]
+pub enum PwdRule {
+	MinLength { min: usize },
+	CharClass { class: PwdCharClass },
+	NotCommon,
+	NotReused { last: usize },
+}
+
+pub struct PwdPolicy {
+	pub min_length: usize,
+	pub required_classes: Vec<PwdCharClass>,
+	/// Previous pwds a new one cannot reuse.
+	pub history_size: usize,
+	/// None for no expiry.
+	pub max_age: Option<Duration>,
+}
+
+impl PwdPolicy {
+	pub fn from_config() -> Self {
+		let config = auth_config();
+
+		PwdPolicy {
+			min_length: config.PWD_MIN_LENGTH,
+			required_classes: config.PWD_REQUIRED_CLASSES.clone(),
+			history_size: config.PWD_HISTORY_SIZE,
+			max_age: (config.PWD_MAX_AGE_DAYS > 0)
+				.then(|| Duration::days(config.PWD_MAX_AGE_DAYS)),
+		}
+	}
+
+	/// Whether a pwd changed at `pwd_mtime` must be changed before login.
+	pub fn is_expired(&self, pwd_mtime: OffsetDateTime) -> bool {
+		self.max_age
+			.is_some_and(|max_age| now_utc() - pwd_mtime > max_age)
+	}
+
+	/// The failed rules of the pwd, none when it complies.
+	pub fn check(&self, pwd_clear: &str) -> Vec<PwdRule> {
+		let mut failed_rules = Vec::new();
+
+		if pwd_clear.chars().count() < self.min_length {
+			failed_rules.push(PwdRule::MinLength {
+				min: self.min_length,
+			});
+		}
+
+		for class in &self.required_classes {
+			if !pwd_clear.chars().any(|c| class.matches(c)) {
+				failed_rules.push(PwdRule::CharClass { class: *class });
+			}
+		}
+
+		if common_pwds().contains(pwd_clear.to_lowercase().as_str()) {
+			failed_rules.push(PwdRule::NotCommon);
+		}
+
+		failed_rules
+	}
+}
+
+fn common_pwds() -> &'static HashSet<&'static str> {
+	static INSTANCE: OnceLock<HashSet<&'static str>> = OnceLock::new();
+
+	INSTANCE.get_or_init(|| {
+		COMMON_PWDS
+			.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty())
+			.collect()
+	})
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use anyhow::Result;
+
+	fn fx_policy() -> PwdPolicy {
+		PwdPolicy {
+			min_length: 10,
+			required_classes: vec![
+				PwdCharClass::Lowercase,
+				PwdCharClass::Digit,
+				PwdCharClass::Symbol,
+			],
+			history_size: 3,
+			max_age: Some(Duration::days(90)),
+		}
+	}
+
+	#[test]
+	fn test_check_ok() -> Result<()> {
+		// -- Exec
+		let failed_rules = fx_policy().check("correct horse 42");
+
+		// -- Check
+		assert!(failed_rules.is_empty(), "{failed_rules:?}");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_check_err_failed_rules() -> Result<()> {
+		// -- Exec
+		let failed_rules = fx_policy().check("Password");
+
+		// -- Check
+		assert_eq!(
+			failed_rules,
+			vec![
+				PwdRule::MinLength { min: 10 },
+				PwdRule::CharClass {
+					class: PwdCharClass::Digit
+				},
+				PwdRule::CharClass {
+					class: PwdCharClass::Symbol
+				},
+				PwdRule::NotCommon,
+			]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_is_expired_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_policy = fx_policy();
+		let now = now_utc();
+
+		// -- Check
+		assert!(!fx_policy.is_expired(now - Duration::days(89)));
+		assert!(fx_policy.is_expired(now - Duration::days(91)));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -7,6 +7,8 @@ pub enum Error {
 	Key,
 	Salt,
 	Hash,
+	Params,
+	PepperNotFound(String),
 	PwdValidate,
 	SchemeNotFound(String),
 }
@@ -2,11 +2,12 @@
 mod error;
 mod scheme_01;
 mod scheme_02;
+mod scheme_03;
 pub use crate::auth::pwd::scheme::error::{Error, Result};
 use crate::auth::pwd::ContentToHash;
 use enum_dispatch::enum_dispatch;
 // endregion: --- Modules
-pub const DEFAULT_SCHEME: &str = "02";
+pub const DEFAULT_SCHEME: &str = "03";
 #[derive(Debug)]
 pub enum SchemeStatus {
 	Ok,
@@ -16,16 +17,23 @@ pub enum SchemeStatus {
 pub trait Scheme {
 	fn hash(&self, to_hash: &ContentToHash) -> Result<String>;
 	fn validate(&self, to_hash: &ContentToHash, pwd_ref: &str) -> Result<()>;
+	/// Outdated when the (validated) `pwd_ref` is not of the current settings of
+	/// its scheme (e.g., its parameters or key).
+	fn status(&self, _pwd_ref: &str) -> Result<SchemeStatus> {
+		Ok(SchemeStatus::Ok)
+	}
 }
 #[enum_dispatch(Scheme)]
 pub enum SchemeDispatcher {
 	Scheme01(scheme_01::Scheme01),
 	Scheme02(scheme_02::Scheme02),
+	Scheme03(scheme_03::Scheme03),
 }
 pub fn get_scheme(scheme_name: &str) -> Result<impl Scheme> {
 	match scheme_name {
 		"01" => Ok(SchemeDispatcher::Scheme01(scheme_01::Scheme01)),
 		"02" => Ok(SchemeDispatcher::Scheme02(scheme_02::Scheme02)),
+		"03" => Ok(SchemeDispatcher::Scheme03(scheme_03::Scheme03)),
 		_ => Err(Error::SchemeNotFound(scheme_name.to_string())),
 	}
 }
@@ -0,0 +1,194 @@
+use super::{Error, Result, SchemeStatus};
+use crate::auth::auth_config;
+use crate::auth::config::PwdPepper;
+use crate::auth::pwd::scheme::Scheme;
+use crate::auth::pwd::ContentToHash;
+use argon2::password_hash::SaltString;
+use argon2::{
+	Algorithm, Argon2, KeyId, Params, ParamsBuilder, PasswordHash,
+	PasswordHasher as _, PasswordVerifier as _, Version,
+};
+
+/// Argon2id, the parameters and the kid of the pepper being in the hash
+/// (e.g., `$argon2id$v=19$m=19456,t=2,p=1,keyid=cDE$salt$hash`).
+pub struct Scheme03;
+
+impl Scheme for Scheme03 {
+	fn hash(&self, to_hash: &ContentToHash) -> Result<String> {
+		let pepper = current_pepper();
+		hash(pepper, current_params(pepper)?, to_hash)
+	}
+
+	fn validate(&self, to_hash: &ContentToHash, pwd_ref: &str) -> Result<()> {
+		let parsed_hash_ref = PasswordHash::new(pwd_ref).map_err(|_| Error::Hash)?;
+		let params_ref =
+			Params::try_from(&parsed_hash_ref).map_err(|_| Error::Hash)?;
+		let pepper = pepper_of_kid(params_ref.keyid())?;
+
+		get_argon2(pepper, params_ref)?
+			.verify_password(to_hash.content.as_bytes(), &parsed_hash_ref)
+			.map_err(|_| Error::PwdValidate)
+	}
+
+	fn status(&self, pwd_ref: &str) -> Result<SchemeStatus> {
+		let parsed_hash_ref = PasswordHash::new(pwd_ref).map_err(|_| Error::Hash)?;
+		let params_ref =
+			Params::try_from(&parsed_hash_ref).map_err(|_| Error::Hash)?;
+		let params = current_params(current_pepper())?;
+
+		let is_current = params_ref.m_cost() == params.m_cost()
+			&& params_ref.t_cost() == params.t_cost()
+			&& params_ref.p_cost() == params.p_cost()
+			&& params_ref.keyid() == params.keyid();
+
+		if is_current {
+			Ok(SchemeStatus::Ok)
+		} else {
+			Ok(SchemeStatus::Outdated)
+		}
+	}
+}
+
+// region:    --- Privates
+
+fn hash(
+	pepper: &PwdPepper,
+	params: Params,
+	to_hash: &ContentToHash,
+) -> Result<String> {
+	let salt_b64 =
+		SaltString::encode_b64(to_hash.salt.as_bytes()).map_err(|_| Error::Salt)?;
+	let pwd = get_argon2(pepper, params)?
+		.hash_password(to_hash.content.as_bytes(), &salt_b64)
+		.map_err(|_| Error::Hash)?
+		.to_string();
+
+	Ok(pwd)
+}
+
+fn get_argon2(pepper: &PwdPepper, params: Params) -> Result<Argon2<'_>> {
+	Argon2::new_with_secret(&pepper.key, Algorithm::Argon2id, Version::V0x13, params)
+		.map_err(|_| Error::Key)
+}
+
+/// The first of the `PWD_PEPPERS` (never empty).
+fn current_pepper() -> &'static PwdPepper {
+	&auth_config().PWD_PEPPERS[0]
+}
+
+fn current_params(pepper: &PwdPepper) -> Result<Params> {
+	let config = auth_config();
+	let keyid = KeyId::new(pepper.kid.as_bytes()).map_err(|_| Error::Params)?;
+
+	ParamsBuilder::new()
+		.m_cost(config.PWD_ARGON2_M_COST)
+		.t_cost(config.PWD_ARGON2_T_COST)
+		.p_cost(config.PWD_ARGON2_P_COST)
+		.keyid(keyid)
+		.build()
+		.map_err(|_| Error::Params)
+}
+
+fn pepper_of_kid(kid: &[u8]) -> Result<&'static PwdPepper> {
+	auth_config()
+		.PWD_PEPPERS
+		.iter()
+		.find(|pepper| pepper.kid.as_bytes() == kid)
+		.ok_or_else(|| {
+			Error::PepperNotFound(String::from_utf8_lossy(kid).to_string())
+		})
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use anyhow::Result;
+	use uuid::Uuid;
+
+	#[test]
+	fn test_scheme_03_hash_validate_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_to_hash = ContentToHash {
+			content: "hello world".to_string(),
+			salt: Uuid::parse_str("f05e8961-d6ad-4086-9e78-a6de065e5453")?,
+		};
+		let fx_kid = &current_pepper().kid;
+
+		// -- Exec
+		let scheme = Scheme03;
+		let res = scheme.hash(&fx_to_hash)?;
+
+		// -- Check
+		assert!(res.starts_with("$argon2id$v=19$"));
+		let params_ref = Params::try_from(&PasswordHash::new(&res)?)?;
+		assert_eq!(params_ref.keyid(), fx_kid.as_bytes());
+		scheme.validate(&fx_to_hash, &res)?;
+		assert!(matches!(scheme.status(&res)?, SchemeStatus::Ok));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_scheme_03_status_outdated_params() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_to_hash = ContentToHash {
+			content: "hello world".to_string(),
+			salt: Uuid::parse_str("f05e8961-d6ad-4086-9e78-a6de065e5453")?,
+		};
+		let pepper = current_pepper();
+		let current = current_params(pepper)?;
+		let fx_params = ParamsBuilder::new()
+			.m_cost(current.m_cost())
+			.t_cost(current.t_cost() + 1)
+			.p_cost(current.p_cost())
+			.keyid(KeyId::new(pepper.kid.as_bytes())?)
+			.build()?;
+		let fx_pwd_ref = hash(pepper, fx_params, &fx_to_hash)?;
+
+		// -- Exec
+		let scheme = Scheme03;
+		let res = scheme.status(&fx_pwd_ref)?;
+
+		// -- Check
+		// Still valid with its own parameters.
+		scheme.validate(&fx_to_hash, &fx_pwd_ref)?;
+		assert!(
+			matches!(res, SchemeStatus::Outdated),
+			"Should have matched `SchemeStatus::Outdated` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_scheme_03_validate_err_pepper_not_found() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_to_hash = ContentToHash {
+			content: "hello world".to_string(),
+			salt: Uuid::parse_str("f05e8961-d6ad-4086-9e78-a6de065e5453")?,
+		};
+		let fx_pepper = PwdPepper {
+			kid: "fx-gone".to_string(),
+			key: current_pepper().key.clone(),
+		};
+		let fx_params = ParamsBuilder::new()
+			.keyid(KeyId::new(fx_pepper.kid.as_bytes())?)
+			.build()?;
+		let fx_pwd_ref = hash(&fx_pepper, fx_params, &fx_to_hash)?;
+
+		// -- Exec
+		let res = Scheme03.validate(&fx_to_hash, &fx_pwd_ref);
+
+		// -- Check
+		assert!(
+			matches!(&res, Err(Error::PepperNotFound(kid)) if kid == "fx-gone"),
+			"Should have matched `Err(Error::PepperNotFound(\"fx-gone\"))` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+}
+// endregion: --- Tests
@@ -6,9 +6,17 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub enum Error {
 	HmacFailNewFromSlice,
 
+	// -- Key ring
+	RingKeyFailNewFromSlice,
+	RingKeyFailEncrypt,
+	RingKeyFailDecrypt,
+	RingKeyInvalidFormat,
+
 	InvalidFormat,
 	CannotDecodeIdent,
 	CannotDecodeExp,
+	/// Unknown or retired key.
+	KidNotAccepted,
 	SignatureNotMatching,
 	ExpNotIso,
 	Expired,
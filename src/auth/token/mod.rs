@@ -1,6 +1,7 @@
 // region:    --- Modules
 
 mod error;
+pub mod ring;
 
 pub use self::error::{Error, Result};
 
@@ -17,13 +18,14 @@ use uuid::Uuid;
 
 // region:    --- Token Type
 
-/// String format: `ident_b64u.exp_b64u.sign_b64u`
+/// String format: `[kid.]ident_b64u.exp_b64u.sign_b64u`
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct Token {
-	pub ident: String,     // Identifier (username for example).
-	pub exp: String,       // Expiration date in Rfc3339.
-	pub sign_b64u: String, // Signature, base64url encoded.
+	pub kid: Option<String>, // Key id of the ring, none for the `TOKEN_KEY`.
+	pub ident: String,       // Identifier (username for example).
+	pub exp: String,         // Expiration date in Rfc3339.
+	pub sign_b64u: String,   // Signature, base64url encoded.
 }
 
 impl FromStr for Token {
@@ -31,12 +33,19 @@ impl FromStr for Token {
 
 	fn from_str(token_str: &str) -> std::result::Result<Self, Self::Err> {
 		let splits: Vec<&str> = token_str.split('.').collect();
-		if splits.len() != 3 {
-			return Err(Error::InvalidFormat);
-		}
-		let (ident_b64u, exp_b64u, sign_b64u) = (splits[0], splits[1], splits[2]);
+		let (kid, ident_b64u, exp_b64u, sign_b64u) = match splits[..] {
+			[ident_b64u, exp_b64u, sign_b64u] => {
+				(None, ident_b64u, exp_b64u, sign_b64u)
+			}
+			[kid, ident_b64u, exp_b64u, sign_b64u] => {
+				(Some(kid.to_string()), ident_b64u, exp_b64u, sign_b64u)
+			}
+			_ => return Err(Error::InvalidFormat),
+		};
 
 		Ok(Self {
+			kid,
+
 			ident: b64u_decode_to_string(ident_b64u)
 				.map_err(|_| Error::CannotDecodeIdent)?,
 
@@ -50,6 +59,9 @@ impl FromStr for Token {
 
 impl Display for Token {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if let Some(kid) = &self.kid {
+			write!(f, "{kid}.")?;
+		}
 		write!(
 			f,
 			"{}.{}.{}",
@@ -66,48 +78,89 @@ impl Display for Token {
 
 pub fn generate_web_token(user: &str, salt: Uuid) -> Result<Token> {
 	let config = &auth_config();
-	_generate_token(user, config.TOKEN_DURATION_SEC, salt, &config.TOKEN_KEY)
+	_generate_token(user, config.TOKEN_DURATION_SEC, salt)
 }
 
-pub fn validate_web_token(origin_token: &Token, salt: Uuid) -> Result<()> {
+/// Token of the `Authorization: Bearer` header (non-browser clients), with
+/// its own duration. Validated as a web token, but never refreshed.
+pub fn generate_header_token(user: &str, salt: Uuid) -> Result<Token> {
 	let config = &auth_config();
-	_validate_token_sign_and_exp(origin_token, salt, &config.TOKEN_KEY)?;
+	_generate_token(user, config.HEADER_TOKEN_DURATION_SEC, salt)
+}
+
+pub fn validate_web_token(origin_token: &Token, salt: Uuid) -> Result<()> {
+	_validate_token_sign_and_exp(origin_token, salt)?;
 
 	Ok(())
 }
 
 // endregion: --- Web Token Gen and Validation
 
-// region:    --- (private) Token Gen and Validation
+// region:    --- Pwd Reset Token Gen and Validation
 
-fn _generate_token(
-	ident: &str,
-	duration_sec: f64,
+/// Signed with the user `token_salt`, so that rotating it also invalidates the
+/// pending pwd resets.
+pub fn generate_pwd_reset_token(ident: &str, salt: Uuid) -> Result<Token> {
+	let config = &auth_config();
+	_generate_token(ident, config.PWD_RESET_TOKEN_DURATION_SEC, salt)
+}
+
+pub fn validate_pwd_reset_token(origin_token: &Token, salt: Uuid) -> Result<()> {
+	_validate_token_sign_and_exp(origin_token, salt)?;
+
+	Ok(())
+}
+
+// endregion: --- Pwd Reset Token Gen and Validation
+
+// region:    --- Login Challenge Token Gen and Validation
+
+/// Token between the pwd and the TOTP code of a login, short lived. Its ident
+/// is never a session `token_id`, so it cannot be used as a web token.
+pub fn generate_login_challenge_token(ident: &str, salt: Uuid) -> Result<Token> {
+	let config = &auth_config();
+	_generate_token(ident, config.LOGIN_CHALLENGE_DURATION_SEC, salt)
+}
+
+pub fn validate_login_challenge_token(
+	origin_token: &Token,
 	salt: Uuid,
-	key: &[u8],
-) -> Result<Token> {
+) -> Result<()> {
+	_validate_token_sign_and_exp(origin_token, salt)?;
+
+	Ok(())
+}
+
+// endregion: --- Login Challenge Token Gen and Validation
+
+// region:    --- (private) Token Gen and Validation
+
+/// Signed with the active key of the ring.
+fn _generate_token(ident: &str, duration_sec: f64, salt: Uuid) -> Result<Token> {
 	// -- Compute the two first components.
 	let ident = ident.to_string();
 	let exp = now_utc_plus_sec_str(duration_sec);
 
 	// -- Sign the two first components.
-	let sign_b64u = _token_sign_into_b64u(&ident, &exp, salt, key)?;
+	let (kid, key) = ring::signing_key(now_utc());
+	let sign_b64u = _token_sign_into_b64u(&ident, &exp, salt, &key)?;
 
 	Ok(Token {
+		kid,
 		ident,
 		exp,
 		sign_b64u,
 	})
 }
 
-fn _validate_token_sign_and_exp(
-	origin_token: &Token,
-	salt: Uuid,
-	key: &[u8],
-) -> Result<()> {
+fn _validate_token_sign_and_exp(origin_token: &Token, salt: Uuid) -> Result<()> {
+	let now = now_utc();
+
 	// -- Validate signature.
+	let key = ring::validation_key(origin_token.kid.as_deref(), now)
+		.ok_or(Error::KidNotAccepted)?;
 	let new_sign_b64u =
-		_token_sign_into_b64u(&origin_token.ident, &origin_token.exp, salt, key)?;
+		_token_sign_into_b64u(&origin_token.ident, &origin_token.exp, salt, &key)?;
 
 	if new_sign_b64u != origin_token.sign_b64u {
 		return Err(Error::SignatureNotMatching);
@@ -115,7 +168,6 @@ fn _validate_token_sign_and_exp(
 
 	// -- Validate expiration.
 	let origin_exp = parse_utc(&origin_token.exp).map_err(|_| Error::ExpNotIso)?;
-	let now = now_utc();
 
 	if origin_exp < now {
 		return Err(Error::Expired);
@@ -157,6 +209,7 @@ fn _token_sign_into_b64u(
 mod tests {
 	use super::*;
 	use anyhow::Result;
+	use serial_test::serial;
 	use std::thread;
 	use std::time::Duration;
 
@@ -166,6 +219,7 @@ mod tests {
 		let fx_token_str =
 			"ZngtaWRlbnQtMDE.MjAyMy0wNS0xN1QxNTozMDowMFo.some-sign-b64u-encoded";
 		let fx_token = Token {
+			kid: None,
 			ident: "fx-ident-01".to_string(),
 			exp: "2023-05-17T15:30:00Z".to_string(),
 			sign_b64u: "some-sign-b64u-encoded".to_string(),
@@ -183,6 +237,27 @@ mod tests {
 		let fx_token_str =
 			"ZngtaWRlbnQtMDE.MjAyMy0wNS0xN1QxNTozMDowMFo.some-sign-b64u-encoded";
 		let fx_token = Token {
+			kid: None,
+			ident: "fx-ident-01".to_string(),
+			exp: "2023-05-17T15:30:00Z".to_string(),
+			sign_b64u: "some-sign-b64u-encoded".to_string(),
+		};
+
+		// -- Exec
+		let token: Token = fx_token_str.parse()?;
+
+		// -- Check
+		assert_eq!(token, fx_token);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_token_from_str_kid_ok() -> Result<()> {
+		// -- Fixtures
+		let fx_token_str = "fx-kid-01.ZngtaWRlbnQtMDE.MjAyMy0wNS0xN1QxNTozMDowMFo.some-sign-b64u-encoded";
+		let fx_token = Token {
+			kid: Some("fx-kid-01".to_string()),
 			ident: "fx-ident-01".to_string(),
 			exp: "2023-05-17T15:30:00Z".to_string(),
 			sign_b64u: "some-sign-b64u-encoded".to_string(),
@@ -193,10 +268,12 @@ mod tests {
 
 		// -- Check
 		assert_eq!(token, fx_token);
+		assert_eq!(token.to_string(), fx_token_str);
 
 		Ok(())
 	}
 
+	#[serial]
 	#[test]
 	fn test_validate_web_token_ok() -> Result<()> {
 		// -- Setup & Fixtures
@@ -204,9 +281,7 @@ mod tests {
 		let fx_salt =
 			Uuid::parse_str("f05e8961-d6ad-4086-9e78-a6de065e5453").unwrap();
 		let fx_duration_sec = 0.02; // 20ms
-		let token_key = &auth_config().TOKEN_KEY;
-		let fx_token =
-			_generate_token(fx_user, fx_duration_sec, fx_salt, token_key)?;
+		let fx_token = _generate_token(fx_user, fx_duration_sec, fx_salt)?;
 
 		// -- Exec
 		thread::sleep(Duration::from_millis(10));
@@ -218,6 +293,7 @@ mod tests {
 		Ok(())
 	}
 
+	#[serial]
 	#[test]
 	fn test_validate_web_token_err_expired() -> Result<()> {
 		// -- Setup & Fixtures
@@ -225,9 +301,7 @@ mod tests {
 		let fx_salt =
 			Uuid::parse_str("f05e8961-d6ad-4086-9e78-a6de065e5453").unwrap();
 		let fx_duration_sec = 0.01; // 10ms
-		let token_key = &auth_config().TOKEN_KEY;
-		let fx_token =
-			_generate_token(fx_user, fx_duration_sec, fx_salt, token_key)?;
+		let fx_token = _generate_token(fx_user, fx_duration_sec, fx_salt)?;
 
 		// -- Exec
 		thread::sleep(Duration::from_millis(20));
@@ -0,0 +1,193 @@
+//! The signing keys of the tokens. The `TOKEN_KEY` is the first key of the
+//! ring (its tokens have no `kid`), followed by the rolled keys (of the
+//! `token_key` table), each key being active from its `active_from` until the
+//! next one and still accepted for the `TOKEN_KEY_GRACE_SEC` after that.
+
+use crate::auth::config::auth_config;
+use crate::auth::token::{Error, Result};
+use crate::utils::b64::{b64u_decode, b64u_encode};
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use rand::RngCore;
+use std::sync::{OnceLock, RwLock};
+use time::{Duration, OffsetDateTime};
+
+/// Same size as the `TOKEN_KEY` (HMAC-SHA512).
+const KEY_LEN: usize = 64;
+const KID_LEN: usize = 8;
+const NONCE_LEN: usize = 12;
+
+/// A key of the ring, without its secret once retired.
+#[derive(Clone, Debug)]
+pub struct RingKey {
+	pub kid: String,
+	pub key: Option<Vec<u8>>,
+	pub active_from: OffsetDateTime,
+}
+
+/// The rolled keys, ordered by `active_from`.
+fn ring() -> &'static RwLock<Vec<RingKey>> {
+	static INSTANCE: OnceLock<RwLock<Vec<RingKey>>> = OnceLock::new();
+
+	INSTANCE.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+/// Replaces the rolled keys (e.g., as loaded from the db).
+pub fn set_ring_keys(mut keys: Vec<RingKey>) {
+	keys.sort_by_key(|key| key.active_from);
+	*ring().write().unwrap_or_else(|ex| ex.into_inner()) = keys;
+}
+
+/// A new key, its kid and its secret encrypted with the `TOKEN_RING_KEY`
+/// (format: `nonce_b64u.ciphertext_b64u`).
+pub fn generate_ring_key() -> Result<(String, String)> {
+	let mut kid = [0u8; KID_LEN];
+	rand::thread_rng().fill_bytes(&mut kid);
+	let mut key = [0u8; KEY_LEN];
+	rand::thread_rng().fill_bytes(&mut key);
+
+	let mut nonce = [0u8; NONCE_LEN];
+	rand::thread_rng().fill_bytes(&mut nonce);
+	let ciphertext = cipher()?
+		.encrypt(Nonce::from_slice(&nonce), key.as_slice())
+		.map_err(|_| Error::RingKeyFailEncrypt)?;
+
+	Ok((
+		b64u_encode(kid),
+		format!("{}.{}", b64u_encode(nonce), b64u_encode(ciphertext)),
+	))
+}
+
+pub fn decrypt_ring_key(key_enc: &str) -> Result<Vec<u8>> {
+	let (nonce_b64u, ciphertext_b64u) =
+		key_enc.split_once('.').ok_or(Error::RingKeyInvalidFormat)?;
+	let nonce = b64u_decode(nonce_b64u).map_err(|_| Error::RingKeyInvalidFormat)?;
+	let ciphertext =
+		b64u_decode(ciphertext_b64u).map_err(|_| Error::RingKeyInvalidFormat)?;
+	if nonce.len() != NONCE_LEN {
+		return Err(Error::RingKeyInvalidFormat);
+	}
+
+	cipher()?
+		.decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+		.map_err(|_| Error::RingKeyFailDecrypt)
+}
+
+/// The kid (none for the `TOKEN_KEY`) and key signing the new tokens.
+pub(super) fn signing_key(now: OffsetDateTime) -> (Option<String>, Vec<u8>) {
+	let keys = ring().read().unwrap_or_else(|ex| ex.into_inner());
+
+	keys.iter()
+		.rev()
+		.filter(|key| key.active_from <= now)
+		.find_map(|key| Some((Some(key.kid.clone()), key.key.clone()?)))
+		.unwrap_or_else(|| (None, auth_config().TOKEN_KEY.clone()))
+}
+
+/// The key of the `kid` (none for the `TOKEN_KEY`), if still accepted: until
+/// the `TOKEN_KEY_GRACE_SEC` after the next key is active.
+pub(super) fn validation_key(
+	kid: Option<&str>,
+	now: OffsetDateTime,
+) -> Option<Vec<u8>> {
+	let keys = ring().read().unwrap_or_else(|ex| ex.into_inner());
+	let grace = Duration::seconds_f64(auth_config().TOKEN_KEY_GRACE_SEC);
+
+	let (key, next) = match kid {
+		None => (Some(auth_config().TOKEN_KEY.clone()), keys.first()),
+		Some(kid) => {
+			let idx = keys.iter().position(|key| key.kid == kid)?;
+			(keys[idx].key.clone(), keys.get(idx + 1))
+		}
+	};
+
+	match next {
+		Some(next) if next.active_from + grace <= now => None,
+		_ => key,
+	}
+}
+
+fn cipher() -> Result<Aes256Gcm> {
+	Aes256Gcm::new_from_slice(&auth_config().TOKEN_RING_KEY)
+		.map_err(|_| Error::RingKeyFailNewFromSlice)
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utils::time::now_utc;
+	use anyhow::Result;
+	use serial_test::serial;
+
+	fn fx_key(kid: &str, active_from: OffsetDateTime) -> Result<RingKey> {
+		let (_, key_enc) = generate_ring_key()?;
+		Ok(RingKey {
+			kid: kid.to_string(),
+			key: Some(decrypt_ring_key(&key_enc)?),
+			active_from,
+		})
+	}
+
+	#[serial]
+	#[test]
+	fn test_ring_keys_rolled_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let now = now_utc();
+		let grace = Duration::seconds_f64(auth_config().TOKEN_KEY_GRACE_SEC);
+		// Retired beyond the grace, retired within it, active, then not yet.
+		let fx_keys = vec![
+			fx_key("fx-kid-01", now - grace * 3)?,
+			fx_key("fx-kid-02", now - grace * 2)?,
+			fx_key("fx-kid-03", now - grace / 2)?,
+			fx_key("fx-kid-04", now + Duration::minutes(1))?,
+		];
+
+		// -- Exec
+		set_ring_keys(fx_keys.clone());
+		let (signing_kid, signing_key) = signing_key(now);
+
+		// -- Check
+		assert_eq!(signing_kid.as_deref(), Some("fx-kid-03"));
+		assert_eq!(Some(signing_key), fx_keys[2].key);
+		// The `TOKEN_KEY` too is retired.
+		assert!(validation_key(None, now).is_none());
+		assert!(validation_key(Some("fx-kid-01"), now).is_none());
+		assert_eq!(validation_key(Some("fx-kid-02"), now), fx_keys[1].key);
+		assert_eq!(validation_key(Some("fx-kid-03"), now), fx_keys[2].key);
+		assert_eq!(validation_key(Some("fx-kid-04"), now), fx_keys[3].key);
+		assert!(validation_key(Some("fx-kid-unknown"), now).is_none());
+
+		// -- Clean
+		set_ring_keys(Vec::new());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_decrypt_ring_key_err_tampered() -> Result<()> {
+		// -- Setup & Fixtures
+		let (_, key_enc) = generate_ring_key()?;
+		let (nonce, ciphertext) = key_enc.split_once('.').unwrap();
+		let first = if ciphertext.starts_with('A') {
+			'B'
+		} else {
+			'A'
+		};
+		let fx_key_enc = format!("{nonce}.{first}{}", &ciphertext[1..]);
+
+		// -- Exec
+		let res = decrypt_ring_key(&fx_key_enc);
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::RingKeyFailDecrypt)),
+			"Should have matched `Err(Error::RingKeyFailDecrypt)` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -1,4 +1,5 @@
-use crate::utils::envs::get_env;
+use crate::utils::envs::{get_env, Error};
+use axum_client_ip::SecureClientIpSource;
 use std::sync::OnceLock;
 
 pub fn web_config() -> &'static WebConfig {
@@ -14,6 +15,11 @@ pub fn web_config() -> &'static WebConfig {
 #[allow(non_snake_case)]
 pub struct WebConfig {
 	pub WEB_FOLDER: String,
+	/// The page of the pwd reset links, the token being its `token` param.
+	pub PWD_RESET_URL: String,
+	/// Where the client ip is taken from, the peer address unless behind a
+	/// trusted proxy (e.g., `RightmostXForwardedFor`, `XRealIp`).
+	pub CLIENT_IP_SOURCE: SecureClientIpSource,
 
 	pub AWS_BUCKET_NAME: String,
 }
@@ -22,6 +28,13 @@ impl WebConfig {
 	fn load_from_env() -> crate::utils::envs::Result<WebConfig> {
 		Ok(WebConfig {
 			WEB_FOLDER: get_env("SERVICE_WEB_FOLDER")?,
+			PWD_RESET_URL: get_env("SERVICE_PWD_RESET_URL")?,
+			CLIENT_IP_SOURCE: match get_env("SERVICE_CLIENT_IP_SOURCE") {
+				Ok(source) => source
+					.parse()
+					.map_err(|_| Error::WrongFormat("SERVICE_CLIENT_IP_SOURCE"))?,
+				Err(_) => SecureClientIpSource::ConnectInfo,
+			},
 
 			AWS_BUCKET_NAME: get_env("AWS_BUCKET_NAME")?,
 		})
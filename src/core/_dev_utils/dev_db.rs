@@ -56,7 +56,7 @@ pub async fn init_dev_db() -> Result<(), Box<dyn std::error::Error>> {
 	let demo1_user: User = UserBmc::first_by_username(&ctx, &mm, "demo1")
 		.await?
 		.unwrap();
-	UserBmc::update_pwd(&ctx, &mm, demo1_user.id, DEMO_PWD).await?;
+	UserBmc::set_pwd(&ctx, &mm, demo1_user.id, DEMO_PWD).await?;
 	info!("{:<12} - init_dev_db - set demo1 pwd", "FOR-DEV-ONLY");
 
 	Ok(())
@@ -68,16 +68,40 @@ async fn pexec(db: &Db, file: &str) -> Result<(), sqlx::Error> {
 	// -- Read the file.
 	let content = fs::read_to_string(file)?;
 
-	// FIXME: Make the split more sql proof.
-	let sqls: Vec<&str> = content.split(';').collect();
-
-	for sql in sqls {
-		sqlx::query(sql).execute(db).await?;
+	for sql in split_sql(&content) {
+		sqlx::query(&sql).execute(db).await?;
 	}
 
 	Ok(())
 }
 
+/// Splits the statements on `;`, except in the `$$` quoted bodies
+/// (e.g., of the functions).
+fn split_sql(content: &str) -> Vec<String> {
+	let mut sqls = vec![String::new()];
+
+	for (i, part) in content.split("$$").enumerate() {
+		let sql = sqls.last_mut().unwrap();
+		if i > 0 {
+			sql.push_str("$$");
+		}
+
+		// Odd parts are inside a `$$` body.
+		if i % 2 == 1 {
+			sql.push_str(part);
+			continue;
+		}
+
+		let mut statements = part.split(';');
+		if let Some(first) = statements.next() {
+			sql.push_str(first);
+		}
+		sqls.extend(statements.map(str::to_string));
+	}
+
+	sqls
+}
+
 async fn new_db_pool(db_con_url: &str) -> Result<Db, sqlx::Error> {
 	PgPoolOptions::new()
 		.max_connections(1)
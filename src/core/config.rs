@@ -1,4 +1,4 @@
-use crate::utils::envs::get_env;
+use crate::utils::envs::{get_env, get_env_parse};
 use std::sync::OnceLock;
 
 pub fn core_config() -> &'static CoreConfig {
@@ -15,6 +15,10 @@ pub fn core_config() -> &'static CoreConfig {
 pub struct CoreConfig {
 	// -- Db
 	pub DB_URL: String,
+
+	// -- Search
+	/// pg_trgm similarity threshold (0 to 1) of the fuzzy matches.
+	pub SEARCH_SIMILARITY_THRESHOLD: f64,
 }
 
 impl CoreConfig {
@@ -22,6 +26,11 @@ impl CoreConfig {
 		Ok(CoreConfig {
 			// -- Db
 			DB_URL: get_env("SERVICE_DB_URL")?,
+
+			// -- Search
+			SEARCH_SIMILARITY_THRESHOLD: get_env_parse(
+				"SERVICE_SEARCH_SIMILARITY_THRESHOLD",
+			)?,
 		})
 	}
 }
@@ -9,21 +9,65 @@ pub use self::error::{Error, Result};
 #[derive(Clone, Debug)]
 pub struct Ctx {
 	user_id: i64,
+	session_id: Option<i64>,
+	key_scope: Option<KeyScope>,
+	client: Option<ClientInfo>,
+}
+
+/// Where a request comes from, for the login history.
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+	pub ip: Option<String>,
+	pub user_agent: Option<String>,
+}
+
+/// Restrictions of a request authenticated with an api key.
+#[derive(Clone, Debug)]
+pub struct KeyScope {
+	/// Replaces the user's assigned role.
+	pub role_name: String,
+	/// The only structures the key can access (all the user's when `None`).
+	pub project_ids: Option<Vec<i64>>,
 }
 
 // Constructor.
 impl Ctx {
 	pub fn root_ctx() -> Self {
-		Ctx { user_id: 0 }
+		Ctx {
+			user_id: 0,
+			session_id: None,
+			key_scope: None,
+			client: None,
+		}
 	}
 
 	pub fn new(user_id: i64) -> Result<Self> {
 		if user_id == 0 {
 			Err(Error::CtxCannotNewRootCtx)
 		} else {
-			Ok(Self { user_id })
+			Ok(Self {
+				user_id,
+				session_id: None,
+				key_scope: None,
+				client: None,
+			})
 		}
 	}
+
+	pub fn with_session_id(mut self, session_id: i64) -> Self {
+		self.session_id = Some(session_id);
+		self
+	}
+
+	pub fn with_key_scope(mut self, key_scope: KeyScope) -> Self {
+		self.key_scope = Some(key_scope);
+		self
+	}
+
+	pub fn with_client(mut self, client: ClientInfo) -> Self {
+		self.client = Some(client);
+		self
+	}
 }
 
 // Property Accessors.
@@ -31,6 +75,20 @@ impl Ctx {
 	pub fn user_id(&self) -> i64 {
 		self.user_id
 	}
+
+	/// The login session of the request token (none for api keys).
+	pub fn session_id(&self) -> Option<i64> {
+		self.session_id
+	}
+
+	pub fn key_scope(&self) -> Option<&KeyScope> {
+		self.key_scope.as_ref()
+	}
+
+	/// Of the web requests only (none for the root and internal ctxs).
+	pub fn client(&self) -> Option<&ClientInfo> {
+		self.client.as_ref()
+	}
 }
 
 #[cfg(test)]
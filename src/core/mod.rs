@@ -2,5 +2,5 @@ pub mod config;
 pub mod ctx;
 pub mod model;
 
-// #[cfg(test)] // Commented during early development.
+#[cfg(test)]
 pub mod _dev_utils;
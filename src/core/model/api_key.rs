@@ -0,0 +1,481 @@
+use crate::auth::pwd::{hash_pwd, validate_pwd, ContentToHash};
+use crate::core::ctx::{Ctx, KeyScope};
+use crate::core::model::base::{self, add_timestamps_for_update, DbBmc};
+use crate::core::model::modql_utils::time_to_sea_value;
+use crate::core::model::ModelManager;
+use crate::core::model::{Error, Result};
+use crate::utils::b64::b64u_encode;
+use crate::utils::time::{now_utc, Rfc3339};
+use modql::field::{Field, Fields, HasFields};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString, OpValsValue};
+use rand::RngCore;
+use sea_query::{Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::postgres::PgRow;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::FromRow;
+use time::Duration;
+use uuid::Uuid;
+
+use super::base::{CursorListOptions, ListResult};
+use super::idens::{ApiKeyIden, UserIden};
+use super::user::{User, UserBmc};
+
+/// Start of every key, to recognize them (e.g., in secret scanners).
+const KEY_MARK: &str = "gdk";
+/// The `last_used_at` of a key is only rewritten past this delay, so that
+/// a busy job doesn't update (and audit) its key on every request.
+const LAST_USED_PRECISION_SEC: i64 = 60;
+
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct ApiKey {
+	pub id: i64,
+	pub user_id: i64,
+	pub name: String,
+	pub prefix: String,
+	pub role_name: String,
+	pub project_ids: Option<Vec<i64>>,
+	#[serde_as(as = "Rfc3339")]
+	pub expires_at: OffsetDateTime,
+	#[serde_as(as = "Option<Rfc3339>")]
+	pub revoked_at: Option<OffsetDateTime>,
+	#[serde_as(as = "Option<Rfc3339>")]
+	pub last_used_at: Option<OffsetDateTime>,
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+/// `project_ids` restricts the key to these structures (all the ones of the
+/// service account when `None`).
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+pub struct ApiKeyForCreate {
+	pub user_id: i64,
+	pub name: String,
+	pub role_name: String,
+	pub project_ids: Option<Vec<i64>>,
+	#[serde_as(as = "Rfc3339")]
+	pub expires_at: OffsetDateTime,
+}
+
+#[derive(Fields)]
+struct ApiKeyForInsert {
+	user_id: i64,
+	name: String,
+	prefix: String,
+	key_hash: String,
+	key_salt: Uuid,
+	role_name: String,
+	project_ids: Option<Vec<i64>>,
+	expires_at: OffsetDateTime,
+}
+
+/// A new key, the clear `key` is only ever returned here.
+#[derive(Debug, Serialize)]
+pub struct ApiKeyCreated {
+	pub key: String,
+	pub api_key: ApiKey,
+}
+
+#[derive(FromRow)]
+struct ApiKeyForAuth {
+	id: i64,
+	user_id: i64,
+	key_hash: String,
+	key_salt: Uuid,
+	role_name: String,
+	project_ids: Option<Vec<i64>>,
+	last_used_at: Option<OffsetDateTime>,
+}
+
+#[allow(dead_code)]
+pub trait ApiKeyBy: HasFields + for<'r> FromRow<'r, PgRow> + Unpin + Send {}
+
+impl ApiKeyBy for ApiKey {}
+
+#[derive(FilterNodes, Deserialize, Default, Debug)]
+pub struct ApiKeyFilter {
+	id: Option<OpValsInt64>,
+	user_id: Option<OpValsInt64>,
+	name: Option<OpValsString>,
+	prefix: Option<OpValsString>,
+	role_name: Option<OpValsString>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	expires_at: Option<OpValsValue>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	revoked_at: Option<OpValsValue>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	last_used_at: Option<OpValsValue>,
+	cid: Option<OpValsInt64>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	ctime: Option<OpValsValue>,
+	mid: Option<OpValsInt64>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	mtime: Option<OpValsValue>,
+}
+
+pub struct ApiKeyBmc;
+
+impl DbBmc for ApiKeyBmc {
+	const TABLE: &'static str = "api_key";
+	const TIMESTAMPED: bool = true;
+	const SOFTDELETED: bool = false;
+}
+
+impl ApiKeyBmc {
+	pub async fn get(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<ApiKey> {
+		UserBmc::check_admin(ctx, mm, Self::TABLE).await?;
+
+		base::get::<Self, _>(ctx, mm, id).await
+	}
+
+	/// Creates a key of a service account, hashed like the pwds.
+	pub async fn create(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		api_key_c: ApiKeyForCreate,
+	) -> Result<ApiKeyCreated> {
+		UserBmc::check_admin(ctx, mm, Self::TABLE).await?;
+
+		let user: User = UserBmc::get(ctx, mm, api_key_c.user_id).await?;
+		if !user.is_service {
+			return Err(Error::InvalidValue(format!(
+				"User {} is not a service account",
+				user.id
+			)));
+		}
+		if api_key_c.expires_at <= now_utc() {
+			return Err(Error::InvalidValue(
+				"An api key must expire in the future".to_string(),
+			));
+		}
+
+		let KeyParts { prefix, secret } = KeyParts::generate();
+		let key_salt = Uuid::new_v4();
+		let key_hash = hash_pwd(ContentToHash {
+			content: secret.clone(),
+			salt: key_salt,
+		})
+		.await?;
+
+		let api_key_i = ApiKeyForInsert {
+			user_id: api_key_c.user_id,
+			name: api_key_c.name,
+			prefix: prefix.clone(),
+			key_hash,
+			key_salt,
+			role_name: api_key_c.role_name,
+			project_ids: api_key_c.project_ids,
+			expires_at: api_key_c.expires_at,
+		};
+		let id = base::create::<Self, _>(ctx, mm, api_key_i).await?;
+
+		let api_key = base::get::<Self, _>(ctx, mm, id).await?;
+		let key = KeyParts { prefix, secret }.to_string();
+
+		Ok(ApiKeyCreated { key, api_key })
+	}
+
+	pub async fn list(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filters: Option<Vec<ApiKeyFilter>>,
+		list_options: Option<CursorListOptions>,
+	) -> Result<ListResult<ApiKey>> {
+		UserBmc::check_admin(ctx, mm, Self::TABLE).await?;
+
+		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
+	}
+
+	/// Revoking an already revoked key keeps its first `revoked_at`.
+	pub async fn revoke(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+		let db = mm.db();
+
+		// Also checks the admin, and that the key exists.
+		Self::get(ctx, mm, id).await?;
+
+		let mut fields =
+			Fields::new(vec![Field::new(ApiKeyIden::RevokedAt, now_utc().into())]);
+		add_timestamps_for_update(&mut fields, ctx.user_id());
+
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.values(fields.for_sea_update())
+			.and_where(Expr::col(ApiKeyIden::Id).eq(id))
+			.and_where(Expr::col(ApiKeyIden::RevokedAt).is_null());
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(())
+	}
+
+	/// The user and scope of a valid key (not revoked, not expired, of a not
+	/// deleted user), `None` otherwise.
+	pub async fn authenticate(
+		mm: &ModelManager,
+		key: &str,
+	) -> Result<Option<(i64, KeyScope)>> {
+		let db = mm.db();
+
+		let Ok(KeyParts { prefix, secret }) = key.parse::<KeyParts>() else {
+			return Ok(None);
+		};
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.columns([
+				(ApiKeyIden::Table, ApiKeyIden::Id),
+				(ApiKeyIden::Table, ApiKeyIden::UserId),
+				(ApiKeyIden::Table, ApiKeyIden::KeyHash),
+				(ApiKeyIden::Table, ApiKeyIden::KeySalt),
+				(ApiKeyIden::Table, ApiKeyIden::RoleName),
+				(ApiKeyIden::Table, ApiKeyIden::ProjectIds),
+				(ApiKeyIden::Table, ApiKeyIden::LastUsedAt),
+			])
+			.inner_join(
+				UserIden::Table,
+				Expr::col((UserIden::Table, UserIden::Id))
+					.equals((ApiKeyIden::Table, ApiKeyIden::UserId)),
+			)
+			.and_where(Expr::col((ApiKeyIden::Table, ApiKeyIden::Prefix)).eq(prefix))
+			.and_where(
+				Expr::col((ApiKeyIden::Table, ApiKeyIden::RevokedAt)).is_null(),
+			)
+			.and_where(
+				Expr::col((ApiKeyIden::Table, ApiKeyIden::ExpiresAt)).gt(now_utc()),
+			)
+			.and_where(Expr::col((UserIden::Table, UserIden::IsDeleted)).eq(false));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let Some(api_key) = sqlx::query_as_with::<_, ApiKeyForAuth, _>(&sql, values)
+			.fetch_optional(db)
+			.await?
+		else {
+			return Ok(None);
+		};
+
+		let to_hash = ContentToHash {
+			content: secret,
+			salt: api_key.key_salt,
+		};
+		if validate_pwd(to_hash, api_key.key_hash).await.is_err() {
+			return Ok(None);
+		}
+
+		Self::touch_last_used(mm, api_key.id, api_key.last_used_at).await?;
+
+		let key_scope = KeyScope {
+			role_name: api_key.role_name,
+			project_ids: api_key.project_ids,
+		};
+
+		Ok(Some((api_key.user_id, key_scope)))
+	}
+}
+
+// region:    --- Privates
+
+impl ApiKeyBmc {
+	/// Only the `last_used_at` column, the `mid` / `mtime` stay the ones of
+	/// the last management change.
+	async fn touch_last_used(
+		mm: &ModelManager,
+		id: i64,
+		last_used_at: Option<OffsetDateTime>,
+	) -> Result<()> {
+		let db = mm.db();
+
+		let now = now_utc();
+		if last_used_at.is_some_and(|last_used_at| {
+			now - last_used_at < Duration::seconds(LAST_USED_PRECISION_SEC)
+		}) {
+			return Ok(());
+		}
+
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.value(ApiKeyIden::LastUsedAt, now)
+			.and_where(Expr::col(ApiKeyIden::Id).eq(id));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(())
+	}
+}
+
+/// A clear key, `gdk_<prefix>_<secret>`.
+/// The prefix (stored in clear) finds the key, the secret is only stored
+/// hashed.
+#[derive(Debug, PartialEq)]
+struct KeyParts {
+	prefix: String,
+	secret: String,
+}
+
+impl KeyParts {
+	fn generate() -> Self {
+		let mut rng = rand::thread_rng();
+
+		let mut prefix = [0u8; 6];
+		rng.fill_bytes(&mut prefix);
+		let mut secret = [0u8; 32];
+		rng.fill_bytes(&mut secret);
+
+		KeyParts {
+			prefix: prefix.iter().map(|byte| format!("{byte:02x}")).collect(),
+			secret: b64u_encode(secret),
+		}
+	}
+}
+
+impl core::fmt::Display for KeyParts {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		write!(f, "{KEY_MARK}_{}_{}", self.prefix, self.secret)
+	}
+}
+
+impl core::str::FromStr for KeyParts {
+	type Err = ();
+
+	/// The secret (base64url) can contain `_`, the prefix (hex) can't.
+	fn from_str(key: &str) -> core::result::Result<Self, ()> {
+		let mut parts = key.splitn(3, '_');
+		match (parts.next(), parts.next(), parts.next()) {
+			(Some(KEY_MARK), Some(prefix), Some(secret))
+				if !prefix.is_empty() && !secret.is_empty() =>
+			{
+				Ok(KeyParts {
+					prefix: prefix.to_string(),
+					secret: secret.to_string(),
+				})
+			}
+			_ => Err(()),
+		}
+	}
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use crate::core::model::user::ServiceAccountForCreate;
+	use anyhow::Result;
+	use serial_test::serial;
+
+	#[test]
+	fn test_key_parts_parse_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_key = KeyParts::generate();
+
+		// -- Exec
+		let parsed: core::result::Result<KeyParts, ()> = fx_key.to_string().parse();
+
+		// -- Check
+		assert_eq!(parsed, Ok(fx_key));
+		assert!("gdk_0a1b2c".parse::<KeyParts>().is_err());
+		assert!("other_0a1b2c_secret".parse::<KeyParts>().is_err());
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_create_authenticate_revoke_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_user_id = UserBmc::create_service_account(
+			&ctx,
+			&mm,
+			ServiceAccountForCreate {
+				username: "test_create_authenticate_revoke_ok".to_string(),
+				email: "test_create_authenticate_revoke_ok@uvg.edu.gt".to_string(),
+				assigned_role: "demo".to_string(),
+			},
+		)
+		.await?;
+
+		// -- Exec
+		let created = ApiKeyBmc::create(
+			&ctx,
+			&mm,
+			ApiKeyForCreate {
+				user_id: fx_user_id,
+				name: "ingestion".to_string(),
+				role_name: "demo".to_string(),
+				project_ids: Some(vec![1, 2]),
+				expires_at: now_utc() + Duration::days(1),
+			},
+		)
+		.await?;
+		let auth = ApiKeyBmc::authenticate(&mm, &created.key).await?;
+		let wrong_secret = format!("{KEY_MARK}_{}_wrong", created.api_key.prefix);
+		let wrong_auth = ApiKeyBmc::authenticate(&mm, &wrong_secret).await?;
+		ApiKeyBmc::revoke(&ctx, &mm, created.api_key.id).await?;
+		let revoked_auth = ApiKeyBmc::authenticate(&mm, &created.key).await?;
+
+		// -- Check
+		let (user_id, key_scope) = auth.ok_or(anyhow::anyhow!("Should be valid"))?;
+		assert_eq!(user_id, fx_user_id);
+		assert_eq!(key_scope.role_name, "demo");
+		assert_eq!(key_scope.project_ids, Some(vec![1, 2]));
+		assert!(wrong_auth.is_none(), "Wrong secret should not authenticate");
+		assert!(
+			revoked_auth.is_none(),
+			"Revoked key should not authenticate"
+		);
+		let api_key = ApiKeyBmc::get(&ctx, &mm, created.api_key.id).await?;
+		assert!(api_key.revoked_at.is_some());
+		assert!(api_key.last_used_at.is_some());
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_create_err_not_service() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+
+		// -- Exec
+		let res = ApiKeyBmc::create(
+			&ctx,
+			&mm,
+			ApiKeyForCreate {
+				user_id: 0,
+				name: "root key".to_string(),
+				role_name: "demo".to_string(),
+				project_ids: None,
+				expires_at: now_utc() + Duration::days(1),
+			},
+		)
+		.await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::InvalidValue(_))),
+			"Should have matched `Err(Error::InvalidValue(_))` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -5,16 +5,18 @@ use crate::core::model::ModelManager;
 use crate::core::model::Result;
 use crate::utils::time::Rfc3339;
 use modql::field::{Fields, HasFields};
-use modql::filter::{
-	FilterNodes, ListOptions, OpValsInt64, OpValsString, OpValsValue,
-};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString, OpValsValue};
+use sea_query::{Expr, SimpleExpr};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use sqlx::postgres::PgRow;
 use sqlx::types::time::OffsetDateTime;
 use sqlx::FromRow;
+use std::collections::HashMap;
 
-use super::base::ListResult;
+use super::base::{CursorListOptions, ListResult};
+use super::idens::ArchiveIden;
+use super::structure_privilege::StructurePrivilegeBmc;
 
 #[serde_as]
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
@@ -32,6 +34,15 @@ pub struct Archive {
 	pub mtime: OffsetDateTime,
 }
 
+/// Archive with the values of its project indexes, keyed by index id.
+/// Indexes without a value for the archive are present with `null`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ArchiveWithValues {
+	#[serde(flatten)]
+	pub archive: Archive,
+	pub values: HashMap<i64, Option<String>>,
+}
+
 #[derive(Clone, Fields, FromRow, Debug, Serialize, Deserialize)]
 pub struct ArchiveForCreate {
 	pub project_id: i64,
@@ -90,6 +101,13 @@ impl DbBmc for ArchiveBmc {
 	const TABLE: &'static str = "archive";
 	const TIMESTAMPED: bool = true;
 	const SOFTDELETED: bool = true;
+
+	/// The archives of the accessible structures.
+	fn access_cond(ctx: &Ctx) -> Option<SimpleExpr> {
+		Some(Expr::col(ArchiveIden::ProjectId).in_subquery(
+			StructurePrivilegeBmc::accessible_project_ids(ctx, ctx.user_id()),
+		))
+	}
 }
 
 impl ArchiveBmc {
@@ -118,7 +136,7 @@ impl ArchiveBmc {
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<ArchiveFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<Archive>> {
 		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
 	}
@@ -141,3 +159,61 @@ impl ArchiveBmc {
 		base::delete::<Self>(ctx, mm, id).await
 	}
 }
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use crate::core::model::structure::{StructureBmc, StructureForOp};
+	use crate::core::model::user::{User, UserBmc};
+	use anyhow::Result;
+	use serde_json::json;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_total_count_past_end_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let demo1: User = UserBmc::first_by_username(&Ctx::root_ctx(), &mm, "demo1")
+			.await?
+			.unwrap();
+		let ctx = Ctx::new(demo1.id)?;
+		let structure_c = StructureForOp {
+			project_name: "test_list_total_count_past_end_ok".to_string(),
+		};
+		let fx_project_id = StructureBmc::create(&ctx, &mm, structure_c).await?;
+		let mut fx_ids = Vec::new();
+		for tag in ["tag-01", "tag-02", "tag-03"] {
+			let archive_c = ArchiveForCreate {
+				project_id: fx_project_id,
+				tag: tag.to_string(),
+			};
+			fx_ids.push(ArchiveBmc::create(&ctx, &mm, archive_c).await?);
+		}
+		ArchiveBmc::delete(&ctx, &mm, fx_ids[1]).await?;
+		let fx_filter: ArchiveFilter =
+			serde_json::from_value(json!({"project_id": fx_project_id}))?;
+		let fx_list_options =
+			serde_json::from_value(json!({"limit": 10, "offset": 10}))?;
+
+		// -- Exec
+		let archives = ArchiveBmc::list(
+			&ctx,
+			&mm,
+			Some(vec![fx_filter]),
+			Some(fx_list_options),
+		)
+		.await?;
+
+		// -- Check
+		assert_eq!(archives.total_count, 2);
+		assert!(archives.items.is_empty());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
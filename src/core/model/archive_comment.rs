@@ -5,14 +5,14 @@ use crate::core::model::ModelManager;
 use crate::core::model::Result;
 use crate::utils::time::Rfc3339;
 use modql::field::{Fields, HasFields};
-use modql::filter::{FilterNodes, ListOptions, OpValsInt64, OpValsValue};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsValue};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use sqlx::postgres::PgRow;
 use sqlx::types::time::OffsetDateTime;
 use sqlx::FromRow;
 
-use super::base::ListResult;
+use super::base::{CursorListOptions, ListResult};
 
 #[serde_as]
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
@@ -102,7 +102,7 @@ impl ArchiveCommentBmc {
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<ArchiveCommentFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<ArchiveComment>> {
 		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
 	}
@@ -3,7 +3,7 @@ use crate::core::model::base::{self, DbBmc};
 use crate::core::model::ModelManager;
 use crate::core::model::{Error, Result};
 use modql::field::{Fields, HasFields};
-use modql::filter::{FilterNodes, ListOptions, OpValsInt64, OpValsString};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString};
 use sea_query::{Expr, PostgresQueryBuilder, Query};
 use sea_query_binder::SqlxBinder;
 use serde::{Deserialize, Serialize};
@@ -11,7 +11,7 @@ use serde_with::serde_as;
 use sqlx::postgres::PgRow;
 use sqlx::FromRow;
 
-use super::base::ListResult;
+use super::base::{CursorListOptions, ListResult};
 use super::idens::{AssociatedPrivilegeIden, CommonIden};
 
 #[serde_as]
@@ -159,7 +159,7 @@ impl AssociatedPrivilegeBmc {
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<AssociatedPrivilegeFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<AssociatedPrivilege>> {
 		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
 	}
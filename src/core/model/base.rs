@@ -1,20 +1,25 @@
 use crate::core::ctx::Ctx;
 use crate::core::model::ModelManager;
 use crate::core::model::{Error, Result};
+use crate::utils::b64::{b64u_decode_to_string, b64u_encode};
 use crate::utils::time::now_utc;
 use modql::field::{Field, Fields, HasFields};
-use modql::filter::{FilterGroups, ListOptions};
+use modql::filter::{FilterGroups, ListOptions, OrderBy, OrderBys};
 use modql::SIden;
 use sea_query::Asterisk;
 use sea_query::{
-	Alias, Condition, Expr, Iden, IntoIden, PostgresQueryBuilder, Query, TableRef,
+	Alias, ColumnRef, Condition, DynIden, Expr, Func, Iden, IntoIden, Order,
+	PostgresQueryBuilder, Query, SelectStatement, SimpleExpr, TableRef,
+	WindowStatement,
 };
 use sea_query_binder::SqlxBinder;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgRow;
 use sqlx::{FromRow, Row};
 
+use super::export::{Export, ExportColumn};
 use super::idens::CommonIden;
+use super::search_operations::FacetCounts;
 
 const LIST_LIMIT_DEFAULT: i64 = 1000;
 const LIST_LIMIT_MAX: i64 = 5000;
@@ -31,6 +36,31 @@ pub enum TimestampIden {
 pub struct ListResult<E> {
 	pub total_count: usize,
 	pub items: Vec<E>,
+	/// Cursor of the next page, `None` on the last one.
+	pub next_cursor: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub facets: Option<Vec<FacetCounts>>,
+}
+
+impl<E> ListResult<E> {
+	/// The same page with other items (e.g., the entities of the rows).
+	pub fn with_items<T>(self, items: Vec<T>) -> ListResult<T> {
+		ListResult {
+			total_count: self.total_count,
+			next_cursor: self.next_cursor,
+			items,
+			facets: self.facets,
+		}
+	}
+}
+
+/// `ListOptions` plus the `cursor` of a keyset page (the `next_cursor` of
+/// the previous page), which replaces the `offset`.
+#[derive(Deserialize, Default, Debug, Clone)]
+pub struct CursorListOptions {
+	#[serde(flatten)]
+	pub list_options: ListOptions,
+	pub cursor: Option<String>,
 }
 
 pub trait DbBmc {
@@ -39,6 +69,12 @@ pub trait DbBmc {
 	const TIMESTAMPED: bool;
 	const SOFTDELETED: bool;
 
+	/// The rows the ctx can access (e.g., those of its accessible
+	/// structures), all of them when `None`.
+	fn access_cond(_ctx: &Ctx) -> Option<SimpleExpr> {
+		None
+	}
+
 	fn table_ref() -> TableRef {
 		match Self::SCHEMA {
 			Some(schema) => TableRef::SchemaTable(
@@ -54,16 +90,8 @@ pub fn compute_list_options(
 	list_options: Option<ListOptions>,
 ) -> Result<ListOptions> {
 	if let Some(mut list_options) = list_options {
-		if let Some(limit) = list_options.limit {
-			if limit > LIST_LIMIT_MAX {
-				return Err(Error::ListLimitOverMax {
-					max: LIST_LIMIT_MAX,
-					actual: limit,
-				});
-			}
-		} else {
-			list_options.limit = Some(LIST_LIMIT_DEFAULT);
-		}
+		list_options.limit = Some(list_limit(list_options.limit)?);
+		check_list_offset(list_options.offset)?;
 
 		Ok(list_options)
 	} else {
@@ -75,6 +103,30 @@ pub fn compute_list_options(
 	}
 }
 
+/// The limit of a page, `LIST_LIMIT_DEFAULT` when not given.
+pub fn list_limit(limit: Option<i64>) -> Result<i64> {
+	match limit {
+		None => Ok(LIST_LIMIT_DEFAULT),
+		Some(limit) if limit < 0 => Err(Error::InvalidValue(format!(
+			"A limit can't be negative, was {limit}"
+		))),
+		Some(limit) if limit > LIST_LIMIT_MAX => Err(Error::ListLimitOverMax {
+			max: LIST_LIMIT_MAX,
+			actual: limit,
+		}),
+		Some(limit) => Ok(limit),
+	}
+}
+
+pub fn check_list_offset(offset: Option<i64>) -> Result<()> {
+	match offset {
+		Some(offset) if offset < 0 => Err(Error::InvalidValue(format!(
+			"An offset can't be negative, was {offset}"
+		))),
+		_ => Ok(()),
+	}
+}
+
 pub async fn create<MC, E>(ctx: &Ctx, mm: &ModelManager, data: E) -> Result<i64>
 where
 	MC: DbBmc,
@@ -106,7 +158,7 @@ where
 	Ok(id)
 }
 
-pub async fn get<MC, E>(_ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<E>
+pub async fn get<MC, E>(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<E>
 where
 	MC: DbBmc,
 	E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
@@ -119,6 +171,9 @@ where
 		.from(MC::table_ref())
 		.columns(E::field_column_refs())
 		.and_where(Expr::col(CommonIden::Id).eq(id));
+	if let Some(access) = MC::access_cond(ctx) {
+		query.and_where(access);
+	}
 
 	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 	let entity = sqlx::query_as_with(&sql, values)
@@ -133,10 +188,10 @@ where
 }
 
 pub async fn list<MC, E, F>(
-	_ctx: &Ctx,
+	ctx: &Ctx,
 	mm: &ModelManager,
 	filter: Option<F>,
-	list_options: Option<ListOptions>,
+	list_options: Option<CursorListOptions>,
 ) -> Result<ListResult<E>>
 where
 	MC: DbBmc,
@@ -144,58 +199,148 @@ where
 	E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
 	E: HasFields,
 {
-	let db = mm.db();
-
 	// Build the base query
-	let mut base_query = Query::select();
-	base_query
-		.from(MC::table_ref())
-		.columns(E::field_column_refs());
+	let mut base_query = filtered_query::<MC, F>(ctx, filter)?;
+	base_query.columns(E::field_column_refs());
+
+	// Page the base query, counting all its rows on the way
+	let page = list_rows(mm, base_query, list_options, |order_bys| {
+		Ok(cursor_columns(MC::table_ref(), order_bys))
+	})
+	.await?;
+	let entities = page
+		.items
+		.iter()
+		.map(E::from_row)
+		.collect::<core::result::Result<Vec<_>, _>>()?;
+
+	// Return the result
+	Ok(page.with_items(entities))
+}
+
+/// All the filtered entities, in the `order_bys` order (then `id`), for
+/// an export file.
+pub fn export<MC, E, F>(
+	ctx: &Ctx,
+	filter: Option<F>,
+	order_bys: Option<OrderBys>,
+) -> Result<Export>
+where
+	MC: DbBmc,
+	F: Into<FilterGroups>,
+	E: HasFields,
+{
+	let mut query = filtered_query::<MC, F>(ctx, filter)?;
+	for column in cursor_columns(MC::table_ref(), order_bys) {
+		query.order_by(column.col(), column.order);
+	}
+
+	let columns = E::field_names()
+		.iter()
+		.zip(E::field_column_refs())
+		.map(|(name, col)| (ExportColumn::new(*name), Expr::col(col).into()))
+		.collect();
+
+	Ok(Export::new(query, columns))
+}
+
+/// The not deleted rows of the table matching the filter that the ctx can
+/// access, without any selected column.
+pub fn filtered_query<MC, F>(ctx: &Ctx, filter: Option<F>) -> Result<SelectStatement>
+where
+	MC: DbBmc,
+	F: Into<FilterGroups>,
+{
+	let mut query = Query::select();
+	query.from(MC::table_ref());
 
 	if let Some(filter) = filter {
 		let filters: FilterGroups = filter.into();
 		let cond: Condition = filters.try_into()?;
-		base_query.cond_where(cond);
+		query.cond_where(cond);
 	}
 
 	if MC::SOFTDELETED {
-		base_query.and_where(Expr::col(CommonIden::IsDeleted).eq(false));
+		query.and_where(Expr::col(CommonIden::IsDeleted).eq(false));
 	}
 
-	// Clone the base query for counting
-	// Build a separate count query
-	let mut count_query = Query::select();
-	count_query.from(MC::table_ref());
-
-	// Modify the count query to select COUNT(*)
-	count_query.expr_as(Expr::col(Asterisk).count(), Alias::new("total_count"));
+	if let Some(access) = MC::access_cond(ctx) {
+		query.and_where(access);
+	}
 
-	// Build and execute the count query
-	let (count_sql, count_values) = count_query.build_sqlx(PostgresQueryBuilder);
-	let total_count_row = sqlx::query_with(&count_sql, count_values)
-		.fetch_one(db)
-		.await?;
+	Ok(query)
+}
 
-	// Use the `get` method to retrieve the total count
-	let total_count: i64 = total_count_row.get("total_count");
+/// A page of the `filtered` query rows (see `apply_cursor_list_options`),
+/// with the `total_count` of all the filtered rows.
+/// The count is a window of the page query itself (same conditions, same
+/// round-trip), unless the page can't carry it (see `window_total_count`).
+pub async fn list_rows(
+	mm: &ModelManager,
+	filtered: SelectStatement,
+	list_options: Option<CursorListOptions>,
+	columns: impl FnOnce(Option<OrderBys>) -> Result<Vec<CursorColumn>>,
+) -> Result<ListResult<PgRow>> {
+	let (cursor, offset) = match &list_options {
+		Some(list_options) => (
+			list_options.cursor.is_some(),
+			list_options.list_options.offset,
+		),
+		None => (false, None),
+	};
+
+	let mut query = filtered.clone();
+	query.expr_window_as(
+		Expr::col(Asterisk).count(),
+		WindowStatement::new(),
+		Alias::new("total_count"),
+	);
+	let limit = apply_cursor_list_options(&mut query, list_options, columns)?;
 
-	// Apply limit and offset to the base query
-	let list_options = compute_list_options(list_options)?;
-	list_options.apply_to_sea_query(&mut base_query);
+	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+	let rows = sqlx::query_with(&sql, values).fetch_all(mm.db()).await?;
 
-	// Build and execute the original query
-	let (sql, values) = base_query.build_sqlx(PostgresQueryBuilder);
-	let entities = sqlx::query_as_with::<_, E, _>(&sql, values)
-		.fetch_all(db)
-		.await?;
+	let total_count =
+		window_total_count(mm, &rows, cursor, offset, || Ok(filtered)).await?;
 
-	// Return the result
 	Ok(ListResult {
 		total_count: total_count as usize,
-		items: entities,
+		next_cursor: next_cursor(&rows, limit)?,
+		items: rows,
+		facets: None,
 	})
 }
 
+/// The `total_count` window of the rows. An empty page (e.g., offset past
+/// the end) needs its own count of the `filtered` query, as does a cursor
+/// page (its rows only count the ones after the cursor).
+pub async fn window_total_count(
+	mm: &ModelManager,
+	rows: &[PgRow],
+	cursor: bool,
+	offset: Option<i64>,
+	filtered: impl FnOnce() -> Result<SelectStatement>,
+) -> Result<i64> {
+	let total_count = match rows.first() {
+		Some(row) if !cursor => row.try_get("total_count")?,
+		None if !cursor && offset.unwrap_or(0) == 0 => 0,
+		_ => {
+			let mut count_query = Query::select();
+			count_query
+				.expr(Expr::col(Asterisk).count())
+				.from_subquery(filtered()?, Alias::new("filtered"));
+
+			let (sql, values) = count_query.build_sqlx(PostgresQueryBuilder);
+			let (count,) = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+				.fetch_one(mm.db())
+				.await?;
+			count
+		}
+	};
+
+	Ok(total_count)
+}
+
 pub async fn update<MC, E>(
 	ctx: &Ctx,
 	mm: &ModelManager,
@@ -219,6 +364,9 @@ where
 		.table(MC::table_ref())
 		.values(fields)
 		.and_where(Expr::col(CommonIden::Id).eq(id));
+	if let Some(access) = MC::access_cond(ctx) {
+		query.and_where(access);
+	}
 
 	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 	let count = sqlx::query_with(&sql, values)
@@ -235,7 +383,7 @@ where
 		Ok(())
 	}
 }
-async fn soft_delete<MC>(_ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()>
+async fn soft_delete<MC>(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()>
 where
 	MC: DbBmc,
 {
@@ -246,6 +394,9 @@ where
 		.table(MC::table_ref())
 		.value(CommonIden::IsDeleted, true)
 		.and_where(Expr::col(CommonIden::Id).eq(id));
+	if let Some(access) = MC::access_cond(ctx) {
+		query.and_where(access);
+	}
 
 	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 	let count = sqlx::query_with(&sql, values)
@@ -263,7 +414,7 @@ where
 	}
 }
 
-async fn phisical_delete<MC>(_ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()>
+async fn phisical_delete<MC>(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()>
 where
 	MC: DbBmc,
 {
@@ -273,6 +424,9 @@ where
 	query
 		.from_table(MC::table_ref())
 		.and_where(Expr::col(CommonIden::Id).eq(id));
+	if let Some(access) = MC::access_cond(ctx) {
+		query.and_where(access);
+	}
 
 	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 	let count = sqlx::query_with(&sql, values)
@@ -353,3 +507,203 @@ pub fn add_timestamps_for_update(fields: &mut Fields, user_id: i64) {
 	fields.push(Field::new(TimestampIden::Mid.into_iden(), user_id.into()));
 	fields.push(Field::new(TimestampIden::Mtime.into_iden(), now.into()));
 }
+
+// region:    --- Cursor
+
+/// Sort column of a keyset page.
+/// `table` is also the row type reading the column back from a cursor.
+#[derive(Clone, Debug)]
+pub struct CursorColumn {
+	pub table: TableRef,
+	pub column: DynIden,
+	pub order: Order,
+}
+
+impl CursorColumn {
+	pub fn new(table: TableRef, column: impl IntoIden, order: Order) -> Self {
+		CursorColumn {
+			table,
+			column: column.into_iden(),
+			order,
+		}
+	}
+
+	pub fn col(&self) -> ColumnRef {
+		let table = match &self.table {
+			TableRef::SchemaTable(_, table) => table.clone(),
+			TableRef::Table(table) => table.clone(),
+			_ => unreachable!("Cursor columns are table columns"),
+		};
+
+		ColumnRef::TableColumn(table, self.column.clone())
+	}
+
+	/// The column value of the cursor, typed as the column
+	/// (`jsonb_populate_record` of the table row type).
+	fn cursor_value(&self, cursor: &serde_json::Value) -> SimpleExpr {
+		let row_type = match &self.table {
+			TableRef::SchemaTable(schema, table) => {
+				format!(r#""{}"."{}""#, schema.to_string(), table.to_string())
+			}
+			TableRef::Table(table) => format!(r#""{}""#, table.to_string()),
+			_ => unreachable!("Cursor columns are table columns"),
+		};
+
+		let mut query = Query::select();
+		query.column(self.column.clone()).from_function(
+			Func::cust(Alias::new("jsonb_populate_record"))
+				.arg(Expr::cust(format!("NULL::{row_type}")))
+				.arg(cursor.clone()),
+			Alias::new("cursor"),
+		);
+
+		SimpleExpr::SubQuery(None, Box::new(query.into_sub_query_statement()))
+	}
+}
+
+/// The `order_bys` columns of the table, then `id` as tie-breaker
+/// (a keyset page needs a total order).
+pub fn cursor_columns(
+	table: TableRef,
+	order_bys: Option<OrderBys>,
+) -> Vec<CursorColumn> {
+	let mut columns: Vec<CursorColumn> = order_bys
+		.map(OrderBys::order_bys)
+		.unwrap_or_default()
+		.into_iter()
+		.map(|order_by| match order_by {
+			OrderBy::Asc(name) => {
+				CursorColumn::new(table.clone(), Alias::new(name), Order::Asc)
+			}
+			OrderBy::Desc(name) => {
+				CursorColumn::new(table.clone(), Alias::new(name), Order::Desc)
+			}
+		})
+		.collect();
+
+	if !columns.iter().any(|c| c.column.to_string() == "id") {
+		columns.push(CursorColumn::new(table, CommonIden::Id, Order::Asc));
+	}
+
+	columns
+}
+
+/// Orders and pages the query, starting after the `cursor` when given.
+/// Also selects the `cursor` of each row, see `next_cursor`.
+/// Returns the limit of the page.
+pub fn apply_cursor_list_options(
+	query: &mut SelectStatement,
+	list_options: Option<CursorListOptions>,
+	columns: impl FnOnce(Option<OrderBys>) -> Result<Vec<CursorColumn>>,
+) -> Result<Option<i64>> {
+	let (list_options, cursor) = match list_options {
+		Some(CursorListOptions {
+			list_options,
+			cursor,
+		}) => (Some(list_options), cursor),
+		None => (None, None),
+	};
+
+	let mut list_options = compute_list_options(list_options)?;
+	let columns = columns(list_options.order_bys.take())?;
+
+	if let Some(cursor) = cursor {
+		if list_options.offset.is_some() {
+			return Err(Error::InvalidCursor(
+				"A cursor can't be combined with an offset".to_string(),
+			));
+		}
+		query.cond_where(cursor_cond(&columns, &cursor)?);
+	}
+
+	query.expr_as(cursor_expr(&columns), Alias::new("cursor"));
+	for column in &columns {
+		query.order_by(column.col(), column.order.clone());
+	}
+
+	let limit = list_options.limit;
+	list_options.apply_to_sea_query(query);
+
+	Ok(limit)
+}
+
+/// The sort key of a row, `jsonb_build_object` of the columns.
+pub fn cursor_expr(columns: &[CursorColumn]) -> SimpleExpr {
+	let mut func = Func::cust(Alias::new("jsonb_build_object"));
+	for column in columns {
+		func = func
+			.arg(column.column.to_string())
+			.arg(Expr::col(column.col()));
+	}
+
+	func.into()
+}
+
+/// Rows after the cursor in the order of the columns:
+/// after on the first column, or equal on it and after on the next, ...
+/// (`NULL`s sort last ascending and first descending, as in Postgres).
+pub fn cursor_cond(columns: &[CursorColumn], cursor: &str) -> Result<Condition> {
+	let invalid = || Error::InvalidCursor(cursor.to_string());
+
+	let cursor: serde_json::Value = b64u_decode_to_string(cursor)
+		.ok()
+		.and_then(|cursor| serde_json::from_str(&cursor).ok())
+		.ok_or_else(invalid)?;
+	let keys = cursor.as_object().ok_or_else(invalid)?;
+
+	// The cursor must come from a page with the same order.
+	if keys.len() != columns.len()
+		|| columns
+			.iter()
+			.any(|column| !keys.contains_key(&column.column.to_string()))
+	{
+		return Err(invalid());
+	}
+
+	let mut cond = Condition::any();
+	let mut equals = Condition::all();
+	for column in columns {
+		let col = Expr::col(column.col());
+		let is_null = keys[&column.column.to_string()].is_null();
+		let value = column.cursor_value(&cursor);
+
+		let after = match (&column.order, is_null) {
+			(Order::Desc, true) => {
+				Some(Condition::all().add(col.clone().is_not_null()))
+			}
+			(Order::Desc, false) => {
+				Some(Condition::all().add(col.clone().lt(value.clone())))
+			}
+			(_, true) => None,
+			(_, false) => Some(
+				Condition::any()
+					.add(col.clone().gt(value.clone()))
+					.add(col.clone().is_null()),
+			),
+		};
+		if let Some(after) = after {
+			cond = cond.add(equals.clone().add(after));
+		}
+
+		equals = match is_null {
+			true => equals.add(col.is_null()),
+			false => equals.add(col.eq(value)),
+		};
+	}
+
+	Ok(cond)
+}
+
+/// The cursor of the last row when the page is full, `None` otherwise
+/// (no more rows after it).
+pub fn next_cursor(rows: &[PgRow], limit: Option<i64>) -> Result<Option<String>> {
+	match (rows.last(), limit) {
+		(Some(row), Some(limit)) if rows.len() as i64 == limit => {
+			let cursor: serde_json::Value = row.try_get("cursor")?;
+			Ok(Some(b64u_encode(cursor.to_string())))
+		}
+		_ => Ok(None),
+	}
+}
+
+// endregion: --- Cursor
@@ -1,16 +1,21 @@
-use derive_more::derive::From;
 use serde::Serialize;
 use serde_with::{serde_as, DisplayFromStr};
 
 pub type Result<T> = core::result::Result<T, Error>;
 
 #[serde_as]
-#[derive(Debug, Serialize, From)]
+#[derive(Debug, Serialize)]
 pub enum Error {
 	FailedToCreateClient(String),
 
-	#[from]
-	AwsSdkConfig(#[serde_as(as = "DisplayFromStr")] aws_sdk_s3::Error),
+	AwsSdkConfig(#[serde_as(as = "DisplayFromStr")] Box<aws_sdk_s3::Error>),
+}
+
+// Boxed, as the sdk error is much larger than the others.
+impl From<aws_sdk_s3::Error> for Error {
+	fn from(val: aws_sdk_s3::Error) -> Self {
+		Self::AwsSdkConfig(Box::new(val))
+	}
 }
 
 impl core::fmt::Display for Error {
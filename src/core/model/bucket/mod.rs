@@ -12,7 +12,7 @@ static S3_CLIENT: OnceCell<Client> = OnceCell::const_new();
 pub type Bucket = Client;
 
 async fn new_s3_client() -> Result<Client> {
-	let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+	let config = aws_config::load_defaults(BehaviorVersion::v2026_01_12()).await;
 
 	let client = Client::new(&config);
 
@@ -25,7 +25,7 @@ pub async fn get_s3_client() -> Result<Bucket> {
 	S3_CLIENT
 		.get_or_try_init(new_s3_client)
 		.await
-		.map(|client| client.clone())
+		.cloned()
 		.map_err(|_| {
 			Error::FailedToCreateClient("Failed to retrieve S3 client".to_string())
 		})
@@ -3,13 +3,13 @@ use crate::core::model::base::{self, DbBmc};
 use crate::core::model::ModelManager;
 use crate::core::model::Result;
 use modql::field::{Fields, HasFields};
-use modql::filter::{FilterNodes, ListOptions, OpValsInt64};
+use modql::filter::{FilterNodes, OpValsInt64};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use sqlx::postgres::PgRow;
 use sqlx::FromRow;
 
-use super::base::ListResult;
+use super::base::{CursorListOptions, ListResult};
 
 #[serde_as]
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
@@ -64,7 +64,7 @@ impl DatatypeBmc {
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<DatatypeFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<Datatype>> {
 		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
 	}
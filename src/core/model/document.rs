@@ -5,10 +5,8 @@ use crate::core::model::ModelManager;
 use crate::core::model::Result;
 use crate::utils::time::Rfc3339;
 use modql::field::{Fields, HasFields};
-use modql::filter::{
-	FilterNodes, ListOptions, OpValsInt64, OpValsString, OpValsValue,
-};
-use sea_query::{Expr, PostgresQueryBuilder, Query};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString, OpValsValue, OrderBys};
+use sea_query::{Expr, PostgresQueryBuilder, Query, SimpleExpr};
 use sea_query_binder::SqlxBinder;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
@@ -16,8 +14,10 @@ use sqlx::postgres::PgRow;
 use sqlx::types::time::OffsetDateTime;
 use sqlx::FromRow;
 
-use super::base::ListResult;
+use super::base::{CursorListOptions, ListResult};
+use super::export::Export;
 use super::idens::DocumentIden;
+use super::structure_privilege::StructurePrivilegeBmc;
 
 #[serde_as]
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
@@ -108,7 +108,6 @@ pub struct DocumentFilter {
 	doc_type: Option<OpValsString>,
 	owner: Option<OpValsInt64>,
 	last_edit_user: Option<OpValsInt64>,
-	url: Option<OpValsString>,
 	cid: Option<OpValsInt64>,
 	#[modql(to_sea_value_fn = "time_to_sea_value")]
 	ctime: Option<OpValsValue>,
@@ -123,6 +122,14 @@ impl DbBmc for DocumentBmc {
 	const TABLE: &'static str = "document";
 	const TIMESTAMPED: bool = true;
 	const SOFTDELETED: bool = true;
+
+	/// The documents of the archives of the accessible structures.
+	fn access_cond(ctx: &Ctx) -> Option<SimpleExpr> {
+		Some(
+			Expr::col(DocumentIden::ArchiveId)
+				.in_subquery(StructurePrivilegeBmc::accessible_archive_ids(ctx)),
+		)
+	}
 }
 
 impl DocumentBmc {
@@ -154,11 +161,20 @@ impl DocumentBmc {
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<DocumentFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<Document>> {
 		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
 	}
 
+	/// All the filtered documents (not only a page), see `Export`.
+	pub fn export(
+		ctx: &Ctx,
+		filters: Option<Vec<DocumentFilter>>,
+		order_bys: Option<OrderBys>,
+	) -> Result<Export> {
+		base::export::<Self, Document, _>(ctx, filters, order_bys)
+	}
+
 	pub async fn update(
 		ctx: &Ctx,
 		mm: &ModelManager,
@@ -182,7 +198,7 @@ impl DocumentBmc {
 	}
 
 	pub async fn rename(
-		_ctx: &Ctx,
+		ctx: &Ctx,
 		mm: &ModelManager,
 		id: i64,
 		new_name: String,
@@ -194,6 +210,9 @@ impl DocumentBmc {
 			.table(Self::table_ref())
 			.value(DocumentIden::Name, new_name)
 			.and_where(Expr::col(DocumentIden::Id).eq(id));
+		if let Some(access) = Self::access_cond(ctx) {
+			query.and_where(access);
+		}
 
 		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 		let _count = sqlx::query_with(&sql, values)
@@ -205,7 +224,7 @@ impl DocumentBmc {
 	}
 
 	pub async fn get_documents_by_archive<E>(
-		_ctx: &Ctx,
+		ctx: &Ctx,
 		mm: &ModelManager,
 		id: i64,
 	) -> Result<Vec<E>>
@@ -219,6 +238,9 @@ impl DocumentBmc {
 			.from(Self::table_ref())
 			.columns(E::field_column_refs())
 			.and_where(Expr::col(DocumentIden::ArchiveId).eq(id));
+		if let Some(access) = Self::access_cond(ctx) {
+			query.and_where(access);
+		}
 
 		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 		let entities = sqlx::query_as_with::<_, E, _>(&sql, values)
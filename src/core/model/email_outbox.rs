@@ -0,0 +1,216 @@
+use crate::core::model::base::DbBmc;
+use crate::core::model::ModelManager;
+use crate::core::model::Result;
+use crate::utils::time::now_utc;
+use modql::field::{Fields, HasFields};
+use sea_query::{Expr, LockBehavior, LockType, Order, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use sqlx::{FromRow, PgExecutor, Postgres, Transaction};
+
+use super::idens::EmailOutboxIden;
+
+/// An email waiting to be sent.
+#[derive(Clone, Fields, FromRow, Debug)]
+pub struct Email {
+	pub id: i64,
+	pub recipient: String,
+	pub subject: String,
+	pub body: String,
+	pub attempts: i32,
+}
+
+#[derive(Fields)]
+pub struct EmailForCreate {
+	pub recipient: String,
+	pub subject: String,
+	pub body: String,
+}
+
+/// Pending emails, locked (the other senders skipping them) until their
+/// sends are recorded by `commit`.
+pub struct EmailBatch {
+	tx: Transaction<'static, Postgres>,
+	pub emails: Vec<Email>,
+}
+
+impl EmailBatch {
+	pub async fn mark_sent(&mut self, id: i64) -> Result<()> {
+		EmailOutboxBmc::mark_sent(&mut *self.tx, id).await
+	}
+
+	/// Records a failed send, retried until the max attempts of the sender.
+	pub async fn mark_failed(&mut self, id: i64, error: &str) -> Result<()> {
+		EmailOutboxBmc::mark_failed(&mut *self.tx, id, error).await
+	}
+
+	pub async fn commit(self) -> Result<()> {
+		self.tx.commit().await?;
+
+		Ok(())
+	}
+}
+
+pub struct EmailOutboxBmc;
+
+impl DbBmc for EmailOutboxBmc {
+	const TABLE: &'static str = "email_outbox";
+	const TIMESTAMPED: bool = false;
+	const SOFTDELETED: bool = false;
+}
+
+impl EmailOutboxBmc {
+	/// Queues the email, sent later by the smtp sender.
+	pub async fn enqueue(mm: &ModelManager, email_c: EmailForCreate) -> Result<i64> {
+		let db = mm.db();
+
+		let (columns, sea_values) = email_c.not_none_fields().for_sea_insert();
+
+		let mut query = Query::insert();
+		query
+			.into_table(Self::table_ref())
+			.columns(columns)
+			.values(sea_values)?
+			.returning(Query::returning().columns([EmailOutboxIden::Id]));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let (id,) = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+			.fetch_one(db)
+			.await?;
+
+		Ok(id)
+	}
+
+	/// The oldest not sent emails, with less than `max_attempts` failed sends,
+	/// not claimed by another sender.
+	pub async fn claim_pending(
+		mm: &ModelManager,
+		max_attempts: i32,
+		limit: u64,
+	) -> Result<EmailBatch> {
+		let mut tx = mm.db().begin().await?;
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.columns(Email::field_column_refs())
+			.and_where(Expr::col(EmailOutboxIden::SentAt).is_null())
+			.and_where(Expr::col(EmailOutboxIden::Attempts).lt(max_attempts))
+			.order_by(EmailOutboxIden::Id, Order::Asc)
+			.limit(limit)
+			.lock_with_behavior(LockType::Update, LockBehavior::SkipLocked);
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let emails = sqlx::query_as_with::<_, Email, _>(&sql, values)
+			.fetch_all(&mut *tx)
+			.await?;
+
+		Ok(EmailBatch { tx, emails })
+	}
+}
+
+// region:    --- Privates
+
+impl EmailOutboxBmc {
+	async fn mark_sent<'e, E>(executor: E, id: i64) -> Result<()>
+	where
+		E: PgExecutor<'e>,
+	{
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.value(EmailOutboxIden::SentAt, now_utc())
+			.value(
+				EmailOutboxIden::Attempts,
+				Expr::col(EmailOutboxIden::Attempts).add(1),
+			)
+			.and_where(Expr::col(EmailOutboxIden::Id).eq(id));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(executor).await?;
+
+		Ok(())
+	}
+
+	async fn mark_failed<'e, E>(executor: E, id: i64, error: &str) -> Result<()>
+	where
+		E: PgExecutor<'e>,
+	{
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.value(EmailOutboxIden::LastError, error)
+			.value(
+				EmailOutboxIden::Attempts,
+				Expr::col(EmailOutboxIden::Attempts).add(1),
+			)
+			.and_where(Expr::col(EmailOutboxIden::Id).eq(id));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(executor).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use anyhow::Result;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_claim_pending_skip_claimed_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let mm_02 = ModelManager::new().await?;
+		let mut fx_ids = Vec::new();
+		for recipient in ["fx-eo-01@example.org", "fx-eo-02@example.org"] {
+			let id = EmailOutboxBmc::enqueue(
+				&mm,
+				EmailForCreate {
+					recipient: recipient.to_string(),
+					subject: "fx subject".to_string(),
+					body: "fx body".to_string(),
+				},
+			)
+			.await?;
+			fx_ids.push(id);
+		}
+		let has_fx =
+			|emails: &[Email], id: i64| emails.iter().any(|email| email.id == id);
+
+		// -- Exec
+		let mut batch_01 = EmailOutboxBmc::claim_pending(&mm, 5, 1000).await?;
+		// Of another instance, while the first batch is sent.
+		let batch_02 = EmailOutboxBmc::claim_pending(&mm_02, 5, 1000).await?;
+		let (emails_01, emails_02) =
+			(batch_01.emails.clone(), batch_02.emails.clone());
+		batch_01.mark_sent(fx_ids[0]).await?;
+		batch_01.mark_failed(fx_ids[1], "fx error").await?;
+		batch_01.commit().await?;
+		batch_02.commit().await?;
+		let mut batch_03 = EmailOutboxBmc::claim_pending(&mm, 5, 1000).await?;
+
+		// -- Check
+		assert!(fx_ids.iter().all(|id| has_fx(&emails_01, *id)));
+		assert!(!fx_ids.iter().any(|id| has_fx(&emails_02, *id)));
+		// Only the failed one, to retry.
+		assert!(!has_fx(&batch_03.emails, fx_ids[0]));
+		let retried = batch_03.emails.iter().find(|email| email.id == fx_ids[1]);
+		assert_eq!(retried.map(|email| email.attempts), Some(1));
+
+		// -- Clean
+		batch_03.mark_sent(fx_ids[1]).await?;
+		batch_03.commit().await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -1,5 +1,6 @@
 use super::bucket;
-use crate::auth::pwd;
+use crate::auth::pwd::policy::PwdRule;
+use crate::auth::{pwd, token, totp};
 use crate::core::model::store;
 use derive_more::From;
 use serde::Serialize;
@@ -33,12 +34,34 @@ pub enum Error {
 	UnknownIndexId(i64),
 	InvalidValue(String),
 	UnsupportedOperator(String),
+	UnsupportedOrderBy(String),
 	UnsupportedDatatype(i64),
+	InvalidCursor(String),
+	AccessDenied {
+		entity: &'static str,
+		id: i64,
+	},
+	PwdPolicyViolated {
+		failed_rules: Vec<PwdRule>,
+	},
+	TotpAlreadyEnabled {
+		user_id: i64,
+	},
+	TotpNotEnrolled {
+		user_id: i64,
+	},
+	TotpCodeNotValid {
+		user_id: i64,
+	},
 
 	// -- Modules
 	#[from]
 	Pwd(pwd::Error),
 	#[from]
+	Token(token::Error),
+	#[from]
+	Totp(totp::Error),
+	#[from]
 	Store(store::Error),
 
 	FailedToCreateUser {
@@ -51,6 +74,9 @@ pub enum Error {
 	#[from]
 	Sqlx(#[serde_as(as = "DisplayFromStr")] sqlx::Error),
 
+	#[from]
+	SerdeJson(#[serde_as(as = "DisplayFromStr")] serde_json::Error),
+
 	#[from]
 	SeaQuery(#[serde_as(as = "DisplayFromStr")] sea_query::error::Error),
 
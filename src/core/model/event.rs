@@ -3,21 +3,21 @@ use crate::core::model::base::{self, DbBmc};
 use crate::core::model::idens::*;
 use crate::core::model::modql_utils::time_to_sea_value;
 use crate::core::model::ModelManager;
-use crate::core::model::Result;
+use crate::core::model::{Error, Result};
 use crate::utils::time::Rfc3339;
 use modql::field::{Fields, HasFields};
 use modql::filter::{
-	FilterGroups, FilterNodes, ListOptions, OpValsInt64, OpValsString, OpValsValue,
+	FilterGroups, FilterNodes, OpValsInt64, OpValsString, OpValsValue, OrderBy,
+	OrderBys,
 };
-use sea_query::{Condition, Expr, PostgresQueryBuilder, Query};
-use sea_query_binder::SqlxBinder;
+use sea_query::{Condition, Expr, IntoIden, Order, Query, TableRef};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use sqlx::postgres::PgRow;
 use sqlx::types::time::OffsetDateTime;
 use sqlx::{FromRow, Row};
 
-use super::base::compute_list_options;
+use super::base::{list_rows, CursorColumn, CursorListOptions, ListResult};
 
 #[serde_as]
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
@@ -51,6 +51,22 @@ pub struct EventWithUsername {
 	pub additional_info: Option<serde_json::Value>,
 }
 
+pub struct EventForCreate {
+	pub action: &'static str,
+	pub object: &'static str,
+	pub object_id: i64,
+	pub additional_info: Option<serde_json::Value>,
+}
+
+#[derive(Fields)]
+struct EventForInsert {
+	user_id: i64,
+	action: String,
+	object: String,
+	object_id: i64,
+	additional_info: Option<serde_json::Value>,
+}
+
 #[allow(dead_code)]
 pub trait EventBy: HasFields + for<'r> FromRow<'r, PgRow> + Unpin + Send {}
 
@@ -80,34 +96,33 @@ impl EventBmc {
 	pub async fn get(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<Event> {
 		base::get::<Self, _>(ctx, mm, id).await
 	}
-	/*
+	/// Events not written by the audit trigger (e.g., logoffs), by the ctx
+	/// user.
 	pub async fn create(
 		ctx: &Ctx,
 		mm: &ModelManager,
-		comment_c: CommentForOp,
+		event_c: EventForCreate,
 	) -> Result<i64> {
-		let comment_data = ArchiveCommentForOpInsert {
-			archive_id: comment_c.archive_id,
-			text: comment_c.text,
+		let event_i = EventForInsert {
 			user_id: ctx.user_id(),
+			action: event_c.action.to_string(),
+			object: event_c.object.to_string(),
+			object_id: event_c.object_id,
+			additional_info: event_c.additional_info,
 		};
 
-		let comment_id = base::create::<Self, _>(ctx, mm, comment_data).await?;
-
-		Ok(comment_id)
+		base::create::<Self, _>(ctx, mm, event_i).await
 	}
-	*/
+
 	pub async fn list<F>(
 		_ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<F>,
-		list_options: Option<ListOptions>,
-	) -> Result<Vec<EventWithUsername>>
+		list_options: Option<CursorListOptions>,
+	) -> Result<ListResult<EventWithUsername>>
 	where
 		F: Into<FilterGroups>,
 	{
-		let db = mm.db();
-
 		let mut query = Query::select();
 		query
 			.columns([
@@ -134,13 +149,25 @@ impl EventBmc {
 			query.cond_where(condition);
 		}
 
-		let list_options = compute_list_options(list_options)?;
-		list_options.apply_to_sea_query(&mut query);
-		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-
-		let rows = sqlx::query_with(&sql, values).fetch_all(db).await?;
-
-		let events = rows
+		let page = list_rows(mm, query, list_options, |order_bys| {
+			let mut columns = Vec::new();
+			for order_by in order_bys.map(OrderBys::order_bys).unwrap_or_default() {
+				let (name, order) = match order_by {
+					OrderBy::Asc(name) => (name, Order::Asc),
+					OrderBy::Desc(name) => (name, Order::Desc),
+				};
+				columns.push(Self::cursor_column(&name, order)?);
+			}
+			if !columns.iter().any(|c| c.column.to_string() == "id") {
+				columns.push(Self::cursor_column("id", Order::Asc)?);
+			}
+
+			Ok(columns)
+		})
+		.await?;
+
+		let events = page
+			.items
 			.iter()
 			.map(|row| EventWithUsername {
 				id: row.get("id"),
@@ -155,7 +182,34 @@ impl EventBmc {
 			})
 			.collect();
 
-		Ok(events)
+		Ok(page.with_items(events))
+	}
+
+	/// The username sorts on the user table, the other fields on the event.
+	fn cursor_column(name: &str, order: Order) -> Result<CursorColumn> {
+		let event = |column: EventIden| {
+			CursorColumn::new(
+				TableRef::Table(EventIden::Table.into_iden()),
+				column,
+				order.clone(),
+			)
+		};
+
+		let column = match name {
+			"id" => event(EventIden::Id),
+			"action" => event(EventIden::Action),
+			"object" => event(EventIden::Object),
+			"object_id" => event(EventIden::ObjectId),
+			"timestamp" => event(EventIden::Timestamp),
+			"username" => CursorColumn::new(
+				TableRef::Table(UserIden::Table.into_iden()),
+				UserIden::Username,
+				order,
+			),
+			_ => return Err(Error::UnsupportedOrderBy(name.to_string())),
+		};
+
+		Ok(column)
 	}
 
 	/*
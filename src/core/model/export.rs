@@ -0,0 +1,132 @@
+use crate::core::model::ModelManager;
+use crate::core::model::Result;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use sea_query::{Alias, Expr, PostgresQueryBuilder, SelectStatement, SimpleExpr};
+use sea_query_binder::{SqlxBinder, SqlxValues};
+use serde_json::Value;
+use sqlx::Row;
+
+/// Column of an export file.
+#[derive(Debug, Clone)]
+pub struct ExportColumn {
+	pub name: String,
+	/// Text values to write as numbers when they parse as such
+	/// (e.g., the values of a NUMERIC index).
+	pub numeric: bool,
+}
+
+impl ExportColumn {
+	pub fn new(name: impl Into<String>) -> Self {
+		ExportColumn {
+			name: name.into(),
+			numeric: false,
+		}
+	}
+
+	pub fn numeric(name: impl Into<String>) -> Self {
+		ExportColumn {
+			name: name.into(),
+			numeric: true,
+		}
+	}
+}
+
+/// All the rows of a listing (no limit, so no `LIST_LIMIT_MAX`), streamed
+/// from the database one at a time for the export files.
+pub struct Export {
+	pub columns: Vec<ExportColumn>,
+	pub(in crate::core::model) sql: String,
+	values: SqlxValues,
+}
+
+impl Export {
+	/// Selects the `to_jsonb` of the column expressions on the `filtered`
+	/// (and ordered) query, so that each cell keeps its json type
+	/// (e.g., numbers, timestamps as RFC 3339 strings).
+	pub fn new(
+		mut filtered: SelectStatement,
+		columns: Vec<(ExportColumn, SimpleExpr)>,
+	) -> Self {
+		let mut export_columns = Vec::with_capacity(columns.len());
+		for (i, (column, expr)) in columns.into_iter().enumerate() {
+			filtered.expr_as(
+				Expr::cust_with_expr("to_jsonb($1)", expr),
+				Alias::new(format!("c{i}")),
+			);
+			export_columns.push(column);
+		}
+
+		let (sql, values) = filtered.build_sqlx(PostgresQueryBuilder);
+
+		Export {
+			columns: export_columns,
+			sql,
+			values,
+		}
+	}
+
+	/// The cells of each row, in the `columns` order (`Value::Null` when
+	/// there is no value).
+	pub fn rows<'a>(
+		&'a self,
+		mm: &'a ModelManager,
+	) -> BoxStream<'a, Result<Vec<Value>>> {
+		let len = self.columns.len();
+
+		sqlx::query_with(&self.sql, self.values.clone())
+			.fetch(mm.db())
+			.map(move |row| {
+				let row = row?;
+				let mut cells = Vec::with_capacity(len);
+				for i in 0..len {
+					let cell: Option<Value> = row.try_get(i)?;
+					cells.push(cell.unwrap_or(Value::Null));
+				}
+				Ok(cells)
+			})
+			.boxed()
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::model::idens::DocumentIden;
+	use anyhow::Result;
+	use sea_query::Query;
+
+	#[test]
+	fn test_export_new_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut fx_query = Query::select();
+		fx_query
+			.from(DocumentIden::Table)
+			.and_where(Expr::col(DocumentIden::IsDeleted).eq(false));
+		let fx_columns = vec![
+			(ExportColumn::new("id"), Expr::col(DocumentIden::Id).into()),
+			(
+				ExportColumn::new("name"),
+				Expr::col(DocumentIden::Name).into(),
+			),
+		];
+
+		// -- Exec
+		let export = Export::new(fx_query, fx_columns);
+
+		// -- Check
+		assert_eq!(
+			export.sql,
+			r#"SELECT to_jsonb("id") AS "c0", to_jsonb("name") AS "c1" FROM "document" WHERE "is_deleted" = $1"#
+		);
+		let names: Vec<&str> =
+			export.columns.iter().map(|c| c.name.as_str()).collect();
+		assert_eq!(names, ["id", "name"]);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -22,21 +22,43 @@ pub enum EventIden {
 }
 
 #[allow(unused)]
-#[derive(Iden)]
+#[derive(Iden, Clone, Copy)]
 pub enum DocumentIden {
 	#[iden = "document"]
 	Table,
 	Id,
+	SeparatorId,
+	ArchiveId,
 	Name,
+	DocType,
+	Owner,
+	LastEditUser,
+	Key,
+	IsDeleted,
+	Cid,
+	Ctime,
+	Mid,
+	Mtime,
+}
+
+#[derive(Iden)]
+pub enum SeparatorIden {
+	#[iden = "separator"]
+	Table,
+	Id,
+	Name,
+	ParentId,
 	ArchiveId,
+	IsDeleted,
 }
 
 #[allow(unused)]
 #[derive(Iden)]
-enum RoleIden {
+pub enum RoleIden {
 	Id,
 	RoleName,
 	Pwd,
+	IsDeleted,
 }
 
 #[derive(Iden)]
@@ -45,6 +67,8 @@ pub enum UserIden {
 	Table,
 	Id,
 	Username,
+	AssignedRole,
+	IsDeleted,
 }
 
 #[derive(Iden)]
@@ -56,6 +80,33 @@ pub enum IndexIden {
 	Required,
 	IndexName,
 	DatatypeId,
+	IsDeleted,
+}
+
+#[derive(Iden, Clone, Copy)]
+pub enum ArchiveIden {
+	#[iden = "archive"]
+	Table,
+	Id,
+	ProjectId,
+	Owner,
+	LastEditUser,
+	Tag,
+	IsDeleted,
+	Cid,
+	Ctime,
+	Mid,
+	Mtime,
+}
+
+#[derive(Iden)]
+pub enum ValueIden {
+	#[iden = "value"]
+	Table,
+	IndexId,
+	ProjectId,
+	ArchiveId,
+	Value,
 }
 
 #[derive(Iden)]
@@ -85,8 +136,14 @@ pub enum StructurePrivilegeIden {
 	Table,
 	Id,
 	UserId,
+	RoleName,
 	ProjectId,
 	IsEnabled,
+	IsDeleted,
+	Cid,
+	Ctime,
+	Mid,
+	Mtime,
 }
 
 #[allow(unused)]
@@ -98,3 +155,161 @@ pub enum StructureIden {
 	ProjectName,
 	IsDeleted,
 }
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum SavedSearchIden {
+	#[iden = "saved_search"]
+	Table,
+	Id,
+	Owner,
+	SharedRole,
+	IsDeleted,
+}
+
+#[derive(Iden)]
+pub enum ApiKeyIden {
+	#[iden = "api_key"]
+	Table,
+	Id,
+	UserId,
+	Prefix,
+	KeyHash,
+	KeySalt,
+	RoleName,
+	ProjectIds,
+	ExpiresAt,
+	RevokedAt,
+	LastUsedAt,
+}
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum SessionIden {
+	#[iden = "session"]
+	Table,
+	Id,
+	TokenId,
+	TokenKind,
+	UserId,
+	LastSeen,
+	RevokedAt,
+}
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum LoginAttemptIden {
+	#[iden = "login_attempt"]
+	Table,
+	Id,
+	Scope,
+	Key,
+	Failures,
+	LastFailure,
+	LockedUntil,
+}
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum PasswordResetIden {
+	#[iden = "password_reset"]
+	Table,
+	Id,
+	TokenId,
+	UserId,
+	UsedAt,
+}
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum EmailOutboxIden {
+	#[iden = "email_outbox"]
+	Table,
+	Id,
+	Attempts,
+	LastError,
+	SentAt,
+}
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum PwdHistoryIden {
+	#[iden = "pwd_history"]
+	Table,
+	Id,
+	UserId,
+	Pwd,
+}
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum UserTotpIden {
+	#[iden = "user_totp"]
+	Table,
+	UserId,
+	SecretEnc,
+	RecoveryCodes,
+	LastUsedStep,
+	EnabledAt,
+}
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum OidcLoginIden {
+	#[iden = "oidc_login"]
+	Table,
+	State,
+	Nonce,
+	CodeVerifier,
+	Ctime,
+}
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum UserIdentityIden {
+	#[iden = "user_identity"]
+	Table,
+	Id,
+	UserId,
+	Issuer,
+	Subject,
+}
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum TokenKeyIden {
+	#[iden = "token_key"]
+	Table,
+	Id,
+	Kid,
+	KeyEnc,
+	ActiveFrom,
+	Ctime,
+}
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum IpAllowlistIden {
+	#[iden = "ip_allowlist"]
+	Table,
+	Id,
+	RoleName,
+	UserId,
+	Cidr,
+	IsDeleted,
+}
+
+#[allow(unused)]
+#[derive(Iden)]
+pub enum LoginHistoryIden {
+	#[iden = "login_history"]
+	Table,
+	Id,
+	UserId,
+	Username,
+	Kind,
+	Ip,
+	UserAgent,
+	Detail,
+	Ctime,
+}
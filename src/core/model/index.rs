@@ -6,7 +6,7 @@ use crate::core::model::Result;
 use crate::utils::time::Rfc3339;
 use modql::field::{Fields, HasFields};
 use modql::filter::{
-	FilterNodes, ListOptions, OpValsBool, OpValsInt64, OpValsString, OpValsValue,
+	FilterNodes, OpValsBool, OpValsInt64, OpValsString, OpValsValue,
 };
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
@@ -14,7 +14,7 @@ use sqlx::postgres::PgRow;
 use sqlx::types::time::OffsetDateTime;
 use sqlx::FromRow;
 
-use super::base::ListResult;
+use super::base::{CursorListOptions, ListResult};
 
 #[serde_as]
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
@@ -73,7 +73,7 @@ pub struct IndexBmc;
 
 impl DbBmc for IndexBmc {
 	const TABLE: &'static str = "index";
-	const TIMESTAMPED: bool = false;
+	const TIMESTAMPED: bool = true;
 	const SOFTDELETED: bool = true;
 }
 
@@ -96,7 +96,7 @@ impl IndexBmc {
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<IndexFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<Index>> {
 		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
 	}
@@ -0,0 +1,349 @@
+use crate::core::ctx::Ctx;
+use crate::core::model::base::{self, DbBmc};
+use crate::core::model::event::{EventBmc, EventForCreate};
+use crate::core::model::modql_utils::time_to_sea_value;
+use crate::core::model::user::UserBmc;
+use crate::core::model::ModelManager;
+use crate::core::model::{Error, Result};
+use crate::utils::time::Rfc3339;
+use ipnet::IpNet;
+use modql::field::{Fields, HasFields};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString, OpValsValue};
+use sea_query::{Cond, Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use serde_with::serde_as;
+use sqlx::postgres::PgRow;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::FromRow;
+use std::net::IpAddr;
+
+use super::base::{CursorListOptions, ListResult};
+use super::idens::{IpAllowlistIden, UserIden};
+
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct IpAllowlist {
+	pub id: i64,
+	pub role_name: Option<String>,
+	pub user_id: Option<i64>,
+	pub cidr: String,
+	pub description: Option<String>,
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+/// Of either a `role_name` or a `user_id`. The `cidr` can also be a single ip
+/// (e.g., `10.1.0.0/16`, `192.168.1.20`).
+#[derive(Clone, Fields, Debug, Deserialize)]
+pub struct IpAllowlistForCreate {
+	pub role_name: Option<String>,
+	pub user_id: Option<i64>,
+	pub cidr: String,
+	pub description: Option<String>,
+}
+
+#[allow(dead_code)]
+pub trait IpAllowlistBy:
+	HasFields + for<'r> FromRow<'r, PgRow> + Unpin + Send
+{
+}
+
+impl IpAllowlistBy for IpAllowlist {}
+
+#[derive(FilterNodes, Deserialize, Default, Debug)]
+pub struct IpAllowlistFilter {
+	id: Option<OpValsInt64>,
+	role_name: Option<OpValsString>,
+	user_id: Option<OpValsInt64>,
+	cidr: Option<OpValsString>,
+	cid: Option<OpValsInt64>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	ctime: Option<OpValsValue>,
+	mid: Option<OpValsInt64>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	mtime: Option<OpValsValue>,
+}
+
+pub struct IpAllowlistBmc;
+
+impl DbBmc for IpAllowlistBmc {
+	const TABLE: &'static str = "ip_allowlist";
+	const TIMESTAMPED: bool = true;
+	const SOFTDELETED: bool = true;
+}
+
+impl IpAllowlistBmc {
+	pub async fn get(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<IpAllowlist> {
+		UserBmc::check_admin(ctx, mm, Self::TABLE).await?;
+
+		base::get::<Self, _>(ctx, mm, id).await
+	}
+
+	/// The `cidr` is stored normalized (e.g., `10.1.2.3/16` as `10.1.0.0/16`).
+	pub async fn create(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		mut ip_allowlist_c: IpAllowlistForCreate,
+	) -> Result<i64> {
+		UserBmc::check_admin(ctx, mm, Self::TABLE).await?;
+
+		if ip_allowlist_c.role_name.is_some() == ip_allowlist_c.user_id.is_some() {
+			return Err(Error::InvalidValue(
+				"An ip allowlist is of either a role_name or a user_id".to_string(),
+			));
+		}
+		ip_allowlist_c.cidr = parse_cidr(&ip_allowlist_c.cidr)
+			.ok_or_else(|| {
+				Error::InvalidValue(format!(
+					"Not a cidr or ip: {}",
+					ip_allowlist_c.cidr
+				))
+			})?
+			.to_string();
+
+		base::create::<Self, _>(ctx, mm, ip_allowlist_c).await
+	}
+
+	pub async fn list(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filters: Option<Vec<IpAllowlistFilter>>,
+		list_options: Option<CursorListOptions>,
+	) -> Result<ListResult<IpAllowlist>> {
+		UserBmc::check_admin(ctx, mm, Self::TABLE).await?;
+
+		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
+	}
+
+	pub async fn delete(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+		UserBmc::check_admin(ctx, mm, Self::TABLE).await?;
+
+		base::delete::<Self>(ctx, mm, id).await
+	}
+
+	/// Whether the user can be used from the `ip`: always when no allowlist is
+	/// of the user nor of its role (the `key_role_name` for an api key, its
+	/// `assigned_role` otherwise), else only from one of their ranges (never
+	/// from an unknown ip). A rejection is logged to the `event` (`via` is
+	/// e.g. `login` or `request`).
+	pub async fn check_ip(
+		mm: &ModelManager,
+		user_id: i64,
+		key_role_name: Option<&str>,
+		ip: Option<IpAddr>,
+		via: &'static str,
+	) -> Result<bool> {
+		let cidrs = Self::cidrs_of_user(mm, user_id, key_role_name).await?;
+		if cidrs.is_empty() {
+			return Ok(true);
+		}
+
+		let allowed = ip.is_some_and(|ip| {
+			cidrs
+				.iter()
+				.filter_map(|cidr| parse_cidr(cidr))
+				.any(|net| net.contains(&ip))
+		});
+
+		if !allowed {
+			let ctx = Ctx::new(user_id).unwrap_or_else(|_| Ctx::root_ctx());
+			EventBmc::create(
+				&ctx,
+				mm,
+				EventForCreate {
+					action: "IP_NOT_ALLOWED",
+					object: "user",
+					object_id: user_id,
+					additional_info: Some(json!({
+						"ip": ip.map(|ip| ip.to_string()),
+						"via": via
+					})),
+				},
+			)
+			.await?;
+		}
+
+		Ok(allowed)
+	}
+}
+
+// region:    --- Privates
+
+impl IpAllowlistBmc {
+	async fn cidrs_of_user(
+		mm: &ModelManager,
+		user_id: i64,
+		key_role_name: Option<&str>,
+	) -> Result<Vec<String>> {
+		let db = mm.db();
+
+		let role_cond = match key_role_name {
+			Some(key_role_name) => {
+				Expr::col(IpAllowlistIden::RoleName).eq(key_role_name)
+			}
+			None => Expr::col(IpAllowlistIden::RoleName).in_subquery(
+				Query::select()
+					.column(UserIden::AssignedRole)
+					.from(UserIden::Table)
+					.and_where(Expr::col(UserIden::Id).eq(user_id))
+					.to_owned(),
+			),
+		};
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.column(IpAllowlistIden::Cidr)
+			.and_where(Expr::col(IpAllowlistIden::IsDeleted).eq(false))
+			.cond_where(
+				Cond::any()
+					.add(Expr::col(IpAllowlistIden::UserId).eq(user_id))
+					.add(role_cond),
+			);
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let cidrs = sqlx::query_as_with::<_, (String,), _>(&sql, values)
+			.fetch_all(db)
+			.await?
+			.into_iter()
+			.map(|(cidr,)| cidr)
+			.collect();
+
+		Ok(cidrs)
+	}
+}
+
+/// A single ip being its own `/32` (or `/128`) range.
+fn parse_cidr(cidr: &str) -> Option<IpNet> {
+	let cidr = cidr.trim();
+
+	cidr.parse::<IpNet>()
+		.map(|net| net.trunc())
+		.or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+		.ok()
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use crate::core::model::user::UserForCreate;
+	use anyhow::Result;
+	use serial_test::serial;
+
+	#[test]
+	fn test_parse_cidr_ok() -> Result<()> {
+		// -- Exec & Check
+		assert_eq!(
+			parse_cidr("10.1.2.3/16")
+				.map(|net| net.to_string())
+				.as_deref(),
+			Some("10.1.0.0/16")
+		);
+		assert_eq!(
+			parse_cidr(" 192.168.1.20 ")
+				.map(|net| net.to_string())
+				.as_deref(),
+			Some("192.168.1.20/32")
+		);
+		assert_eq!(
+			parse_cidr("2001:db8::1/32")
+				.map(|net| net.to_string())
+				.as_deref(),
+			Some("2001:db8::/32")
+		);
+		assert!(parse_cidr("10.1.0.0/33").is_none());
+		assert!(parse_cidr("office").is_none());
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_check_ip_user_and_role_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_username = "test_check_ip_user_and_role_ok-user-01";
+		let user_id = UserBmc::create(
+			&root_ctx,
+			&mm,
+			UserForCreate {
+				username: fx_username.to_string(),
+				email: format!("{fx_username}@example.org"),
+				pwd_clear: "welcome-01".to_string(),
+				assigned_role: "demo".to_string(),
+			},
+		)
+		.await?;
+		let fx_office: IpAddr = "10.1.2.3".parse()?;
+		let fx_home: IpAddr = "192.168.1.20".parse()?;
+		let fx_other: IpAddr = "172.16.0.1".parse()?;
+
+		// -- Exec & Check
+		// Not listed.
+		assert!(IpAllowlistBmc::check_ip(&mm, user_id, None, None, "test").await?);
+
+		let role_list_id = IpAllowlistBmc::create(
+			&root_ctx,
+			&mm,
+			IpAllowlistForCreate {
+				role_name: Some("demo".to_string()),
+				user_id: None,
+				cidr: "10.1.0.0/16".to_string(),
+				description: None,
+			},
+		)
+		.await?;
+		let user_list_id = IpAllowlistBmc::create(
+			&root_ctx,
+			&mm,
+			IpAllowlistForCreate {
+				role_name: None,
+				user_id: Some(user_id),
+				cidr: fx_home.to_string(),
+				description: Some("home".to_string()),
+			},
+		)
+		.await?;
+
+		let check = |ip: Option<IpAddr>| {
+			IpAllowlistBmc::check_ip(&mm, user_id, None, ip, "test")
+		};
+		assert!(check(Some(fx_office)).await?);
+		assert!(check(Some(fx_home)).await?);
+		assert!(!check(Some(fx_other)).await?);
+		assert!(!check(None).await?);
+		// The role of an api key replaces the assigned one.
+		assert!(
+			!IpAllowlistBmc::check_ip(
+				&mm,
+				user_id,
+				Some("fx-key-role"),
+				Some(fx_office),
+				"test"
+			)
+			.await?
+		);
+
+		// -- Clean
+		IpAllowlistBmc::delete(&root_ctx, &mm, role_list_id).await?;
+		IpAllowlistBmc::delete(&root_ctx, &mm, user_list_id).await?;
+		assert!(check(Some(fx_other)).await?);
+		UserBmc::delete(&root_ctx, &mm, user_id).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,349 @@
+use crate::auth::config::auth_config;
+use crate::core::ctx::Ctx;
+use crate::core::model::base::DbBmc;
+use crate::core::model::ModelManager;
+use crate::core::model::Result;
+use crate::utils::time::now_utc;
+use sea_query::{Expr, OnConflict, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::FromRow;
+use time::Duration;
+
+use super::idens::LoginAttemptIden;
+use super::user::{User, UserBmc};
+
+/// The `login_attempt.key` column size.
+const KEY_MAX_LEN: usize = 256;
+
+/// What the failures are counted by. The pwd reset requests are counted
+/// apart, as all failures.
+#[derive(Clone, Copy, Debug)]
+pub enum AttemptScope {
+	Username,
+	Ip,
+	PwdResetEmail,
+	PwdResetIp,
+}
+
+impl AttemptScope {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::Username => "username",
+			Self::Ip => "ip",
+			Self::PwdResetEmail => "pwd_reset_email",
+			Self::PwdResetIp => "pwd_reset_ip",
+		}
+	}
+}
+
+/// The login throttling settings.
+#[derive(Clone, Copy, Debug)]
+pub struct Throttle {
+	pub max_failures: i32,
+	pub backoff_base: Duration,
+	pub lockout: Duration,
+}
+
+impl Throttle {
+	pub fn from_config() -> Self {
+		let config = auth_config();
+
+		Throttle {
+			max_failures: config.LOGIN_MAX_FAILURES,
+			backoff_base: Duration::seconds_f64(config.LOGIN_BACKOFF_BASE_SEC),
+			lockout: Duration::seconds_f64(config.LOGIN_LOCKOUT_SEC),
+		}
+	}
+
+	/// `backoff_base * 2^(failures - 1)`, at most the lockout.
+	fn backoff(&self, failures: i32) -> Duration {
+		let doublings = (failures - 1).clamp(0, 30) as u32;
+
+		(self.backoff_base * 2_i32.pow(doublings)).min(self.lockout)
+	}
+}
+
+#[derive(Clone, FromRow, Debug)]
+struct LoginAttempt {
+	failures: i32,
+	last_failure: OffsetDateTime,
+	locked_until: Option<OffsetDateTime>,
+}
+
+impl LoginAttempt {
+	fn is_locked(&self, now: OffsetDateTime) -> bool {
+		self.locked_until
+			.is_some_and(|locked_until| locked_until > now)
+	}
+
+	/// Failures older than a lockout are forgotten.
+	fn is_stale(&self, now: OffsetDateTime, throttle: &Throttle) -> bool {
+		!self.is_locked(now) && now - self.last_failure >= throttle.lockout
+	}
+
+	/// The wait before the next attempt, if any.
+	fn retry_after(
+		&self,
+		now: OffsetDateTime,
+		throttle: &Throttle,
+	) -> Option<Duration> {
+		let next_attempt = match self.locked_until {
+			Some(locked_until) if locked_until > now => locked_until,
+			// The lockout is over, starting over.
+			Some(_) => return None,
+			None if self.is_stale(now, throttle) => return None,
+			None => self.last_failure + throttle.backoff(self.failures),
+		};
+
+		(next_attempt > now).then(|| next_attempt - now)
+	}
+
+	/// The attempt after one more failure, locked at `max_failures`.
+	fn failed(
+		previous: Option<LoginAttempt>,
+		now: OffsetDateTime,
+		throttle: &Throttle,
+	) -> LoginAttempt {
+		let failures = match previous {
+			Some(previous) if previous.is_locked(now) => return previous,
+			Some(previous)
+				if previous.locked_until.is_none()
+					&& !previous.is_stale(now, throttle) =>
+			{
+				previous.failures + 1
+			}
+			_ => 1,
+		};
+
+		LoginAttempt {
+			failures,
+			last_failure: now,
+			locked_until: (failures >= throttle.max_failures)
+				.then(|| now + throttle.lockout),
+		}
+	}
+}
+
+pub struct LoginAttemptBmc;
+
+impl DbBmc for LoginAttemptBmc {
+	const TABLE: &'static str = "login_attempt";
+	const TIMESTAMPED: bool = false;
+	const SOFTDELETED: bool = false;
+}
+
+impl LoginAttemptBmc {
+	/// The longest wait of the keys before their next login attempt, if any.
+	pub async fn retry_after(
+		mm: &ModelManager,
+		keys: &[(AttemptScope, String)],
+		throttle: &Throttle,
+	) -> Result<Option<Duration>> {
+		let now = now_utc();
+
+		let mut retry_after: Option<Duration> = None;
+		for (scope, key) in keys {
+			let attempt = Self::first(mm, *scope, key).await?;
+			let key_retry_after =
+				attempt.and_then(|attempt| attempt.retry_after(now, throttle));
+			retry_after = retry_after.max(key_retry_after);
+		}
+
+		Ok(retry_after)
+	}
+
+	/// Whether this failure locked the key.
+	pub async fn record_failure(
+		mm: &ModelManager,
+		scope: AttemptScope,
+		key: &str,
+		throttle: &Throttle,
+	) -> Result<bool> {
+		let db = mm.db();
+
+		let now = now_utc();
+		let key = Self::key(key);
+		let previous = Self::first(mm, scope, &key).await?;
+		let was_locked = previous
+			.as_ref()
+			.is_some_and(|previous| previous.is_locked(now));
+		let attempt = LoginAttempt::failed(previous, now, throttle);
+
+		let mut query = Query::insert();
+		query
+			.into_table(Self::table_ref())
+			.columns([
+				LoginAttemptIden::Scope,
+				LoginAttemptIden::Key,
+				LoginAttemptIden::Failures,
+				LoginAttemptIden::LastFailure,
+				LoginAttemptIden::LockedUntil,
+			])
+			.values([
+				scope.as_str().into(),
+				key.into(),
+				attempt.failures.into(),
+				attempt.last_failure.into(),
+				attempt.locked_until.into(),
+			])?
+			.on_conflict(
+				OnConflict::columns([
+					LoginAttemptIden::Scope,
+					LoginAttemptIden::Key,
+				])
+				.update_columns([
+					LoginAttemptIden::Failures,
+					LoginAttemptIden::LastFailure,
+					LoginAttemptIden::LockedUntil,
+				])
+				.to_owned(),
+			);
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(!was_locked && attempt.is_locked(now))
+	}
+
+	/// Forgets the failures of the key (e.g., after a successful login).
+	pub async fn clear(
+		mm: &ModelManager,
+		scope: AttemptScope,
+		key: &str,
+	) -> Result<()> {
+		let db = mm.db();
+
+		let mut query = Query::delete();
+		query
+			.from_table(Self::table_ref())
+			.and_where(Expr::col(LoginAttemptIden::Scope).eq(scope.as_str()))
+			.and_where(Expr::col(LoginAttemptIden::Key).eq(Self::key(key)));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(())
+	}
+
+	/// Lifts the lockout (and backoff) of the user's username, admins only.
+	pub async fn unlock_user(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		user_id: i64,
+	) -> Result<()> {
+		UserBmc::check_admin(ctx, mm, Self::TABLE).await?;
+
+		let user: User = UserBmc::get(ctx, mm, user_id).await?;
+
+		Self::clear(mm, AttemptScope::Username, &user.username).await
+	}
+}
+
+// region:    --- Privates
+
+impl LoginAttemptBmc {
+	async fn first(
+		mm: &ModelManager,
+		scope: AttemptScope,
+		key: &str,
+	) -> Result<Option<LoginAttempt>> {
+		let db = mm.db();
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.columns([
+				LoginAttemptIden::Failures,
+				LoginAttemptIden::LastFailure,
+				LoginAttemptIden::LockedUntil,
+			])
+			.and_where(Expr::col(LoginAttemptIden::Scope).eq(scope.as_str()))
+			.and_where(Expr::col(LoginAttemptIden::Key).eq(Self::key(key)));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let attempt = sqlx::query_as_with::<_, LoginAttempt, _>(&sql, values)
+			.fetch_optional(db)
+			.await?;
+
+		Ok(attempt)
+	}
+
+	/// The (any length) usernames sent to the login, cut to the column size.
+	fn key(key: &str) -> String {
+		key.chars().take(KEY_MAX_LEN).collect()
+	}
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use anyhow::Result;
+
+	fn fx_throttle() -> Throttle {
+		Throttle {
+			max_failures: 3,
+			backoff_base: Duration::seconds(1),
+			lockout: Duration::minutes(15),
+		}
+	}
+
+	#[test]
+	fn test_failed_backoff_then_lockout_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_throttle = fx_throttle();
+		let now = now_utc();
+
+		// -- Exec
+		let first = LoginAttempt::failed(None, now, &fx_throttle);
+		let second = LoginAttempt::failed(Some(first.clone()), now, &fx_throttle);
+		let third = LoginAttempt::failed(Some(second.clone()), now, &fx_throttle);
+
+		// -- Check
+		assert_eq!(
+			first.retry_after(now, &fx_throttle),
+			Some(Duration::seconds(1))
+		);
+		assert_eq!(
+			second.retry_after(now, &fx_throttle),
+			Some(Duration::seconds(2))
+		);
+		assert!(second.locked_until.is_none());
+		assert_eq!(third.locked_until, Some(now + Duration::minutes(15)));
+		assert_eq!(
+			third.retry_after(now, &fx_throttle),
+			Some(Duration::minutes(15))
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_failed_after_lockout_starts_over_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_throttle = fx_throttle();
+		let now = now_utc();
+		let fx_locked = LoginAttempt {
+			failures: 3,
+			last_failure: now - Duration::minutes(20),
+			locked_until: Some(now - Duration::minutes(5)),
+		};
+
+		// -- Exec
+		let attempt =
+			LoginAttempt::failed(Some(fx_locked.clone()), now, &fx_throttle);
+
+		// -- Check
+		assert_eq!(fx_locked.retry_after(now, &fx_throttle), None);
+		assert_eq!(attempt.failures, 1);
+		assert!(attempt.locked_until.is_none());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
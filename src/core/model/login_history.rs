@@ -0,0 +1,275 @@
+use crate::core::ctx::{ClientInfo, Ctx};
+use crate::core::model::base::{self, cursor_columns, list_rows, DbBmc};
+use crate::core::model::modql_utils::time_to_sea_value;
+use crate::core::model::ModelManager;
+use crate::core::model::Result;
+use crate::utils::time::Rfc3339;
+use modql::field::{Fields, HasFields};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString, OpValsValue};
+use sea_query::{Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_with::serde_as;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::FromRow;
+
+use super::base::{CursorListOptions, ListResult};
+use super::idens::LoginHistoryIden;
+use super::user::{UserBmc, UserForAuth};
+
+/// The `login_history.username` column size.
+const USERNAME_MAX_LEN: usize = 50;
+
+#[derive(Clone, Copy, Debug)]
+pub enum LoginHistoryKind {
+	LoginOk,
+	LoginFail,
+	Lockout,
+	Logoff,
+	LogoffEverywhere,
+	PwdChange,
+	TotpEnable,
+	TotpDisable,
+}
+
+impl LoginHistoryKind {
+	fn as_str(self) -> &'static str {
+		match self {
+			Self::LoginOk => "LOGIN_OK",
+			Self::LoginFail => "LOGIN_FAIL",
+			Self::Lockout => "LOCKOUT",
+			Self::Logoff => "LOGOFF",
+			Self::LogoffEverywhere => "LOGOFF_EVERYWHERE",
+			Self::PwdChange => "PWD_CHANGE",
+			Self::TotpEnable => "TOTP_ENABLE",
+			Self::TotpDisable => "TOTP_DISABLE",
+		}
+	}
+}
+
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct LoginHistory {
+	pub id: i64,
+	pub user_id: Option<i64>,
+	pub username: String,
+	pub kind: String,
+	pub ip: Option<String>,
+	pub user_agent: Option<String>,
+	pub detail: Option<Value>,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+}
+
+#[derive(FilterNodes, Deserialize, Default, Debug)]
+pub struct LoginHistoryFilter {
+	id: Option<OpValsInt64>,
+	user_id: Option<OpValsInt64>,
+	username: Option<OpValsString>,
+	kind: Option<OpValsString>,
+	ip: Option<OpValsString>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	ctime: Option<OpValsValue>,
+}
+
+pub struct LoginHistoryBmc;
+
+impl DbBmc for LoginHistoryBmc {
+	const TABLE: &'static str = "login_history";
+	const TIMESTAMPED: bool = false;
+	const SOFTDELETED: bool = false;
+}
+
+impl LoginHistoryBmc {
+	/// Of the `username` of a login, its user (if any) looked up.
+	pub async fn record_for_username(
+		mm: &ModelManager,
+		username: &str,
+		kind: LoginHistoryKind,
+		client: &ClientInfo,
+		detail: Option<Value>,
+	) -> Result<()> {
+		let user: Option<UserForAuth> =
+			UserBmc::first_by_username(&Ctx::root_ctx(), mm, username).await?;
+
+		Self::record(
+			mm,
+			user.map(|user| user.id),
+			username,
+			kind,
+			Some(client),
+			detail,
+		)
+		.await
+	}
+
+	/// Of the user, from the client of the ctx (if any).
+	pub async fn record_for_user(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		user_id: i64,
+		kind: LoginHistoryKind,
+		detail: Option<Value>,
+	) -> Result<()> {
+		let user: UserForAuth = UserBmc::get(&Ctx::root_ctx(), mm, user_id).await?;
+
+		Self::record(
+			mm,
+			Some(user_id),
+			&user.username,
+			kind,
+			ctx.client(),
+			detail,
+		)
+		.await
+	}
+
+	pub async fn record(
+		mm: &ModelManager,
+		user_id: Option<i64>,
+		username: &str,
+		kind: LoginHistoryKind,
+		client: Option<&ClientInfo>,
+		detail: Option<Value>,
+	) -> Result<()> {
+		let db = mm.db();
+
+		let username: String = username.chars().take(USERNAME_MAX_LEN).collect();
+		let (ip, user_agent) = match client {
+			Some(client) => (client.ip.clone(), client.user_agent.clone()),
+			None => (None, None),
+		};
+
+		let mut query = Query::insert();
+		query
+			.into_table(Self::table_ref())
+			.columns([
+				LoginHistoryIden::UserId,
+				LoginHistoryIden::Username,
+				LoginHistoryIden::Kind,
+				LoginHistoryIden::Ip,
+				LoginHistoryIden::UserAgent,
+				LoginHistoryIden::Detail,
+			])
+			.values([
+				user_id.into(),
+				username.into(),
+				kind.as_str().into(),
+				ip.into(),
+				user_agent.into(),
+				detail.into(),
+			])?;
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(())
+	}
+
+	/// All the history for the admins, only the user's own otherwise.
+	pub async fn list(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filters: Option<Vec<LoginHistoryFilter>>,
+		list_options: Option<CursorListOptions>,
+	) -> Result<ListResult<LoginHistory>> {
+		let mut query = base::filtered_query::<Self, _>(ctx, filters)?;
+		query.columns(LoginHistory::field_column_refs());
+		if UserBmc::check_admin(ctx, mm, Self::TABLE).await.is_err() {
+			query.and_where(Expr::col(LoginHistoryIden::UserId).eq(ctx.user_id()));
+		}
+
+		let page = list_rows(mm, query, list_options, |order_bys| {
+			Ok(cursor_columns(Self::table_ref(), order_bys))
+		})
+		.await?;
+		let login_histories = page
+			.items
+			.iter()
+			.map(LoginHistory::from_row)
+			.collect::<core::result::Result<Vec<_>, _>>()?;
+
+		Ok(page.with_items(login_histories))
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use crate::core::model::user::UserForCreate;
+	use anyhow::Result;
+	use serde_json::json;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_own_and_admin_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_username = "test_list_own_and_admin_ok-user-01";
+		let user_id = UserBmc::create(
+			&root_ctx,
+			&mm,
+			UserForCreate {
+				username: fx_username.to_string(),
+				email: format!("{fx_username}@example.org"),
+				pwd_clear: "welcome-01".to_string(),
+				assigned_role: "demo".to_string(),
+			},
+		)
+		.await?;
+		let user_ctx = Ctx::new(user_id)?.with_client(ClientInfo {
+			ip: Some("10.1.2.3".to_string()),
+			user_agent: Some("fx-agent".to_string()),
+		});
+		let fx_unknown = "test_list_own_and_admin_ok-unknown";
+
+		// -- Exec
+		LoginHistoryBmc::record_for_user(
+			&user_ctx,
+			&mm,
+			user_id,
+			LoginHistoryKind::LoginOk,
+			None,
+		)
+		.await?;
+		LoginHistoryBmc::record_for_username(
+			&mm,
+			fx_unknown,
+			LoginHistoryKind::LoginFail,
+			&ClientInfo::default(),
+			Some(json!({"reason": "fx-reason"})),
+		)
+		.await?;
+
+		// -- Check
+		let own = LoginHistoryBmc::list(&user_ctx, &mm, None, None).await?;
+		assert_eq!(own.items.len(), 1);
+		let login_ok = &own.items[0];
+		assert_eq!(login_ok.username, fx_username);
+		assert_eq!(login_ok.kind, "LOGIN_OK");
+		assert_eq!(login_ok.ip.as_deref(), Some("10.1.2.3"));
+		assert_eq!(login_ok.user_agent.as_deref(), Some("fx-agent"));
+
+		let all = LoginHistoryBmc::list(&root_ctx, &mm, None, None).await?;
+		let login_fail = all
+			.items
+			.iter()
+			.find(|login_history| login_history.username == fx_unknown)
+			.ok_or_else(|| anyhow::anyhow!("no login fail of `{fx_unknown}`"))?;
+		assert_eq!(login_fail.user_id, None);
+		assert_eq!(login_fail.kind, "LOGIN_FAIL");
+
+		// -- Clean
+		UserBmc::delete(&root_ctx, &mm, user_id).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
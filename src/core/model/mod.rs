@@ -1,5 +1,6 @@
 // region:    --- Modules
 
+pub mod api_key;
 pub mod archive;
 pub mod archive_comment;
 pub mod associated_privilege;
@@ -8,19 +9,31 @@ pub mod bucket;
 pub mod datatype;
 pub mod document;
 pub mod document_comment;
+pub mod email_outbox;
 pub mod error;
 pub mod event;
+pub mod export;
 mod idens;
 pub mod index;
+pub mod ip_allowlist;
+pub mod login_attempt;
+pub mod login_history;
 pub mod modql_utils;
+pub mod oidc_login;
+pub mod password_reset;
 pub mod privilege;
 pub mod role;
+pub mod saved_search;
 pub mod search_operations;
 pub mod separator;
+pub mod session;
 mod store;
 pub mod structure;
 pub mod structure_privilege;
+pub mod token_key;
 pub mod user;
+pub mod user_identity;
+pub mod user_totp;
 pub mod value;
 
 use self::bucket::{get_s3_client, Bucket};
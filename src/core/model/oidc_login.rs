@@ -0,0 +1,83 @@
+use crate::core::model::base::DbBmc;
+use crate::core::model::ModelManager;
+use crate::core::model::Result;
+use crate::utils::time::now_utc;
+use modql::field::{Fields, HasFields};
+use sea_query::{Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use sqlx::FromRow;
+use time::Duration;
+
+use super::idens::OidcLoginIden;
+
+/// A login sent to the IdP, until its callback.
+#[derive(Clone, Fields, FromRow, Debug)]
+pub struct OidcLogin {
+	pub state: String,
+	pub nonce: String,
+	pub code_verifier: String,
+}
+
+pub struct OidcLoginBmc;
+
+impl DbBmc for OidcLoginBmc {
+	const TABLE: &'static str = "oidc_login";
+	const TIMESTAMPED: bool = false;
+	const SOFTDELETED: bool = false;
+}
+
+impl OidcLoginBmc {
+	/// Also removes the logins older than `max_age` (never called back).
+	pub async fn create(
+		mm: &ModelManager,
+		oidc_login: OidcLogin,
+		max_age: Duration,
+	) -> Result<()> {
+		let db = mm.db();
+
+		let mut query = Query::delete();
+		query
+			.from_table(Self::table_ref())
+			.and_where(Expr::col(OidcLoginIden::Ctime).lt(now_utc() - max_age));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		let (columns, sea_values) = oidc_login.all_fields().for_sea_insert();
+
+		let mut query = Query::insert();
+		query
+			.into_table(Self::table_ref())
+			.columns(columns)
+			.values(sea_values)?;
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(())
+	}
+
+	/// Removes and returns the login of the `state`, none when unknown, already
+	/// called back or older than `max_age`.
+	pub async fn take(
+		mm: &ModelManager,
+		state: &str,
+		max_age: Duration,
+	) -> Result<Option<OidcLogin>> {
+		let db = mm.db();
+
+		let mut query = Query::delete();
+		query
+			.from_table(Self::table_ref())
+			.and_where(Expr::col(OidcLoginIden::State).eq(state))
+			.and_where(Expr::col(OidcLoginIden::Ctime).gte(now_utc() - max_age))
+			.returning(Query::returning().columns(OidcLogin::field_column_refs()));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let oidc_login = sqlx::query_as_with::<_, OidcLogin, _>(&sql, values)
+			.fetch_optional(db)
+			.await?;
+
+		Ok(oidc_login)
+	}
+}
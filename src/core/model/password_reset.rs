@@ -0,0 +1,145 @@
+use crate::core::model::base::DbBmc;
+use crate::core::model::ModelManager;
+use crate::core::model::{Error, Result};
+use crate::utils::time::now_utc;
+use sea_query::{Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use super::idens::PasswordResetIden;
+
+/// A pwd reset request, `token_id` is the ident of its token.
+#[derive(Clone, FromRow, Debug)]
+pub struct PasswordReset {
+	pub id: i64,
+	pub token_id: Uuid,
+	pub user_id: i64,
+}
+
+pub struct PasswordResetBmc;
+
+impl DbBmc for PasswordResetBmc {
+	const TABLE: &'static str = "password_reset";
+	const TIMESTAMPED: bool = false;
+	const SOFTDELETED: bool = false;
+}
+
+impl PasswordResetBmc {
+	pub async fn create(mm: &ModelManager, user_id: i64) -> Result<PasswordReset> {
+		let db = mm.db();
+
+		let mut query = Query::insert();
+		query
+			.into_table(Self::table_ref())
+			.columns([PasswordResetIden::UserId])
+			.values([user_id.into()])?
+			.returning(Query::returning().columns([
+				PasswordResetIden::Id,
+				PasswordResetIden::TokenId,
+				PasswordResetIden::UserId,
+			]));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let reset = sqlx::query_as_with::<_, PasswordReset, _>(&sql, values)
+			.fetch_one(db)
+			.await?;
+
+		Ok(reset)
+	}
+
+	/// The not yet used reset of a token ident, if any.
+	pub async fn first_unused_by_token_id(
+		mm: &ModelManager,
+		token_id: Uuid,
+	) -> Result<Option<PasswordReset>> {
+		let db = mm.db();
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.columns([
+				PasswordResetIden::Id,
+				PasswordResetIden::TokenId,
+				PasswordResetIden::UserId,
+			])
+			.and_where(Expr::col(PasswordResetIden::TokenId).eq(token_id))
+			.and_where(Expr::col(PasswordResetIden::UsedAt).is_null());
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let reset = sqlx::query_as_with::<_, PasswordReset, _>(&sql, values)
+			.fetch_optional(db)
+			.await?;
+
+		Ok(reset)
+	}
+
+	/// Marks the reset used, an `EntityNotFound` when already used (so that
+	/// two concurrent confirms cannot both succeed).
+	pub async fn consume(mm: &ModelManager, id: i64) -> Result<()> {
+		let db = mm.db();
+
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.value(PasswordResetIden::UsedAt, now_utc())
+			.and_where(Expr::col(PasswordResetIden::Id).eq(id))
+			.and_where(Expr::col(PasswordResetIden::UsedAt).is_null());
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let count = sqlx::query_with(&sql, values)
+			.execute(db)
+			.await?
+			.rows_affected();
+
+		if count == 0 {
+			return Err(Error::EntityNotFound {
+				entity: Self::TABLE,
+				id,
+			});
+		}
+
+		Ok(())
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use crate::core::ctx::Ctx;
+	use crate::core::model::user::{User, UserBmc};
+	use anyhow::Result;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_consume_err_already_used() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let demo1: User = UserBmc::first_by_username(&Ctx::root_ctx(), &mm, "demo1")
+			.await?
+			.unwrap();
+		let fx_reset = PasswordResetBmc::create(&mm, demo1.id).await?;
+
+		// -- Exec
+		PasswordResetBmc::consume(&mm, fx_reset.id).await?;
+		let res = PasswordResetBmc::consume(&mm, fx_reset.id).await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::EntityNotFound { .. })),
+			"Should have matched `Err(Error::EntityNotFound)` but was `{res:?}`"
+		);
+		let unused =
+			PasswordResetBmc::first_unused_by_token_id(&mm, fx_reset.token_id)
+				.await?;
+		assert!(unused.is_none(), "Used reset should not be found");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -3,12 +3,12 @@ use crate::core::model::base::{self, DbBmc};
 use crate::core::model::ModelManager;
 use crate::core::model::Result;
 use modql::field::{Fields, HasFields};
-use modql::filter::{FilterNodes, ListOptions, OpValsInt64, OpValsString};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgRow;
 use sqlx::FromRow;
 
-use super::base::ListResult;
+use super::base::{CursorListOptions, ListResult};
 
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
 pub struct Privilege {
@@ -51,7 +51,7 @@ impl PrivilegeBmc {
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<PrivilegeFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<Privilege>> {
 		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
 	}
@@ -5,16 +5,17 @@ use crate::core::model::ModelManager;
 use crate::core::model::Result;
 use crate::utils::time::Rfc3339;
 use modql::field::{Fields, HasFields};
-use modql::filter::{
-	FilterNodes, ListOptions, OpValsInt64, OpValsString, OpValsValue,
-};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString, OpValsValue};
+use sea_query::{Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use sqlx::postgres::PgRow;
 use sqlx::types::time::OffsetDateTime;
 use sqlx::FromRow;
 
-use super::base::ListResult;
+use super::base::{CursorListOptions, ListResult};
+use super::idens::RoleIden;
 
 #[serde_as]
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
@@ -68,6 +69,32 @@ impl RoleBmc {
 		base::get::<Self, _>(ctx, mm, id).await
 	}
 
+	/// The not deleted role of the name, if any.
+	pub async fn first_by_role_name<E>(
+		_ctx: &Ctx,
+		mm: &ModelManager,
+		role_name: &str,
+	) -> Result<Option<E>>
+	where
+		E: RoleBy,
+	{
+		let db = mm.db();
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.columns(E::field_idens())
+			.and_where(Expr::col(RoleIden::RoleName).eq(role_name))
+			.and_where(Expr::col(RoleIden::IsDeleted).eq(false));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let entity = sqlx::query_as_with::<_, E, _>(&sql, values)
+			.fetch_optional(db)
+			.await?;
+
+		Ok(entity)
+	}
+
 	pub async fn create(
 		ctx: &Ctx,
 		mm: &ModelManager,
@@ -82,7 +109,7 @@ impl RoleBmc {
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<RoleFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<Role>> {
 		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
 	}
@@ -100,3 +127,54 @@ impl RoleBmc {
 		base::delete::<Self>(ctx, mm, id).await
 	}
 }
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use anyhow::Result;
+	use serde_json::json;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_total_count_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_role_names = [
+			"test_list_total_count_ok-role-01",
+			"test_list_total_count_ok-role-02",
+			"test_list_total_count_ok-role-03",
+		];
+		let mut fx_ids = Vec::new();
+		for role_name in fx_role_names {
+			let role_c = RoleForOp {
+				role_name: role_name.to_string(),
+				description: "test role".to_string(),
+			};
+			fx_ids.push(RoleBmc::create(&ctx, &mm, role_c).await?);
+		}
+		RoleBmc::delete(&ctx, &mm, fx_ids[0]).await?;
+		let fx_filter: RoleFilter = serde_json::from_value(json!({
+			"role_name": {"$startsWith": "test_list_total_count_ok-role"}
+		}))?;
+		let fx_list_options = serde_json::from_value(json!({"limit": 1}))?;
+
+		// -- Exec
+		let roles =
+			RoleBmc::list(&ctx, &mm, Some(vec![fx_filter]), Some(fx_list_options))
+				.await?;
+
+		// -- Check
+		assert_eq!(roles.total_count, 2);
+		assert_eq!(roles.items.len(), 1);
+		assert_eq!(roles.items[0].id, fx_ids[1]);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
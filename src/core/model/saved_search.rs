@@ -0,0 +1,590 @@
+use crate::core::ctx::Ctx;
+use crate::core::model::base::{self, add_timestamps_for_create, DbBmc};
+use crate::core::model::modql_utils::time_to_sea_value;
+use crate::core::model::ModelManager;
+use crate::core::model::{Error, Result};
+use crate::utils::time::Rfc3339;
+use modql::field::{Fields, HasFields};
+use modql::filter::{
+	FilterGroups, FilterNodes, OpValsInt64, OpValsString, OpValsValue,
+};
+use sea_query::{Condition, Expr, PostgresQueryBuilder, Query, SelectStatement};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::postgres::PgRow;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::FromRow;
+
+use super::archive::ArchiveWithValues;
+use super::base::{
+	add_timestamps_for_update, cursor_columns, list_rows, CursorListOptions,
+	ListResult,
+};
+use super::idens::{IndexIden, SavedSearchIden};
+use super::role::{Role, RoleBmc};
+use super::search_operations::{ArchiveIndexFilter, Listoptions, SearchBmc};
+use super::structure_privilege::StructurePrivilegeBmc;
+use super::user::{UserBmc, UserForAuth};
+
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct SavedSearch {
+	pub id: i64,
+	pub name: String,
+	pub owner: i64,
+	pub shared_role: Option<String>,
+	pub filters: serde_json::Value,
+	pub list_options: Option<serde_json::Value>,
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+/// Create and update data, an update replaces the whole search
+/// (a `None` `shared_role` stops sharing it).
+#[derive(Clone, Debug, Deserialize)]
+pub struct SavedSearchForOp {
+	pub name: String,
+	pub shared_role: Option<String>,
+	pub filters: Vec<ArchiveIndexFilter>,
+	pub list_options: Option<Listoptions>,
+}
+
+#[derive(Fields)]
+struct SavedSearchForInsert {
+	name: String,
+	owner: i64,
+	shared_role: Option<String>,
+	filters: serde_json::Value,
+	list_options: Option<serde_json::Value>,
+}
+
+#[derive(Fields)]
+struct SavedSearchForUpdate {
+	name: String,
+	shared_role: Option<String>,
+	filters: serde_json::Value,
+	list_options: Option<serde_json::Value>,
+}
+
+#[allow(dead_code)]
+pub trait SavedSearchBy:
+	HasFields + for<'r> FromRow<'r, PgRow> + Unpin + Send
+{
+}
+
+impl SavedSearchBy for SavedSearch {}
+
+#[derive(FilterNodes, Deserialize, Default, Debug)]
+pub struct SavedSearchFilter {
+	id: Option<OpValsInt64>,
+	name: Option<OpValsString>,
+	owner: Option<OpValsInt64>,
+	shared_role: Option<OpValsString>,
+	cid: Option<OpValsInt64>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	ctime: Option<OpValsValue>,
+	mid: Option<OpValsInt64>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	mtime: Option<OpValsValue>,
+}
+
+pub struct SavedSearchBmc;
+
+impl DbBmc for SavedSearchBmc {
+	const TABLE: &'static str = "saved_search";
+	const TIMESTAMPED: bool = true;
+	const SOFTDELETED: bool = true;
+}
+
+impl SavedSearchBmc {
+	/// Gets a search owned by the user or shared with the user's role.
+	pub async fn get(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<SavedSearch> {
+		let db = mm.db();
+
+		let mut query = Self::visible_query(ctx, mm).await?;
+		query.and_where(Expr::col(SavedSearchIden::Id).eq(id));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let saved_search = sqlx::query_as_with::<_, SavedSearch, _>(&sql, values)
+			.fetch_optional(db)
+			.await?
+			.ok_or(Error::EntityNotFound {
+				entity: Self::TABLE,
+				id,
+			})?;
+
+		Ok(saved_search)
+	}
+
+	pub async fn create(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		saved_search_c: SavedSearchForOp,
+	) -> Result<i64> {
+		let db = mm.db();
+
+		Self::check_shared_role(ctx, mm, saved_search_c.shared_role.as_deref())
+			.await?;
+		Self::check_structure_access(ctx, mm, &saved_search_c.filters).await?;
+
+		let saved_search_i = SavedSearchForInsert {
+			name: saved_search_c.name,
+			owner: ctx.user_id(),
+			shared_role: saved_search_c.shared_role,
+			filters: serde_json::to_value(saved_search_c.filters)?,
+			list_options: saved_search_c
+				.list_options
+				.map(serde_json::to_value)
+				.transpose()?,
+		};
+
+		// All the fields, a not shared search stores a NULL shared_role.
+		let mut fields = saved_search_i.all_fields();
+		add_timestamps_for_create(&mut fields, ctx.user_id());
+		let (columns, sea_values) = fields.for_sea_insert();
+
+		let mut query = Query::insert();
+		query
+			.into_table(Self::table_ref())
+			.columns(columns)
+			.values(sea_values)?
+			.returning(Query::returning().columns([SavedSearchIden::Id]));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let (id,) = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+			.fetch_one(db)
+			.await?;
+
+		Ok(id)
+	}
+
+	pub async fn list(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filters: Option<Vec<SavedSearchFilter>>,
+		list_options: Option<CursorListOptions>,
+	) -> Result<ListResult<SavedSearch>> {
+		let mut query = Self::visible_query(ctx, mm).await?;
+		if let Some(filters) = filters {
+			let filters: FilterGroups = filters.into();
+			let cond: Condition = filters.try_into()?;
+			query.cond_where(cond);
+		}
+
+		let page = list_rows(mm, query, list_options, |order_bys| {
+			Ok(cursor_columns(Self::table_ref(), order_bys))
+		})
+		.await?;
+		let saved_searches = page
+			.items
+			.iter()
+			.map(SavedSearch::from_row)
+			.collect::<core::result::Result<Vec<_>, _>>()?;
+
+		Ok(page.with_items(saved_searches))
+	}
+
+	/// Runs the stored filters, with `list_options` replacing the stored
+	/// ones when given.
+	/// Only the archives of the caller's accessible structures are found,
+	/// whoever saved the search.
+	pub async fn run(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+		list_options: Option<Listoptions>,
+	) -> Result<ListResult<ArchiveWithValues>> {
+		let saved_search = Self::get(ctx, mm, id).await?;
+
+		let filters: Vec<ArchiveIndexFilter> =
+			serde_json::from_value(saved_search.filters)?;
+		let list_options = match list_options {
+			Some(list_options) => Some(list_options),
+			None => saved_search
+				.list_options
+				.map(serde_json::from_value)
+				.transpose()?,
+		};
+
+		SearchBmc::search_archives(ctx, mm, Some(filters), list_options, None, None)
+			.await
+	}
+
+	/// Only the owner can update a search.
+	pub async fn update(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+		saved_search_u: SavedSearchForOp,
+	) -> Result<()> {
+		let db = mm.db();
+
+		Self::check_owner(ctx, mm, id).await?;
+		Self::check_shared_role(ctx, mm, saved_search_u.shared_role.as_deref())
+			.await?;
+		Self::check_structure_access(ctx, mm, &saved_search_u.filters).await?;
+
+		let saved_search_u = SavedSearchForUpdate {
+			name: saved_search_u.name,
+			shared_role: saved_search_u.shared_role,
+			filters: serde_json::to_value(saved_search_u.filters)?,
+			list_options: saved_search_u
+				.list_options
+				.map(serde_json::to_value)
+				.transpose()?,
+		};
+
+		let mut fields = saved_search_u.all_fields();
+		add_timestamps_for_update(&mut fields, ctx.user_id());
+
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.values(fields.for_sea_update())
+			.and_where(Expr::col(SavedSearchIden::Id).eq(id));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(())
+	}
+
+	/// Only the owner can delete a search.
+	pub async fn delete(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+		Self::check_owner(ctx, mm, id).await?;
+
+		base::delete::<Self>(ctx, mm, id).await
+	}
+}
+
+// region:    --- Access
+
+impl SavedSearchBmc {
+	/// Not deleted searches, owned by the user or shared with the user's role
+	/// (the api key role, when authenticated with a key).
+	async fn visible_query(ctx: &Ctx, mm: &ModelManager) -> Result<SelectStatement> {
+		let user: UserForAuth = UserBmc::get(ctx, mm, ctx.user_id()).await?;
+		let role_name = match ctx.key_scope() {
+			Some(scope) => scope.role_name.clone(),
+			None => user.assigned_role,
+		};
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.columns(SavedSearch::field_column_refs())
+			.cond_where(
+				Condition::any()
+					.add(Expr::col(SavedSearchIden::Owner).eq(user.id))
+					.add(Expr::col(SavedSearchIden::SharedRole).eq(role_name)),
+			)
+			.and_where(Expr::col(SavedSearchIden::IsDeleted).eq(false));
+
+		Ok(query)
+	}
+
+	async fn check_owner(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+		let saved_search = Self::get(ctx, mm, id).await?;
+
+		if saved_search.owner != ctx.user_id() {
+			return Err(Error::AccessDenied {
+				entity: Self::TABLE,
+				id,
+			});
+		}
+
+		Ok(())
+	}
+
+	/// A search is shared with an existing role, the user's own (the api key
+	/// role, when authenticated with a key) unless an admin.
+	async fn check_shared_role(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		shared_role: Option<&str>,
+	) -> Result<()> {
+		let Some(shared_role) = shared_role else {
+			return Ok(());
+		};
+
+		let role: Option<Role> =
+			RoleBmc::first_by_role_name(ctx, mm, shared_role).await?;
+		if role.is_none() {
+			return Err(Error::RoleNotFound {
+				entity: RoleBmc::TABLE,
+				id: shared_role.to_string(),
+			});
+		}
+
+		let user: UserForAuth = UserBmc::get(ctx, mm, ctx.user_id()).await?;
+		let own_role = match ctx.key_scope() {
+			Some(scope) => scope.role_name.as_str(),
+			None => user.assigned_role.as_str(),
+		};
+		if shared_role != own_role {
+			UserBmc::check_admin(ctx, mm, Self::TABLE).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Every filtered index must exist, and the user must have access to
+	/// its structure.
+	async fn check_structure_access(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filters: &[ArchiveIndexFilter],
+	) -> Result<()> {
+		let db = mm.db();
+
+		let mut index_ids: Vec<i64> =
+			filters.iter().map(|filter| filter.index_id).collect();
+		index_ids.sort_unstable();
+		index_ids.dedup();
+
+		let mut query = Query::select();
+		query
+			.from(IndexIden::Table)
+			.columns([IndexIden::Id, IndexIden::ProjectId])
+			.and_where(Expr::col(IndexIden::Id).is_in(index_ids.clone()))
+			.and_where(Expr::col(IndexIden::IsDeleted).eq(false));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let indexes = sqlx::query_as_with::<_, (i64, i64), _>(&sql, values)
+			.fetch_all(db)
+			.await?;
+
+		if let Some(index_id) = index_ids
+			.iter()
+			.find(|index_id| !indexes.iter().any(|(id, _)| id == *index_id))
+		{
+			return Err(Error::UnknownIndexId(*index_id));
+		}
+
+		let mut project_ids: Vec<i64> = indexes
+			.into_iter()
+			.map(|(_, project_id)| project_id)
+			.collect();
+		project_ids.sort_unstable();
+		project_ids.dedup();
+
+		for project_id in project_ids {
+			if !StructurePrivilegeBmc::has_access(ctx, mm, ctx.user_id(), project_id)
+				.await?
+			{
+				return Err(Error::AccessDenied {
+					entity: "structure",
+					id: project_id,
+				});
+			}
+		}
+
+		Ok(())
+	}
+}
+
+// endregion: --- Access
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use crate::core::model::archive::{ArchiveBmc, ArchiveForCreate};
+	use crate::core::model::index::{IndexBmc, IndexForCreate};
+	use crate::core::model::role::RoleForOp;
+	use crate::core::model::structure::{StructureBmc, StructureForOp};
+	use crate::core::model::structure_privilege::StructureGrantee;
+	use crate::core::model::user::UserForCreate;
+	use crate::core::model::value::{ValueBmc, ValueForCreate};
+	use anyhow::Result;
+	use serde_json::json;
+	use serial_test::serial;
+
+	async fn fx_user(mm: &ModelManager, username: &str) -> Result<i64> {
+		let id = UserBmc::create(
+			&Ctx::root_ctx(),
+			mm,
+			UserForCreate {
+				username: username.to_string(),
+				email: format!("{username}@example.org"),
+				pwd_clear: "welcome-01".to_string(),
+				assigned_role: "demo".to_string(),
+			},
+		)
+		.await?;
+
+		Ok(id)
+	}
+
+	/// A structure with a TEXT index, and an archive valued `name` for it.
+	/// Returns the structure id and the filters finding the archive.
+	async fn fx_structure(
+		mm: &ModelManager,
+		name: &str,
+	) -> Result<(i64, Vec<ArchiveIndexFilter>)> {
+		let root_ctx = Ctx::root_ctx();
+
+		let project_id = StructureBmc::create(
+			&root_ctx,
+			mm,
+			StructureForOp {
+				project_name: name.to_string(),
+			},
+		)
+		.await?;
+		let index_id = IndexBmc::create(
+			&root_ctx,
+			mm,
+			IndexForCreate {
+				datatype_id: 1,
+				project_id,
+				required: false,
+				index_name: format!("{name}-index"),
+			},
+		)
+		.await?;
+		let archive_id = ArchiveBmc::create(
+			&root_ctx,
+			mm,
+			ArchiveForCreate {
+				project_id,
+				tag: name.to_string(),
+			},
+		)
+		.await?;
+		ValueBmc::create(
+			&root_ctx,
+			mm,
+			ValueForCreate {
+				index_id,
+				project_id,
+				archive_id,
+				value: name.to_string(),
+			},
+		)
+		.await?;
+		let filters = serde_json::from_value(json!([
+			{"index_id": index_id, "value": name, "operator": "Eq", "datatype_id": 1},
+		]))?;
+
+		Ok((project_id, filters))
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_create_run_shared_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let owner_id = fx_user(&mm, "fx-ss-user-01").await?;
+		let member_id = fx_user(&mm, "fx-ss-user-02").await?;
+		let (project_id, filters) = fx_structure(&mm, "fx-ss-structure-01").await?;
+		let owner_ctx = Ctx::new(owner_id)?;
+		let member_ctx = Ctx::new(member_id)?;
+
+		// -- Exec
+		let id = SavedSearchBmc::create(
+			&owner_ctx,
+			&mm,
+			SavedSearchForOp {
+				name: "fx-search".to_string(),
+				shared_role: Some("demo".to_string()),
+				filters,
+				list_options: None,
+			},
+		)
+		.await?;
+		let owner_run = SavedSearchBmc::run(&owner_ctx, &mm, id, None).await?;
+		let member_list = SavedSearchBmc::list(&member_ctx, &mm, None, None).await?;
+		let member_run = SavedSearchBmc::run(&member_ctx, &mm, id, None).await?;
+		StructurePrivilegeBmc::disable(
+			&root_ctx,
+			&mm,
+			&StructureGrantee::User(member_id),
+			project_id,
+		)
+		.await?;
+		let member_run_denied =
+			SavedSearchBmc::run(&member_ctx, &mm, id, None).await?;
+		let owner_run_after = SavedSearchBmc::run(&owner_ctx, &mm, id, None).await?;
+
+		// -- Check
+		assert_eq!(owner_run.total_count, 1);
+		assert_eq!(owner_run.items[0].archive.tag, "fx-ss-structure-01");
+		assert!(member_list.items.iter().any(|search| search.id == id));
+		assert_eq!(member_run.total_count, 1);
+		// No rows of a structure the member lost access to.
+		assert_eq!(member_run_denied.total_count, 0);
+		assert!(member_run_denied.items.is_empty());
+		assert_eq!(owner_run_after.total_count, 1);
+
+		// -- Clean
+		SavedSearchBmc::delete(&owner_ctx, &mm, id).await?;
+		UserBmc::delete(&root_ctx, &mm, owner_id).await?;
+		UserBmc::delete(&root_ctx, &mm, member_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_create_shared_role_err() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let user_id = fx_user(&mm, "fx-ss-user-03").await?;
+		RoleBmc::create(
+			&root_ctx,
+			&mm,
+			RoleForOp {
+				role_name: "fx-ss-role-01".to_string(),
+				description: "fx other role".to_string(),
+			},
+		)
+		.await?;
+		let (_, filters) = fx_structure(&mm, "fx-ss-structure-02").await?;
+		let user_ctx = Ctx::new(user_id)?;
+		let fx_search = |shared_role: &str| SavedSearchForOp {
+			name: "fx-search".to_string(),
+			shared_role: Some(shared_role.to_string()),
+			filters: filters.clone(),
+			list_options: None,
+		};
+
+		// -- Exec
+		let res_unknown =
+			SavedSearchBmc::create(&user_ctx, &mm, fx_search("fx-ss-unknown")).await;
+		let res_other =
+			SavedSearchBmc::create(&user_ctx, &mm, fx_search("fx-ss-role-01")).await;
+		let admin_id =
+			SavedSearchBmc::create(&root_ctx, &mm, fx_search("fx-ss-role-01"))
+				.await?;
+
+		// -- Check
+		assert!(
+			matches!(res_unknown, Err(Error::RoleNotFound { .. })),
+			"Should have matched `Err(Error::RoleNotFound {{ .. }})` but was `{res_unknown:?}`"
+		);
+		assert!(
+			matches!(res_other, Err(Error::AccessDenied { .. })),
+			"Should have matched `Err(Error::AccessDenied {{ .. }})` but was `{res_other:?}`"
+		);
+		let admin_search = SavedSearchBmc::get(&root_ctx, &mm, admin_id).await?;
+		assert_eq!(admin_search.shared_role.as_deref(), Some("fx-ss-role-01"));
+
+		// -- Clean
+		SavedSearchBmc::delete(&root_ctx, &mm, admin_id).await?;
+		UserBmc::delete(&root_ctx, &mm, user_id).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -1,21 +1,36 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::core::ctx::Ctx;
 use crate::core::model::base::DbBmc;
 use crate::core::model::idens::*;
 use crate::core::model::ModelManager;
 use crate::core::model::{Error, Result};
+use crate::utils::time::Rfc3339;
 use modql::field::{Fields, HasFields};
 use modql::filter::FilterNodes;
 use modql::filter::{FilterGroups, ListOptions};
-use sea_query::{Condition, Expr, PostgresQueryBuilder, Query};
+use sea_query::extension::postgres::{PgBinOper, PgExpr};
+use sea_query::{
+	Alias, Asterisk, BinOper, Condition, Expr, Func, Iden, IntoIden, JoinType,
+	Order, PostgresQueryBuilder, Query, SelectStatement, SimpleExpr, TableRef,
+	WindowStatement,
+};
 use sea_query_binder::SqlxBinder;
 use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
 use sqlx::postgres::PgRow;
-use sqlx::{FromRow, QueryBuilder, Row};
+use sqlx::types::time::OffsetDateTime;
+use sqlx::types::Json;
+use sqlx::{FromRow, Row};
 
-use super::archive::Archive;
-use super::base::{compute_list_options, ListResult};
+use super::archive::{Archive, ArchiveWithValues};
+use super::base::{
+	check_list_offset, compute_list_options, cursor_cond, cursor_expr, list_limit,
+	next_cursor, window_total_count, CursorColumn, ListResult,
+};
+use super::document::Document;
+use super::export::{Export, ExportColumn};
+use super::structure_privilege::StructurePrivilegeBmc;
 
 #[derive(Debug, Serialize, FromRow, Fields, Clone)]
 pub struct IndexWithDatatype {
@@ -26,21 +41,145 @@ pub struct IndexWithDatatype {
 	datatype_name: String,
 }
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
 pub struct Listoptions {
 	pub order_bys: Option<String>, // e.g., "!id,name"
 	pub limit: Option<i64>,
 	pub offset: Option<i64>,
+	/// `next_cursor` of the previous page, replaces the `offset`.
+	pub cursor: Option<String>,
 }
 
-#[derive(Deserialize, Default, Debug, FilterNodes)]
+#[derive(Deserialize, Serialize, Default, Clone, Debug, FilterNodes)]
 pub struct ArchiveIndexFilter {
-	index_id: i64,
+	pub index_id: i64,
 	value: String,
 	operator: String,
 	datatype_id: i64,
 }
 
+/// Aggregated counts requested alongside `search_archives`,
+/// computed over the same filtered archives (not only the returned page).
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Facet {
+	/// Count per distinct value of an index (most frequent first).
+	Values {
+		index_id: i64,
+		limit: Option<i64>,
+	},
+	/// Count per `bucket_size` wide range of a NUMERIC index.
+	NumericHistogram {
+		index_id: i64,
+		bucket_size: f64,
+	},
+	/// Count per day/week/month/year of a DATE index.
+	DateHistogram {
+		index_id: i64,
+		interval: DateInterval,
+	},
+	Owner {
+		limit: Option<i64>,
+	},
+	Project {
+		limit: Option<i64>,
+	},
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DateInterval {
+	Day,
+	Week,
+	Month,
+	Year,
+}
+
+impl DateInterval {
+	fn as_str(self) -> &'static str {
+		match self {
+			Self::Day => "day",
+			Self::Week => "week",
+			Self::Month => "month",
+			Self::Year => "year",
+		}
+	}
+}
+
+#[derive(Serialize, Debug)]
+pub struct FacetCounts {
+	#[serde(flatten)]
+	pub facet: Facet,
+	pub buckets: Vec<FacetBucket>,
+}
+
+/// `key` is the distinct value, the bucket lower bound (histograms),
+/// or the owner/project id.
+#[derive(Serialize, FromRow, Debug)]
+pub struct FacetBucket {
+	pub key: serde_json::Value,
+	pub count: i64,
+}
+
+/// Document search criteria, all optional and combined with AND.
+#[serde_as]
+#[derive(Deserialize, Default, Debug)]
+pub struct DocumentSearchFilter {
+	/// Part of the document name, matched as per `name_match`.
+	pub name: Option<String>,
+	pub name_match: Option<NameMatch>,
+	/// MIME type, exact.
+	pub doc_type: Option<String>,
+	/// Uploader user id.
+	pub owner: Option<i64>,
+	#[serde_as(as = "Option<Rfc3339>")]
+	pub uploaded_from: Option<OffsetDateTime>,
+	#[serde_as(as = "Option<Rfc3339>")]
+	pub uploaded_to: Option<OffsetDateTime>,
+	/// Case-insensitive part of the name of the document's folder.
+	pub separator_name: Option<String>,
+	pub project_id: Option<i64>,
+	/// Index value filters on the parent archive.
+	pub archive_filters: Option<Vec<ArchiveIndexFilter>>,
+}
+
+/// How `DocumentSearchFilter::name` is matched.
+#[derive(Deserialize, Default, Clone, Copy, Debug)]
+pub enum NameMatch {
+	/// Case insensitive part of the name.
+	#[default]
+	Contains,
+	/// Accent and case insensitive part of the name.
+	Unaccent,
+	/// Trigram word similarity over the `pg_trgm.word_similarity_threshold`
+	/// of the connection, best matches first.
+	Fuzzy,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DocumentWithPath {
+	#[serde(flatten)]
+	pub document: Document,
+	/// From the structure, through the archive, down to the document folder.
+	pub path: Vec<PathItem>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PathItem {
+	pub id: i64,
+	pub name: String,
+	/// "structure", "archive" or "folder".
+	pub r#type: &'static str,
+}
+
+/// Index of an archives export column.
+#[derive(FromRow, Debug)]
+struct ExportIndex {
+	id: i64,
+	index_name: String,
+	datatype_id: i64,
+}
+
 #[allow(dead_code)]
 pub trait SearchBy: HasFields + for<'r> FromRow<'r, PgRow> + Unpin + Send {}
 
@@ -110,430 +249,1949 @@ impl SearchBmc {
 	}
 
 	pub async fn search_archives(
-		_ctx: &Ctx,
+		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<ArchiveIndexFilter>>,
 		list_options: Option<Listoptions>,
-	) -> Result<ListResult<Archive>> {
+		index_ids: Option<Vec<i64>>,
+		facets: Option<Vec<Facet>>,
+	) -> Result<ListResult<ArchiveWithValues>> {
 		let db = mm.db();
 
-		// Unwrap filters and list options or use default values.
 		let filters = filters.unwrap_or_default();
 		let list_options = list_options.unwrap_or_default();
 
-		// Initialize the query builder with the SELECT clause.
-		let mut query_builder = QueryBuilder::new("SELECT ");
-
-		// Define fields to select from the archive table.
-		let archive_fields = [
-			"id",
-			"project_id",
-			"owner",
-			"last_edit_user",
-			"tag",
-			"cid",
-			"ctime",
-			"mid",
-			"mtime",
-		];
+		let query = search_archives_query(ctx, &filters, &list_options, index_ids)?;
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let rows = sqlx::query_with(&sql, values).fetch_all(db).await?;
+
+		let total_count = window_total_count(
+			mm,
+			&rows,
+			list_options.cursor.is_some(),
+			list_options.offset,
+			|| filtered_archives_query(ctx, &filters),
+		)
+		.await?;
 
-		// Build the SELECT clause.
-		for (i, field) in archive_fields.iter().enumerate() {
-			if i > 0 {
-				query_builder.push(", ");
+		let archives = rows
+			.iter()
+			.map(archive_with_values_from_row)
+			.collect::<core::result::Result<Vec<_>, _>>()?;
+
+		let facets = match facets {
+			Some(facets) => {
+				Some(Self::list_facet_counts(ctx, mm, &filters, facets).await?)
 			}
-			query_builder.push(format!("\"archive\".\"{}\"", field));
+			None => None,
+		};
+
+		// No cursor when ranking by similarity.
+		let next_cursor = match archives_rank(&filters, &list_options) {
+			Some(_) => None,
+			None => next_cursor(&rows, Some(list_limit(list_options.limit)?))?,
+		};
+
+		Ok(ListResult {
+			total_count: total_count as usize,
+			next_cursor,
+			items: archives,
+			facets,
+		})
+	}
+
+	pub async fn search_documents(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filter: Option<DocumentSearchFilter>,
+		list_options: Option<Listoptions>,
+	) -> Result<ListResult<DocumentWithPath>> {
+		let db = mm.db();
+
+		let filter = filter.unwrap_or_default();
+		let list_options = list_options.unwrap_or_default();
+
+		let query = search_documents_query(ctx, &filter, &list_options)?;
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let rows = sqlx::query_with(&sql, values).fetch_all(db).await?;
+
+		let total_count = window_total_count(
+			mm,
+			&rows,
+			list_options.cursor.is_some(),
+			list_options.offset,
+			|| filtered_documents_query(ctx, &filter),
+		)
+		.await?;
+
+		// -- Folders of the hit archives, for the breadcrumbs.
+		let mut archive_ids = Vec::with_capacity(rows.len());
+		for row in &rows {
+			archive_ids.push(row.try_get::<i64, _>("archive_id")?);
 		}
+		archive_ids.sort_unstable();
+		archive_ids.dedup();
 
-		// Group filters by index_id and ensure datatype consistency.
-		let mut filters_by_index: HashMap<i64, Vec<&ArchiveIndexFilter>> =
-			HashMap::new();
-		let mut index_datatype_ids: HashMap<i64, i64> = HashMap::new();
-
-		for filter in &filters {
-			filters_by_index
-				.entry(filter.index_id)
-				.or_insert_with(Vec::new)
-				.push(filter);
-
-			// Ensure that the datatype_id is consistent for each index_id.
-			if let Some(&existing_datatype_id) =
-				index_datatype_ids.get(&filter.index_id)
-			{
-				if existing_datatype_id != filter.datatype_id {
-					return Err(Error::UnsupportedDatatype(filter.index_id));
-				}
-			} else {
-				index_datatype_ids.insert(filter.index_id, filter.datatype_id);
-			}
+		let mut query = Query::select();
+		query
+			.from(SeparatorIden::Table)
+			.columns([
+				SeparatorIden::Id,
+				SeparatorIden::Name,
+				SeparatorIden::ParentId,
+			])
+			.and_where(Expr::col(SeparatorIden::ArchiveId).is_in(archive_ids));
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let folders: HashMap<i64, (String, Option<i64>)> =
+			sqlx::query_as_with::<_, (i64, String, Option<i64>), _>(&sql, values)
+				.fetch_all(db)
+				.await?
+				.into_iter()
+				.map(|(id, name, parent_id)| (id, (name, parent_id)))
+				.collect();
+
+		let documents = rows
+			.iter()
+			.map(|row| document_with_path_from_row(row, &folders))
+			.collect::<core::result::Result<Vec<_>, _>>()?;
+
+		// No cursor when ranking by similarity.
+		let next_cursor = match documents_rank(&filter, &list_options) {
+			Some(_) => None,
+			None => next_cursor(&rows, Some(list_limit(list_options.limit)?))?,
+		};
+
+		Ok(ListResult {
+			total_count: total_count as usize,
+			next_cursor,
+			items: documents,
+			facets: None,
+		})
+	}
+
+	/// All the archives of `search_archives` (only the `order_bys` of the
+	/// list options apply), with a column per index of their projects,
+	/// named by its `index_name`, see `Export`.
+	pub async fn export_archives(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filters: Option<Vec<ArchiveIndexFilter>>,
+		list_options: Option<Listoptions>,
+		index_ids: Option<Vec<i64>>,
+	) -> Result<Export> {
+		let db = mm.db();
+
+		let filters = filters.unwrap_or_default();
+		let list_options = list_options.unwrap_or_default();
+
+		let query = export_indexes_query(ctx, &filters, index_ids)?;
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let indexes = sqlx::query_as_with::<_, ExportIndex, _>(&sql, values)
+			.fetch_all(db)
+			.await?;
+
+		export_archives_query(ctx, &filters, &list_options, &indexes)
+	}
+
+	async fn list_facet_counts(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filters: &[ArchiveIndexFilter],
+		facets: Vec<Facet>,
+	) -> Result<Vec<FacetCounts>> {
+		let db = mm.db();
+
+		let mut facet_counts = Vec::with_capacity(facets.len());
+		for facet in facets {
+			let query = facet_query(ctx, filters, &facet)?;
+			let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+			let buckets = sqlx::query_as_with::<_, FacetBucket, _>(&sql, values)
+				.fetch_all(db)
+				.await?;
+
+			facet_counts.push(FacetCounts { facet, buckets });
 		}
 
-		// Add value columns to SELECT clause.
-		for (n, (&index_id, _)) in filters_by_index.iter().enumerate() {
-			query_builder.push(", ");
-			query_builder
-				.push(format!("\"v{n}\".\"value\" AS \"v{index_id}_value\""));
+		Ok(facet_counts)
+	}
+}
+
+// region:    --- Search Query Builders
+
+/// Columns of the archive table that can be selected and ordered by.
+const ARCHIVE_FIELDS: [ArchiveIden; 9] = [
+	ArchiveIden::Id,
+	ArchiveIden::ProjectId,
+	ArchiveIden::Owner,
+	ArchiveIden::LastEditUser,
+	ArchiveIden::Tag,
+	ArchiveIden::Cid,
+	ArchiveIden::Ctime,
+	ArchiveIden::Mid,
+	ArchiveIden::Mtime,
+];
+
+/// Format of the DATE index values (as stored in `value.value`).
+const DATE_FORMAT: &str = "YYYY-MM-DD";
+
+/// How an index value is compared, from the `consts.datatype` id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchDatatype {
+	Text,
+	Numeric,
+	Date,
+}
+
+impl SearchDatatype {
+	fn from_id(index_id: i64, datatype_id: i64) -> Result<Self> {
+		match datatype_id {
+			1 => Ok(Self::Text),
+			2 => Ok(Self::Numeric),
+			3 => Ok(Self::Date),
+			_ => Err(Error::UnsupportedDatatype(index_id)),
 		}
+	}
 
-		// FROM clause.
-		query_builder.push(" FROM \"archive\"");
-
-		// Build INNER JOINs for each index_id.
-		let mut join_index = 0;
-
-		for (&index_id, index_filters) in &filters_by_index {
-			let alias = format!("v{}", join_index);
-			let datatype_id = index_datatype_ids
-				.get(&index_id)
-				.ok_or_else(|| Error::UnsupportedDatatype(index_id))?;
-
-			query_builder.push(format!(
-            " INNER JOIN \"value\" AS \"{}\" ON \"{}\".\"archive_id\" = \"archive\".\"id\" AND \"{}\".\"index_id\" = ",
-            alias, alias, alias
-        ));
-			query_builder.push_bind(index_id);
-
-			// Collect conditions for this index_id.
-			let mut eq_values = Vec::new();
-			let mut gte_value = None;
-			let mut lte_value = None;
-
-			for filter in index_filters {
-				match filter.operator.as_str() {
-					"Eq" => eq_values.push(&filter.value),
-					"Gte" => gte_value = Some(&filter.value),
-					"Lte" => lte_value = Some(&filter.value),
-					_ => return Err(Error::UnsupportedDatatype(index_id)),
-				}
-			}
+	/// Cast both the stored value and the filter value the same way.
+	fn cast(self, expr: SimpleExpr) -> SimpleExpr {
+		match self {
+			Self::Text => expr,
+			Self::Numeric => expr.cast_as(Alias::new("NUMERIC")),
+			Self::Date => Func::cust(Alias::new("TO_DATE"))
+				.arg(expr)
+				.arg(DATE_FORMAT)
+				.into(),
+		}
+	}
 
-			// Adjust casting based on datatype_id and cast both sides.
-			match datatype_id {
-				1 => {
-					// TEXT
-					if !eq_values.is_empty() {
-						for value in eq_values {
-							query_builder.push(" AND ");
-							query_builder
-								.push(format!("\"{}\".\"value\" = ", alias));
-							query_builder.push_bind(value);
-						}
-					}
-					if gte_value.is_some() || lte_value.is_some() {
-						return Err(Error::UnsupportedDatatype(index_id));
-					}
-				}
-				2 => {
-					// NUMERIC
-					if !eq_values.is_empty() {
-						for value in eq_values {
-							query_builder.push(" AND ");
-							query_builder.push(format!(
-								"CAST(\"{}\".\"value\" AS NUMERIC) = CAST(",
-								alias
-							));
-							query_builder.push_bind(value);
-							query_builder.push(" AS NUMERIC)");
-						}
-					}
-					if gte_value.is_some() && lte_value.is_some() {
-						query_builder.push(" AND CAST(\"");
-						query_builder.push(&alias);
-						query_builder.push("\".\"value\" AS NUMERIC) BETWEEN CAST(");
-						query_builder.push_bind(gte_value.unwrap());
-						query_builder.push(" AS NUMERIC) AND CAST(");
-						query_builder.push_bind(lte_value.unwrap());
-						query_builder.push(" AS NUMERIC)");
-					} else {
-						if let Some(value) = gte_value {
-							query_builder.push(" AND CAST(\"");
-							query_builder.push(&alias);
-							query_builder.push("\".\"value\" AS NUMERIC) >= CAST(");
-							query_builder.push_bind(value);
-							query_builder.push(" AS NUMERIC)");
-						}
-						if let Some(value) = lte_value {
-							query_builder.push(" AND CAST(\"");
-							query_builder.push(&alias);
-							query_builder.push("\".\"value\" AS NUMERIC) <= CAST(");
-							query_builder.push_bind(value);
-							query_builder.push(" AS NUMERIC)");
-						}
-					}
-				}
-				3 => {
-					// TIMESTAMP stored as mm-dd-yyyy
-					let date_format = "YYYY-MM-DD";
-					if !eq_values.is_empty() {
-						for value in eq_values {
-							query_builder.push(" AND ");
-							query_builder.push(format!(
-								"TO_DATE(\"{}\".\"value\", '{}') = TO_DATE(",
-								alias, date_format
-							));
-							query_builder.push_bind(value);
-							query_builder.push(format!(", '{}')", date_format));
-						}
-					}
-					if gte_value.is_some() && lte_value.is_some() {
-						query_builder.push(" AND TO_DATE(\"");
-						query_builder.push(&alias);
-						query_builder.push(format!(
-							"\".\"value\", '{}') BETWEEN TO_DATE(",
-							date_format
-						));
-						query_builder.push_bind(gte_value.unwrap());
-						query_builder
-							.push(format!(", '{}') AND TO_DATE(", date_format));
-						query_builder.push_bind(lte_value.unwrap());
-						query_builder.push(format!(", '{}')", date_format));
-					} else {
-						if let Some(value) = gte_value {
-							query_builder.push(" AND TO_DATE(\"");
-							query_builder.push(&alias);
-							query_builder.push(format!(
-								"\".\"value\", '{}') >= TO_DATE(",
-								date_format
-							));
-							query_builder.push_bind(value);
-							query_builder.push(format!(", '{}')", date_format));
-						}
-						if let Some(value) = lte_value {
-							query_builder.push(" AND TO_DATE(\"");
-							query_builder.push(&alias);
-							query_builder.push(format!(
-								"\".\"value\", '{}') <= TO_DATE(",
-								date_format
-							));
-							query_builder.push_bind(value);
-							query_builder.push(format!(", '{}')", date_format));
-						}
-					}
-				}
-				_ => return Err(Error::UnsupportedDatatype(index_id)),
+	fn supports_range(self) -> bool {
+		!matches!(self, Self::Text)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchOperator {
+	Eq,
+	Gte,
+	Lte,
+	/// Accent and case insensitive equality (TEXT only).
+	Unaccent,
+	/// Trigram similarity over the `pg_trgm.similarity_threshold` of the
+	/// connection, accent and case insensitive (TEXT only).
+	Fuzzy,
+}
+
+impl SearchOperator {
+	fn parse(operator: &str) -> Result<Self> {
+		match operator {
+			"Eq" => Ok(Self::Eq),
+			"Gte" => Ok(Self::Gte),
+			"Lte" => Ok(Self::Lte),
+			"Unaccent" => Ok(Self::Unaccent),
+			"Fuzzy" => Ok(Self::Fuzzy),
+			_ => Err(Error::UnsupportedOperator(operator.to_string())),
+		}
+	}
+
+	fn supports(self, datatype: SearchDatatype) -> bool {
+		match self {
+			Self::Eq => true,
+			Self::Gte | Self::Lte => datatype.supports_range(),
+			Self::Unaccent | Self::Fuzzy => datatype == SearchDatatype::Text,
+		}
+	}
+
+	/// `value` and `filter_value` are already cast to the index datatype.
+	fn cond(self, value: SimpleExpr, filter_value: SimpleExpr) -> SimpleExpr {
+		match self {
+			Self::Eq => value.binary(BinOper::Equal, filter_value),
+			Self::Gte => value.binary(BinOper::GreaterThanOrEqual, filter_value),
+			Self::Lte => value.binary(BinOper::SmallerThanOrEqual, filter_value),
+			Self::Unaccent => {
+				normalized(value).binary(BinOper::Equal, normalized(filter_value))
 			}
+			Self::Fuzzy => normalized(value)
+				.binary(PgBinOper::Similarity, normalized(filter_value)),
+		}
+	}
+}
 
-			join_index += 1;
+/// Lowercase without accents, see `f_unaccent` in the schema.
+fn normalized(expr: SimpleExpr) -> SimpleExpr {
+	Func::cust(Alias::new("f_unaccent"))
+		.arg(Func::lower(expr))
+		.into()
+}
+
+/// Best matches first (`fuzzy_rank`), unless ordered otherwise.
+fn archives_rank(
+	filters: &[ArchiveIndexFilter],
+	list_options: &Listoptions,
+) -> Option<SimpleExpr> {
+	match list_options.order_bys {
+		Some(_) => None,
+		None => fuzzy_rank(filters),
+	}
+}
+
+/// Sum of the trigram similarities of the `Fuzzy` filters, to rank by.
+fn fuzzy_rank(filters: &[ArchiveIndexFilter]) -> Option<SimpleExpr> {
+	filters
+		.iter()
+		.filter(|filter| filter.operator == "Fuzzy")
+		.map(|filter| -> SimpleExpr {
+			let alias = Alias::new(format!("v{}", filter.index_id));
+			Func::cust(Alias::new("similarity"))
+				.arg(normalized(Expr::col((alias, ValueIden::Value)).into()))
+				.arg(normalized(Expr::val(filter.value.clone()).into()))
+				.into()
+		})
+		.reduce(|rank, similarity| rank.add(similarity))
+}
+
+/// The join condition of the `value` row of one filtered index.
+fn index_filter_cond(
+	alias: &Alias,
+	index_id: i64,
+	index_filters: &[&ArchiveIndexFilter],
+) -> Result<Condition> {
+	let mut datatype_id = None;
+	let mut cond = Condition::all()
+		.add(
+			Expr::col((alias.clone(), ValueIden::ArchiveId))
+				.equals((ArchiveIden::Table, ArchiveIden::Id)),
+		)
+		.add(Expr::col((alias.clone(), ValueIden::IndexId)).eq(index_id));
+
+	for filter in index_filters {
+		// All the filters of an index must agree on its datatype.
+		if *datatype_id.get_or_insert(filter.datatype_id) != filter.datatype_id {
+			return Err(Error::UnsupportedDatatype(index_id));
+		}
+
+		let datatype = SearchDatatype::from_id(index_id, filter.datatype_id)?;
+		let operator = SearchOperator::parse(&filter.operator)?;
+		if !operator.supports(datatype) {
+			return Err(Error::UnsupportedDatatype(index_id));
 		}
 
-		if let Some(order_bys) = &list_options.order_bys {
-			let order_by_fields = order_bys.split(',').collect::<Vec<&str>>();
-			query_builder.push(" ORDER BY ");
+		let value =
+			datatype.cast(Expr::col((alias.clone(), ValueIden::Value)).into());
+		let filter_value = datatype.cast(Expr::val(filter.value.clone()).into());
+		cond = cond.add(operator.cond(value, filter_value));
+	}
 
-			for (i, field) in order_by_fields.iter().enumerate() {
-				let (order_field, order_direction) = if field.starts_with('!') {
-					(&field[1..], "DESC")
-				} else {
-					(field.as_ref(), "ASC")
-				};
+	Ok(cond)
+}
 
-				if !archive_fields.contains(&order_field) {
-					return Err(Error::UnsupportedDatatype(1));
-				}
+/// The archives of the `project_id` column the ctx can access, see
+/// `StructurePrivilegeBmc::accessible_project_ids`.
+fn archive_access_cond(ctx: &Ctx) -> SimpleExpr {
+	Expr::col((ArchiveIden::Table, ArchiveIden::ProjectId)).in_subquery(
+		StructurePrivilegeBmc::accessible_project_ids(ctx, ctx.user_id()),
+	)
+}
 
-				if i > 0 {
-					query_builder.push(", ");
-				}
+/// The accessible archives matching all the index filters, without any
+/// selected column.
+/// (one `value` inner join per filtered index, aliased `v{index_id}`)
+fn filtered_archives_query(
+	ctx: &Ctx,
+	filters: &[ArchiveIndexFilter],
+) -> Result<SelectStatement> {
+	let mut query = Query::select();
+	query.from(ArchiveIden::Table);
 
-				query_builder.push(format!(
-					"\"archive\".\"{}\" {}",
-					order_field, order_direction
-				));
+	join_index_filters(&mut query, filters)?;
+
+	query
+		.and_where(Expr::col((ArchiveIden::Table, ArchiveIden::IsDeleted)).eq(false))
+		.and_where(archive_access_cond(ctx));
+
+	Ok(query)
+}
+
+/// One `value` join per filtered index, on the `archive` of the query.
+fn join_index_filters(
+	query: &mut SelectStatement,
+	filters: &[ArchiveIndexFilter],
+) -> Result<()> {
+	// BTreeMap to keep the joins (and the generated sql) in index_id order.
+	let mut filters_by_index: BTreeMap<i64, Vec<&ArchiveIndexFilter>> =
+		BTreeMap::new();
+	for filter in filters {
+		filters_by_index
+			.entry(filter.index_id)
+			.or_default()
+			.push(filter);
+	}
+
+	for (index_id, index_filters) in filters_by_index {
+		let alias = Alias::new(format!("v{index_id}"));
+		let cond = index_filter_cond(&alias, index_id, &index_filters)?;
+		query.join_as(JoinType::InnerJoin, ValueIden::Table, alias, cond);
+	}
+
+	Ok(())
+}
+
+/// `index_id -> value` json object of the archive project indexes
+/// (or of the requested `index_ids` only).
+fn archive_values_expr(index_ids: Option<Vec<i64>>) -> SimpleExpr {
+	let mut query = Query::select();
+	query
+		.expr(
+			Func::cust(Alias::new("jsonb_object_agg"))
+				.arg(Expr::col((IndexIden::Table, IndexIden::Id)))
+				.arg(Expr::col((ValueIden::Table, ValueIden::Value))),
+		)
+		.from(IndexIden::Table)
+		.left_join(
+			ValueIden::Table,
+			Condition::all()
+				.add(
+					Expr::col((ValueIden::Table, ValueIden::IndexId))
+						.equals((IndexIden::Table, IndexIden::Id)),
+				)
+				.add(
+					Expr::col((ValueIden::Table, ValueIden::ArchiveId))
+						.equals((ArchiveIden::Table, ArchiveIden::Id)),
+				),
+		)
+		.and_where(
+			Expr::col((IndexIden::Table, IndexIden::ProjectId))
+				.equals((ArchiveIden::Table, ArchiveIden::ProjectId)),
+		)
+		.and_where(Expr::col((IndexIden::Table, IndexIden::IsDeleted)).eq(false));
+
+	if let Some(index_ids) = index_ids {
+		query.and_where(
+			Expr::col((IndexIden::Table, IndexIden::Id)).is_in(index_ids),
+		);
+	}
+
+	Func::coalesce([
+		SimpleExpr::SubQuery(None, Box::new(query.into_sub_query_statement())),
+		Expr::cust("'{}'::jsonb"),
+	])
+	.into()
+}
+
+/// Sort columns of the `Listoptions` order_bys, as in `"!ctime,id"`
+/// ('!' for descending) over the `fields` of `table`, then `id` as
+/// tie-breaker (a keyset page needs a total order).
+fn order_columns<T>(
+	table: T,
+	fields: &[T],
+	id: T,
+	order_bys: Option<&str>,
+) -> Result<Vec<CursorColumn>>
+where
+	T: Iden + Copy + 'static,
+{
+	let table_ref = || TableRef::Table(table.into_iden());
+
+	let mut columns = Vec::new();
+	for field in order_bys
+		.into_iter()
+		.flat_map(|order_bys| order_bys.split(','))
+	{
+		let (name, order) = match field.strip_prefix('!') {
+			Some(name) => (name, Order::Desc),
+			None => (field, Order::Asc),
+		};
+
+		let column = fields
+			.iter()
+			.find(|column| Iden::to_string(*column) == name)
+			.ok_or_else(|| Error::UnsupportedOrderBy(name.to_string()))?;
+
+		columns.push(CursorColumn::new(table_ref(), *column, order));
+	}
+
+	if !columns
+		.iter()
+		.any(|c| c.column.to_string() == id.to_string())
+	{
+		columns.push(CursorColumn::new(table_ref(), id, Order::Asc));
+	}
+
+	Ok(columns)
+}
+
+/// Orders and pages a search, by the similarity `rank` first when given
+/// (no cursor then), or after the `cursor` of the `columns`. The limit is
+/// bounded as the other listings, see `list_limit`.
+fn apply_search_page(
+	query: &mut SelectStatement,
+	list_options: &Listoptions,
+	rank: Option<SimpleExpr>,
+	columns: Vec<CursorColumn>,
+) -> Result<()> {
+	match (rank, &list_options.cursor) {
+		(Some(_), Some(_)) => {
+			return Err(Error::InvalidCursor(
+				"A cursor needs order_bys when ranking by similarity".to_string(),
+			));
+		}
+		(Some(rank), None) => {
+			query.order_by_expr(rank, Order::Desc);
+		}
+		(None, cursor) => {
+			if let Some(cursor) = cursor {
+				if list_options.offset.is_some() {
+					return Err(Error::InvalidCursor(
+						"A cursor can't be combined with an offset".to_string(),
+					));
+				}
+				query.cond_where(cursor_cond(&columns, cursor)?);
 			}
+			query.expr_as(cursor_expr(&columns), Alias::new("cursor"));
+		}
+	}
+
+	for column in columns {
+		query.order_by(column.col(), column.order);
+	}
+
+	query.limit(list_limit(list_options.limit)? as u64);
+	check_list_offset(list_options.offset)?;
+	if let Some(offset) = list_options.offset {
+		query.offset(offset as u64);
+	}
+
+	Ok(())
+}
+
+/// Full search query: archive columns, their `values` and the
+/// `total_count` of the filtered set (window count, before limit/offset).
+fn search_archives_query(
+	ctx: &Ctx,
+	filters: &[ArchiveIndexFilter],
+	list_options: &Listoptions,
+	index_ids: Option<Vec<i64>>,
+) -> Result<SelectStatement> {
+	let mut query = filtered_archives_query(ctx, filters)?;
+
+	query
+		.columns(ARCHIVE_FIELDS.map(|column| (ArchiveIden::Table, column)))
+		.expr_as(archive_values_expr(index_ids), Alias::new("values"))
+		.expr_window_as(
+			Expr::col(Asterisk).count(),
+			WindowStatement::new(),
+			Alias::new("total_count"),
+		);
+
+	let columns = order_columns(
+		ArchiveIden::Table,
+		&ARCHIVE_FIELDS,
+		ArchiveIden::Id,
+		list_options.order_bys.as_deref(),
+	)?;
+	apply_search_page(
+		&mut query,
+		list_options,
+		archives_rank(filters, list_options),
+		columns,
+	)?;
+
+	Ok(query)
+}
+
+/// The (not deleted) indexes of the projects of the filtered archives,
+/// or the requested `index_ids` of them only.
+fn export_indexes_query(
+	ctx: &Ctx,
+	filters: &[ArchiveIndexFilter],
+	index_ids: Option<Vec<i64>>,
+) -> Result<SelectStatement> {
+	let mut project_ids = filtered_archives_query(ctx, filters)?;
+	project_ids
+		.distinct()
+		.column((ArchiveIden::Table, ArchiveIden::ProjectId));
+
+	let mut query = Query::select();
+	query
+		.from(IndexIden::Table)
+		.columns([IndexIden::Id, IndexIden::IndexName, IndexIden::DatatypeId])
+		.and_where(Expr::col(IndexIden::ProjectId).in_subquery(project_ids))
+		.and_where(Expr::col(IndexIden::IsDeleted).eq(false))
+		.order_by(IndexIden::ProjectId, Order::Asc)
+		.order_by(IndexIden::Id, Order::Asc);
+
+	if let Some(index_ids) = index_ids {
+		query.and_where(Expr::col(IndexIden::Id).is_in(index_ids));
+	}
+
+	Ok(query)
+}
+
+/// The archive columns, then one column per index. An index name found in
+/// several projects gets its id appended, to tell the columns apart.
+fn export_archives_query(
+	ctx: &Ctx,
+	filters: &[ArchiveIndexFilter],
+	list_options: &Listoptions,
+	indexes: &[ExportIndex],
+) -> Result<Export> {
+	let mut query = filtered_archives_query(ctx, filters)?;
+	if let Some(rank) = archives_rank(filters, list_options) {
+		query.order_by_expr(rank, Order::Desc);
+	}
+	for column in order_columns(
+		ArchiveIden::Table,
+		&ARCHIVE_FIELDS,
+		ArchiveIden::Id,
+		list_options.order_bys.as_deref(),
+	)? {
+		query.order_by(column.col(), column.order);
+	}
+
+	let mut columns: Vec<(ExportColumn, SimpleExpr)> = ARCHIVE_FIELDS
+		.iter()
+		.map(|field| {
+			let column = ExportColumn::new(Iden::to_string(field));
+			(column, Expr::col((ArchiveIden::Table, *field)).into())
+		})
+		.collect();
+
+	for index in indexes {
+		let name = if indexes
+			.iter()
+			.filter(|other| other.index_name == index.index_name)
+			.count() > 1
+		{
+			format!("{} ({})", index.index_name, index.id)
 		} else {
-			// Default ordering if none provided.
-			query_builder.push(" ORDER BY \"archive\".\"id\" ASC");
+			index.index_name.clone()
+		};
+		let column = match SearchDatatype::from_id(index.id, index.datatype_id) {
+			Ok(SearchDatatype::Numeric) => ExportColumn::numeric(name),
+			_ => ExportColumn::new(name),
+		};
+
+		let mut value = Query::select();
+		value
+			.column((ValueIden::Table, ValueIden::Value))
+			.from(ValueIden::Table)
+			.and_where(
+				Expr::col((ValueIden::Table, ValueIden::ArchiveId))
+					.equals((ArchiveIden::Table, ArchiveIden::Id)),
+			)
+			.and_where(
+				Expr::col((ValueIden::Table, ValueIden::IndexId)).eq(index.id),
+			)
+			.limit(1);
+
+		columns.push((
+			column,
+			SimpleExpr::SubQuery(None, Box::new(value.into_sub_query_statement())),
+		));
+	}
+
+	Ok(Export::new(query, columns))
+}
+
+const DOCUMENT_FIELDS: [DocumentIden; 12] = [
+	DocumentIden::Id,
+	DocumentIden::SeparatorId,
+	DocumentIden::ArchiveId,
+	DocumentIden::Name,
+	DocumentIden::DocType,
+	DocumentIden::Owner,
+	DocumentIden::LastEditUser,
+	DocumentIden::Key,
+	DocumentIden::Cid,
+	DocumentIden::Ctime,
+	DocumentIden::Mid,
+	DocumentIden::Mtime,
+];
+
+/// `%part%` ILIKE pattern, with the LIKE wildcards of `part` escaped.
+fn contains_pattern(part: &str) -> String {
+	let part = part
+		.replace('\\', "\\\\")
+		.replace('%', "\\%")
+		.replace('_', "\\_");
+	format!("%{part}%")
+}
+
+/// The documents matching the filter, with their (not deleted) folder,
+/// archive and accessible structure joined, without any selected column.
+fn filtered_documents_query(
+	ctx: &Ctx,
+	filter: &DocumentSearchFilter,
+) -> Result<SelectStatement> {
+	let mut query = Query::select();
+	query
+		.from(DocumentIden::Table)
+		.inner_join(
+			SeparatorIden::Table,
+			Expr::col((SeparatorIden::Table, SeparatorIden::Id))
+				.equals((DocumentIden::Table, DocumentIden::SeparatorId)),
+		)
+		.inner_join(
+			ArchiveIden::Table,
+			Expr::col((ArchiveIden::Table, ArchiveIden::Id))
+				.equals((DocumentIden::Table, DocumentIden::ArchiveId)),
+		)
+		.inner_join(
+			StructureIden::Table,
+			Expr::col((StructureIden::Table, StructureIden::Id))
+				.equals((ArchiveIden::Table, ArchiveIden::ProjectId)),
+		);
+
+	if let Some(archive_filters) = &filter.archive_filters {
+		join_index_filters(&mut query, archive_filters)?;
+	}
+
+	let mut cond = Condition::all()
+		.add(Expr::col((DocumentIden::Table, DocumentIden::IsDeleted)).eq(false))
+		.add(Expr::col((SeparatorIden::Table, SeparatorIden::IsDeleted)).eq(false))
+		.add(Expr::col((ArchiveIden::Table, ArchiveIden::IsDeleted)).eq(false))
+		.add(Expr::col((StructureIden::Table, StructureIden::IsDeleted)).eq(false))
+		.add(archive_access_cond(ctx));
+
+	if let Some(name) = &filter.name {
+		let doc_name = Expr::col((DocumentIden::Table, DocumentIden::Name));
+		cond = cond.add(match filter.name_match.unwrap_or_default() {
+			NameMatch::Contains => doc_name.ilike(contains_pattern(name)),
+			NameMatch::Unaccent => normalized(doc_name.into()).binary(
+				BinOper::Like,
+				normalized(Expr::val(contains_pattern(name)).into()),
+			),
+			NameMatch::Fuzzy => normalized(Expr::val(name).into())
+				.binary(PgBinOper::WordSimilarity, normalized(doc_name.into())),
+		});
+	}
+	if let Some(doc_type) = &filter.doc_type {
+		cond = cond.add(
+			Expr::col((DocumentIden::Table, DocumentIden::DocType)).eq(doc_type),
+		);
+	}
+	if let Some(owner) = filter.owner {
+		cond = cond
+			.add(Expr::col((DocumentIden::Table, DocumentIden::Owner)).eq(owner));
+	}
+	if let Some(uploaded_from) = filter.uploaded_from {
+		cond = cond.add(
+			Expr::col((DocumentIden::Table, DocumentIden::Ctime)).gte(uploaded_from),
+		);
+	}
+	if let Some(uploaded_to) = filter.uploaded_to {
+		cond = cond.add(
+			Expr::col((DocumentIden::Table, DocumentIden::Ctime)).lte(uploaded_to),
+		);
+	}
+	if let Some(separator_name) = &filter.separator_name {
+		cond = cond.add(
+			Expr::col((SeparatorIden::Table, SeparatorIden::Name))
+				.ilike(contains_pattern(separator_name)),
+		);
+	}
+	if let Some(project_id) = filter.project_id {
+		cond = cond.add(
+			Expr::col((ArchiveIden::Table, ArchiveIden::ProjectId)).eq(project_id),
+		);
+	}
+
+	query.cond_where(cond);
+
+	Ok(query)
+}
+
+fn search_documents_query(
+	ctx: &Ctx,
+	filter: &DocumentSearchFilter,
+	list_options: &Listoptions,
+) -> Result<SelectStatement> {
+	let mut query = filtered_documents_query(ctx, filter)?;
+
+	query
+		.columns(DOCUMENT_FIELDS.map(|column| (DocumentIden::Table, column)))
+		.expr_as(
+			Expr::col((StructureIden::Table, StructureIden::Id)),
+			Alias::new("project_id"),
+		)
+		.expr_as(
+			Expr::col((StructureIden::Table, StructureIden::ProjectName)),
+			Alias::new("project_name"),
+		)
+		.expr_as(
+			Expr::col((ArchiveIden::Table, ArchiveIden::Tag)),
+			Alias::new("archive_tag"),
+		)
+		.expr_window_as(
+			Expr::col(Asterisk).count(),
+			WindowStatement::new(),
+			Alias::new("total_count"),
+		);
+
+	let columns = order_columns(
+		DocumentIden::Table,
+		&DOCUMENT_FIELDS,
+		DocumentIden::Id,
+		list_options.order_bys.as_deref(),
+	)?;
+	apply_search_page(
+		&mut query,
+		list_options,
+		documents_rank(filter, list_options),
+		columns,
+	)?;
+
+	Ok(query)
+}
+
+/// Best matches first (`word_similarity` of a fuzzy name), unless ordered
+/// otherwise.
+fn documents_rank(
+	filter: &DocumentSearchFilter,
+	list_options: &Listoptions,
+) -> Option<SimpleExpr> {
+	match (&list_options.order_bys, &filter.name, filter.name_match) {
+		(None, Some(name), Some(NameMatch::Fuzzy)) => Some(
+			Func::cust(Alias::new("word_similarity"))
+				.arg(normalized(Expr::val(name).into()))
+				.arg(normalized(
+					Expr::col((DocumentIden::Table, DocumentIden::Name)).into(),
+				))
+				.into(),
+		),
+		_ => None,
+	}
+}
+
+const FACET_LIMIT_DEFAULT: i64 = 50;
+const FACET_LIMIT_MAX: i64 = 1000;
+
+/// Alias of the `value` join of the faceted index.
+fn facet_alias() -> Alias {
+	Alias::new("facet")
+}
+
+fn join_facet_value(query: &mut SelectStatement, index_id: i64) {
+	query.join_as(
+		JoinType::InnerJoin,
+		ValueIden::Table,
+		facet_alias(),
+		Condition::all()
+			.add(
+				Expr::col((facet_alias(), ValueIden::ArchiveId))
+					.equals((ArchiveIden::Table, ArchiveIden::Id)),
+			)
+			.add(Expr::col((facet_alias(), ValueIden::IndexId)).eq(index_id)),
+	);
+}
+
+/// `key`/`count` rows of one facet over the filtered archives.
+fn facet_query(
+	ctx: &Ctx,
+	filters: &[ArchiveIndexFilter],
+	facet: &Facet,
+) -> Result<SelectStatement> {
+	let mut query = filtered_archives_query(ctx, filters)?;
+	let facet_value =
+		|| SimpleExpr::from(Expr::col((facet_alias(), ValueIden::Value)));
+
+	// (group key, most frequent first, requested limit)
+	let (key, by_count, limit): (SimpleExpr, bool, Option<i64>) = match facet {
+		Facet::Values { index_id, limit } => {
+			join_facet_value(&mut query, *index_id);
+			(facet_value(), true, *limit)
 		}
+		Facet::NumericHistogram {
+			index_id,
+			bucket_size,
+		} => {
+			if *bucket_size <= 0.0 {
+				return Err(Error::InvalidValue(format!(
+					"bucket_size must be positive, was {bucket_size}"
+				)));
+			}
+			join_facet_value(&mut query, *index_id);
+			let numeric = SearchDatatype::Numeric;
+			let bucket_size = numeric.cast(Expr::val(*bucket_size).into());
+			let bucket = Func::cust(Alias::new("FLOOR"))
+				.arg(numeric.cast(facet_value()).div(bucket_size.clone()));
+			(SimpleExpr::from(bucket).mul(bucket_size), false, None)
+		}
+		Facet::DateHistogram { index_id, interval } => {
+			join_facet_value(&mut query, *index_id);
+			let bucket = Func::cust(Alias::new("DATE_TRUNC"))
+				.arg(interval.as_str())
+				.arg(SearchDatatype::Date.cast(facet_value()));
+			(
+				SimpleExpr::from(bucket).cast_as(Alias::new("DATE")),
+				false,
+				None,
+			)
+		}
+		Facet::Owner { limit } => (
+			Expr::col((ArchiveIden::Table, ArchiveIden::Owner)).into(),
+			true,
+			*limit,
+		),
+		Facet::Project { limit } => (
+			Expr::col((ArchiveIden::Table, ArchiveIden::ProjectId)).into(),
+			true,
+			*limit,
+		),
+	};
 
-		if let Some(limit) = list_options.limit {
-			query_builder.push(" LIMIT ");
-			query_builder.push_bind(limit);
+	let limit = match limit {
+		Some(limit) if limit > FACET_LIMIT_MAX => {
+			return Err(Error::ListLimitOverMax {
+				max: FACET_LIMIT_MAX,
+				actual: limit,
+			});
 		}
+		Some(limit) => limit,
+		None if by_count => FACET_LIMIT_DEFAULT,
+		None => FACET_LIMIT_MAX,
+	};
+
+	query
+		.expr_as(
+			Func::cust(Alias::new("to_jsonb")).arg(key),
+			Alias::new("key"),
+		)
+		.expr_as(Expr::col(Asterisk).count(), Alias::new("count"))
+		// Grouped by output name, the bound values of `key` can't be matched
+		// between the SELECT and the GROUP BY.
+		.group_by_col(Alias::new("key"));
+	if by_count {
+		query.order_by(Alias::new("count"), Order::Desc);
+	}
+	query
+		.order_by(Alias::new("key"), Order::Asc)
+		.limit(limit as u64);
+
+	Ok(query)
+}
+
+fn archive_with_values_from_row(row: &PgRow) -> sqlx::Result<ArchiveWithValues> {
+	let Json(values) = row.try_get("values")?;
+
+	Ok(ArchiveWithValues {
+		archive: Archive {
+			id: row.try_get("id")?,
+			project_id: row.try_get("project_id")?,
+			owner: row.try_get("owner")?,
+			last_edit_user: row.try_get("last_edit_user")?,
+			tag: row.try_get("tag")?,
+			cid: row.try_get("cid")?,
+			ctime: row.try_get("ctime")?,
+			mid: row.try_get("mid")?,
+			mtime: row.try_get("mtime")?,
+		},
+		values,
+	})
+}
+
+fn document_with_path_from_row(
+	row: &PgRow,
+	folders: &HashMap<i64, (String, Option<i64>)>,
+) -> sqlx::Result<DocumentWithPath> {
+	let document = Document::from_row(row)?;
 
-		if let Some(offset) = list_options.offset {
-			query_builder.push(" OFFSET ");
-			query_builder.push_bind(offset);
+	let archive_tag: Option<String> = row.try_get("archive_tag")?;
+	let mut path = vec![
+		PathItem {
+			id: row.try_get("project_id")?,
+			name: row.try_get("project_name")?,
+			r#type: "structure",
+		},
+		PathItem {
+			id: document.archive_id,
+			name: archive_tag.unwrap_or_else(|| document.archive_id.to_string()),
+			r#type: "archive",
+		},
+	];
+	path.extend(folder_path(folders, document.separator_id));
+
+	Ok(DocumentWithPath { document, path })
+}
+
+/// Folders from the root down to `separator_id`.
+fn folder_path(
+	folders: &HashMap<i64, (String, Option<i64>)>,
+	separator_id: i64,
+) -> Vec<PathItem> {
+	let mut path = Vec::new();
+
+	let mut current = Some(separator_id);
+	while let Some(id) = current {
+		// Guard against a parent cycle in the data.
+		if path.iter().any(|item: &PathItem| item.id == id) {
+			break;
 		}
+		let Some((name, parent_id)) = folders.get(&id) else {
+			break;
+		};
+		path.push(PathItem {
+			id,
+			name: name.clone(),
+			r#type: "folder",
+		});
+		current = *parent_id;
+	}
 
-		// Build and execute the query.
-		let query = query_builder.build();
+	path.reverse();
+	path
+}
 
-		let archives = query
-			.map(|row: sqlx::postgres::PgRow| Archive {
-				id: row.get("id"),
-				project_id: row.get("project_id"),
-				owner: row.get("owner"),
-				last_edit_user: row.get("last_edit_user"),
-				tag: row.get("tag"),
-				cid: row.get("cid"),
-				ctime: row.get("ctime"),
-				mid: row.get("mid"),
-				mtime: row.get("mtime"),
-			})
-			.fetch_all(db)
+// endregion: --- Search Query Builders
+
+// region:    --- Tests
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use crate::core::ctx::KeyScope;
+	use crate::core::model::archive::{ArchiveBmc, ArchiveFilter, ArchiveForCreate};
+	use crate::core::model::document::{DocumentBmc, DocumentForCreate};
+	use crate::core::model::index::{IndexBmc, IndexForCreate};
+	use crate::core::model::role::{RoleBmc, RoleForOp};
+	use crate::core::model::separator::{SeparatorBmc, SeparatorForCreate};
+	use crate::core::model::structure::{StructureBmc, StructureForOp};
+	use crate::core::model::structure_privilege::StructureGrantee;
+	use crate::core::model::user::{UserBmc, UserForCreate};
+	use crate::core::model::value::{ValueBmc, ValueFilter, ValueForCreate};
+	use crate::utils::b64::b64u_encode;
+	use anyhow::Result;
+	use futures::StreamExt;
+	use serde_json::json;
+	use serial_test::serial;
+
+	const SNAP_SELECT: &str = concat!(
+		r#"SELECT "archive"."id", "archive"."project_id", "archive"."owner", "#,
+		r#""archive"."last_edit_user", "archive"."tag", "archive"."cid", "#,
+		r#""archive"."ctime", "archive"."mid", "archive"."mtime", "#,
+		r#"COALESCE((SELECT jsonb_object_agg("index"."id", "value"."value") "#,
+		r#"FROM "index" LEFT JOIN "value" ON "value"."index_id" = "index"."id" "#,
+		r#"AND "value"."archive_id" = "archive"."id" "#,
+		r#"WHERE "index"."project_id" = "archive"."project_id" "#,
+		r#"AND "index"."is_deleted" = FALSE"#,
+	);
+
+	/// The accessible structures of the root ctx.
+	const SNAP_ACCESS: &str = concat!(
+		r#""archive"."project_id" IN (SELECT "project_id" "#,
+		r#"FROM "structure_privilege" WHERE "is_enabled" = TRUE "#,
+		r#"AND "is_deleted" = FALSE AND ("user_id" = 0 OR ("role_name" IN "#,
+		r#"(SELECT "assigned_role" FROM "user" WHERE "id" = 0) "#,
+		r#"AND "project_id" NOT IN (SELECT "project_id" FROM "structure_privilege" "#,
+		r#"WHERE "user_id" = 0 AND "is_deleted" = FALSE)))"#,
+		")",
+	);
+
+	fn fx_filters(value: serde_json::Value) -> Vec<ArchiveIndexFilter> {
+		serde_json::from_value(value).unwrap()
+	}
+
+	/// A structure with two TEXT indexes, and an archive with a value of
+	/// the first one only (its `name`) and a document named `name`.
+	struct FxArchive {
+		project_id: i64,
+		index_ids: [i64; 2],
+		archive_id: i64,
+		document_id: i64,
+	}
+
+	impl FxArchive {
+		/// Its archive, by the value of the first index.
+		fn filters(&self, name: &str) -> Vec<ArchiveIndexFilter> {
+			fx_filters(json!([
+				{"index_id": self.index_ids[0], "value": name, "operator": "Eq", "datatype_id": 1},
+			]))
+		}
+	}
+
+	async fn fx_archive(mm: &ModelManager, name: &str) -> Result<FxArchive> {
+		let root_ctx = Ctx::root_ctx();
+
+		let project_id = StructureBmc::create(
+			&root_ctx,
+			mm,
+			StructureForOp {
+				project_name: name.to_string(),
+			},
+		)
+		.await?;
+		let mut index_ids = [0; 2];
+		for (i, index_id) in index_ids.iter_mut().enumerate() {
+			*index_id = IndexBmc::create(
+				&root_ctx,
+				mm,
+				IndexForCreate {
+					datatype_id: 1,
+					project_id,
+					required: false,
+					index_name: format!("{name}-index-{i}"),
+				},
+			)
 			.await?;
+		}
+		let archive_id = ArchiveBmc::create(
+			&root_ctx,
+			mm,
+			ArchiveForCreate {
+				project_id,
+				tag: name.to_string(),
+			},
+		)
+		.await?;
+		ValueBmc::create(
+			&root_ctx,
+			mm,
+			ValueForCreate {
+				index_id: index_ids[0],
+				project_id,
+				archive_id,
+				value: name.to_string(),
+			},
+		)
+		.await?;
+		let separator_id = SeparatorBmc::create(
+			&root_ctx,
+			mm,
+			SeparatorForCreate {
+				name: format!("{name}-folder"),
+				parent_id: None,
+				archive_id,
+			},
+		)
+		.await?;
+		let document_id = DocumentBmc::create(
+			&root_ctx,
+			mm,
+			DocumentForCreate {
+				separator_id,
+				archive_id,
+				name: name.to_string(),
+				doc_type: "application/pdf".to_string(),
+				key: format!("{name}.pdf"),
+			},
+		)
+		.await?;
 
-		let mut count_query_builder =
-			QueryBuilder::new("SELECT COUNT(*) FROM \"archive\"");
-
-		// Build INNER JOINs for each index_id.
-		let mut count_join_index = 0;
-
-		for (&index_id, index_filters) in &filters_by_index {
-			let alias = format!("v{}", count_join_index);
-			let datatype_id = index_datatype_ids
-				.get(&index_id)
-				.ok_or_else(|| Error::UnsupportedDatatype(index_id))?;
-
-			count_query_builder.push(format!(
-        " INNER JOIN \"value\" AS \"{}\" ON \"{}\".\"archive_id\" = \"archive\".\"id\" AND \"{}\".\"index_id\" = ",
-        alias, alias, alias
-    ));
-			count_query_builder.push_bind(index_id);
-
-			// Collect conditions for this index_id.
-			let mut eq_values = Vec::new();
-			let mut gte_value = None;
-			let mut lte_value = None;
-
-			for filter in index_filters {
-				match filter.operator.as_str() {
-					"Eq" => eq_values.push(&filter.value),
-					"Gte" => gte_value = Some(&filter.value),
-					"Lte" => lte_value = Some(&filter.value),
-					_ => return Err(Error::UnsupportedDatatype(index_id)),
-				}
-			}
+		Ok(FxArchive {
+			project_id,
+			index_ids,
+			archive_id,
+			document_id,
+		})
+	}
 
-			// Adjust casting based on datatype_id and cast both sides.
-			match datatype_id {
-				1 => {
-					// TEXT
-					if !eq_values.is_empty() {
-						for value in eq_values {
-							count_query_builder.push(" AND ");
-							count_query_builder
-								.push(format!("\"{}\".\"value\" = ", alias));
-							count_query_builder.push_bind(value);
-						}
-					}
-					if gte_value.is_some() || lte_value.is_some() {
-						return Err(Error::UnsupportedDatatype(index_id));
-					}
-				}
-				2 => {
-					// NUMERIC
-					if !eq_values.is_empty() {
-						for value in eq_values {
-							count_query_builder.push(" AND ");
-							count_query_builder.push(format!(
-								"CAST(\"{}\".\"value\" AS NUMERIC) = CAST(",
-								alias
-							));
-							count_query_builder.push_bind(value);
-							count_query_builder.push(" AS NUMERIC)");
-						}
-					}
-					if gte_value.is_some() && lte_value.is_some() {
-						count_query_builder.push(" AND CAST(\"");
-						count_query_builder.push(&alias);
-						count_query_builder
-							.push("\".\"value\" AS NUMERIC) BETWEEN CAST(");
-						count_query_builder.push_bind(gte_value.unwrap());
-						count_query_builder.push(" AS NUMERIC) AND CAST(");
-						count_query_builder.push_bind(lte_value.unwrap());
-						count_query_builder.push(" AS NUMERIC)");
-					} else {
-						if let Some(value) = gte_value {
-							count_query_builder.push(" AND CAST(\"");
-							count_query_builder.push(&alias);
-							count_query_builder
-								.push("\".\"value\" AS NUMERIC) >= CAST(");
-							count_query_builder.push_bind(value);
-							count_query_builder.push(" AS NUMERIC)");
-						}
-						if let Some(value) = lte_value {
-							count_query_builder.push(" AND CAST(\"");
-							count_query_builder.push(&alias);
-							count_query_builder
-								.push("\".\"value\" AS NUMERIC) <= CAST(");
-							count_query_builder.push_bind(value);
-							count_query_builder.push(" AS NUMERIC)");
-						}
-					}
-				}
-				3 => {
-					// TIMESTAMP stored as mm-dd-yyyy
-					let date_format = "YYYY-MM-DD";
-					if !eq_values.is_empty() {
-						for value in eq_values {
-							count_query_builder.push(" AND ");
-							count_query_builder.push(format!(
-								"TO_DATE(\"{}\".\"value\", '{}') = TO_DATE(",
-								alias, date_format
-							));
-							count_query_builder.push_bind(value);
-							count_query_builder
-								.push(format!(", '{}')", date_format));
-						}
-					}
-					if gte_value.is_some() && lte_value.is_some() {
-						count_query_builder.push(" AND TO_DATE(\"");
-						count_query_builder.push(&alias);
-						count_query_builder.push(format!(
-							"\".\"value\", '{}') BETWEEN TO_DATE(",
-							date_format
-						));
-						count_query_builder.push_bind(gte_value.unwrap());
-						count_query_builder
-							.push(format!(", '{}') AND TO_DATE(", date_format));
-						count_query_builder.push_bind(lte_value.unwrap());
-						count_query_builder.push(format!(", '{}')", date_format));
-					} else {
-						if let Some(value) = gte_value {
-							count_query_builder.push(" AND TO_DATE(\"");
-							count_query_builder.push(&alias);
-							count_query_builder.push(format!(
-								"\".\"value\", '{}') >= TO_DATE(",
-								date_format
-							));
-							count_query_builder.push_bind(value);
-							count_query_builder
-								.push(format!(", '{}')", date_format));
-						}
-						if let Some(value) = lte_value {
-							count_query_builder.push(" AND TO_DATE(\"");
-							count_query_builder.push(&alias);
-							count_query_builder.push(format!(
-								"\".\"value\", '{}') <= TO_DATE(",
-								date_format
-							));
-							count_query_builder.push_bind(value);
-							count_query_builder
-								.push(format!(", '{}')", date_format));
-						}
-					}
-				}
-				_ => return Err(Error::UnsupportedDatatype(index_id)),
+	#[serial]
+	#[tokio::test]
+	async fn test_search_archives_values_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_name = "fx-so-values-01";
+		let fx = fx_archive(&mm, fx_name).await?;
+		let search_archives = |index_ids: Option<Vec<i64>>| {
+			SearchBmc::search_archives(
+				&root_ctx,
+				&mm,
+				Some(fx.filters(fx_name)),
+				None,
+				index_ids,
+				None,
+			)
+		};
+
+		// -- Exec
+		let all = search_archives(None).await?;
+		let subset = search_archives(Some(vec![fx.index_ids[0]])).await?;
+
+		// -- Check
+		assert_eq!(all.items.len(), 1);
+		let archive = &all.items[0];
+		assert_eq!(archive.archive.id, fx.archive_id);
+		assert_eq!(archive.archive.project_id, fx.project_id);
+		assert_eq!(archive.archive.tag, fx_name);
+		// All the indexes of the structure, null without a value.
+		let fx_values = HashMap::from([
+			(fx.index_ids[0], Some(fx_name.to_string())),
+			(fx.index_ids[1], None),
+		]);
+		assert_eq!(archive.values, fx_values);
+		assert_eq!(subset.items.len(), 1);
+		let fx_values =
+			HashMap::from([(fx.index_ids[0], Some(fx_name.to_string()))]);
+		assert_eq!(subset.items[0].values, fx_values);
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_search_no_grant_no_rows_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_name = "fx-so-grant-01";
+		let fx_role = "fx-so-role-01";
+		RoleBmc::create(
+			&root_ctx,
+			&mm,
+			RoleForOp {
+				role_name: fx_role.to_string(),
+				description: "fx role".to_string(),
+			},
+		)
+		.await?;
+		let user_id = UserBmc::create(
+			&root_ctx,
+			&mm,
+			UserForCreate {
+				username: "fx-so-user-01".to_string(),
+				email: "fx-so-user-01@example.org".to_string(),
+				pwd_clear: "welcome-01".to_string(),
+				assigned_role: fx_role.to_string(),
+			},
+		)
+		.await?;
+		let fx = fx_archive(&mm, fx_name).await?;
+		let user_ctx = Ctx::new(user_id)?;
+		let fx_document_filter = || DocumentSearchFilter {
+			project_id: Some(fx.project_id),
+			..Default::default()
+		};
+		let fx_archive_filter: ArchiveFilter =
+			serde_json::from_value(json!({"project_id": fx.project_id}))?;
+		let fx_value_filter: ValueFilter =
+			serde_json::from_value(json!({"archive_id": fx.archive_id}))?;
+
+		// -- Exec
+		let archives_granted = SearchBmc::search_archives(
+			&user_ctx,
+			&mm,
+			Some(fx.filters(fx_name)),
+			None,
+			None,
+			None,
+		)
+		.await?;
+		StructurePrivilegeBmc::disable(
+			&root_ctx,
+			&mm,
+			&StructureGrantee::Role(fx_role.to_string()),
+			fx.project_id,
+		)
+		.await?;
+		let archives = SearchBmc::search_archives(
+			&user_ctx,
+			&mm,
+			Some(fx.filters(fx_name)),
+			None,
+			None,
+			None,
+		)
+		.await?;
+		let documents = SearchBmc::search_documents(
+			&user_ctx,
+			&mm,
+			Some(fx_document_filter()),
+			None,
+		)
+		.await?;
+		let export = SearchBmc::export_archives(
+			&user_ctx,
+			&mm,
+			Some(fx.filters(fx_name)),
+			None,
+			None,
+		)
+		.await?;
+		let archive_list =
+			ArchiveBmc::list(&user_ctx, &mm, Some(vec![fx_archive_filter]), None)
+				.await?;
+		let value_list =
+			ValueBmc::list(&user_ctx, &mm, Some(vec![fx_value_filter]), None)
+				.await?;
+		let archive_res = ArchiveBmc::get(&user_ctx, &mm, fx.archive_id).await;
+		let document_res = DocumentBmc::get(&user_ctx, &mm, fx.document_id).await;
+
+		// -- Check
+		assert_eq!(archives_granted.total_count, 1);
+		assert_eq!(archives.total_count, 0);
+		assert!(archives.items.is_empty());
+		assert_eq!(documents.total_count, 0);
+		assert!(documents.items.is_empty());
+		assert_eq!(export.rows(&mm).count().await, 0);
+		assert_eq!(archive_list.total_count, 0);
+		assert_eq!(value_list.total_count, 0);
+		assert!(
+			matches!(archive_res, Err(Error::EntityNotFound { .. })),
+			"Should have matched `Err(Error::EntityNotFound)` but was `{archive_res:?}`"
+		);
+		assert!(
+			matches!(document_res, Err(Error::EntityNotFound { .. })),
+			"Should have matched `Err(Error::EntityNotFound)` but was `{document_res:?}`"
+		);
+		// Still there for the users of the other roles.
+		let documents = SearchBmc::search_documents(
+			&root_ctx,
+			&mm,
+			Some(fx_document_filter()),
+			None,
+		)
+		.await?;
+		assert_eq!(documents.total_count, 1);
+
+		// -- Clean
+		UserBmc::delete(&root_ctx, &mm, user_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_search_key_scope_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let user_id = UserBmc::create(
+			&root_ctx,
+			&mm,
+			UserForCreate {
+				username: "fx-so-user-02".to_string(),
+				email: "fx-so-user-02@example.org".to_string(),
+				pwd_clear: "welcome-01".to_string(),
+				assigned_role: "demo".to_string(),
+			},
+		)
+		.await?;
+		let fx_01 = fx_archive(&mm, "fx-so-key-01").await?;
+		let fx_02 = fx_archive(&mm, "fx-so-key-02").await?;
+		let user_ctx = Ctx::new(user_id)?;
+		let key_ctx = Ctx::new(user_id)?.with_key_scope(KeyScope {
+			role_name: "demo".to_string(),
+			project_ids: Some(vec![fx_01.project_id]),
+		});
+		let fx_document_filter = || DocumentSearchFilter {
+			name: Some("fx-so-key-".to_string()),
+			..Default::default()
+		};
+		let search_archives = |ctx: Ctx, filters: Vec<ArchiveIndexFilter>| {
+			let mm = mm.clone();
+			async move {
+				SearchBmc::search_archives(
+					&ctx,
+					&mm,
+					Some(filters),
+					None,
+					None,
+					None,
+				)
+				.await
 			}
+		};
+
+		// -- Exec
+		let archives_01 =
+			search_archives(key_ctx.clone(), fx_01.filters("fx-so-key-01")).await?;
+		let archives_02 =
+			search_archives(key_ctx.clone(), fx_02.filters("fx-so-key-02")).await?;
+		let user_archives_02 =
+			search_archives(user_ctx.clone(), fx_02.filters("fx-so-key-02")).await?;
+		let documents = SearchBmc::search_documents(
+			&key_ctx,
+			&mm,
+			Some(fx_document_filter()),
+			None,
+		)
+		.await?;
+		let user_documents = SearchBmc::search_documents(
+			&user_ctx,
+			&mm,
+			Some(fx_document_filter()),
+			None,
+		)
+		.await?;
+
+		// -- Check
+		// Only the archives and documents of the key structures.
+		assert_eq!(archives_01.total_count, 1);
+		assert_eq!(archives_01.items[0].archive.id, fx_01.archive_id);
+		assert_eq!(archives_02.total_count, 0);
+		let document_ids: Vec<i64> = documents
+			.items
+			.iter()
+			.map(|document| document.document.id)
+			.collect();
+		assert_eq!(document_ids, [fx_01.document_id]);
+		// All of them for the user itself.
+		assert_eq!(user_archives_02.total_count, 1);
+		assert_eq!(user_documents.total_count, 2);
+
+		// -- Clean
+		UserBmc::delete(&root_ctx, &mm, user_id).await?;
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_search_archives_query_no_filters_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_sql = [
+			SNAP_SELECT,
+			r#"), '{}'::jsonb) AS "values", COUNT(*) OVER (  ) AS "total_count", "#,
+			r#"jsonb_build_object('id', "archive"."id") AS "cursor" "#,
+			r#"FROM "archive" WHERE "archive"."is_deleted" = FALSE AND "#,
+			SNAP_ACCESS,
+			r#" ORDER BY "archive"."id" ASC LIMIT 1000"#,
+		]
+		.concat();
+
+		// -- Exec
+		let query =
+			search_archives_query(&fx_ctx, &[], &Listoptions::default(), None)?;
+
+		// -- Check
+		assert_eq!(query.to_string(PostgresQueryBuilder), fx_sql);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_search_archives_query_filters_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_filters = fx_filters(json!([
+			{"index_id": 7, "value": "Ana", "operator": "Eq", "datatype_id": 1},
+			{"index_id": 3, "value": "2024-01-01", "operator": "Gte", "datatype_id": 3},
+			{"index_id": 5, "value": "10", "operator": "Lte", "datatype_id": 2},
+			{"index_id": 3, "value": "2024-12-31", "operator": "Lte", "datatype_id": 3},
+		]));
+		let fx_list_options = Listoptions {
+			order_bys: Some("!ctime,id".to_string()),
+			limit: Some(20),
+			offset: Some(40),
+			cursor: None,
+		};
+		// Joins are in index_id order, whatever the filters order.
+		let fx_sql = [
+			SNAP_SELECT,
+			r#" AND "index"."id" IN (3, 7)), '{}'::jsonb) AS "values", "#,
+			r#"COUNT(*) OVER (  ) AS "total_count", "#,
+			r#"jsonb_build_object('ctime', "archive"."ctime", 'id', "archive"."id") "#,
+			r#"AS "cursor" FROM "archive" "#,
+			r#"INNER JOIN "value" AS "v3" ON "v3"."archive_id" = "archive"."id" "#,
+			r#"AND "v3"."index_id" = 3 "#,
+			r#"AND TO_DATE("v3"."value", 'YYYY-MM-DD') >= TO_DATE('2024-01-01', 'YYYY-MM-DD') "#,
+			r#"AND TO_DATE("v3"."value", 'YYYY-MM-DD') <= TO_DATE('2024-12-31', 'YYYY-MM-DD') "#,
+			r#"INNER JOIN "value" AS "v5" ON "v5"."archive_id" = "archive"."id" "#,
+			r#"AND "v5"."index_id" = 5 "#,
+			r#"AND CAST("v5"."value" AS NUMERIC) <= CAST('10' AS NUMERIC) "#,
+			r#"INNER JOIN "value" AS "v7" ON "v7"."archive_id" = "archive"."id" "#,
+			r#"AND "v7"."index_id" = 7 AND "v7"."value" = 'Ana' "#,
+			r#"WHERE "archive"."is_deleted" = FALSE AND "#,
+			SNAP_ACCESS,
+			r#" ORDER BY "archive"."ctime" DESC, "archive"."id" ASC LIMIT 20 OFFSET 40"#,
+		]
+		.concat();
+
+		// -- Exec
+		let query = search_archives_query(
+			&fx_ctx,
+			&fx_filters,
+			&fx_list_options,
+			Some(vec![3, 7]),
+		)?;
+
+		// -- Check
+		assert_eq!(query.to_string(PostgresQueryBuilder), fx_sql);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_search_archives_query_fuzzy_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_filters = fx_filters(json!([
+			{"index_id": 7, "value": "Jose Peres", "operator": "Fuzzy", "datatype_id": 1},
+			{"index_id": 8, "value": "Jose", "operator": "Unaccent", "datatype_id": 1},
+		]));
+		let fx_sql = [
+			SNAP_SELECT,
+			r#"), '{}'::jsonb) AS "values", COUNT(*) OVER (  ) AS "total_count" "#,
+			r#"FROM "archive" "#,
+			r#"INNER JOIN "value" AS "v7" ON "v7"."archive_id" = "archive"."id" "#,
+			r#"AND "v7"."index_id" = 7 "#,
+			r#"AND f_unaccent(LOWER("v7"."value")) % f_unaccent(LOWER('Jose Peres')) "#,
+			r#"INNER JOIN "value" AS "v8" ON "v8"."archive_id" = "archive"."id" "#,
+			r#"AND "v8"."index_id" = 8 "#,
+			r#"AND f_unaccent(LOWER("v8"."value")) = f_unaccent(LOWER('Jose')) "#,
+			r#"WHERE "archive"."is_deleted" = FALSE AND "#,
+			SNAP_ACCESS,
+			r#" ORDER BY similarity(f_unaccent(LOWER("v7"."value")), "#,
+			r#"f_unaccent(LOWER('Jose Peres'))) DESC, "archive"."id" ASC LIMIT 1000"#,
+		]
+		.concat();
+
+		// -- Exec
+		let query = search_archives_query(
+			&fx_ctx,
+			&fx_filters,
+			&Listoptions::default(),
+			None,
+		)?;
+
+		// -- Check
+		assert_eq!(query.to_string(PostgresQueryBuilder), fx_sql);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_search_archives_query_cursor_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_list_options = Listoptions {
+			order_bys: Some("!tag".to_string()),
+			limit: Some(2),
+			offset: None,
+			cursor: Some(b64u_encode(r#"{"tag":"B-2","id":7}"#)),
+		};
+		let fx_cursor_value = |column: &str| {
+			format!(
+				r#"(SELECT "{column}" FROM jsonb_populate_record(NULL::"archive", "#
+			) + r#"E'{\"id\":7,\"tag\":\"B-2\"}') AS "cursor")"#
+		};
+		let fx_sql = [
+			SNAP_SELECT,
+			r#"), '{}'::jsonb) AS "values", COUNT(*) OVER (  ) AS "total_count", "#,
+			r#"jsonb_build_object('tag', "archive"."tag", 'id', "archive"."id") "#,
+			r#"AS "cursor" FROM "archive" WHERE "archive"."is_deleted" = FALSE AND "#,
+			SNAP_ACCESS,
+			r#" AND ("archive"."tag" < "#,
+			&fx_cursor_value("tag"),
+			r#" OR ("archive"."tag" = "#,
+			&fx_cursor_value("tag"),
+			r#" AND ("archive"."id" > "#,
+			&fx_cursor_value("id"),
+			r#" OR "archive"."id" IS NULL))) "#,
+			r#"ORDER BY "archive"."tag" DESC, "archive"."id" ASC LIMIT 2"#,
+		]
+		.concat();
+
+		// -- Exec
+		let query = search_archives_query(&fx_ctx, &[], &fx_list_options, None)?;
+
+		// -- Check
+		assert_eq!(query.to_string(PostgresQueryBuilder), fx_sql);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_search_archives_query_err_cursor_order() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_list_options = Listoptions {
+			order_bys: Some("!ctime".to_string()),
+			cursor: Some(b64u_encode(r#"{"tag":"B-2","id":7}"#)),
+			..Default::default()
+		};
+
+		// -- Exec
+		let res = search_archives_query(&fx_ctx, &[], &fx_list_options, None);
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::InvalidCursor(_))),
+			"Should have matched `Err(Error::InvalidCursor(_))` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_search_archives_query_err_fuzzy_numeric() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_filters = fx_filters(json!([
+			{"index_id": 5, "value": "10", "operator": "Fuzzy", "datatype_id": 2},
+		]));
+
+		// -- Exec
+		let res = search_archives_query(
+			&fx_ctx,
+			&fx_filters,
+			&Listoptions::default(),
+			None,
+		);
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::UnsupportedDatatype(5))),
+			"Should have matched `Err(Error::UnsupportedDatatype(5))` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
 
-			count_join_index += 1;
+	#[test]
+	fn test_search_archives_query_err_text_range() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_filters = fx_filters(json!([
+			{"index_id": 7, "value": "Ana", "operator": "Gte", "datatype_id": 1},
+		]));
+
+		// -- Exec
+		let res = search_archives_query(
+			&fx_ctx,
+			&fx_filters,
+			&Listoptions::default(),
+			None,
+		);
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::UnsupportedDatatype(7))),
+			"Should have matched `Err(Error::UnsupportedDatatype(7))` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_search_archives_query_err_order_by() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_list_options = Listoptions {
+			order_bys: Some("id,value".to_string()),
+			..Default::default()
+		};
+
+		// -- Exec
+		let res = search_archives_query(&fx_ctx, &[], &fx_list_options, None);
+
+		// -- Check
+		assert!(
+			matches!(&res, Err(Error::UnsupportedOrderBy(field)) if field == "value"),
+			"Should have matched `Err(Error::UnsupportedOrderBy)` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_search_archives_query_err_limit() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_list_options = |limit, offset| Listoptions {
+			limit,
+			offset,
+			..Default::default()
+		};
+
+		// -- Exec
+		let res_over = search_archives_query(
+			&fx_ctx,
+			&[],
+			&fx_list_options(Some(5001), None),
+			None,
+		);
+		let res_negative = search_archives_query(
+			&fx_ctx,
+			&[],
+			&fx_list_options(Some(-1), None),
+			None,
+		);
+		let res_offset = search_archives_query(
+			&fx_ctx,
+			&[],
+			&fx_list_options(None, Some(-1)),
+			None,
+		);
+
+		// -- Check
+		assert!(
+			matches!(res_over, Err(Error::ListLimitOverMax { max: 5000, actual: 5001 })),
+			"Should have matched `Err(Error::ListLimitOverMax)` but was `{res_over:?}`"
+		);
+		for res in [res_negative, res_offset] {
+			assert!(
+				matches!(res, Err(Error::InvalidValue(_))),
+				"Should have matched `Err(Error::InvalidValue(_))` but was `{res:?}`"
+			);
 		}
 
-		// Build the count query
-		let count_query = count_query_builder.build();
+		Ok(())
+	}
 
-		// Execute the count query and retrieve the total count
-		let total_count_row = count_query.fetch_one(db).await?;
-		let total_count: i64 = total_count_row.get(0);
+	#[test]
+	fn test_export_archives_query_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_filters = fx_filters(json!([
+			{"index_id": 2, "value": "100", "operator": "Gte", "datatype_id": 2},
+		]));
+		let fx_list_options = Listoptions {
+			order_bys: Some("!ctime".to_string()),
+			limit: Some(10),
+			offset: Some(20),
+			cursor: None,
+		};
+		let fx_index = |id: i64, index_name: &str, datatype_id: i64| ExportIndex {
+			id,
+			index_name: index_name.to_string(),
+			datatype_id,
+		};
+		let fx_indexes = [
+			fx_index(1, "Nombre", 1),
+			fx_index(2, "Monto", 2),
+			fx_index(4, "Nombre", 1),
+		];
+		let fx_value = |index_id: &str, limit: &str| {
+			[
+				r#"to_jsonb((SELECT "value"."value" FROM "value" "#,
+				r#"WHERE "value"."archive_id" = "archive"."id" "#,
+				&format!(r#"AND "value"."index_id" = {index_id} LIMIT {limit}))"#),
+			]
+			.concat()
+		};
+		let fx_sql = [
+			r#"SELECT to_jsonb("archive"."id") AS "c0", "#,
+			r#"to_jsonb("archive"."project_id") AS "c1", "#,
+			r#"to_jsonb("archive"."owner") AS "c2", "#,
+			r#"to_jsonb("archive"."last_edit_user") AS "c3", "#,
+			r#"to_jsonb("archive"."tag") AS "c4", "#,
+			r#"to_jsonb("archive"."cid") AS "c5", "#,
+			r#"to_jsonb("archive"."ctime") AS "c6", "#,
+			r#"to_jsonb("archive"."mid") AS "c7", "#,
+			r#"to_jsonb("archive"."mtime") AS "c8", "#,
+			&fx_value("$1", "$2"),
+			r#" AS "c9", "#,
+			&fx_value("$3", "$4"),
+			r#" AS "c10", "#,
+			&fx_value("$5", "$6"),
+			r#" AS "c11" FROM "archive" INNER JOIN "value" AS "v2" "#,
+			r#"ON "v2"."archive_id" = "archive"."id" AND "v2"."index_id" = $7 "#,
+			r#"AND CAST("v2"."value" AS NUMERIC) >= CAST($8 AS NUMERIC) "#,
+			r#"WHERE "archive"."is_deleted" = $9 "#,
+			// The accessible structures of the root ctx, as bound values.
+			r#"AND "archive"."project_id" IN (SELECT "project_id" "#,
+			r#"FROM "structure_privilege" WHERE "is_enabled" = $10 "#,
+			r#"AND "is_deleted" = $11 AND ("user_id" = $12 OR ("role_name" IN "#,
+			r#"(SELECT "assigned_role" FROM "user" WHERE "id" = $13) "#,
+			r#"AND "project_id" NOT IN (SELECT "project_id" FROM "structure_privilege" "#,
+			r#"WHERE "user_id" = $14 AND "is_deleted" = $15)))) "#,
+			// No limit/offset, whatever the list options.
+			r#"ORDER BY "archive"."ctime" DESC, "archive"."id" ASC"#,
+		]
+		.concat();
 
-		// Convert total_count from i64 to usize
-		let total_count: usize = total_count.try_into().unwrap();
+		// -- Exec
+		let export = export_archives_query(
+			&fx_ctx,
+			&fx_filters,
+			&fx_list_options,
+			&fx_indexes,
+		)?;
 
-		// Return the result
-		Ok(ListResult {
-			total_count,
-			items: archives,
-		})
+		// -- Check
+		let names: Vec<&str> =
+			export.columns.iter().map(|c| c.name.as_str()).collect();
+		assert_eq!(
+			names,
+			[
+				"id",
+				"project_id",
+				"owner",
+				"last_edit_user",
+				"tag",
+				"cid",
+				"ctime",
+				"mid",
+				"mtime",
+				"Nombre (1)",
+				"Monto",
+				"Nombre (4)"
+			]
+		);
+		let numeric: Vec<bool> = export.columns.iter().map(|c| c.numeric).collect();
+		assert_eq!(numeric[9..], [false, true, false]);
+		assert_eq!(export.sql, fx_sql);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_search_documents_query_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_filter: DocumentSearchFilter = serde_json::from_value(json!({
+			"name": "50%_off",
+			"doc_type": "application/pdf",
+			"uploaded_from": "2024-01-01T00:00:00Z",
+			"separator_name": "Facturas",
+			"archive_filters": [
+				{"index_id": 7, "value": "Ana", "operator": "Eq", "datatype_id": 1},
+			],
+		}))?;
+		let fx_list_options = Listoptions {
+			order_bys: Some("!ctime".to_string()),
+			limit: Some(10),
+			offset: None,
+			cursor: None,
+		};
+		let fx_sql = [
+			r#"SELECT "document"."id", "document"."separator_id", "#,
+			r#""document"."archive_id", "document"."name", "document"."doc_type", "#,
+			r#""document"."owner", "document"."last_edit_user", "document"."key", "#,
+			r#""document"."cid", "document"."ctime", "document"."mid", "#,
+			r#""document"."mtime", "structure"."id" AS "project_id", "#,
+			r#""structure"."project_name" AS "project_name", "#,
+			r#""archive"."tag" AS "archive_tag", "#,
+			r#"COUNT(*) OVER (  ) AS "total_count", "#,
+			r#"jsonb_build_object('ctime', "document"."ctime", 'id', "document"."id") "#,
+			r#"AS "cursor" FROM "document" "#,
+			r#"INNER JOIN "separator" ON "separator"."id" = "document"."separator_id" "#,
+			r#"INNER JOIN "archive" ON "archive"."id" = "document"."archive_id" "#,
+			r#"INNER JOIN "structure" ON "structure"."id" = "archive"."project_id" "#,
+			r#"INNER JOIN "value" AS "v7" ON "v7"."archive_id" = "archive"."id" "#,
+			r#"AND "v7"."index_id" = 7 AND "v7"."value" = 'Ana' "#,
+			r#"WHERE "document"."is_deleted" = FALSE "#,
+			r#"AND "separator"."is_deleted" = FALSE "#,
+			r#"AND "archive"."is_deleted" = FALSE "#,
+			r#"AND "structure"."is_deleted" = FALSE AND "#,
+			SNAP_ACCESS,
+			r#" AND ("document"."name" ILIKE E'%50\\%\\_off%') "#,
+			r#"AND "document"."doc_type" = 'application/pdf' "#,
+			r#"AND "document"."ctime" >= '2024-01-01 00:00:00.000000 +00:00' "#,
+			r#"AND ("separator"."name" ILIKE '%Facturas%') "#,
+			r#"ORDER BY "document"."ctime" DESC, "document"."id" ASC LIMIT 10"#,
+		]
+		.concat();
+
+		// -- Exec
+		let query = search_documents_query(&fx_ctx, &fx_filter, &fx_list_options)?;
+
+		// -- Check
+		assert_eq!(query.to_string(PostgresQueryBuilder), fx_sql);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_folder_path_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_folders = HashMap::from([
+			(1, ("Root".to_string(), None)),
+			(2, ("Sub".to_string(), Some(1))),
+			(3, ("Leaf".to_string(), Some(2))),
+			// -- Cycle
+			(4, ("A".to_string(), Some(5))),
+			(5, ("B".to_string(), Some(4))),
+		]);
+
+		// -- Exec
+		let path = folder_path(&fx_folders, 3);
+		let cycle_path = folder_path(&fx_folders, 4);
+
+		// -- Check
+		let names: Vec<&str> = path.iter().map(|item| item.name.as_str()).collect();
+		assert_eq!(names, ["Root", "Sub", "Leaf"]);
+		let names: Vec<&str> =
+			cycle_path.iter().map(|item| item.name.as_str()).collect();
+		assert_eq!(names, ["B", "A"]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_facet_query_numeric_histogram_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_filters = fx_filters(json!([
+			{"index_id": 7, "value": "Ana", "operator": "Eq", "datatype_id": 1},
+		]));
+		let fx_facet = Facet::NumericHistogram {
+			index_id: 5,
+			bucket_size: 100.,
+		};
+		let fx_bucket = r#"FLOOR(CAST("facet"."value" AS NUMERIC) / CAST(100 AS NUMERIC)) * CAST(100 AS NUMERIC)"#;
+		let fx_sql = [
+			r#"SELECT to_jsonb("#,
+			fx_bucket,
+			r#") AS "key", COUNT(*) AS "count" FROM "archive" "#,
+			r#"INNER JOIN "value" AS "v7" ON "v7"."archive_id" = "archive"."id" "#,
+			r#"AND "v7"."index_id" = 7 AND "v7"."value" = 'Ana' "#,
+			r#"INNER JOIN "value" AS "facet" ON "facet"."archive_id" = "archive"."id" "#,
+			r#"AND "facet"."index_id" = 5 "#,
+			r#"WHERE "archive"."is_deleted" = FALSE AND "#,
+			SNAP_ACCESS,
+			r#" GROUP BY "key" "#,
+			r#"ORDER BY "key" ASC LIMIT 1000"#,
+		]
+		.concat();
+
+		// -- Exec
+		let query = facet_query(&fx_ctx, &fx_filters, &fx_facet)?;
+
+		// -- Check
+		assert_eq!(query.to_string(PostgresQueryBuilder), fx_sql);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_facet_query_values_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_facet = Facet::Values {
+			index_id: 5,
+			limit: None,
+		};
+		let fx_sql = [
+			r#"SELECT to_jsonb("facet"."value") AS "key", COUNT(*) AS "count" "#,
+			r#"FROM "archive" INNER JOIN "value" AS "facet" "#,
+			r#"ON "facet"."archive_id" = "archive"."id" AND "facet"."index_id" = 5 "#,
+			r#"WHERE "archive"."is_deleted" = FALSE AND "#,
+			SNAP_ACCESS,
+			r#" GROUP BY "key" "#,
+			r#"ORDER BY "count" DESC, "key" ASC LIMIT 50"#,
+		]
+		.concat();
+
+		// -- Exec
+		let query = facet_query(&fx_ctx, &[], &fx_facet)?;
+
+		// -- Check
+		assert_eq!(query.to_string(PostgresQueryBuilder), fx_sql);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_facet_query_err_bucket_size() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_ctx = Ctx::root_ctx();
+		let fx_facet = Facet::NumericHistogram {
+			index_id: 5,
+			bucket_size: 0.,
+		};
+
+		// -- Exec
+		let res = facet_query(&fx_ctx, &[], &fx_facet);
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::InvalidValue(_))),
+			"Should have matched `Err(Error::InvalidValue)` but was `{res:?}`"
+		);
+
+		Ok(())
 	}
 }
+// endregion: --- Tests
@@ -0,0 +1,351 @@
+use crate::core::ctx::Ctx;
+use crate::core::model::base::DbBmc;
+use crate::core::model::ModelManager;
+use crate::core::model::{Error, Result};
+use crate::utils::time::{now_utc, Rfc3339};
+use modql::field::{Fields, HasFields};
+use sea_query::{Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::Serialize;
+use serde_with::serde_as;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::FromRow;
+use time::Duration;
+use uuid::Uuid;
+
+use super::base::{cursor_columns, list_rows, CursorListOptions, ListResult};
+use super::idens::SessionIden;
+
+/// The `last_seen` of a session is only rewritten past this delay, not on
+/// every request.
+const LAST_SEEN_PRECISION_SEC: i64 = 60;
+
+/// How the tokens of a session are sent, a token only being valid in its
+/// own transport (e.g., a header token is never slid as a cookie one).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionTokenKind {
+	Cookie,
+	Header,
+}
+
+impl SessionTokenKind {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::Cookie => "cookie",
+			Self::Header => "header",
+		}
+	}
+}
+
+/// A login of a user. The `token_id` (the ident of its tokens) is never
+/// returned.
+#[serde_as]
+#[derive(Clone, Fields, FromRow, Debug, Serialize)]
+pub struct Session {
+	pub id: i64,
+	pub token_kind: String,
+	pub user_id: i64,
+	pub ip: Option<String>,
+	pub user_agent: Option<String>,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	#[serde_as(as = "Rfc3339")]
+	pub last_seen: OffsetDateTime,
+	#[serde_as(as = "Option<Rfc3339>")]
+	pub revoked_at: Option<OffsetDateTime>,
+}
+
+/// A session of the ctx user, `current` for the one of the request.
+#[derive(Debug, Serialize)]
+pub struct MySession {
+	#[serde(flatten)]
+	pub session: Session,
+	pub current: bool,
+}
+
+#[derive(Fields)]
+pub struct SessionForCreate {
+	pub token_kind: String,
+	pub user_id: i64,
+	pub ip: Option<String>,
+	pub user_agent: Option<String>,
+}
+
+#[derive(Clone, FromRow, Debug)]
+pub struct SessionForAuth {
+	pub id: i64,
+	pub token_id: Uuid,
+	pub token_kind: String,
+	pub user_id: i64,
+	pub last_seen: OffsetDateTime,
+}
+
+pub struct SessionBmc;
+
+impl DbBmc for SessionBmc {
+	const TABLE: &'static str = "session";
+	const TIMESTAMPED: bool = false;
+	const SOFTDELETED: bool = false;
+}
+
+impl SessionBmc {
+	/// Starts a session at login, its `token_id` is the ident of the tokens.
+	pub async fn create(
+		mm: &ModelManager,
+		session_c: SessionForCreate,
+	) -> Result<SessionForAuth> {
+		let db = mm.db();
+
+		let (columns, sea_values) = session_c.not_none_fields().for_sea_insert();
+
+		let mut query = Query::insert();
+		query
+			.into_table(Self::table_ref())
+			.columns(columns)
+			.values(sea_values)?
+			.returning(Query::returning().columns([
+				SessionIden::Id,
+				SessionIden::TokenId,
+				SessionIden::TokenKind,
+				SessionIden::UserId,
+				SessionIden::LastSeen,
+			]));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let session = sqlx::query_as_with::<_, SessionForAuth, _>(&sql, values)
+			.fetch_one(db)
+			.await?;
+
+		Ok(session)
+	}
+
+	/// The not revoked session of a token ident, if any.
+	pub async fn first_active_by_token_id(
+		mm: &ModelManager,
+		token_id: Uuid,
+	) -> Result<Option<SessionForAuth>> {
+		let db = mm.db();
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.columns([
+				SessionIden::Id,
+				SessionIden::TokenId,
+				SessionIden::TokenKind,
+				SessionIden::UserId,
+				SessionIden::LastSeen,
+			])
+			.and_where(Expr::col(SessionIden::TokenId).eq(token_id))
+			.and_where(Expr::col(SessionIden::RevokedAt).is_null());
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let session = sqlx::query_as_with::<_, SessionForAuth, _>(&sql, values)
+			.fetch_optional(db)
+			.await?;
+
+		Ok(session)
+	}
+
+	/// Records a request of the session (at most once a
+	/// `LAST_SEEN_PRECISION_SEC`).
+	pub async fn touch_last_seen(
+		mm: &ModelManager,
+		session: &SessionForAuth,
+	) -> Result<()> {
+		let db = mm.db();
+
+		let now = now_utc();
+		if now - session.last_seen < Duration::seconds(LAST_SEEN_PRECISION_SEC) {
+			return Ok(());
+		}
+
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.value(SessionIden::LastSeen, now)
+			.and_where(Expr::col(SessionIden::Id).eq(session.id));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(())
+	}
+
+	/// The not revoked sessions of the ctx user.
+	pub async fn list_mine(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		list_options: Option<CursorListOptions>,
+	) -> Result<ListResult<MySession>> {
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.columns(Session::field_column_refs())
+			.and_where(Expr::col(SessionIden::UserId).eq(ctx.user_id()))
+			.and_where(Expr::col(SessionIden::RevokedAt).is_null());
+
+		let page = list_rows(mm, query, list_options, |order_bys| {
+			Ok(cursor_columns(Self::table_ref(), order_bys))
+		})
+		.await?;
+		let sessions = page
+			.items
+			.iter()
+			.map(Session::from_row)
+			.map(|session| {
+				session.map(|session| MySession {
+					current: ctx.session_id() == Some(session.id),
+					session,
+				})
+			})
+			.collect::<core::result::Result<Vec<_>, _>>()?;
+
+		Ok(page.with_items(sessions))
+	}
+
+	/// Only the user's own sessions can be revoked.
+	pub async fn revoke(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+		let db = mm.db();
+
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.value(SessionIden::RevokedAt, now_utc())
+			.and_where(Expr::col(SessionIden::Id).eq(id))
+			.and_where(Expr::col(SessionIden::UserId).eq(ctx.user_id()))
+			.and_where(Expr::col(SessionIden::RevokedAt).is_null());
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let count = sqlx::query_with(&sql, values)
+			.execute(db)
+			.await?
+			.rows_affected();
+
+		if count == 0 {
+			return Err(Error::EntityNotFound {
+				entity: Self::TABLE,
+				id,
+			});
+		}
+
+		Ok(())
+	}
+
+	/// Revokes all the sessions of the ctx user (but the current one when
+	/// `keep_current`), returning how many were revoked.
+	pub async fn revoke_all(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		keep_current: bool,
+	) -> Result<u64> {
+		let db = mm.db();
+
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.value(SessionIden::RevokedAt, now_utc())
+			.and_where(Expr::col(SessionIden::UserId).eq(ctx.user_id()))
+			.and_where(Expr::col(SessionIden::RevokedAt).is_null());
+		if let (true, Some(session_id)) = (keep_current, ctx.session_id()) {
+			query.and_where(Expr::col(SessionIden::Id).ne(session_id));
+		}
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let count = sqlx::query_with(&sql, values)
+			.execute(db)
+			.await?
+			.rows_affected();
+
+		Ok(count)
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use crate::core::model::user::{User, UserBmc};
+	use anyhow::Result;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_revoke_all_keeps_current_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let demo1: User = UserBmc::first_by_username(&Ctx::root_ctx(), &mm, "demo1")
+			.await?
+			.unwrap();
+		let fx_user_id = demo1.id;
+		let mut fx_sessions = Vec::new();
+		for user_agent in ["test_revoke_all-a", "test_revoke_all-b"] {
+			let session = SessionBmc::create(
+				&mm,
+				SessionForCreate {
+					token_kind: SessionTokenKind::Cookie.as_str().to_string(),
+					user_id: fx_user_id,
+					ip: Some("127.0.0.1".to_string()),
+					user_agent: Some(user_agent.to_string()),
+				},
+			)
+			.await?;
+			fx_sessions.push(session);
+		}
+		let ctx = Ctx::new(fx_user_id)?.with_session_id(fx_sessions[0].id);
+
+		// -- Exec
+		SessionBmc::revoke_all(&ctx, &mm, true).await?;
+
+		// -- Check
+		let current =
+			SessionBmc::first_active_by_token_id(&mm, fx_sessions[0].token_id)
+				.await?;
+		assert!(current.is_some(), "Current session should stay active");
+		let other =
+			SessionBmc::first_active_by_token_id(&mm, fx_sessions[1].token_id)
+				.await?;
+		assert!(other.is_none(), "Other session should be revoked");
+		let mine = SessionBmc::list_mine(&ctx, &mm, None).await?;
+		assert_eq!(mine.items.len(), 1);
+		assert!(mine.items[0].current);
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_revoke_err_not_mine() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let demo1: User = UserBmc::first_by_username(&Ctx::root_ctx(), &mm, "demo1")
+			.await?
+			.unwrap();
+		let fx_session = SessionBmc::create(
+			&mm,
+			SessionForCreate {
+				token_kind: SessionTokenKind::Cookie.as_str().to_string(),
+				user_id: demo1.id,
+				ip: None,
+				user_agent: Some("test_revoke_err_not_mine".to_string()),
+			},
+		)
+		.await?;
+		let ctx = Ctx::root_ctx();
+
+		// -- Exec
+		let res = SessionBmc::revoke(&ctx, &mm, fx_session.id).await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::EntityNotFound { .. })),
+			"Should have matched `Err(Error::EntityNotFound)` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -14,12 +14,27 @@ pub async fn new_db_pool() -> Result<Db> {
 	let max_connections = if cfg!(test) { 1 } else { 5 };
 
 	let db_url = core_config().DB_URL.clone();
+	let similarity_threshold = core_config().SEARCH_SIMILARITY_THRESHOLD.to_string();
 
 	// let ssl_db_url = format!("{}?sslmode=require", db_url);
 
 	PgPoolOptions::new()
 		.max_connections(max_connections)
 		.acquire_timeout(Duration::from_millis(10000))
+		.after_connect(move |conn, _meta| {
+			let similarity_threshold = similarity_threshold.clone();
+			Box::pin(async move {
+				// Threshold of the pg_trgm `%` and `<%` fuzzy search operators.
+				sqlx::query(
+					"SELECT set_config('pg_trgm.similarity_threshold', $1, false), \
+					set_config('pg_trgm.word_similarity_threshold', $1, false)",
+				)
+				.bind(similarity_threshold)
+				.execute(conn)
+				.await?;
+				Ok(())
+			})
+		})
 		.connect(&db_url)
 		.await
 		.map_err(|ex| Error::FailedToCreatePool(ex.to_string()))
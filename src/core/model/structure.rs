@@ -6,18 +6,18 @@ use crate::core::model::Result;
 use crate::utils::time::Rfc3339;
 use modql::field::{Fields, HasFields};
 use modql::filter::{
-	FilterGroups, FilterNodes, ListOptions, OpValsInt64, OpValsString, OpValsValue,
+	FilterGroups, FilterNodes, OpValsInt64, OpValsString, OpValsValue,
 };
-use sea_query::{Alias, Asterisk, Condition, Expr, PostgresQueryBuilder, Query};
-use sea_query_binder::SqlxBinder;
+use sea_query::{Condition, Expr, Query};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use sqlx::postgres::PgRow;
 use sqlx::types::time::OffsetDateTime;
-use sqlx::{FromRow, Row};
+use sqlx::FromRow;
 
-use super::base::{compute_list_options, ListResult};
-use super::idens::{StructureIden, StructurePrivilegeIden};
+use super::base::{cursor_columns, list_rows, CursorListOptions, ListResult};
+use super::idens::StructureIden;
+use super::structure_privilege::StructurePrivilegeBmc;
 
 #[serde_as]
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
@@ -85,27 +85,16 @@ impl StructureBmc {
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<StructureFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<Structure>> {
-		let db = mm.db();
-
 		// Build the base query
 		let mut base_query = Query::select();
 		base_query
 			.from(Self::table_ref())
 			.columns(Structure::field_column_refs())
-			.and_where(
-				Expr::col(StructureIden::Id).in_subquery(
-					Query::select()
-						.from(StructurePrivilegeIden::Table)
-						.column(StructurePrivilegeIden::ProjectId)
-						.and_where(
-							Expr::col(StructurePrivilegeIden::UserId)
-								.eq(ctx.user_id()),
-						)
-						.take(),
-				),
-			);
+			.and_where(Expr::col(StructureIden::Id).in_subquery(
+				StructurePrivilegeBmc::accessible_project_ids(ctx, ctx.user_id()),
+			));
 
 		if let Some(filter) = filters {
 			let filters: FilterGroups = filter.into();
@@ -117,31 +106,19 @@ impl StructureBmc {
 			base_query.and_where(Expr::col(StructureIden::IsDeleted).eq(false));
 		}
 
-		let mut count_query = Query::select();
-		count_query.from(Self::table_ref());
-
-		count_query.expr_as(Expr::col(Asterisk).count(), Alias::new("total_count"));
-
-		let (count_sql, count_values) = count_query.build_sqlx(PostgresQueryBuilder);
-		let total_count_row = sqlx::query_with(&count_sql, count_values)
-			.fetch_one(db)
-			.await?;
-
-		let total_count: i64 = total_count_row.get("total_count");
-
-		let list_options = compute_list_options(list_options)?;
-		list_options.apply_to_sea_query(&mut base_query);
-
-		let (sql, values) = base_query.build_sqlx(PostgresQueryBuilder);
-		let entities = sqlx::query_as_with::<_, Structure, _>(&sql, values)
-			.fetch_all(db)
-			.await?;
+		// Page the base query, counting all its rows on the way
+		let page = list_rows(mm, base_query, list_options, |order_bys| {
+			Ok(cursor_columns(Self::table_ref(), order_bys))
+		})
+		.await?;
+		let entities = page
+			.items
+			.iter()
+			.map(Structure::from_row)
+			.collect::<core::result::Result<Vec<_>, _>>()?;
 
 		// Return the result
-		Ok(ListResult {
-			total_count: total_count as usize,
-			items: entities,
-		})
+		Ok(page.with_items(entities))
 	}
 
 	pub async fn update(
@@ -157,3 +134,55 @@ impl StructureBmc {
 		base::delete::<Self>(ctx, mm, id).await
 	}
 }
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use crate::core::model::user::{User, UserBmc};
+	use anyhow::Result;
+	use serde_json::json;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_total_count_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let demo1: User = UserBmc::first_by_username(&Ctx::root_ctx(), &mm, "demo1")
+			.await?
+			.unwrap();
+		let ctx = Ctx::new(demo1.id)?;
+		let fx_project_names = [
+			"test_list_total_count_ok-01",
+			"test_list_total_count_ok-02",
+			"test_list_total_count_ok-03",
+		];
+		let mut fx_ids = Vec::new();
+		for project_name in fx_project_names {
+			let structure_c = StructureForOp {
+				project_name: project_name.to_string(),
+			};
+			fx_ids.push(StructureBmc::create(&ctx, &mm, structure_c).await?);
+		}
+		StructureBmc::delete(&ctx, &mm, fx_ids[2]).await?;
+		let fx_filter: StructureFilter = serde_json::from_value(json!({
+			"project_name": {"$startsWith": "test_list_total_count_ok"}
+		}))?;
+
+		// -- Exec
+		let structures =
+			StructureBmc::list(&ctx, &mm, Some(vec![fx_filter]), None).await?;
+
+		// -- Check
+		assert_eq!(structures.total_count, 2);
+		let ids: Vec<i64> = structures.items.iter().map(|s| s.id).collect();
+		assert_eq!(ids, &fx_ids[..2]);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
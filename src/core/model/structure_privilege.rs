@@ -1,43 +1,68 @@
 use crate::core::ctx::Ctx;
 use crate::core::model::base::{self, DbBmc};
+use crate::core::model::user::UserBmc;
 use crate::core::model::ModelManager;
 use crate::core::model::{Error, Result};
+use crate::utils::time::now_utc;
 use modql::field::{Fields, HasFields};
-use modql::filter::{FilterNodes, ListOptions, OpValsInt64, OpValsString};
-use sea_query::{Expr, PostgresQueryBuilder, Query};
+use modql::filter::{FilterNodes, OpValsBool, OpValsInt64, OpValsString};
+use sea_query::{
+	Cond, Expr, OnConflict, PostgresQueryBuilder, Query, SelectStatement,
+};
 use sea_query_binder::SqlxBinder;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use sqlx::postgres::PgRow;
 use sqlx::FromRow;
 
-use super::base::ListResult;
-use super::idens::StructurePrivilegeIden;
+use super::base::{CursorListOptions, ListResult};
+use super::idens::{ArchiveIden, StructurePrivilegeIden, UserIden};
 
+/// A grant of a role (`role_name`) or an override of a user (`user_id`).
 #[serde_as]
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
 pub struct StructurePrivilege {
 	pub id: i64,
-	pub user_id: i64,
 	pub project_id: i64,
+	pub user_id: Option<i64>,
+	pub role_name: Option<String>,
 	pub is_enabled: bool,
 }
 
-#[derive(Clone, Fields, FromRow, Debug, Serialize, Deserialize)]
-pub struct StructurePrivilegeForOp {
-	pub user_id: i64,
-	pub project_id: i64,
+/// Of either a `user_id` (an override) or a `role_name` (a grant).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StructuresForAct {
+	pub user_id: Option<i64>,
+	pub role_name: Option<String>,
+	pub ids: Vec<i64>,
 }
 
-#[derive(Clone, Fields, FromRow, Debug, Serialize, Deserialize)]
-pub struct StructurePrivilegeForSearchByUser {
-	pub user_id: i64,
+impl StructuresForAct {
+	pub fn grantee(&self) -> Result<StructureGrantee> {
+		match (self.user_id, &self.role_name) {
+			(Some(user_id), None) => Ok(StructureGrantee::User(user_id)),
+			(None, Some(role_name)) => Ok(StructureGrantee::Role(role_name.clone())),
+			_ => Err(Error::InvalidValue(
+				"A structure privilege is of either a user_id or a role_name"
+					.to_string(),
+			)),
+		}
+	}
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct StructuresForAct {
-	pub user_id: i64,
-	pub ids: Vec<i64>,
+#[derive(Clone, Debug)]
+pub enum StructureGrantee {
+	User(i64),
+	Role(String),
+}
+
+impl StructureGrantee {
+	fn column(&self) -> StructurePrivilegeIden {
+		match self {
+			Self::User(_) => StructurePrivilegeIden::UserId,
+			Self::Role(_) => StructurePrivilegeIden::RoleName,
+		}
+	}
 }
 
 #[allow(dead_code)]
@@ -47,13 +72,14 @@ pub trait StructurePrivilegeBy:
 }
 
 impl StructurePrivilegeBy for StructurePrivilege {}
-impl StructurePrivilegeBy for StructurePrivilegeForOp {}
 
 #[derive(FilterNodes, Deserialize, Default, Debug)]
 pub struct StructurePrivilegeFilter {
 	pub id: Option<OpValsInt64>,
+	pub project_id: Option<OpValsInt64>,
+	pub user_id: Option<OpValsInt64>,
 	pub role_name: Option<OpValsString>,
-	pub privilege_id: Option<OpValsInt64>,
+	pub is_enabled: Option<OpValsBool>,
 }
 
 pub struct StructurePrivilegeBmc;
@@ -61,7 +87,7 @@ pub struct StructurePrivilegeBmc;
 impl DbBmc for StructurePrivilegeBmc {
 	const TABLE: &'static str = "structure_privilege";
 	const TIMESTAMPED: bool = true;
-	const SOFTDELETED: bool = false;
+	const SOFTDELETED: bool = true;
 }
 
 impl StructurePrivilegeBmc {
@@ -74,31 +100,134 @@ impl StructurePrivilegeBmc {
 		base::get::<Self, _>(ctx, mm, id).await
 	}
 
-	pub async fn get_on_user_and_project_id(
+	pub async fn get_of_grantee(
 		_ctx: &Ctx,
 		mm: &ModelManager,
-		user_id: i64,
+		grantee: &StructureGrantee,
 		pid: i64,
 	) -> Result<StructurePrivilege> {
 		let db = mm.db();
 
 		let mut query = Query::select();
-
 		query
 			.from(Self::table_ref())
 			.columns(StructurePrivilege::field_idens())
-			.and_where(Expr::col(StructurePrivilegeIden::UserId).eq(user_id))
-			.and_where(Expr::col(StructurePrivilegeIden::ProjectId).eq(pid));
+			.and_where(Expr::col(StructurePrivilegeIden::ProjectId).eq(pid))
+			.and_where(Expr::col(StructurePrivilegeIden::IsDeleted).eq(false));
+		match grantee {
+			StructureGrantee::User(user_id) => {
+				query.and_where(Expr::col(grantee.column()).eq(*user_id))
+			}
+			StructureGrantee::Role(role_name) => {
+				query.and_where(Expr::col(grantee.column()).eq(role_name.as_str()))
+			}
+		};
 
 		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 		let association =
 			sqlx::query_as_with::<_, StructurePrivilege, _>(&sql, values)
-				.fetch_one(db)
-				.await?;
+				.fetch_optional(db)
+				.await?
+				.ok_or(Error::EntityNotFound {
+					entity: Self::TABLE,
+					id: pid,
+				})?;
 
 		Ok(association)
 	}
 
+	/// The ids of the structures the user can access: those of its enabled
+	/// overrides, then those its role has enabled and it has no override of.
+	/// For the ctx user of an api key, only those the key role has enabled
+	/// (the user overrides being ignored), and of the key scope.
+	pub fn accessible_project_ids(ctx: &Ctx, user_id: i64) -> SelectStatement {
+		let mut query = Query::select();
+		query
+			.column(StructurePrivilegeIden::ProjectId)
+			.from(Self::table_ref())
+			.and_where(Expr::col(StructurePrivilegeIden::IsEnabled).eq(true))
+			.and_where(Expr::col(StructurePrivilegeIden::IsDeleted).eq(false));
+
+		match ctx.key_scope().filter(|_| user_id == ctx.user_id()) {
+			Some(key_scope) => {
+				query.and_where(
+					Expr::col(StructurePrivilegeIden::RoleName)
+						.eq(key_scope.role_name.as_str()),
+				);
+				if let Some(project_ids) = key_scope.project_ids.clone() {
+					query.and_where(
+						Expr::col(StructurePrivilegeIden::ProjectId)
+							.is_in(project_ids),
+					);
+				}
+			}
+			None => {
+				let role_cond = Expr::col(StructurePrivilegeIden::RoleName)
+					.in_subquery(
+						Query::select()
+							.column(UserIden::AssignedRole)
+							.from(UserIden::Table)
+							.and_where(Expr::col(UserIden::Id).eq(user_id))
+							.to_owned(),
+					);
+				let overridden = Query::select()
+					.column(StructurePrivilegeIden::ProjectId)
+					.from(Self::table_ref())
+					.and_where(Expr::col(StructurePrivilegeIden::UserId).eq(user_id))
+					.and_where(
+						Expr::col(StructurePrivilegeIden::IsDeleted).eq(false),
+					)
+					.to_owned();
+
+				query.cond_where(
+					Cond::any()
+						.add(Expr::col(StructurePrivilegeIden::UserId).eq(user_id))
+						.add(
+							Cond::all().add(role_cond).add(
+								Expr::col(StructurePrivilegeIden::ProjectId)
+									.not_in_subquery(overridden),
+							),
+						),
+				);
+			}
+		}
+
+		query
+	}
+
+	/// The ids of the archives of the structures the ctx user can access.
+	pub fn accessible_archive_ids(ctx: &Ctx) -> SelectStatement {
+		let mut query = Query::select();
+		query
+			.column(ArchiveIden::Id)
+			.from(ArchiveIden::Table)
+			.and_where(
+				Expr::col(ArchiveIden::ProjectId)
+					.in_subquery(Self::accessible_project_ids(ctx, ctx.user_id())),
+			);
+
+		query
+	}
+
+	/// Whether the user can access the structure (see `accessible_project_ids`).
+	pub async fn has_access(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		user_id: i64,
+		pid: i64,
+	) -> Result<bool> {
+		let db = mm.db();
+
+		let mut query = Self::accessible_project_ids(ctx, user_id);
+		query.and_where(Expr::col(StructurePrivilegeIden::ProjectId).eq(pid));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let association = sqlx::query_with(&sql, values).fetch_optional(db).await?;
+
+		Ok(association.is_some())
+	}
+
+	/// The overrides of the user and the grants of its role.
 	pub async fn list_by_user_id(
 		_ctx: &Ctx,
 		mm: &ModelManager,
@@ -110,7 +239,20 @@ impl StructurePrivilegeBmc {
 		query
 			.from(Self::table_ref())
 			.columns(StructurePrivilege::field_idens())
-			.and_where(Expr::col(StructurePrivilegeIden::UserId).eq(id));
+			.and_where(Expr::col(StructurePrivilegeIden::IsDeleted).eq(false))
+			.cond_where(
+				Cond::any()
+					.add(Expr::col(StructurePrivilegeIden::UserId).eq(id))
+					.add(
+						Expr::col(StructurePrivilegeIden::RoleName).in_subquery(
+							Query::select()
+								.column(UserIden::AssignedRole)
+								.from(UserIden::Table)
+								.and_where(Expr::col(UserIden::Id).eq(id))
+								.to_owned(),
+						),
+					),
+			);
 
 		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 		let role = sqlx::query_as_with::<_, StructurePrivilege, _>(&sql, values)
@@ -120,101 +262,299 @@ impl StructurePrivilegeBmc {
 		Ok(role)
 	}
 
-	#[allow(unused)]
-	pub async fn create(
-		ctx: &Ctx,
-		mm: &ModelManager,
-		datatype_c: StructurePrivilegeForOp,
-	) -> Result<i64> {
-		let datatype_id = base::create::<Self, _>(ctx, mm, datatype_c).await?;
-
-		Ok(datatype_id)
-	}
-
 	#[allow(unused)]
 	pub async fn list(
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<StructurePrivilegeFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<StructurePrivilege>> {
 		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
 	}
 
-	#[allow(unused)]
-	pub async fn update(
+	pub async fn enable(
 		ctx: &Ctx,
 		mm: &ModelManager,
-		id: i64,
-		datatype_u: StructurePrivilegeForOp,
+		grantee: &StructureGrantee,
+		pid: i64,
 	) -> Result<()> {
-		base::update::<Self, _>(ctx, mm, id, datatype_u).await
+		Self::set_enabled(ctx, mm, grantee, pid, true).await
 	}
 
-	#[allow(unused)]
-	pub async fn delete(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
-		base::delete::<Self>(ctx, mm, id).await
+	pub async fn disable(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		grantee: &StructureGrantee,
+		pid: i64,
+	) -> Result<()> {
+		Self::set_enabled(ctx, mm, grantee, pid, false).await
 	}
 
-	pub async fn enable(
-		_ctx: &Ctx,
+	/// Removes the override of the user, its role grant applying again.
+	pub async fn clear_override(
+		ctx: &Ctx,
 		mm: &ModelManager,
-		user_id: i64,
+		grantee: &StructureGrantee,
 		pid: i64,
 	) -> Result<()> {
-		let db = mm.db();
+		UserBmc::check_admin(ctx, mm, Self::TABLE).await?;
 
-		let mut query = Query::update();
-		query
-			.table(Self::table_ref())
-			.value(StructurePrivilegeIden::IsEnabled, true)
-			.and_where(Expr::col(StructurePrivilegeIden::UserId).eq(user_id))
-			.and_where(Expr::col(StructurePrivilegeIden::ProjectId).eq(pid));
-
-		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-		let count = sqlx::query_with(&sql, values)
-			.execute(db)
-			.await?
-			.rows_affected();
-
-		if count == 0 {
-			Err(Error::EntityNotFound {
-				entity: Self::TABLE,
-				id: pid,
-			})
-		} else {
-			Ok(())
+		if let StructureGrantee::Role(_) = grantee {
+			return Err(Error::InvalidValue(
+				"Only a user has structure privilege overrides".to_string(),
+			));
 		}
+		let privilege = Self::get_of_grantee(ctx, mm, grantee, pid).await?;
+
+		base::delete::<Self>(ctx, mm, privilege.id).await
 	}
+}
 
-	pub async fn disable(
-		_ctx: &Ctx,
+// region:    --- Privates
+
+impl StructurePrivilegeBmc {
+	/// Creates the grant (or override) when missing, admins only.
+	async fn set_enabled(
+		ctx: &Ctx,
 		mm: &ModelManager,
-		user_id: i64,
+		grantee: &StructureGrantee,
 		pid: i64,
+		is_enabled: bool,
 	) -> Result<()> {
+		UserBmc::check_admin(ctx, mm, Self::TABLE).await?;
+
 		let db = mm.db();
+		let now = now_utc();
+
+		let grantee_value = match grantee {
+			StructureGrantee::User(user_id) => (*user_id).into(),
+			StructureGrantee::Role(role_name) => role_name.clone().into(),
+		};
 
-		let mut query = Query::update();
+		let mut query = Query::insert();
 		query
-			.table(Self::table_ref())
-			.value(StructurePrivilegeIden::IsEnabled, false)
-			.and_where(Expr::col(StructurePrivilegeIden::UserId).eq(user_id))
-			.and_where(Expr::col(StructurePrivilegeIden::ProjectId).eq(pid));
+			.into_table(Self::table_ref())
+			.columns([
+				StructurePrivilegeIden::ProjectId,
+				grantee.column(),
+				StructurePrivilegeIden::IsEnabled,
+				StructurePrivilegeIden::Cid,
+				StructurePrivilegeIden::Ctime,
+				StructurePrivilegeIden::Mid,
+				StructurePrivilegeIden::Mtime,
+			])
+			.values([
+				pid.into(),
+				grantee_value,
+				is_enabled.into(),
+				ctx.user_id().into(),
+				now.into(),
+				ctx.user_id().into(),
+				now.into(),
+			])?
+			.on_conflict(
+				OnConflict::columns([
+					StructurePrivilegeIden::ProjectId,
+					grantee.column(),
+				])
+				.value(StructurePrivilegeIden::IsEnabled, is_enabled)
+				.value(StructurePrivilegeIden::IsDeleted, false)
+				.value(StructurePrivilegeIden::Mid, ctx.user_id())
+				.value(StructurePrivilegeIden::Mtime, now)
+				.to_owned(),
+			);
 
 		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-		let count = sqlx::query_with(&sql, values)
-			.execute(db)
-			.await?
-			.rows_affected();
-
-		if count == 0 {
-			Err(Error::EntityNotFound {
-				entity: Self::TABLE,
-				id: pid,
-			})
-		} else {
-			Ok(())
-		}
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use crate::core::ctx::KeyScope;
+	use crate::core::model::role::{RoleBmc, RoleForOp};
+	use crate::core::model::structure::{StructureBmc, StructureForOp};
+	use crate::core::model::user::UserForCreate;
+	use anyhow::Result;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_has_access_role_grant_and_override_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_role = "fx-sp-role-01";
+		let fx_username = "test_has_access_role_grant-user-01";
+		RoleBmc::create(
+			&root_ctx,
+			&mm,
+			RoleForOp {
+				role_name: fx_role.to_string(),
+				description: "fx role".to_string(),
+			},
+		)
+		.await?;
+		let user_id = UserBmc::create(
+			&root_ctx,
+			&mm,
+			UserForCreate {
+				username: fx_username.to_string(),
+				email: format!("{fx_username}@example.org"),
+				pwd_clear: "welcome-01".to_string(),
+				assigned_role: fx_role.to_string(),
+			},
+		)
+		.await?;
+		// The new structure is granted to all the existing roles.
+		let pid = StructureBmc::create(
+			&root_ctx,
+			&mm,
+			StructureForOp {
+				project_name: "fx-sp-structure-01".to_string(),
+			},
+		)
+		.await?;
+		let role = StructureGrantee::Role(fx_role.to_string());
+		let user = StructureGrantee::User(user_id);
+		let has_access =
+			|| StructurePrivilegeBmc::has_access(&root_ctx, &mm, user_id, pid);
+
+		// -- Exec & Check
+		// Of the role grant.
+		assert!(has_access().await?);
+		StructurePrivilegeBmc::disable(&root_ctx, &mm, &role, pid).await?;
+		assert!(!has_access().await?);
+
+		// The override replaces the role grant.
+		StructurePrivilegeBmc::enable(&root_ctx, &mm, &user, pid).await?;
+		assert!(has_access().await?);
+		StructurePrivilegeBmc::enable(&root_ctx, &mm, &role, pid).await?;
+		StructurePrivilegeBmc::disable(&root_ctx, &mm, &user, pid).await?;
+		assert!(!has_access().await?);
+
+		// Back to the role grant.
+		StructurePrivilegeBmc::clear_override(&root_ctx, &mm, &user, pid).await?;
+		assert!(has_access().await?);
+		let res =
+			StructurePrivilegeBmc::get_of_grantee(&root_ctx, &mm, &user, pid).await;
+		assert!(
+			matches!(res, Err(Error::EntityNotFound { .. })),
+			"Should have matched `Err(Error::EntityNotFound)` but was `{res:?}`"
+		);
+
+		// -- Clean
+		UserBmc::delete(&root_ctx, &mm, user_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_has_access_key_scope_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_username = "test_has_access_key_scope_ok-user-01";
+		let user_id = UserBmc::create(
+			&root_ctx,
+			&mm,
+			UserForCreate {
+				username: fx_username.to_string(),
+				email: format!("{fx_username}@example.org"),
+				pwd_clear: "welcome-01".to_string(),
+				assigned_role: "demo".to_string(),
+			},
+		)
+		.await?;
+		RoleBmc::create(
+			&root_ctx,
+			&mm,
+			RoleForOp {
+				role_name: "fx-sp-key-role-01".to_string(),
+				description: "fx key role".to_string(),
+			},
+		)
+		.await?;
+		let pid_01 = StructureBmc::create(
+			&root_ctx,
+			&mm,
+			StructureForOp {
+				project_name: "fx-sp-key-structure-01".to_string(),
+			},
+		)
+		.await?;
+		let pid_02 = StructureBmc::create(
+			&root_ctx,
+			&mm,
+			StructureForOp {
+				project_name: "fx-sp-key-structure-02".to_string(),
+			},
+		)
+		.await?;
+		StructurePrivilegeBmc::disable(
+			&root_ctx,
+			&mm,
+			&StructureGrantee::Role("fx-sp-key-role-01".to_string()),
+			pid_02,
+		)
+		.await?;
+		let key_ctx = Ctx::new(user_id)?.with_key_scope(KeyScope {
+			role_name: "fx-sp-key-role-01".to_string(),
+			project_ids: None,
+		});
+
+		// -- Exec & Check
+		// The grants of the key role, not of the assigned one.
+		assert!(
+			StructurePrivilegeBmc::has_access(&key_ctx, &mm, user_id, pid_01)
+				.await?
+		);
+		assert!(
+			!StructurePrivilegeBmc::has_access(&key_ctx, &mm, user_id, pid_02)
+				.await?
+		);
+		assert!(
+			StructurePrivilegeBmc::has_access(&root_ctx, &mm, user_id, pid_02)
+				.await?
+		);
+		// Nor the overrides of the user.
+		StructurePrivilegeBmc::enable(
+			&root_ctx,
+			&mm,
+			&StructureGrantee::User(user_id),
+			pid_02,
+		)
+		.await?;
+		StructurePrivilegeBmc::disable(
+			&root_ctx,
+			&mm,
+			&StructureGrantee::User(user_id),
+			pid_01,
+		)
+		.await?;
+		assert!(
+			StructurePrivilegeBmc::has_access(&key_ctx, &mm, user_id, pid_01)
+				.await?
+		);
+		assert!(
+			!StructurePrivilegeBmc::has_access(&key_ctx, &mm, user_id, pid_02)
+				.await?
+		);
+
+		// -- Clean
+		UserBmc::delete(&root_ctx, &mm, user_id).await?;
+
+		Ok(())
 	}
 }
+
+// endregion: --- Tests
@@ -0,0 +1,409 @@
+use crate::auth::config::auth_config;
+use crate::auth::token::ring::{self, RingKey};
+use crate::core::ctx::Ctx;
+use crate::core::model::base::DbBmc;
+use crate::core::model::event::{EventBmc, EventForCreate};
+use crate::core::model::ModelManager;
+use crate::core::model::{Error, Result};
+use crate::utils::time::{now_utc, Rfc3339};
+use sea_query::{Alias, Expr, Order, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::Serialize;
+use serde_json::json;
+use serde_with::serde_as;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::{FromRow, PgExecutor};
+use std::time::Duration as StdDuration;
+use time::Duration;
+use tracing::{error, info};
+
+use super::idens::TokenKeyIden;
+
+/// The advisory lock of the scheduled rolls, one instance rolling at a time.
+const TOKEN_KEY_ROLL_LOCK: i64 = 0x746f_6b65_6e5f_6b65;
+
+/// A key of the token ring, its secret is never returned.
+#[serde_as]
+#[derive(Clone, FromRow, Debug, Serialize)]
+pub struct TokenKey {
+	pub id: i64,
+	pub kid: String,
+	#[serde_as(as = "Rfc3339")]
+	pub active_from: OffsetDateTime,
+	/// Its secret erased, past the grace after the next key.
+	pub retired: bool,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+}
+
+#[derive(FromRow)]
+struct TokenKeyForRing {
+	kid: String,
+	key_enc: Option<String>,
+	active_from: OffsetDateTime,
+}
+
+pub struct TokenKeyBmc;
+
+impl DbBmc for TokenKeyBmc {
+	const TABLE: &'static str = "token_key";
+	const TIMESTAMPED: bool = false;
+	const SOFTDELETED: bool = false;
+}
+
+impl TokenKeyBmc {
+	/// Oldest first, the last (active) key included.
+	pub async fn list(_ctx: &Ctx, mm: &ModelManager) -> Result<Vec<TokenKey>> {
+		let db = mm.db();
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.columns([
+				TokenKeyIden::Id,
+				TokenKeyIden::Kid,
+				TokenKeyIden::ActiveFrom,
+				TokenKeyIden::Ctime,
+			])
+			.expr_as(
+				Expr::col(TokenKeyIden::KeyEnc).is_null(),
+				Alias::new("retired"),
+			)
+			.order_by(TokenKeyIden::ActiveFrom, Order::Asc);
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let token_keys = sqlx::query_as_with::<_, TokenKey, _>(&sql, values)
+			.fetch_all(db)
+			.await?;
+
+		Ok(token_keys)
+	}
+
+	/// Loads the keys of the db into the ring of the token signing.
+	pub async fn load_ring(mm: &ModelManager) -> Result<()> {
+		let db = mm.db();
+
+		let mut query = Query::select();
+		query.from(Self::table_ref()).columns([
+			TokenKeyIden::Kid,
+			TokenKeyIden::KeyEnc,
+			TokenKeyIden::ActiveFrom,
+		]);
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let token_keys = sqlx::query_as_with::<_, TokenKeyForRing, _>(&sql, values)
+			.fetch_all(db)
+			.await?;
+
+		let ring_keys = token_keys
+			.into_iter()
+			.map(|token_key| {
+				Ok(RingKey {
+					kid: token_key.kid,
+					key: token_key
+						.key_enc
+						.map(|key_enc| ring::decrypt_ring_key(&key_enc))
+						.transpose()?,
+					active_from: token_key.active_from,
+				})
+			})
+			.collect::<Result<Vec<_>>>()?;
+		ring::set_ring_keys(ring_keys);
+
+		Ok(())
+	}
+
+	/// A new key, signing once all the instances reloaded the ring (after the
+	/// `TOKEN_KEY_REFRESH_SEC`). Also erases the secrets of the retired keys.
+	pub async fn roll(ctx: &Ctx, mm: &ModelManager) -> Result<TokenKey> {
+		let (id, kid) = Self::insert_key(mm.db(), None)
+			.await?
+			.ok_or(Error::InvalidValue("No token key inserted".to_string()))?;
+
+		Self::after_roll(ctx, mm, id, kid).await
+	}
+
+	/// `roll`, only if no key was rolled within the `rotation`. Among the
+	/// instances, only the one getting the lock rolls (`None` for the others).
+	pub async fn roll_if_due(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		rotation: Duration,
+	) -> Result<Option<TokenKey>> {
+		let mut tx = mm.db().begin().await?;
+
+		let (locked,): (bool,) =
+			sqlx::query_as("SELECT pg_try_advisory_xact_lock($1)")
+				.bind(TOKEN_KEY_ROLL_LOCK)
+				.fetch_one(&mut *tx)
+				.await?;
+		if !locked {
+			return Ok(None);
+		}
+
+		let inserted = Self::insert_key(&mut *tx, Some(rotation)).await?;
+		tx.commit().await?;
+
+		match inserted {
+			Some((id, kid)) => Self::after_roll(ctx, mm, id, kid).await.map(Some),
+			None => Ok(None),
+		}
+	}
+}
+
+// region:    --- Rotation
+
+/// Reloads the ring every `TOKEN_KEY_REFRESH_SEC`, rolling a new key when the
+/// last one is older than the `TOKEN_KEY_ROTATION_SEC` (if not 0).
+pub fn spawn_token_key_rotation(mm: ModelManager) {
+	let interval = StdDuration::from_secs_f64(auth_config().TOKEN_KEY_REFRESH_SEC);
+
+	tokio::spawn(async move {
+		let mut ticker = tokio::time::interval(interval);
+		loop {
+			ticker.tick().await;
+			match roll_if_due(&mm).await {
+				Ok(Some(kid)) => {
+					info!("{:<12} - rolled token key {kid}", "TOKEN_KEY")
+				}
+				Ok(None) => (),
+				Err(err) => {
+					error!(
+						"{:<12} - spawn_token_key_rotation - {err:?}",
+						"TOKEN_KEY"
+					)
+				}
+			}
+		}
+	});
+}
+
+async fn roll_if_due(mm: &ModelManager) -> Result<Option<String>> {
+	let rotation_sec = auth_config().TOKEN_KEY_ROTATION_SEC;
+
+	let token_key = if rotation_sec > 0. {
+		let rotation = Duration::seconds_f64(rotation_sec);
+		TokenKeyBmc::roll_if_due(&Ctx::root_ctx(), mm, rotation).await?
+	} else {
+		None
+	};
+
+	// The key may have been rolled by another instance.
+	if token_key.is_none() {
+		TokenKeyBmc::load_ring(mm).await?;
+	}
+
+	Ok(token_key.map(|token_key| token_key.kid))
+}
+
+// endregion: --- Rotation
+
+// region:    --- Privates
+
+impl TokenKeyBmc {
+	/// A new key (its id and kid), unless one was rolled within the
+	/// `due_after` when given.
+	async fn insert_key<'e, E>(
+		executor: E,
+		due_after: Option<Duration>,
+	) -> Result<Option<(i64, String)>>
+	where
+		E: PgExecutor<'e>,
+	{
+		let config = auth_config();
+
+		let (kid, key_enc) = ring::generate_ring_key()?;
+		let active_from =
+			now_utc() + Duration::seconds_f64(config.TOKEN_KEY_REFRESH_SEC);
+
+		let mut query = Query::insert();
+		query.into_table(Self::table_ref()).columns([
+			TokenKeyIden::Kid,
+			TokenKeyIden::KeyEnc,
+			TokenKeyIden::ActiveFrom,
+		]);
+		match due_after {
+			Some(due_after) => {
+				let rolled = Query::select()
+					.expr(Expr::val(1))
+					.from(Self::table_ref())
+					.and_where(
+						Expr::col(TokenKeyIden::ActiveFrom)
+							.gt(now_utc() - due_after),
+					)
+					.to_owned();
+				query.select_from(
+					Query::select()
+						.exprs([
+							Expr::val(kid.clone()),
+							Expr::val(key_enc),
+							Expr::val(active_from),
+						])
+						.and_where(Expr::exists(rolled).not())
+						.to_owned(),
+				)?;
+			}
+			None => {
+				query.values([
+					kid.clone().into(),
+					key_enc.into(),
+					active_from.into(),
+				])?;
+			}
+		}
+		query.returning(Query::returning().columns([TokenKeyIden::Id]));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let inserted = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+			.fetch_optional(executor)
+			.await?;
+
+		Ok(inserted.map(|(id,)| (id, kid)))
+	}
+
+	/// Of a rolled key: the retired secrets erased, the ring reloaded, and
+	/// the roll event.
+	async fn after_roll(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+		kid: String,
+	) -> Result<TokenKey> {
+		Self::retire_replaced(mm).await?;
+		Self::load_ring(mm).await?;
+
+		EventBmc::create(
+			ctx,
+			mm,
+			EventForCreate {
+				action: "TOKEN_KEY_ROLL",
+				object: "token_key",
+				object_id: id,
+				additional_info: Some(json!({ "kid": kid })),
+			},
+		)
+		.await?;
+
+		let token_key = Self::list(ctx, mm)
+			.await?
+			.into_iter()
+			.find(|token_key| token_key.id == id)
+			.ok_or(Error::EntityNotFound {
+				entity: Self::TABLE,
+				id,
+			})?;
+
+		Ok(token_key)
+	}
+
+	/// Erases the secrets of the keys replaced for longer than the
+	/// `TOKEN_KEY_GRACE_SEC`, their rows being kept for the order of the ring.
+	async fn retire_replaced(mm: &ModelManager) -> Result<()> {
+		let db = mm.db();
+
+		let grace = Duration::seconds_f64(auth_config().TOKEN_KEY_GRACE_SEC);
+		let token_keys = Self::list(&Ctx::root_ctx(), mm).await?;
+		let retired_ids: Vec<i64> = token_keys
+			.windows(2)
+			.filter(|pair| {
+				!pair[0].retired && pair[1].active_from + grace <= now_utc()
+			})
+			.map(|pair| pair[0].id)
+			.collect();
+		if retired_ids.is_empty() {
+			return Ok(());
+		}
+
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.value(TokenKeyIden::KeyEnc, Option::<String>::None)
+			.and_where(Expr::col(TokenKeyIden::Id).is_in(retired_ids));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::auth::token::{generate_web_token, validate_web_token};
+	use crate::core::_dev_utils;
+	use anyhow::Result;
+	use serial_test::serial;
+	use uuid::Uuid;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_roll_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_salt = Uuid::new_v4();
+		let fx_token_before = generate_web_token("fx-ident-01", fx_salt)?;
+
+		// -- Exec
+		let token_key = TokenKeyBmc::roll(&root_ctx, &mm).await?;
+		// Signing from the next refresh only.
+		let fx_token_pending = generate_web_token("fx-ident-01", fx_salt)?;
+
+		// -- Check
+		assert!(!token_key.retired);
+		assert_ne!(fx_token_pending.kid.as_ref(), Some(&token_key.kid));
+		let token_keys = TokenKeyBmc::list(&root_ctx, &mm).await?;
+		assert_eq!(token_keys.last().map(|k| &k.kid), Some(&token_key.kid));
+		// The tokens of the replaced key are still valid (grace).
+		validate_web_token(&fx_token_before, fx_salt)?;
+
+		// -- Clean
+		sqlx::query("DELETE FROM token_key")
+			.execute(mm.db())
+			.await?;
+		TokenKeyBmc::load_ring(&mm).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_roll_if_due_once_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_rotation = Duration::hours(1);
+		sqlx::query("DELETE FROM token_key")
+			.execute(mm.db())
+			.await?;
+
+		// -- Exec
+		// As two instances at the same time, then once more within the rotation.
+		let (res_01, res_02) = tokio::join!(
+			TokenKeyBmc::roll_if_due(&root_ctx, &mm, fx_rotation),
+			TokenKeyBmc::roll_if_due(&root_ctx, &mm, fx_rotation)
+		);
+		let res_03 = TokenKeyBmc::roll_if_due(&root_ctx, &mm, fx_rotation).await?;
+
+		// -- Check
+		let rolled = [res_01?, res_02?].into_iter().flatten().count();
+		assert_eq!(rolled, 1);
+		assert!(res_03.is_none());
+		assert_eq!(TokenKeyBmc::list(&root_ctx, &mm).await?.len(), 1);
+
+		// -- Clean
+		sqlx::query("DELETE FROM token_key")
+			.execute(mm.db())
+			.await?;
+		TokenKeyBmc::load_ring(&mm).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
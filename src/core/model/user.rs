@@ -1,24 +1,28 @@
-use crate::auth::pwd::{hash_pwd, ContentToHash};
+use crate::auth::pwd::policy::{PwdPolicy, PwdRule};
+use crate::auth::pwd::{hash_pwd, validate_pwd, ContentToHash};
 use crate::core::ctx::Ctx;
 use crate::core::model::base::{self, add_timestamps_for_update, DbBmc};
 use crate::core::model::modql_utils::time_to_sea_value;
 use crate::core::model::ModelManager;
-use crate::core::model::Result;
-use crate::utils::time::Rfc3339;
+use crate::core::model::{Error, Result};
+use crate::utils::time::{now_utc, Rfc3339};
 use modql::field::{Field, Fields, HasFields};
 use modql::filter::{
-	FilterNodes, ListOptions, OpValsInt64, OpValsString, OpValsValue,
+	FilterNodes, OpValsBool, OpValsInt64, OpValsString, OpValsValue,
 };
-use sea_query::{Expr, Iden, PostgresQueryBuilder, Query};
+use sea_query::{Expr, Iden, Order, PostgresQueryBuilder, Query};
 use sea_query_binder::SqlxBinder;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use serde_with::serde_as;
 use sqlx::postgres::PgRow;
 use sqlx::types::time::OffsetDateTime;
 use sqlx::FromRow;
 use uuid::Uuid;
 
-use super::base::ListResult;
+use super::base::{CursorListOptions, ListResult};
+use super::idens::PwdHistoryIden;
+use super::login_history::{LoginHistoryBmc, LoginHistoryKind};
 
 #[serde_as]
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
@@ -27,6 +31,7 @@ pub struct User {
 	pub email: String,
 	pub username: String,
 	pub assigned_role: String,
+	pub is_service: bool,
 	pub cid: i64,
 	#[serde_as(as = "Rfc3339")]
 	pub ctime: OffsetDateTime,
@@ -43,6 +48,22 @@ pub struct UserForCreate {
 	pub assigned_role: String,
 }
 
+/// A user of the automated jobs, authenticated with api keys only
+/// (no pwd, no interactive login).
+#[derive(Deserialize)]
+pub struct ServiceAccountForCreate {
+	pub username: String,
+	pub email: String,
+	pub assigned_role: String,
+}
+
+/// A user of the IdP (auto-provisioned at its first OpenID Connect login).
+pub struct ExternalUserForCreate {
+	pub username: String,
+	pub email: String,
+	pub assigned_role: String,
+}
+
 #[derive(Deserialize, Fields)]
 pub struct UserForUpdatePwd {
 	pub pwd_clear: String,
@@ -53,6 +74,7 @@ pub struct UserForInsert {
 	pub username: String,
 	pub email: String,
 	pub assigned_role: String,
+	pub is_service: bool,
 }
 
 #[derive(Clone, FromRow, Fields, Debug)]
@@ -65,7 +87,9 @@ pub struct UserForLogin {
 	pub pwd: Option<String>, // encrypted, #_scheme_id_#....
 	pub pwd_salt: Uuid,
 	pub token_salt: Uuid,
+	pub pwd_mtime: OffsetDateTime,
 	pub assigned_role: String,
+	pub is_service: bool,
 }
 
 #[derive(Clone, FromRow, Fields, Debug)]
@@ -91,6 +115,7 @@ pub struct UserFilter {
 	email: Option<OpValsString>,
 	username: Option<OpValsString>,
 	assigned_role: Option<OpValsString>,
+	is_service: Option<OpValsBool>,
 	cid: Option<OpValsInt64>,
 	#[modql(to_sea_value_fn = "time_to_sea_value")]
 	ctime: Option<OpValsValue>,
@@ -112,9 +137,15 @@ impl UserBy for UserForUpdate {}
 enum UserIden {
 	Id,
 	Username,
+	Email,
 	Pwd,
+	PwdMtime,
+	TokenSalt,
 }
 
+/// The role of the users managing the others (e.g., their api keys).
+const ADMIN_ROLE: &str = "ADMIN";
+
 pub struct UserBmc;
 
 impl DbBmc for UserBmc {
@@ -131,6 +162,33 @@ impl UserBmc {
 		base::get::<Self, _>(ctx, mm, id).await
 	}
 
+	/// Only admins (or root), not themselves authenticated with an api key,
+	/// otherwise an `AccessDenied` of the `entity`.
+	pub async fn check_admin(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		entity: &'static str,
+	) -> Result<()> {
+		let denied = Error::AccessDenied {
+			entity,
+			id: ctx.user_id(),
+		};
+
+		if ctx.key_scope().is_some() {
+			return Err(denied);
+		}
+		if ctx.user_id() == 0 {
+			return Ok(());
+		}
+
+		let user: UserForAuth = Self::get(ctx, mm, ctx.user_id()).await?;
+		if user.assigned_role != ADMIN_ROLE {
+			return Err(denied);
+		}
+
+		Ok(())
+	}
+
 	pub async fn first_by_username<E>(
 		_ctx: &Ctx,
 		mm: &ModelManager,
@@ -155,36 +213,103 @@ impl UserBmc {
 		Ok(entity)
 	}
 
+	pub async fn first_by_email<E>(
+		_ctx: &Ctx,
+		mm: &ModelManager,
+		email: &str,
+	) -> Result<Option<E>>
+	where
+		E: UserBy,
+	{
+		let db = mm.db();
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.columns(E::field_idens())
+			.and_where(Expr::col(UserIden::Email).eq(email));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let entity = sqlx::query_as_with::<_, E, _>(&sql, values)
+			.fetch_optional(db)
+			.await?;
+
+		Ok(entity)
+	}
+
+	/// Sets a new pwd, which must comply with the pwd policy and not be one of
+	/// the last `history_size` pwds of the user (otherwise a
+	/// `PwdPolicyViolated` of the failed rules). Also renews its expiry.
 	pub async fn update_pwd(
 		ctx: &Ctx,
 		mm: &ModelManager,
 		id: i64,
 		pwd_clear: &str,
 	) -> Result<()> {
-		let db = mm.db();
+		Self::change_pwd(ctx, mm, id, pwd_clear).await?;
+
+		// By an admin, or the user itself.
+		let detail = (ctx.user_id() != id).then(|| json!({ "by": ctx.user_id() }));
+		LoginHistoryBmc::record_for_user(
+			ctx,
+			mm,
+			id,
+			LoginHistoryKind::PwdChange,
+			detail,
+		)
+		.await?;
 
+		Ok(())
+	}
+
+	/// Only the pwd policy (e.g., before a pwd reset consumes its token).
+	pub async fn validate_new_pwd(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+		pwd_clear: &str,
+	) -> Result<()> {
 		let user: UserForLogin = Self::get(ctx, mm, id).await?;
-		let pwd = hash_pwd(ContentToHash {
-			content: pwd_clear.to_string(),
-			salt: user.pwd_salt,
-		})
-		.await?;
 
-		let mut fields = Fields::new(vec![Field::new(UserIden::Pwd, pwd.into())]);
+		Self::check_pwd_policy(mm, &PwdPolicy::from_config(), &user, pwd_clear).await
+	}
+
+	/// Re-hashes the pwd without the pwd policy, for the pwd scheme upgrades
+	/// (and the dev seed), its expiry unchanged.
+	pub async fn set_pwd(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+		pwd_clear: &str,
+	) -> Result<()> {
+		let user: UserForLogin = Self::get(ctx, mm, id).await?;
+		Self::write_pwd(ctx, mm, &user, pwd_clear, false).await?;
+
+		Ok(())
+	}
+
+	/// A new `token_salt` invalidates all the tokens of the user.
+	pub async fn rotate_token_salt(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+	) -> Result<()> {
+		let db = mm.db();
+
+		let mut fields = Fields::new(vec![Field::new(
+			UserIden::TokenSalt,
+			Uuid::new_v4().into(),
+		)]);
 		add_timestamps_for_update(&mut fields, ctx.user_id());
 
-		let fields = fields.for_sea_update();
 		let mut query = Query::update();
 		query
 			.table(Self::table_ref())
-			.values(fields)
+			.values(fields.for_sea_update())
 			.and_where(Expr::col(UserIden::Id).eq(id));
 
 		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-		let _count = sqlx::query_with(&sql, values)
-			.execute(db)
-			.await?
-			.rows_affected();
+		sqlx::query_with(&sql, values).execute(db).await?;
 
 		Ok(())
 	}
@@ -194,24 +319,64 @@ impl UserBmc {
 		mm: &ModelManager,
 		user_c: UserForCreate,
 	) -> Result<i64> {
+		// Before the insert, so that no user is left without a pwd.
+		let failed_rules = PwdPolicy::from_config().check(&user_c.pwd_clear);
+		if !failed_rules.is_empty() {
+			return Err(Error::PwdPolicyViolated { failed_rules });
+		}
+
 		let data: UserForInsert = UserForInsert {
 			username: user_c.username,
 			email: user_c.email,
 			assigned_role: user_c.assigned_role,
+			is_service: false,
 		};
 
 		let user_id = base::create::<Self, _>(ctx, mm, data).await?;
 
-		Self::update_pwd(ctx, mm, user_id, &user_c.pwd_clear).await?;
+		Self::change_pwd(ctx, mm, user_id, &user_c.pwd_clear).await?;
 
 		Ok(user_id)
 	}
 
+	/// Creates a user without pwd, which can only use api keys.
+	pub async fn create_service_account(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		service_account_c: ServiceAccountForCreate,
+	) -> Result<i64> {
+		let data = UserForInsert {
+			username: service_account_c.username,
+			email: service_account_c.email,
+			assigned_role: service_account_c.assigned_role,
+			is_service: true,
+		};
+
+		base::create::<Self, _>(ctx, mm, data).await
+	}
+
+	/// Creates a user without pwd, which logs in with its IdP (or once it set
+	/// a pwd with a pwd reset).
+	pub async fn create_external(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		external_user_c: ExternalUserForCreate,
+	) -> Result<i64> {
+		let data = UserForInsert {
+			username: external_user_c.username,
+			email: external_user_c.email,
+			assigned_role: external_user_c.assigned_role,
+			is_service: false,
+		};
+
+		base::create::<Self, _>(ctx, mm, data).await
+	}
+
 	pub async fn list(
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<UserFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<User>> {
 		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
 	}
@@ -229,3 +394,214 @@ impl UserBmc {
 		base::delete::<Self>(ctx, mm, id).await
 	}
 }
+
+// region:    --- Privates
+
+impl UserBmc {
+	/// `update_pwd` without the login history (e.g., the first pwd).
+	async fn change_pwd(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+		pwd_clear: &str,
+	) -> Result<()> {
+		let policy = PwdPolicy::from_config();
+
+		let user: UserForLogin = Self::get(ctx, mm, id).await?;
+		Self::check_pwd_policy(mm, &policy, &user, pwd_clear).await?;
+
+		let pwd = Self::write_pwd(ctx, mm, &user, pwd_clear, true).await?;
+		Self::push_pwd_history(mm, &policy, id, pwd).await?;
+
+		Ok(())
+	}
+
+	async fn check_pwd_policy(
+		mm: &ModelManager,
+		policy: &PwdPolicy,
+		user: &UserForLogin,
+		pwd_clear: &str,
+	) -> Result<()> {
+		let mut failed_rules = policy.check(pwd_clear);
+
+		if policy.history_size > 0 {
+			let previous_pwds =
+				Self::last_pwds(mm, user.id, policy.history_size).await?;
+			for previous_pwd in previous_pwds {
+				let to_hash = ContentToHash {
+					content: pwd_clear.to_string(),
+					salt: user.pwd_salt,
+				};
+				if validate_pwd(to_hash, previous_pwd).await.is_ok() {
+					failed_rules.push(PwdRule::NotReused {
+						last: policy.history_size,
+					});
+					break;
+				}
+			}
+		}
+
+		if !failed_rules.is_empty() {
+			return Err(Error::PwdPolicyViolated { failed_rules });
+		}
+
+		Ok(())
+	}
+
+	/// Writes the hashed pwd (returned), `renewed` for a new pwd (restarting
+	/// its expiry).
+	async fn write_pwd(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		user: &UserForLogin,
+		pwd_clear: &str,
+		renewed: bool,
+	) -> Result<String> {
+		let db = mm.db();
+
+		let pwd = hash_pwd(ContentToHash {
+			content: pwd_clear.to_string(),
+			salt: user.pwd_salt,
+		})
+		.await?;
+
+		let mut fields =
+			Fields::new(vec![Field::new(UserIden::Pwd, pwd.clone().into())]);
+		if renewed {
+			fields.push(Field::new(UserIden::PwdMtime, now_utc().into()));
+		}
+		add_timestamps_for_update(&mut fields, ctx.user_id());
+
+		let fields = fields.for_sea_update();
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.values(fields)
+			.and_where(Expr::col(UserIden::Id).eq(user.id));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(pwd)
+	}
+
+	/// The last (hashed) pwds of the user, newest first.
+	async fn last_pwds(
+		mm: &ModelManager,
+		user_id: i64,
+		count: usize,
+	) -> Result<Vec<String>> {
+		let db = mm.db();
+
+		let mut query = Query::select();
+		query
+			.from(PwdHistoryIden::Table)
+			.column(PwdHistoryIden::Pwd)
+			.and_where(Expr::col(PwdHistoryIden::UserId).eq(user_id))
+			.order_by(PwdHistoryIden::Id, Order::Desc)
+			.limit(count as u64);
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let pwds = sqlx::query_as_with::<_, (String,), _>(&sql, values)
+			.fetch_all(db)
+			.await?
+			.into_iter()
+			.map(|(pwd,)| pwd)
+			.collect();
+
+		Ok(pwds)
+	}
+
+	/// Records the new pwd, only keeping the last `history_size` ones.
+	async fn push_pwd_history(
+		mm: &ModelManager,
+		policy: &PwdPolicy,
+		user_id: i64,
+		pwd: String,
+	) -> Result<()> {
+		let db = mm.db();
+
+		if policy.history_size == 0 {
+			return Ok(());
+		}
+
+		let mut query = Query::insert();
+		query
+			.into_table(PwdHistoryIden::Table)
+			.columns([PwdHistoryIden::UserId, PwdHistoryIden::Pwd])
+			.values([user_id.into(), pwd.into()])?;
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		let mut kept = Query::select();
+		kept.from(PwdHistoryIden::Table)
+			.column(PwdHistoryIden::Id)
+			.and_where(Expr::col(PwdHistoryIden::UserId).eq(user_id))
+			.order_by(PwdHistoryIden::Id, Order::Desc)
+			.limit(policy.history_size as u64);
+
+		let mut query = Query::delete();
+		query
+			.from_table(PwdHistoryIden::Table)
+			.and_where(Expr::col(PwdHistoryIden::UserId).eq(user_id))
+			.and_where(Expr::col(PwdHistoryIden::Id).not_in_subquery(kept));
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use anyhow::Result;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_update_pwd_err_reused() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_pwd = "test_update_pwd 01";
+		let fx_user_id = UserBmc::create(
+			&ctx,
+			&mm,
+			UserForCreate {
+				username: "test_update_pwd_err_reused".to_string(),
+				pwd_clear: fx_pwd.to_string(),
+				email: "test_update_pwd@example.com".to_string(),
+				assigned_role: "demo".to_string(),
+			},
+		)
+		.await?;
+		UserBmc::update_pwd(&ctx, &mm, fx_user_id, "test_update_pwd 02").await?;
+
+		// -- Exec
+		let res = UserBmc::update_pwd(&ctx, &mm, fx_user_id, fx_pwd).await;
+
+		// -- Check
+		match res {
+			Err(Error::PwdPolicyViolated { failed_rules }) => assert!(
+				matches!(failed_rules.as_slice(), [PwdRule::NotReused { .. }]),
+				"{failed_rules:?}"
+			),
+			other => {
+				panic!("Should have been a `PwdPolicyViolated` but was {other:?}")
+			}
+		}
+
+		// -- Clean
+		UserBmc::delete(&ctx, &mm, fx_user_id).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,69 @@
+use crate::core::model::base::DbBmc;
+use crate::core::model::ModelManager;
+use crate::core::model::Result;
+use sea_query::{Expr, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+
+use super::idens::UserIdentityIden;
+
+/// The IdP accounts of the users, by OpenID Connect issuer and subject.
+pub struct UserIdentityBmc;
+
+impl DbBmc for UserIdentityBmc {
+	const TABLE: &'static str = "user_identity";
+	const TIMESTAMPED: bool = false;
+	const SOFTDELETED: bool = false;
+}
+
+impl UserIdentityBmc {
+	/// The user of the IdP account, if linked.
+	pub async fn first_user_id(
+		mm: &ModelManager,
+		issuer: &str,
+		subject: &str,
+	) -> Result<Option<i64>> {
+		let db = mm.db();
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.column(UserIdentityIden::UserId)
+			.and_where(Expr::col(UserIdentityIden::Issuer).eq(issuer))
+			.and_where(Expr::col(UserIdentityIden::Subject).eq(subject));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let user_id = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+			.fetch_optional(db)
+			.await?
+			.map(|(user_id,)| user_id);
+
+		Ok(user_id)
+	}
+
+	pub async fn link(
+		mm: &ModelManager,
+		user_id: i64,
+		issuer: &str,
+		subject: &str,
+	) -> Result<i64> {
+		let db = mm.db();
+
+		let mut query = Query::insert();
+		query
+			.into_table(Self::table_ref())
+			.columns([
+				UserIdentityIden::UserId,
+				UserIdentityIden::Issuer,
+				UserIdentityIden::Subject,
+			])
+			.values([user_id.into(), issuer.into(), subject.into()])?
+			.returning(Query::returning().columns([UserIdentityIden::Id]));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let (id,) = sqlx::query_as_with::<_, (i64,), _>(&sql, values)
+			.fetch_one(db)
+			.await?;
+
+		Ok(id)
+	}
+}
@@ -0,0 +1,329 @@
+use crate::auth::totp;
+use crate::core::ctx::Ctx;
+use crate::core::model::base::DbBmc;
+use crate::core::model::ModelManager;
+use crate::core::model::{Error, Result};
+use crate::utils::time::now_utc;
+use sea_query::{Expr, OnConflict, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::Serialize;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::FromRow;
+
+use super::idens::UserTotpIden;
+use super::login_history::{LoginHistoryBmc, LoginHistoryKind};
+use super::user::{User, UserBmc};
+
+/// To add to an authenticator app, with the QR code of the
+/// `provisioning_uri` or by typing the `secret`.
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollment {
+	pub secret: String,
+	pub provisioning_uri: String,
+}
+
+/// Only returned once, when the TOTP is enabled.
+#[derive(Debug, Serialize)]
+pub struct TotpRecoveryCodes {
+	pub recovery_codes: Vec<String>,
+}
+
+#[derive(Clone, FromRow, Debug)]
+struct UserTotp {
+	secret_enc: String,
+	last_used_step: Option<i64>,
+	enabled_at: Option<OffsetDateTime>,
+}
+
+pub struct UserTotpBmc;
+
+impl DbBmc for UserTotpBmc {
+	const TABLE: &'static str = "user_totp";
+	const TIMESTAMPED: bool = false;
+	const SOFTDELETED: bool = false;
+}
+
+impl UserTotpBmc {
+	/// A new (not yet enabled) secret of the ctx user, replacing any pending
+	/// enrollment.
+	pub async fn begin_enrollment(
+		ctx: &Ctx,
+		mm: &ModelManager,
+	) -> Result<TotpEnrollment> {
+		let db = mm.db();
+		let user_id = ctx.user_id();
+
+		let user: User = UserBmc::get(ctx, mm, user_id).await?;
+		// Api keys (and their service accounts) do not log in.
+		if ctx.key_scope().is_some() || user.is_service {
+			return Err(Error::AccessDenied {
+				entity: Self::TABLE,
+				id: user_id,
+			});
+		}
+		if Self::is_enabled(mm, user_id).await? {
+			return Err(Error::TotpAlreadyEnabled { user_id });
+		}
+
+		let secret = totp::generate_secret();
+
+		let mut query = Query::insert();
+		query
+			.into_table(Self::table_ref())
+			.columns([UserTotpIden::UserId, UserTotpIden::SecretEnc])
+			.values([user_id.into(), totp::encrypt_secret(&secret)?.into()])?
+			.on_conflict(
+				OnConflict::column(UserTotpIden::UserId)
+					.update_column(UserTotpIden::SecretEnc)
+					.to_owned(),
+			);
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		Ok(TotpEnrollment {
+			secret: totp::secret_to_base32(&secret),
+			provisioning_uri: totp::provisioning_uri(&user.username, &secret),
+		})
+	}
+
+	/// Enables the pending enrollment with a first code of the authenticator
+	/// app, returning the recovery codes.
+	pub async fn confirm_enrollment(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		code: &str,
+	) -> Result<TotpRecoveryCodes> {
+		let db = mm.db();
+		let user_id = ctx.user_id();
+
+		let user_totp = Self::first(mm, user_id)
+			.await?
+			.filter(|user_totp| user_totp.enabled_at.is_none())
+			.ok_or(Error::TotpNotEnrolled { user_id })?;
+
+		let secret = totp::decrypt_secret(&user_totp.secret_enc)?;
+		let step = totp::verify_code(&secret, code, now_utc(), None)
+			.ok_or(Error::TotpCodeNotValid { user_id })?;
+
+		let recovery_codes = totp::generate_recovery_codes();
+		let recovery_code_hashes = recovery_codes
+			.iter()
+			.map(|code| totp::hash_recovery_code(code))
+			.collect::<core::result::Result<Vec<_>, _>>()?;
+
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.value(UserTotpIden::EnabledAt, now_utc())
+			.value(UserTotpIden::LastUsedStep, step)
+			.value(UserTotpIden::RecoveryCodes, recovery_code_hashes)
+			.and_where(Expr::col(UserTotpIden::UserId).eq(user_id));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		LoginHistoryBmc::record_for_user(
+			ctx,
+			mm,
+			user_id,
+			LoginHistoryKind::TotpEnable,
+			None,
+		)
+		.await?;
+
+		Ok(TotpRecoveryCodes { recovery_codes })
+	}
+
+	/// Checks a TOTP code (once per time step) or a recovery code (once) of
+	/// the user, a `TotpCodeNotValid` otherwise.
+	pub async fn verify(mm: &ModelManager, user_id: i64, code: &str) -> Result<()> {
+		let user_totp = Self::first(mm, user_id)
+			.await?
+			.filter(|user_totp| user_totp.enabled_at.is_some())
+			.ok_or(Error::TotpNotEnrolled { user_id })?;
+
+		let secret = totp::decrypt_secret(&user_totp.secret_enc)?;
+		let verified = match totp::verify_code(
+			&secret,
+			code,
+			now_utc(),
+			user_totp.last_used_step,
+		) {
+			Some(step) => Self::use_step(mm, user_id, step).await?,
+			None => Self::use_recovery_code(mm, user_id, code).await?,
+		};
+
+		if !verified {
+			return Err(Error::TotpCodeNotValid { user_id });
+		}
+
+		Ok(())
+	}
+
+	/// Turns the TOTP of the ctx user off, with one of its codes.
+	pub async fn disable(ctx: &Ctx, mm: &ModelManager, code: &str) -> Result<()> {
+		let db = mm.db();
+		let user_id = ctx.user_id();
+
+		Self::verify(mm, user_id, code).await?;
+
+		let mut query = Query::delete();
+		query
+			.from_table(Self::table_ref())
+			.and_where(Expr::col(UserTotpIden::UserId).eq(user_id));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		sqlx::query_with(&sql, values).execute(db).await?;
+
+		LoginHistoryBmc::record_for_user(
+			ctx,
+			mm,
+			user_id,
+			LoginHistoryKind::TotpDisable,
+			None,
+		)
+		.await?;
+
+		Ok(())
+	}
+
+	pub async fn is_enabled(mm: &ModelManager, user_id: i64) -> Result<bool> {
+		let user_totp = Self::first(mm, user_id).await?;
+
+		Ok(user_totp.is_some_and(|user_totp| user_totp.enabled_at.is_some()))
+	}
+}
+
+// region:    --- Privates
+
+impl UserTotpBmc {
+	async fn first(mm: &ModelManager, user_id: i64) -> Result<Option<UserTotp>> {
+		let db = mm.db();
+
+		let mut query = Query::select();
+		query
+			.from(Self::table_ref())
+			.columns([
+				UserTotpIden::SecretEnc,
+				UserTotpIden::LastUsedStep,
+				UserTotpIden::EnabledAt,
+			])
+			.and_where(Expr::col(UserTotpIden::UserId).eq(user_id));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let user_totp = sqlx::query_as_with::<_, UserTotp, _>(&sql, values)
+			.fetch_optional(db)
+			.await?;
+
+		Ok(user_totp)
+	}
+
+	/// Records the time step of a code, false when it was (concurrently) used.
+	async fn use_step(mm: &ModelManager, user_id: i64, step: i64) -> Result<bool> {
+		let db = mm.db();
+
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.value(UserTotpIden::LastUsedStep, step)
+			.and_where(Expr::col(UserTotpIden::UserId).eq(user_id))
+			.and_where(
+				Expr::col(UserTotpIden::LastUsedStep)
+					.is_null()
+					.or(Expr::col(UserTotpIden::LastUsedStep).lt(step)),
+			);
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let count = sqlx::query_with(&sql, values)
+			.execute(db)
+			.await?
+			.rows_affected();
+
+		Ok(count == 1)
+	}
+
+	/// Removes the recovery code, false when it is not one of the user's.
+	async fn use_recovery_code(
+		mm: &ModelManager,
+		user_id: i64,
+		code: &str,
+	) -> Result<bool> {
+		let db = mm.db();
+
+		let code_hash = totp::hash_recovery_code(code)?;
+
+		let mut query = Query::update();
+		query
+			.table(Self::table_ref())
+			.value(
+				UserTotpIden::RecoveryCodes,
+				Expr::cust_with_values(
+					"array_remove(recovery_codes, $1)",
+					[code_hash.clone()],
+				),
+			)
+			.and_where(Expr::col(UserTotpIden::UserId).eq(user_id))
+			.and_where(Expr::cust_with_values(
+				"$1 = ANY(recovery_codes)",
+				[code_hash],
+			));
+
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let count = sqlx::query_with(&sql, values)
+			.execute(db)
+			.await?
+			.rows_affected();
+
+		Ok(count == 1)
+	}
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::_dev_utils;
+	use anyhow::Result;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_recovery_code_single_use_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let demo1: User = UserBmc::first_by_username(&Ctx::root_ctx(), &mm, "demo1")
+			.await?
+			.unwrap();
+		let ctx = Ctx::new(demo1.id)?;
+		UserTotpBmc::begin_enrollment(&ctx, &mm).await?;
+		// The enrollment secret is only returned to the client, read it back.
+		let user_totp = UserTotpBmc::first(&mm, demo1.id).await?.unwrap();
+		let secret = totp::decrypt_secret(&user_totp.secret_enc)?;
+		let code = totp::generate_code(&secret, now_utc());
+		let fx_recovery_codes =
+			UserTotpBmc::confirm_enrollment(&ctx, &mm, &code).await?;
+		let fx_recovery_code = &fx_recovery_codes.recovery_codes[0];
+
+		// -- Exec
+		UserTotpBmc::verify(&mm, demo1.id, fx_recovery_code).await?;
+		let res = UserTotpBmc::verify(&mm, demo1.id, fx_recovery_code).await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::TotpCodeNotValid { .. })),
+			"Should have matched `Err(Error::TotpCodeNotValid)` but was `{res:?}`"
+		);
+
+		// -- Clean
+		UserTotpBmc::disable(&ctx, &mm, &fx_recovery_codes.recovery_codes[1])
+			.await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -5,16 +5,17 @@ use crate::core::model::ModelManager;
 use crate::core::model::Result;
 use crate::utils::time::Rfc3339;
 use modql::field::{Fields, HasFields};
-use modql::filter::{
-	FilterNodes, ListOptions, OpValsInt64, OpValsString, OpValsValue,
-};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString, OpValsValue};
+use sea_query::{Expr, SimpleExpr};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use sqlx::postgres::PgRow;
 use sqlx::types::time::OffsetDateTime;
 use sqlx::FromRow;
 
-use super::base::ListResult;
+use super::base::{CursorListOptions, ListResult};
+use super::idens::ValueIden;
+use super::structure_privilege::StructurePrivilegeBmc;
 
 #[serde_as]
 #[derive(Clone, Fields, FromRow, Debug, Serialize)]
@@ -92,6 +93,13 @@ impl DbBmc for ValueBmc {
 	const TABLE: &'static str = "value";
 	const TIMESTAMPED: bool = true;
 	const SOFTDELETED: bool = false;
+
+	/// The values of the accessible structures.
+	fn access_cond(ctx: &Ctx) -> Option<SimpleExpr> {
+		Some(Expr::col(ValueIden::ProjectId).in_subquery(
+			StructurePrivilegeBmc::accessible_project_ids(ctx, ctx.user_id()),
+		))
+	}
 }
 
 impl ValueBmc {
@@ -121,7 +129,7 @@ impl ValueBmc {
 		ctx: &Ctx,
 		mm: &ModelManager,
 		filters: Option<Vec<ValueFilter>>,
-		list_options: Option<ListOptions>,
+		list_options: Option<CursorListOptions>,
 	) -> Result<ListResult<Value>> {
 		base::list::<Self, _, _>(ctx, mm, filters, list_options).await
 	}
@@ -1,4 +1,5 @@
 use crate::core::model;
+use crate::mail;
 use crate::rpc;
 use derive_more::From;
 
@@ -12,6 +13,9 @@ pub enum Error {
 
 	#[from]
 	Rpc(rpc::Error),
+
+	#[from]
+	Mail(mail::Error),
 }
 
 // region:    --- Error Boilerplate
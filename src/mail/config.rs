@@ -0,0 +1,77 @@
+use crate::utils::envs::{get_env, get_env_parse};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// `None` without a `SERVICE_SMTP_HOST` (the emails then stay queued).
+pub fn mail_config() -> Option<&'static MailConfig> {
+	static INSTANCE: OnceLock<Option<MailConfig>> = OnceLock::new();
+
+	INSTANCE
+		.get_or_init(|| {
+			MailConfig::load_from_env().unwrap_or_else(|ex| {
+				panic!("FATAL - WHILE LOADING CONF - Cause: {ex:?}")
+			})
+		})
+		.as_ref()
+}
+
+#[allow(non_snake_case)]
+pub struct MailConfig {
+	// -- Smtp
+	pub SMTP_HOST: String,
+	pub SMTP_PORT: u16,
+	pub SMTP_TLS: SmtpTls,
+	/// No authentication without a username (e.g., a local test smtp server).
+	pub SMTP_USERNAME: Option<String>,
+	pub SMTP_PWD: Option<String>,
+
+	// -- Outbox
+	/// The `From` of the emails, e.g., `Gestor Documental <no-reply@host>`.
+	pub MAIL_FROM: String,
+	pub MAIL_SEND_INTERVAL_SEC: f64,
+	/// Failed sends of an email before it is given up.
+	pub MAIL_MAX_ATTEMPTS: i32,
+}
+
+/// `none` (plain smtp, for the local test servers), `starttls` or `tls`.
+#[derive(Clone, Copy, Debug)]
+pub enum SmtpTls {
+	None,
+	StartTls,
+	Tls,
+}
+
+impl FromStr for SmtpTls {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"none" => Ok(Self::None),
+			"starttls" => Ok(Self::StartTls),
+			"tls" => Ok(Self::Tls),
+			_ => Err(()),
+		}
+	}
+}
+
+impl MailConfig {
+	fn load_from_env() -> crate::utils::envs::Result<Option<MailConfig>> {
+		let Ok(smtp_host) = get_env("SERVICE_SMTP_HOST") else {
+			return Ok(None);
+		};
+
+		Ok(Some(MailConfig {
+			// -- Smtp
+			SMTP_HOST: smtp_host,
+			SMTP_PORT: get_env_parse("SERVICE_SMTP_PORT")?,
+			SMTP_TLS: get_env_parse("SERVICE_SMTP_TLS")?,
+			SMTP_USERNAME: get_env("SERVICE_SMTP_USERNAME").ok(),
+			SMTP_PWD: get_env("SERVICE_SMTP_PWD").ok(),
+
+			// -- Outbox
+			MAIL_FROM: get_env("SERVICE_MAIL_FROM")?,
+			MAIL_SEND_INTERVAL_SEC: get_env_parse("SERVICE_MAIL_SEND_INTERVAL_SEC")?,
+			MAIL_MAX_ATTEMPTS: get_env_parse("SERVICE_MAIL_MAX_ATTEMPTS")?,
+		}))
+	}
+}
@@ -0,0 +1,35 @@
+use crate::core::model;
+use derive_more::From;
+use serde::Serialize;
+use serde_with::{serde_as, DisplayFromStr};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[serde_as]
+#[derive(Debug, Serialize, From)]
+pub enum Error {
+	// -- Modules
+	#[from]
+	Model(model::Error),
+
+	// -- Externals
+	#[from]
+	Address(#[serde_as(as = "DisplayFromStr")] lettre::address::AddressError),
+	#[from]
+	Message(#[serde_as(as = "DisplayFromStr")] lettre::error::Error),
+	#[from]
+	Smtp(#[serde_as(as = "DisplayFromStr")] lettre::transport::smtp::Error),
+}
+
+// region:    --- Error Boilerplate
+impl core::fmt::Display for Error {
+	fn fmt(
+		&self,
+		fmt: &mut core::fmt::Formatter,
+	) -> core::result::Result<(), core::fmt::Error> {
+		write!(fmt, "{self:?}")
+	}
+}
+
+impl std::error::Error for Error {}
+// endregion: --- Error Boilerplate
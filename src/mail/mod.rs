@@ -0,0 +1,132 @@
+//! The smtp delivery of the `email_outbox`: the emails are queued with the
+//! `EmailOutboxBmc` and sent by a background task, retried until the
+//! `MAIL_MAX_ATTEMPTS`.
+
+// region:    --- Modules
+
+mod config;
+mod error;
+
+pub use self::error::{Error, Result};
+
+use crate::core::model::email_outbox::{Email, EmailOutboxBmc};
+use crate::core::model::ModelManager;
+use config::{mail_config, MailConfig, SmtpTls};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::mem;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+// endregion: --- Modules
+
+/// Emails sent per pass of the outbox.
+const SEND_BATCH_SIZE: u64 = 20;
+
+pub struct SmtpSender {
+	transport: AsyncSmtpTransport<Tokio1Executor>,
+	from: Mailbox,
+	max_attempts: i32,
+}
+
+impl SmtpSender {
+	pub fn new(config: &MailConfig) -> Result<Self> {
+		let host = config.SMTP_HOST.as_str();
+		let mut builder = match config.SMTP_TLS {
+			SmtpTls::None => {
+				AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+			}
+			SmtpTls::StartTls => {
+				AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+			}
+			SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+		}
+		.port(config.SMTP_PORT);
+
+		if let Some(username) = &config.SMTP_USERNAME {
+			builder = builder.credentials(Credentials::new(
+				username.to_string(),
+				config.SMTP_PWD.clone().unwrap_or_default(),
+			));
+		}
+
+		Ok(SmtpSender {
+			transport: builder.build(),
+			from: config.MAIL_FROM.parse()?,
+			max_attempts: config.MAIL_MAX_ATTEMPTS,
+		})
+	}
+
+	pub async fn send(&self, email: &Email) -> Result<()> {
+		let message = Message::builder()
+			.from(self.from.clone())
+			.to(email.recipient.parse()?)
+			.subject(&email.subject)
+			.header(ContentType::TEXT_PLAIN)
+			.body(email.body.clone())?;
+
+		self.transport.send(message).await?;
+
+		Ok(())
+	}
+}
+
+/// Sends the pending emails of the outbox, returning how many were sent.
+/// The batch stays claimed until its sends are recorded, so that the other
+/// instances never send the same emails.
+pub async fn send_pending(mm: &ModelManager, sender: &SmtpSender) -> Result<usize> {
+	let mut batch =
+		EmailOutboxBmc::claim_pending(mm, sender.max_attempts, SEND_BATCH_SIZE)
+			.await?;
+
+	let mut sent = 0;
+	for email in mem::take(&mut batch.emails) {
+		match sender.send(&email).await {
+			Ok(()) => {
+				batch.mark_sent(email.id).await?;
+				sent += 1;
+			}
+			Err(err) => {
+				warn!(
+					"{:<12} - send_pending - email {} - {err:?}",
+					"MAIL", email.id
+				);
+				batch.mark_failed(email.id, &err.to_string()).await?;
+			}
+		}
+	}
+
+	batch.commit().await?;
+
+	Ok(sent)
+}
+
+/// Starts the background sending of the outbox, every
+/// `MAIL_SEND_INTERVAL_SEC`. Not without an smtp configured.
+pub fn spawn_outbox_sender(mm: ModelManager) -> Result<()> {
+	let Some(config) = mail_config() else {
+		warn!(
+			"{:<12} - no smtp configured, the emails stay queued",
+			"MAIL"
+		);
+		return Ok(());
+	};
+	let sender = SmtpSender::new(config)?;
+	let interval = Duration::from_secs_f64(config.MAIL_SEND_INTERVAL_SEC);
+
+	tokio::spawn(async move {
+		let mut ticker = tokio::time::interval(interval);
+		loop {
+			ticker.tick().await;
+			match send_pending(&mm, &sender).await {
+				Ok(0) => (),
+				Ok(sent) => info!("{:<12} - sent {sent} emails", "MAIL"),
+				Err(err) => error!("{:<12} - spawn_outbox_sender - {err:?}", "MAIL"),
+			}
+		}
+	});
+
+	Ok(())
+}
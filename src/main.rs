@@ -5,6 +5,7 @@ mod config;
 mod core;
 mod error;
 mod log;
+mod mail;
 mod rpc;
 mod utils;
 mod web;
@@ -12,15 +13,23 @@ mod web;
 
 pub use crate::error::{Error, Result};
 use crate::web::mw_auth::{mw_ctx_require, mw_ctx_resolve};
+use crate::web::mw_csrf::{mw_csrf_require, X_CSRF_TOKEN};
 use crate::web::mw_res_map::mw_reponse_map;
-use crate::web::{routes_login, routes_rpc, routes_static};
-use axum::http::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, COOKIE, SET_COOKIE};
-use axum::http::{HeaderValue, Method};
+use crate::web::{
+	routes_export, routes_login, routes_oidc, routes_pwd_reset, routes_rpc,
+	routes_static,
+};
+use axum::http::header::{
+	ACCEPT, AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE, COOKIE, SET_COOKIE,
+};
+use axum::http::{HeaderName, HeaderValue, Method};
 use axum::response::Html;
 use axum::routing::get;
 use axum::{middleware, Router};
 pub use config::web_config;
+use core::model::token_key::{spawn_token_key_rotation, TokenKeyBmc};
 use core::model::ModelManager;
+use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use tower_cookies::CookieManagerLayer;
 use tower_http::cors::CorsLayer;
@@ -57,33 +66,57 @@ async fn main() -> Result<()> {
 		.allow_origin(origins)
 		.allow_credentials(true)
 		.allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-		.allow_headers([CONTENT_TYPE, AUTHORIZATION, ACCEPT, COOKIE, SET_COOKIE]);
+		.allow_headers([
+			CONTENT_TYPE,
+			AUTHORIZATION,
+			ACCEPT,
+			COOKIE,
+			SET_COOKIE,
+			HeaderName::from_static(X_CSRF_TOKEN),
+		])
+		.expose_headers([CONTENT_DISPOSITION]);
 	// Initialize ModelManager.
 	let mm = ModelManager::new().await?;
 
+	// Deliver the queued emails (e.g., the pwd resets), if an smtp is set.
+	mail::spawn_outbox_sender(mm.clone())?;
+
+	// Sign the tokens with the key ring, rolled on schedule.
+	TokenKeyBmc::load_ring(&mm).await?;
+	spawn_token_key_rotation(mm.clone());
+
 	// -- Define Routes
 	let route_healthcheck =
 		Router::new().route("/healthcheck", get(|| async { Html("I'm alive") }));
 
 	let routes_rpc = routes_rpc::routes(mm.clone())
+		.merge(routes_export::routes(mm.clone()))
+		.route_layer(middleware::from_fn(mw_csrf_require))
 		.route_layer(middleware::from_fn(mw_ctx_require));
 
 	let routes_all = Router::new()
 		.merge(routes_login::routes(mm.clone()))
+		.merge(routes_oidc::routes(mm.clone()))
+		.merge(routes_pwd_reset::routes(mm.clone()))
 		.nest("/api", routes_rpc)
 		.layer(middleware::map_response(mw_reponse_map))
 		.layer(cors.clone())
 		.layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolve))
 		.layer(CookieManagerLayer::new())
 		.fallback_service(routes_static::serve_dir())
-		.merge(route_healthcheck);
+		.merge(route_healthcheck)
+		.layer(web_config().CLIENT_IP_SOURCE.clone().into_extension());
 
 	// region:    --- Start Server
 	let listener = TcpListener::bind("0.0.0.0:8080").await.unwrap();
 	info!("{:<12} - {:?}\n", "LISTENING", listener.local_addr());
-	axum::serve(listener, routes_all.into_make_service())
-		.await
-		.unwrap();
+	// The connect info is the client ip, unless behind a trusted proxy.
+	axum::serve(
+		listener,
+		routes_all.into_make_service_with_connect_info::<SocketAddr>(),
+	)
+	.await
+	.unwrap();
 	// endregion: --- Start Server
 
 	Ok(())
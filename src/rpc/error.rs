@@ -37,8 +37,14 @@ pub enum Error {
 	#[from]
 	SerdeJson(#[serde_as(as = "DisplayFromStr")] serde_json::Error),
 
-	#[from]
-	S3GetObjectError(#[serde_as(as = "DisplayFromStr")] SdkError<GetObjectError>),
+	S3GetObject(#[serde_as(as = "DisplayFromStr")] Box<SdkError<GetObjectError>>),
+}
+
+// Boxed, as the sdk error is much larger than the others.
+impl From<SdkError<GetObjectError>> for Error {
+	fn from(val: SdkError<GetObjectError>) -> Self {
+		Self::S3GetObject(Box::new(val))
+	}
 }
 
 // region:    --- Error Boilerplate
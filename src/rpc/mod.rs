@@ -7,12 +7,15 @@ mod utils;
 pub use self::error::{Error, Result};
 
 use self::rpcs::{
-	archive_comment_rpc::*, archive_rpc::*, associated_privilege_rpc::*,
-	datatype_rpc::*, document_comment_rpc::*, document_rpc::*, event_rpc::*,
-	index_rpc::*, privilege_rpc::*, role_rpc::*, search_operations_rpc::*,
-	separator_rpc::*, structure_privilege::*, structure_rpc::*, user_rpc::*,
-	value_rpc::*,
+	api_key_rpc::*, archive_comment_rpc::*, archive_rpc::*,
+	associated_privilege_rpc::*, datatype_rpc::*, document_comment_rpc::*,
+	document_rpc::*, event_rpc::*, index_rpc::*, ip_allowlist_rpc::*,
+	login_history_rpc::*, privilege_rpc::*, role_rpc::*, saved_search_rpc::*,
+	search_operations_rpc::*, separator_rpc::*, session_rpc::*,
+	structure_privilege::*, structure_rpc::*, token_key_rpc::*, totp_rpc::*,
+	user_rpc::*, value_rpc::*,
 };
+use crate::core::model::export::Export;
 use crate::core::{ctx::Ctx, model::ModelManager};
 use axum::body::Bytes;
 use serde::{Deserialize, Serialize};
@@ -115,6 +118,23 @@ macro_rules! exec_rpc_fn {
 	};
 }
 
+/// Like `exec_rpc_fn!`, for the rpc functions returning an `Export`.
+macro_rules! exec_export_fn {
+	($export_fn:expr, $ctx:expr, $mm:expr, $rpc_params: expr) => {{
+		let rpc_fn_name = stringify!($export_fn);
+
+		let params = $rpc_params.ok_or(Error::RpcMissingParams {
+			rpc_method: rpc_fn_name.to_string(),
+		})?;
+
+		let params = from_value(params).map_err(|_| Error::RpcFailJsonParams {
+			rpc_method: rpc_fn_name.to_string(),
+		})?;
+
+		$export_fn($ctx, $mm, params).await?
+	}};
+}
+
 pub async fn exec_rpc(
 	ctx: Ctx,
 	mm: ModelManager,
@@ -133,6 +153,47 @@ pub async fn exec_rpc(
 		"delete_user" => exec_rpc_fn!(delete_user, ctx, mm, rpc_params),
 
 		"update_pwd" => exec_rpc_fn!(update_pwd, ctx, mm, rpc_params),
+		"create_service_account" => {
+			exec_rpc_fn!(create_service_account, ctx, mm, rpc_params)
+		}
+		"unlock_user" => exec_rpc_fn!(unlock_user, ctx, mm, rpc_params),
+
+		// Api keys
+		"create_api_key" => exec_rpc_fn!(create_api_key, ctx, mm, rpc_params),
+		"list_api_keys" => exec_rpc_fn!(list_api_keys, ctx, mm, rpc_params),
+		"revoke_api_key" => exec_rpc_fn!(revoke_api_key, ctx, mm, rpc_params),
+
+		// Ip allowlists
+		"create_ip_allowlist" => {
+			exec_rpc_fn!(create_ip_allowlist, ctx, mm, rpc_params)
+		}
+		"list_ip_allowlists" => {
+			exec_rpc_fn!(list_ip_allowlists, ctx, mm, rpc_params)
+		}
+		"delete_ip_allowlist" => {
+			exec_rpc_fn!(delete_ip_allowlist, ctx, mm, rpc_params)
+		}
+
+		// Login history
+		"list_login_history" => {
+			exec_rpc_fn!(list_login_history, ctx, mm, rpc_params)
+		}
+
+		// Sessions
+		"list_my_sessions" => exec_rpc_fn!(list_my_sessions, ctx, mm),
+		"revoke_session" => exec_rpc_fn!(revoke_session, ctx, mm, rpc_params),
+		"revoke_all_sessions" => exec_rpc_fn!(revoke_all_sessions, ctx, mm),
+
+		// Two-factor (TOTP)
+		"begin_totp_enrollment" => exec_rpc_fn!(begin_totp_enrollment, ctx, mm),
+		"confirm_totp_enrollment" => {
+			exec_rpc_fn!(confirm_totp_enrollment, ctx, mm, rpc_params)
+		}
+		"disable_totp" => exec_rpc_fn!(disable_totp, ctx, mm, rpc_params),
+
+		// Token signing keys
+		"list_token_keys" => exec_rpc_fn!(list_token_keys, ctx, mm),
+		"roll_token_key" => exec_rpc_fn!(roll_token_key, ctx, mm),
 
 		// Role CRUD
 		"create_role" => exec_rpc_fn!(create_role, ctx, mm, rpc_params),
@@ -246,10 +307,28 @@ pub async fn exec_rpc(
 		"search_archives" => {
 			exec_rpc_fn!(search_archives, ctx, mm, rpc_params)
 		}
+		"search_documents" => {
+			exec_rpc_fn!(search_documents, ctx, mm, rpc_params)
+		}
 		"get_doc_url" => {
 			exec_rpc_fn!(get_doc_url, ctx, mm, rpc_params)
 		}
 
+		// Saved Search CRUD
+		"create_saved_search" => {
+			exec_rpc_fn!(create_saved_search, ctx, mm, rpc_params)
+		}
+		"list_saved_searches" => {
+			exec_rpc_fn!(list_saved_searches, ctx, mm, rpc_params)
+		}
+		"run_saved_search" => exec_rpc_fn!(run_saved_search, ctx, mm, rpc_params),
+		"update_saved_search" => {
+			exec_rpc_fn!(update_saved_search, ctx, mm, rpc_params)
+		}
+		"delete_saved_search" => {
+			exec_rpc_fn!(delete_saved_search, ctx, mm, rpc_params)
+		}
+
 		// Permission association CRUD
 		"create_association" => {
 			exec_rpc_fn!(create_associated_privilege, ctx, mm, rpc_params)
@@ -290,6 +369,9 @@ pub async fn exec_rpc(
 		"enable_structure_privilege" => {
 			exec_rpc_fn!(enable_structure_privileges, ctx, mm, rpc_params)
 		}
+		"clear_structure_privilege_override" => {
+			exec_rpc_fn!(clear_structure_privilege_overrides, ctx, mm, rpc_params)
+		}
 		"list_enabled_privileges" => {
 			exec_rpc_fn!(list_enabled_privileges, ctx, mm, rpc_params)
 		}
@@ -299,3 +381,24 @@ pub async fn exec_rpc(
 
 	Ok(result_json)
 }
+
+/// All the rows of a listing rpc (same params), for the export files.
+pub async fn exec_export(
+	ctx: Ctx,
+	mm: ModelManager,
+	rpc_req: RpcRequest,
+) -> Result<Export> {
+	let rpc_method = rpc_req.method;
+	let rpc_params = rpc_req.params;
+
+	let export = match rpc_method.as_str() {
+		"search_archives" => {
+			exec_export_fn!(export_archives, ctx, mm, rpc_params)
+		}
+		"list_documents" => exec_export_fn!(export_documents, ctx, mm, rpc_params),
+		// -- Fallback error
+		_ => return Err(Error::RpcMethodUnknown(rpc_method)),
+	};
+
+	Ok(export)
+}
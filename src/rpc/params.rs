@@ -1,8 +1,10 @@
-use modql::filter::ListOptions;
 use serde::{de::DeserializeOwned, Deserialize};
 use serde_with::{serde_as, OneOrMany};
 
-use crate::core::model::search_operations::Listoptions;
+use crate::core::model::base::CursorListOptions;
+use crate::core::model::search_operations::{
+	ArchiveIndexFilter, Facet, Listoptions,
+};
 
 #[derive(Deserialize)]
 pub struct ParamsForCreate<D> {
@@ -28,7 +30,7 @@ where
 {
 	#[serde_as(deserialize_as = "Option<OneOrMany<_>>")]
 	pub filters: Option<Vec<F>>,
-	pub list_options: Option<ListOptions>,
+	pub list_options: Option<CursorListOptions>,
 }
 
 #[serde_as]
@@ -41,3 +43,33 @@ where
 	pub filters: Option<Vec<F>>,
 	pub list_options: Option<Listoptions>,
 }
+
+/// The `Paramslist` of `search_archives` (and of its export).
+#[derive(Deserialize)]
+pub struct ParamsSearchArchives {
+	#[serde(flatten)]
+	pub list: Paramslist<ArchiveIndexFilter>,
+	/// Restrict the returned values to these index ids (all when `None`).
+	pub index_ids: Option<Vec<i64>>,
+	/// Not of the export.
+	pub facets: Option<Vec<Facet>>,
+}
+
+#[derive(Deserialize)]
+pub struct ParamsSearch<F> {
+	pub filter: Option<F>,
+	pub list_options: Option<Listoptions>,
+}
+
+#[derive(Deserialize)]
+pub struct ParamsForRun {
+	pub id: i64,
+	/// Replaces the stored list options when present (e.g., to page).
+	pub list_options: Option<Listoptions>,
+}
+
+/// A TOTP code of the authenticator app, or a recovery code.
+#[derive(Deserialize)]
+pub struct ParamsTotpCode {
+	pub code: String,
+}
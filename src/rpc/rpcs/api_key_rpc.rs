@@ -0,0 +1,45 @@
+use crate::core::ctx::Ctx;
+use crate::core::model::api_key::{
+	ApiKey, ApiKeyBmc, ApiKeyCreated, ApiKeyFilter, ApiKeyForCreate,
+};
+use crate::core::model::base::ListResult;
+use crate::core::model::ModelManager;
+use crate::rpc::params::{ParamsForCreate, ParamsIded, ParamsList};
+use crate::rpc::Result;
+
+/// The clear key is only in this response.
+pub async fn create_api_key(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ApiKeyForCreate>,
+) -> Result<ApiKeyCreated> {
+	let ParamsForCreate { data } = params;
+
+	let api_key = ApiKeyBmc::create(&ctx, &mm, data).await?;
+
+	Ok(api_key)
+}
+
+pub async fn list_api_keys(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsList<ApiKeyFilter>,
+) -> Result<ListResult<ApiKey>> {
+	let api_keys =
+		ApiKeyBmc::list(&ctx, &mm, params.filters, params.list_options).await?;
+
+	Ok(api_keys)
+}
+
+pub async fn revoke_api_key(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<ApiKey> {
+	let ParamsIded { id } = params;
+
+	ApiKeyBmc::revoke(&ctx, &mm, id).await?;
+	let api_key = ApiKeyBmc::get(&ctx, &mm, id).await?;
+
+	Ok(api_key)
+}
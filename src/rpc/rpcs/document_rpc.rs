@@ -6,6 +6,7 @@ use crate::core::model::document::{
 	Document, DocumentBmc, DocumentFilter, DocumentForCreate, DocumentForRename,
 	DocumentForRequest, DocumentForUpdate,
 };
+use crate::core::model::export::Export;
 use crate::core::model::separator::SeparatorBmc;
 use crate::core::model::ModelManager;
 use crate::rpc::config::rpc_config;
@@ -57,6 +58,20 @@ pub async fn list_documents(
 	Ok(documents)
 }
 
+/// `list_documents` without paging, see `exec_export`.
+pub async fn export_documents(
+	ctx: Ctx,
+	_mm: ModelManager,
+	params: ParamsList<DocumentFilter>,
+) -> Result<Export> {
+	let order_bys = params
+		.list_options
+		.and_then(|list_options| list_options.list_options.order_bys);
+	let export = DocumentBmc::export(&ctx, params.filters, order_bys)?;
+
+	Ok(export)
+}
+
 pub async fn get_document(
 	ctx: Ctx,
 	mm: ModelManager,
@@ -90,8 +105,8 @@ pub async fn update_document(
 		key: document.key,
 	};
 
-	if let Some(mut file) = file {
-		upload_to_s3(&s3_client, &mut file).await?;
+	if let Some(file) = file {
+		upload_to_s3(&s3_client, &file).await?;
 
 		new_data.name = file.file_name.clone();
 		new_data.doc_type = file.content_type.clone();
@@ -146,9 +161,7 @@ pub async fn get_doc_url(
 }
 
 async fn generate_presigned_url(s3_client: &Client, key: &str) -> Result<String> {
-	let expiration = Duration::from_secs(900)
-		.try_into()
-		.unwrap_or(Duration::from_secs(900));
+	let expiration = Duration::from_secs(900);
 
 	let presign_config = PresigningConfig::builder()
 		.expires_in(expiration)
@@ -1,4 +1,5 @@
 use crate::core::ctx::Ctx;
+use crate::core::model::base::ListResult;
 use crate::core::model::event::{EventBmc, EventFilter, EventWithUsername};
 use crate::core::model::ModelManager;
 use crate::rpc::params::ParamsList;
@@ -8,7 +9,7 @@ pub async fn list_events(
 	ctx: Ctx,
 	mm: ModelManager,
 	params: ParamsList<EventFilter>,
-) -> Result<Vec<EventWithUsername>> {
+) -> Result<ListResult<EventWithUsername>> {
 	let archive_events =
 		EventBmc::list(&ctx, &mm, params.filters, params.list_options).await?;
 
@@ -0,0 +1,45 @@
+use crate::core::ctx::Ctx;
+use crate::core::model::base::ListResult;
+use crate::core::model::ip_allowlist::{
+	IpAllowlist, IpAllowlistBmc, IpAllowlistFilter, IpAllowlistForCreate,
+};
+use crate::core::model::ModelManager;
+use crate::rpc::params::{ParamsForCreate, ParamsIded, ParamsList};
+use crate::rpc::Result;
+
+pub async fn create_ip_allowlist(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<IpAllowlistForCreate>,
+) -> Result<IpAllowlist> {
+	let ParamsForCreate { data } = params;
+
+	let id = IpAllowlistBmc::create(&ctx, &mm, data).await?;
+	let ip_allowlist = IpAllowlistBmc::get(&ctx, &mm, id).await?;
+
+	Ok(ip_allowlist)
+}
+
+pub async fn list_ip_allowlists(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsList<IpAllowlistFilter>,
+) -> Result<ListResult<IpAllowlist>> {
+	let ip_allowlists =
+		IpAllowlistBmc::list(&ctx, &mm, params.filters, params.list_options).await?;
+
+	Ok(ip_allowlists)
+}
+
+pub async fn delete_ip_allowlist(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<IpAllowlist> {
+	let ParamsIded { id } = params;
+
+	let ip_allowlist = IpAllowlistBmc::get(&ctx, &mm, id).await?;
+	IpAllowlistBmc::delete(&ctx, &mm, id).await?;
+
+	Ok(ip_allowlist)
+}
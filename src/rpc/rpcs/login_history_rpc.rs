@@ -0,0 +1,21 @@
+use crate::core::ctx::Ctx;
+use crate::core::model::base::ListResult;
+use crate::core::model::login_history::{
+	LoginHistory, LoginHistoryBmc, LoginHistoryFilter,
+};
+use crate::core::model::ModelManager;
+use crate::rpc::params::ParamsList;
+use crate::rpc::Result;
+
+/// Of all the users for the admins, of the ctx user otherwise.
+pub async fn list_login_history(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsList<LoginHistoryFilter>,
+) -> Result<ListResult<LoginHistory>> {
+	let login_history =
+		LoginHistoryBmc::list(&ctx, &mm, params.filters, params.list_options)
+			.await?;
+
+	Ok(login_history)
+}
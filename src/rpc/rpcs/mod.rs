@@ -1,3 +1,4 @@
+pub mod api_key_rpc;
 pub mod archive_comment_rpc;
 pub mod archive_rpc;
 pub mod associated_privilege_rpc;
@@ -6,11 +7,17 @@ pub mod document_comment_rpc;
 pub mod document_rpc;
 pub mod event_rpc;
 pub mod index_rpc;
+pub mod ip_allowlist_rpc;
+pub mod login_history_rpc;
 pub mod privilege_rpc;
 pub mod role_rpc;
+pub mod saved_search_rpc;
 pub mod search_operations_rpc;
 pub mod separator_rpc;
+pub mod session_rpc;
 pub mod structure_privilege;
 pub mod structure_rpc;
+pub mod token_key_rpc;
+pub mod totp_rpc;
 pub mod user_rpc;
 pub mod value_rpc;
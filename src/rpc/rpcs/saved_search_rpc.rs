@@ -0,0 +1,74 @@
+use crate::core::ctx::Ctx;
+use crate::core::model::archive::ArchiveWithValues;
+use crate::core::model::base::ListResult;
+use crate::core::model::saved_search::{
+	SavedSearch, SavedSearchBmc, SavedSearchFilter, SavedSearchForOp,
+};
+use crate::core::model::ModelManager;
+use crate::rpc::params::{
+	ParamsForCreate, ParamsForRun, ParamsForUpdate, ParamsIded, ParamsList,
+};
+use crate::rpc::Result;
+
+pub async fn create_saved_search(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<SavedSearchForOp>,
+) -> Result<SavedSearch> {
+	let ParamsForCreate { data } = params;
+
+	let id = SavedSearchBmc::create(&ctx, &mm, data).await?;
+	let saved_search = SavedSearchBmc::get(&ctx, &mm, id).await?;
+
+	Ok(saved_search)
+}
+
+pub async fn list_saved_searches(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsList<SavedSearchFilter>,
+) -> Result<ListResult<SavedSearch>> {
+	let saved_searches =
+		SavedSearchBmc::list(&ctx, &mm, params.filters, params.list_options).await?;
+
+	Ok(saved_searches)
+}
+
+pub async fn run_saved_search(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForRun,
+) -> Result<ListResult<ArchiveWithValues>> {
+	let ParamsForRun { id, list_options } = params;
+
+	let archives = SavedSearchBmc::run(&ctx, &mm, id, list_options).await?;
+
+	Ok(archives)
+}
+
+pub async fn update_saved_search(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForUpdate<SavedSearchForOp>,
+) -> Result<SavedSearch> {
+	let ParamsForUpdate { id, data } = params;
+
+	SavedSearchBmc::update(&ctx, &mm, id, data).await?;
+
+	let saved_search = SavedSearchBmc::get(&ctx, &mm, id).await?;
+
+	Ok(saved_search)
+}
+
+pub async fn delete_saved_search(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<SavedSearch> {
+	let ParamsIded { id } = params;
+
+	let saved_search = SavedSearchBmc::get(&ctx, &mm, id).await?;
+	SavedSearchBmc::delete(&ctx, &mm, id).await?;
+
+	Ok(saved_search)
+}
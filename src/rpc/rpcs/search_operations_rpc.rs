@@ -1,17 +1,20 @@
 use std::collections::HashMap;
 
 use crate::core::ctx::Ctx;
-use crate::core::model::archive::Archive;
+use crate::core::model::archive::ArchiveWithValues;
 //use crate::core::model::archive_event::ArchiveEventFilter;
 use crate::core::model::base::ListResult;
 use crate::core::model::document::{Document, DocumentBmc};
+use crate::core::model::export::Export;
 use crate::core::model::index::IndexFilter;
 use crate::core::model::search_operations::{
-	ArchiveIndexFilter, IndexWithDatatype, SearchBmc,
+	DocumentSearchFilter, DocumentWithPath, IndexWithDatatype, SearchBmc,
 };
 use crate::core::model::separator::{Separator, SeparatorBmc};
 use crate::core::model::ModelManager;
-use crate::rpc::params::{ParamsIded, ParamsList, Paramslist};
+use crate::rpc::params::{
+	ParamsIded, ParamsList, ParamsSearch, ParamsSearchArchives,
+};
 use crate::rpc::Result;
 use futures::future::BoxFuture;
 use futures::FutureExt;
@@ -35,7 +38,9 @@ pub async fn get_project_fields(
 		&ctx,
 		&mm,
 		params.filters,
-		params.list_options,
+		params
+			.list_options
+			.map(|list_options| list_options.list_options),
 	)
 	.await?;
 
@@ -45,15 +50,51 @@ pub async fn get_project_fields(
 pub async fn search_archives(
 	ctx: Ctx,
 	mm: ModelManager,
-	params: Paramslist<ArchiveIndexFilter>,
-) -> Result<ListResult<Archive>> {
-	let archives =
-		SearchBmc::search_archives(&ctx, &mm, params.filters, params.list_options)
-			.await?;
+	params: ParamsSearchArchives,
+) -> Result<ListResult<ArchiveWithValues>> {
+	let archives = SearchBmc::search_archives(
+		&ctx,
+		&mm,
+		params.list.filters,
+		params.list.list_options,
+		params.index_ids,
+		params.facets,
+	)
+	.await?;
 
 	Ok(archives)
 }
 
+/// `search_archives` without paging, see `exec_export`.
+pub async fn export_archives(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsSearchArchives,
+) -> Result<Export> {
+	let export = SearchBmc::export_archives(
+		&ctx,
+		&mm,
+		params.list.filters,
+		params.list.list_options,
+		params.index_ids,
+	)
+	.await?;
+
+	Ok(export)
+}
+
+pub async fn search_documents(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsSearch<DocumentSearchFilter>,
+) -> Result<ListResult<DocumentWithPath>> {
+	let documents =
+		SearchBmc::search_documents(&ctx, &mm, params.filter, params.list_options)
+			.await?;
+
+	Ok(documents)
+}
+
 pub fn build_tree(
 	parent_name: String,
 	parent_id: Option<i64>,
@@ -114,7 +155,7 @@ pub async fn get_file_tree(
 	for doc in documents {
 		documents_by_separator
 			.entry(Some(doc.separator_id))
-			.or_insert_with(Vec::new)
+			.or_default()
 			.push(doc);
 	}
 
@@ -123,7 +164,7 @@ pub async fn get_file_tree(
 	for sep in separators {
 		separators_by_parent
 			.entry(sep.parent_id)
-			.or_insert_with(Vec::new)
+			.or_default()
 			.push(sep);
 	}
 
@@ -0,0 +1,43 @@
+use crate::core::ctx::Ctx;
+use crate::core::model::base::ListResult;
+use crate::core::model::session::{MySession, SessionBmc};
+use crate::core::model::ModelManager;
+use crate::rpc::params::ParamsIded;
+use crate::rpc::Result;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct RevokedSessions {
+	pub revoked: u64,
+}
+
+pub async fn list_my_sessions(
+	ctx: Ctx,
+	mm: ModelManager,
+) -> Result<ListResult<MySession>> {
+	let sessions = SessionBmc::list_mine(&ctx, &mm, None).await?;
+
+	Ok(sessions)
+}
+
+pub async fn revoke_session(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<RevokedSessions> {
+	let ParamsIded { id } = params;
+
+	SessionBmc::revoke(&ctx, &mm, id).await?;
+
+	Ok(RevokedSessions { revoked: 1 })
+}
+
+/// All the other sessions, the current one stays logged in.
+pub async fn revoke_all_sessions(
+	ctx: Ctx,
+	mm: ModelManager,
+) -> Result<RevokedSessions> {
+	let revoked = SessionBmc::revoke_all(&ctx, &mm, true).await?;
+
+	Ok(RevokedSessions { revoked })
+}
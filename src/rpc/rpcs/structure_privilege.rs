@@ -30,23 +30,20 @@ pub async fn get_structure_privilege(
 	Ok(association)
 }
 
+/// Of a role (all its users without an override) or of a user.
 pub async fn enable_structure_privileges(
 	ctx: Ctx,
 	mm: ModelManager,
 	params: ParamsForCreate<StructuresForAct>,
 ) -> Result<Vec<StructurePrivilege>> {
 	let ParamsForCreate { data } = params;
+	let grantee = data.grantee()?;
 	let mut enabled_privileges = Vec::new();
 
 	for id in data.ids {
-		StructurePrivilegeBmc::enable(&ctx, &mm, data.user_id, id).await?;
-		let association = StructurePrivilegeBmc::get_on_user_and_project_id(
-			&ctx,
-			&mm,
-			data.user_id,
-			id,
-		)
-		.await?;
+		StructurePrivilegeBmc::enable(&ctx, &mm, &grantee, id).await?;
+		let association =
+			StructurePrivilegeBmc::get_of_grantee(&ctx, &mm, &grantee, id).await?;
 		enabled_privileges.push(association);
 	}
 
@@ -59,19 +56,31 @@ pub async fn disable_structure_privileges(
 	params: ParamsForCreate<StructuresForAct>,
 ) -> Result<Vec<StructurePrivilege>> {
 	let ParamsForCreate { data } = params;
-	let mut enabled_privileges = Vec::new();
+	let grantee = data.grantee()?;
+	let mut disabled_privileges = Vec::new();
 
 	for id in data.ids {
-		StructurePrivilegeBmc::disable(&ctx, &mm, data.user_id, id).await?;
-		let association = StructurePrivilegeBmc::get_on_user_and_project_id(
-			&ctx,
-			&mm,
-			data.user_id,
-			id,
-		)
-		.await?;
-		enabled_privileges.push(association);
+		StructurePrivilegeBmc::disable(&ctx, &mm, &grantee, id).await?;
+		let association =
+			StructurePrivilegeBmc::get_of_grantee(&ctx, &mm, &grantee, id).await?;
+		disabled_privileges.push(association);
 	}
 
-	Ok(enabled_privileges)
+	Ok(disabled_privileges)
+}
+
+/// The user gets the access of its role again on the structures.
+pub async fn clear_structure_privilege_overrides(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<StructuresForAct>,
+) -> Result<()> {
+	let ParamsForCreate { data } = params;
+	let grantee = data.grantee()?;
+
+	for id in data.ids {
+		StructurePrivilegeBmc::clear_override(&ctx, &mm, &grantee, id).await?;
+	}
+
+	Ok(())
 }
@@ -0,0 +1,17 @@
+use crate::core::ctx::Ctx;
+use crate::core::model::token_key::{TokenKey, TokenKeyBmc};
+use crate::core::model::ModelManager;
+use crate::rpc::Result;
+
+pub async fn list_token_keys(ctx: Ctx, mm: ModelManager) -> Result<Vec<TokenKey>> {
+	let token_keys = TokenKeyBmc::list(&ctx, &mm).await?;
+
+	Ok(token_keys)
+}
+
+/// Rolls the signing key now, instead of at the next scheduled rotation.
+pub async fn roll_token_key(ctx: Ctx, mm: ModelManager) -> Result<TokenKey> {
+	let token_key = TokenKeyBmc::roll(&ctx, &mm).await?;
+
+	Ok(token_key)
+}
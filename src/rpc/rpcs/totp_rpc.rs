@@ -0,0 +1,46 @@
+use crate::core::ctx::Ctx;
+use crate::core::model::user_totp::{
+	TotpEnrollment, TotpRecoveryCodes, UserTotpBmc,
+};
+use crate::core::model::ModelManager;
+use crate::rpc::params::ParamsTotpCode;
+use crate::rpc::Result;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct TotpStatus {
+	pub enabled: bool,
+}
+
+pub async fn begin_totp_enrollment(
+	ctx: Ctx,
+	mm: ModelManager,
+) -> Result<TotpEnrollment> {
+	let enrollment = UserTotpBmc::begin_enrollment(&ctx, &mm).await?;
+
+	Ok(enrollment)
+}
+
+pub async fn confirm_totp_enrollment(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsTotpCode,
+) -> Result<TotpRecoveryCodes> {
+	let ParamsTotpCode { code } = params;
+
+	let recovery_codes = UserTotpBmc::confirm_enrollment(&ctx, &mm, &code).await?;
+
+	Ok(recovery_codes)
+}
+
+pub async fn disable_totp(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsTotpCode,
+) -> Result<TotpStatus> {
+	let ParamsTotpCode { code } = params;
+
+	UserTotpBmc::disable(&ctx, &mm, &code).await?;
+
+	Ok(TotpStatus { enabled: false })
+}
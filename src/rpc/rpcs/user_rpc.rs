@@ -1,15 +1,14 @@
 use crate::core::ctx::Ctx;
 use crate::core::model::base::ListResult;
+use crate::core::model::login_attempt::LoginAttemptBmc;
 use crate::core::model::user::{
-	User, UserBmc, UserFilter, UserForCreate, UserForUpdate, UserForUpdatePwd,
+	ServiceAccountForCreate, User, UserBmc, UserFilter, UserForCreate,
+	UserForUpdate, UserForUpdatePwd,
 };
 use crate::core::model::ModelManager;
 use crate::rpc::params::{ParamsForCreate, ParamsForUpdate, ParamsIded, ParamsList};
 use crate::rpc::Result;
 
-const READ_USERS: i64 = 1;
-const WRITE_USERS: i64 = 2;
-
 pub async fn create_user(
 	ctx: Ctx,
 	mm: ModelManager,
@@ -25,6 +24,21 @@ pub async fn create_user(
 	Ok(user)
 }
 
+pub async fn create_service_account(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ServiceAccountForCreate>,
+) -> Result<User> {
+	//check_permission(&ctx, WRITE_USERS)?;
+
+	let ParamsForCreate { data } = params;
+
+	let id = UserBmc::create_service_account(&ctx, &mm, data).await?;
+	let user = UserBmc::get(&ctx, &mm, id).await?;
+
+	Ok(user)
+}
+
 pub async fn list_users(
 	ctx: Ctx,
 	mm: ModelManager,
@@ -98,3 +112,18 @@ pub async fn update_pwd(
 
 	Ok(user)
 }
+
+/// Lifts the login lockout of the user (admins only).
+pub async fn unlock_user(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<User> {
+	let ParamsIded { id } = params;
+
+	LoginAttemptBmc::unlock_user(&ctx, &mm, id).await?;
+
+	let user = UserBmc::get(&ctx, &mm, id).await?;
+
+	Ok(user)
+}
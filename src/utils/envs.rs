@@ -30,7 +30,10 @@ impl core::fmt::Display for Error {
 		&self,
 		fmt: &mut core::fmt::Formatter,
 	) -> core::result::Result<(), core::fmt::Error> {
-		write!(fmt, "{self:?}")
+		match self {
+			Self::MissingEnv(name) => write!(fmt, "MissingEnv({name})"),
+			Self::WrongFormat(name) => write!(fmt, "WrongFormat({name})"),
+		}
 	}
 }
 
@@ -35,7 +35,11 @@ impl core::fmt::Display for Error {
 		&self,
 		fmt: &mut core::fmt::Formatter,
 	) -> core::result::Result<(), core::fmt::Error> {
-		write!(fmt, "{self:?}")
+		match self {
+			Self::FailToDateParse(moment) => {
+				write!(fmt, "FailToDateParse({moment})")
+			}
+		}
 	}
 }
 
@@ -0,0 +1,198 @@
+use crate::auth::ldap::{self, LdapClient};
+use crate::core::ctx::Ctx;
+use crate::core::model::user::{
+	ExternalUserForCreate, UserBmc, UserForLogin, UserForUpdate,
+};
+use crate::core::model::ModelManager;
+use crate::web::authenticator::Authenticator;
+use crate::web::routes_login::LoginPayload;
+use crate::web::{Error, Result};
+use async_trait::async_trait;
+use tracing::debug;
+
+/// The bind of the user at the directory, which is authoritative for its
+/// usernames: its users are created at their first login and their role
+/// (and email) synced on the next ones.
+pub struct LdapAuthenticator {
+	client: &'static LdapClient,
+}
+
+impl LdapAuthenticator {
+	pub fn new(client: &'static LdapClient) -> Self {
+		LdapAuthenticator { client }
+	}
+}
+
+#[async_trait]
+impl Authenticator for LdapAuthenticator {
+	async fn authenticate(
+		&self,
+		mm: &ModelManager,
+		payload: &LoginPayload,
+	) -> Result<Option<UserForLogin>> {
+		let username = payload.username.clone();
+
+		let ldap_user = match self.client.authenticate(&username, &payload.pwd).await
+		{
+			Ok(Some(ldap_user)) => ldap_user,
+			Ok(None) => return Ok(None),
+			Err(ldap::Error::PwdNotMatching { .. }) => {
+				return Err(Error::LoginFailLdapPwdNotMatching { username })
+			}
+			Err(ex) => return Err(ex.into()),
+		};
+
+		debug!(" {:<12} - ldap_authenticate - {}", "AUTH", ldap_user.dn);
+
+		let role = ldap_user.role.ok_or(Error::LoginFailLdapNoRole {
+			username: username.clone(),
+		})?;
+
+		let root_ctx = Ctx::root_ctx();
+		let user: Option<UserForLogin> =
+			UserBmc::first_by_username(&root_ctx, mm, &username).await?;
+
+		let user_id = match user {
+			Some(user) if user.is_service => {
+				return Err(Error::LoginFailServiceAccount { user_id: user.id })
+			}
+			Some(user) => {
+				let email = ldap_user.email.unwrap_or_else(|| user.email.clone());
+				if user.assigned_role != role || user.email != email {
+					UserBmc::update(
+						&root_ctx,
+						mm,
+						user.id,
+						UserForUpdate {
+							username: user.username,
+							email,
+							assigned_role: role,
+						},
+					)
+					.await?;
+				}
+				user.id
+			}
+			None => {
+				let email = ldap_user.email.ok_or(Error::LoginFailLdapNoEmail {
+					username: username.clone(),
+				})?;
+				UserBmc::create_external(
+					&root_ctx,
+					mm,
+					ExternalUserForCreate {
+						username,
+						email,
+						assigned_role: role,
+					},
+				)
+				.await?
+			}
+		};
+
+		Ok(Some(UserBmc::get(&root_ctx, mm, user_id).await?))
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::auth::ldap::fixture::{fx_client, FX_BOB, FX_CAROL};
+	use crate::core::_dev_utils;
+	use crate::core::model::role::{RoleBmc, RoleForOp};
+	use anyhow::Result;
+	use serial_test::serial;
+
+	fn fx_payload((username, pwd): (&str, &str)) -> LoginPayload {
+		LoginPayload {
+			username: username.to_string(),
+			pwd: pwd.to_string(),
+			new_pwd: None,
+		}
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_authenticate_provision_and_sync_role_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let client: &'static LdapClient =
+			Box::leak(Box::new(fx_client(Some("demo")).await?));
+		// Bob is of the staff, but still of the default role here.
+		let fx_role_id = RoleBmc::create(
+			&root_ctx,
+			&mm,
+			RoleForOp {
+				role_name: "fx-staff".to_string(),
+				description: "test_authenticate_provision_and_sync_role_ok"
+					.to_string(),
+			},
+		)
+		.await?;
+		let fx_bob_id = UserBmc::create_external(
+			&root_ctx,
+			&mm,
+			ExternalUserForCreate {
+				username: FX_BOB.0.to_string(),
+				email: "bob@example.org".to_string(),
+				assigned_role: "demo".to_string(),
+			},
+		)
+		.await?;
+
+		// -- Exec
+		let authenticator = LdapAuthenticator::new(client);
+		let provisioned = authenticator
+			.authenticate(&mm, &fx_payload(FX_CAROL))
+			.await?;
+		let synced = authenticator.authenticate(&mm, &fx_payload(FX_BOB)).await?;
+		let unknown = authenticator
+			.authenticate(&mm, &fx_payload(("fx-unknown", "fx-pwd")))
+			.await?;
+
+		// -- Check
+		let provisioned = provisioned.ok_or(anyhow::anyhow!("Should be some"))?;
+		assert_eq!(provisioned.username, FX_CAROL.0);
+		assert_eq!(provisioned.email, "carol@example.org");
+		assert_eq!(provisioned.assigned_role, "demo");
+		assert!(provisioned.pwd.is_none());
+		let synced = synced.ok_or(anyhow::anyhow!("Should be some"))?;
+		assert_eq!(synced.id, fx_bob_id);
+		assert_eq!(synced.assigned_role, "fx-staff");
+		assert!(unknown.is_none());
+
+		// -- Clean
+		UserBmc::delete(&root_ctx, &mm, provisioned.id).await?;
+		UserBmc::delete(&root_ctx, &mm, fx_bob_id).await?;
+		RoleBmc::delete(&root_ctx, &mm, fx_role_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_authenticate_err_pwd_not_matching() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let client: &'static LdapClient =
+			Box::leak(Box::new(fx_client(Some("demo")).await?));
+
+		// -- Exec
+		let res = LdapAuthenticator::new(client)
+			.authenticate(&mm, &fx_payload((FX_CAROL.0, "fx-wrong-pwd")))
+			.await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(Error::LoginFailLdapPwdNotMatching { .. })),
+			"Should have matched `Err(Error::LoginFailLdapPwdNotMatching)` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,184 @@
+//! The authenticators of the login credentials, tried in the order of the
+//! `LOGIN_AUTHENTICATORS` config until one knows the username (the `pwd` one
+//! not knowing the users without a pwd, e.g., those of the ldap).
+
+// region:    --- Modules
+
+mod ldap;
+mod pwd;
+
+use crate::auth::config::auth_config;
+use crate::auth::ldap::ldap_client;
+use crate::auth::pwd::{hash_pwd, ContentToHash};
+use crate::core::ctx::Ctx;
+use crate::core::model::user::{UserBmc, UserForLogin};
+use crate::core::model::ModelManager;
+use crate::web::routes_login::LoginPayload;
+use crate::web::{Error, Result};
+use async_trait::async_trait;
+use enum_dispatch::enum_dispatch;
+use uuid::Uuid;
+
+pub use self::ldap::LdapAuthenticator;
+pub use self::pwd::PwdAuthenticator;
+
+// endregion: --- Modules
+
+#[async_trait]
+#[enum_dispatch]
+pub trait Authenticator {
+	/// The user of the credentials, none when the username is unknown to this
+	/// authenticator (the next one is then tried).
+	async fn authenticate(
+		&self,
+		mm: &ModelManager,
+		payload: &LoginPayload,
+	) -> Result<Option<UserForLogin>>;
+}
+
+#[enum_dispatch(Authenticator)]
+pub enum AuthenticatorDispatcher {
+	Pwd(PwdAuthenticator),
+	Ldap(LdapAuthenticator),
+}
+
+/// The `ldap` authenticator only exists with a configured directory.
+pub fn get_authenticator(name: &str) -> Result<impl Authenticator> {
+	match name {
+		"pwd" => Ok(AuthenticatorDispatcher::Pwd(PwdAuthenticator)),
+		"ldap" => ldap_client()
+			.map(|client| {
+				AuthenticatorDispatcher::Ldap(LdapAuthenticator::new(client))
+			})
+			.ok_or(Error::LoginAuthenticatorNotFound {
+				name: name.to_string(),
+			}),
+		_ => Err(Error::LoginAuthenticatorNotFound {
+			name: name.to_string(),
+		}),
+	}
+}
+
+/// The user of the credentials, from the first authenticator knowing the
+/// username.
+pub async fn authenticate(
+	mm: &ModelManager,
+	payload: &LoginPayload,
+) -> Result<UserForLogin> {
+	let authenticators = auth_config()
+		.LOGIN_AUTHENTICATORS
+		.iter()
+		.map(String::as_str)
+		.map(get_authenticator);
+
+	authenticate_with(mm, payload, authenticators).await
+}
+
+/// `authenticate` with the `authenticators`, created as they are tried.
+async fn authenticate_with<A: Authenticator>(
+	mm: &ModelManager,
+	payload: &LoginPayload,
+	authenticators: impl IntoIterator<Item = Result<A>>,
+) -> Result<UserForLogin> {
+	for authenticator in authenticators {
+		if let Some(user) = authenticator?.authenticate(mm, payload).await? {
+			return Ok(user);
+		}
+	}
+
+	hash_pwd_for_timing(&payload.pwd).await?;
+
+	// A user none of them knows has no pwd (e.g., of an ldap not tried).
+	let user: Option<UserForLogin> =
+		UserBmc::first_by_username(&Ctx::root_ctx(), mm, &payload.username).await?;
+	match user {
+		Some(user) => Err(Error::LoginFailUserHasNoPwd { user_id: user.id }),
+		None => Err(Error::LoginFailUsernameNotFound),
+	}
+}
+
+/// Hashes the pwd anyway when there is none to validate, so that the response
+/// time does not tell which usernames exist.
+async fn hash_pwd_for_timing(pwd_clear: &str) -> Result<()> {
+	hash_pwd(ContentToHash {
+		content: pwd_clear.to_string(),
+		salt: Uuid::new_v4(),
+	})
+	.await?;
+
+	Ok(())
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::auth::ldap::fixture::{fx_client, FX_ALICE};
+	use crate::auth::ldap::LdapClient;
+	use crate::core::_dev_utils;
+	use crate::core::model::role::{RoleBmc, RoleForOp};
+	use anyhow::Result;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_authenticate_pwd_then_ldap_provisioned_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let client: &'static LdapClient =
+			Box::leak(Box::new(fx_client(None).await?));
+		let fx_role_id = RoleBmc::create(
+			&root_ctx,
+			&mm,
+			RoleForOp {
+				role_name: "fx-admin".to_string(),
+				description: "test_authenticate_pwd_then_ldap_provisioned_ok"
+					.to_string(),
+			},
+		)
+		.await?;
+		let fx_payload = LoginPayload {
+			username: FX_ALICE.0.to_string(),
+			pwd: FX_ALICE.1.to_string(),
+			new_pwd: None,
+		};
+		let pwd_then_ldap = || {
+			[
+				Ok(AuthenticatorDispatcher::Pwd(PwdAuthenticator)),
+				Ok(AuthenticatorDispatcher::Ldap(LdapAuthenticator::new(
+					client,
+				))),
+			]
+		};
+
+		// -- Exec
+		// Provisioned (without a pwd) at the first login.
+		let provisioned =
+			authenticate_with(&mm, &fx_payload, pwd_then_ldap()).await?;
+		let logged_in = authenticate_with(&mm, &fx_payload, pwd_then_ldap()).await?;
+		let res_pwd_only = authenticate_with(
+			&mm,
+			&fx_payload,
+			[Ok(AuthenticatorDispatcher::Pwd(PwdAuthenticator))],
+		)
+		.await;
+
+		// -- Check
+		assert!(provisioned.pwd.is_none());
+		assert_eq!(logged_in.id, provisioned.id);
+		assert!(
+			matches!(res_pwd_only, Err(Error::LoginFailUserHasNoPwd { user_id }) if user_id == provisioned.id),
+			"Should have matched `Err(Error::LoginFailUserHasNoPwd)` but was `{res_pwd_only:?}`"
+		);
+
+		// -- Clean
+		UserBmc::delete(&root_ctx, &mm, provisioned.id).await?;
+		RoleBmc::delete(&root_ctx, &mm, fx_role_id).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,71 @@
+use crate::auth::pwd::policy::PwdPolicy;
+use crate::auth::pwd::{self, ContentToHash, SchemeStatus};
+use crate::core::ctx::Ctx;
+use crate::core::model::user::{UserBmc, UserForLogin};
+use crate::core::model::ModelManager;
+use crate::web::authenticator::{hash_pwd_for_timing, Authenticator};
+use crate::web::mw_auth::CtxExtError;
+use crate::web::routes_login::LoginPayload;
+use crate::web::{Error, Result};
+use async_trait::async_trait;
+use tracing::debug;
+
+/// The pwd of the `user` table.
+pub struct PwdAuthenticator;
+
+#[async_trait]
+impl Authenticator for PwdAuthenticator {
+	/// Validates the pwd, upgrading an outdated pwd scheme. An expired pwd must
+	/// be changed with the `new_pwd` of the login.
+	async fn authenticate(
+		&self,
+		mm: &ModelManager,
+		payload: &LoginPayload,
+	) -> Result<Option<UserForLogin>> {
+		let root_ctx = Ctx::root_ctx();
+		let pwd_clear = &payload.pwd;
+
+		let Some(user): Option<UserForLogin> =
+			UserBmc::first_by_username(&root_ctx, mm, &payload.username).await?
+		else {
+			return Ok(None);
+		};
+
+		let user_id = user.id;
+
+		if user.is_service {
+			hash_pwd_for_timing(pwd_clear).await?;
+			return Err(Error::LoginFailServiceAccount { user_id });
+		}
+
+		// An external (e.g., ldap) user, for the next authenticators.
+		let Some(pwd) = user.pwd.clone() else {
+			return Ok(None);
+		};
+
+		let scheme_status = pwd::validate_pwd(
+			ContentToHash {
+				salt: user.pwd_salt,
+				content: pwd_clear.clone(),
+			},
+			pwd,
+		)
+		.await
+		.map_err(|_| Error::LoginFailPwdNotMatching { user_id })?;
+
+		if PwdPolicy::from_config().is_expired(user.pwd_mtime) {
+			let new_pwd = payload
+				.new_pwd
+				.as_deref()
+				.ok_or(Error::LoginPwdExpired { user_id })?;
+			let ctx = Ctx::new(user_id)
+				.map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))?;
+			UserBmc::update_pwd(&ctx, mm, user_id, new_pwd).await?;
+		} else if let SchemeStatus::Outdated = scheme_status {
+			debug!("pwd encrypt scheme outdated, upgrading");
+			UserBmc::set_pwd(&root_ctx, mm, user.id, pwd_clear).await?;
+		}
+
+		Ok(Some(user))
+	}
+}
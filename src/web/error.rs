@@ -1,4 +1,5 @@
-use crate::auth::{pwd, token};
+use crate::auth::pwd::policy::PwdRule;
+use crate::auth::{ldap, oidc, pwd, token};
 use crate::core::model;
 use crate::web;
 use axum::http::StatusCode;
@@ -23,11 +24,70 @@ pub enum Error {
 	LoginFailPwdNotMatching {
 		user_id: i64,
 	},
+	LoginFailServiceAccount {
+		user_id: i64,
+	},
+	LoginPwdExpired {
+		user_id: i64,
+	},
+	LoginFailThrottled {
+		username: String,
+		retry_after_sec: i64,
+	},
+	LoginFailTotpChallengeNotValid,
+	LoginFailTotpCodeNotValid {
+		user_id: i64,
+	},
+	/// Not from one of the ip allowlists of the user or its role.
+	LoginFailIpNotAllowed {
+		user_id: i64,
+	},
+	LoginAuthenticatorNotFound {
+		name: String,
+	},
+
+	// -- Ldap
+	LoginFailLdapPwdNotMatching {
+		username: String,
+	},
+	/// In none of the mapped groups, without a default role.
+	LoginFailLdapNoRole {
+		username: String,
+	},
+	LoginFailLdapNoEmail {
+		username: String,
+	},
+
+	// -- OpenID Connect
+	OidcFailStateNotValid,
+	OidcFailIdp {
+		error: String,
+	},
+	OidcFailUserNotFound {
+		subject: String,
+	},
+	/// The user of the email has a TOTP, which the IdP login would skip.
+	OidcFailUserHasTotp {
+		user_id: i64,
+	},
+
+	// -- Csrf
+	/// Of a state-changing request authenticated by the auth cookie.
+	CsrfTokenNotValid,
+
+	// -- Pwd reset
+	PwdResetFailTokenNotFound,
+	PwdResetFailTokenNotValid {
+		user_id: i64,
+	},
+	PwdResetFailThrottled {
+		retry_after_sec: i64,
+	},
+
 	FileExtractFailed,
 	InvalidJson,
 	NoJsonInRequest,
 
-	FailedToExtractCookies(String),
 	// -- CtxExtError
 	#[from]
 	CtxExt(web::mw_auth::CtxExtError),
@@ -36,6 +96,10 @@ pub enum Error {
 	#[from]
 	Model(model::Error),
 	#[from]
+	Ldap(ldap::Error),
+	#[from]
+	Oidc(oidc::Error),
+	#[from]
 	Pwd(pwd::Error),
 	#[from]
 	Token(token::Error),
@@ -45,6 +109,10 @@ pub enum Error {
 	// -- External Modules
 	#[from]
 	SerdeJson(#[serde_as(as = "DisplayFromStr")] serde_json::Error),
+	#[from]
+	Csv(#[serde_as(as = "DisplayFromStr")] csv::Error),
+	#[from]
+	Xlsx(#[serde_as(as = "DisplayFromStr")] rust_xlsxwriter::XlsxError),
 }
 
 // region:    --- Axum IntoResponse
@@ -87,10 +155,41 @@ impl Error {
 			// -- Login
 			LoginFailUsernameNotFound
 			| LoginFailUserHasNoPwd { .. }
-			| LoginFailPwdNotMatching { .. } => {
+			| LoginFailPwdNotMatching { .. }
+			| LoginFailServiceAccount { .. }
+			| LoginFailThrottled { .. }
+			| LoginFailTotpChallengeNotValid
+			| LoginFailTotpCodeNotValid { .. }
+			| LoginFailIpNotAllowed { .. } => {
 				(StatusCode::FORBIDDEN, ClientError::LOGIN_FAIL)
 			}
 
+			// -- Ldap
+			LoginFailLdapPwdNotMatching { .. }
+			| LoginFailLdapNoRole { .. }
+			| LoginFailLdapNoEmail { .. } => (StatusCode::FORBIDDEN, ClientError::LOGIN_FAIL),
+
+			LoginPwdExpired { .. } => {
+				(StatusCode::FORBIDDEN, ClientError::PWD_EXPIRED)
+			}
+
+			// -- OpenID Connect
+			OidcFailStateNotValid
+			| OidcFailIdp { .. }
+			| OidcFailUserNotFound { .. }
+			| OidcFailUserHasTotp { .. }
+			| Oidc(_) => (StatusCode::FORBIDDEN, ClientError::LOGIN_FAIL),
+
+			// -- Csrf
+			CsrfTokenNotValid => (StatusCode::FORBIDDEN, ClientError::CSRF_FAIL),
+
+			// -- Pwd reset
+			PwdResetFailTokenNotFound
+			| PwdResetFailTokenNotValid { .. }
+			| PwdResetFailThrottled { .. } => {
+				(StatusCode::FORBIDDEN, ClientError::PWD_RESET_FAIL)
+			}
+
 			// -- Auth
 			CtxExt(_) => (StatusCode::FORBIDDEN, ClientError::NO_AUTH),
 
@@ -100,6 +199,22 @@ impl Error {
 				ClientError::ENTITY_NOT_FOUND { entity, id: *id },
 			),
 
+			Model(model::Error::PwdPolicyViolated { failed_rules })
+			| Rpc(crate::rpc::Error::Model(model::Error::PwdPolicyViolated {
+				failed_rules,
+			})) => (
+				StatusCode::BAD_REQUEST,
+				ClientError::PWD_POLICY_VIOLATED {
+					failed_rules: failed_rules.clone(),
+				},
+			),
+
+			Rpc(crate::rpc::Error::Model(
+				model::Error::TotpAlreadyEnabled { .. }
+				| model::Error::TotpNotEnrolled { .. }
+				| model::Error::TotpCodeNotValid { .. },
+			)) => (StatusCode::BAD_REQUEST, ClientError::TOTP_FAIL),
+
 			// -- Fallback.
 			_ => (
 				StatusCode::INTERNAL_SERVER_ERROR,
@@ -114,8 +229,21 @@ impl Error {
 #[allow(non_camel_case_types)]
 pub enum ClientError {
 	LOGIN_FAIL,
+	PWD_RESET_FAIL,
+	/// The login must send a `new_pwd`.
+	PWD_EXPIRED,
+	PWD_POLICY_VIOLATED {
+		failed_rules: Vec<PwdRule>,
+	},
+	/// TOTP enrollment or disable, not the login (`LOGIN_FAIL`).
+	TOTP_FAIL,
+	/// The `X-CSRF-Token` header is missing or not the `/api/csrf` one.
+	CSRF_FAIL,
 	NO_AUTH,
-	ENTITY_NOT_FOUND { entity: &'static str, id: i64 },
+	ENTITY_NOT_FOUND {
+		entity: &'static str,
+		id: i64,
+	},
 
 	SERVICE_ERROR,
 }
@@ -1,12 +1,21 @@
 // region:    --- Modules
 
+mod authenticator;
 mod error;
 pub mod mw_auth;
+pub mod mw_csrf;
 pub mod mw_res_map;
+pub mod routes_export;
 pub mod routes_login;
+pub mod routes_oidc;
+pub mod routes_pwd_reset;
 pub mod routes_rpc;
 pub mod routes_static;
 use crate::auth::token::generate_web_token;
+use crate::core::ctx::ClientInfo;
+use axum::http::header::USER_AGENT;
+use axum::http::HeaderMap;
+use axum_client_ip::SecureClientIp;
 use tower_cookies::cookie::SameSite;
 use tower_cookies::{Cookie, Cookies};
 use uuid::Uuid;
@@ -19,29 +28,39 @@ pub use self::error::{Error, Result};
 pub const AUTH_TOKEN: &str = "auth-token";
 pub const PRIVILEGES: &str = "privileges";
 
-fn set_token_cookie(cookies: &Cookies, user: &str, salt: Uuid) -> Result<()> {
-	let token = generate_web_token(user, salt)?;
+/// The `session.user_agent` (and `login_history.user_agent`) column size.
+const USER_AGENT_MAX_LEN: usize = 512;
+/// The `login_attempt` ip key of the clients without a resolved ip.
+const UNKNOWN_IP_KEY: &str = "unknown";
 
-	let mut cookie = Cookie::new(AUTH_TOKEN, token.to_string());
-	cookie.set_http_only(true);
-	cookie.set_path("/");
-	cookie.set_secure(true);
-	cookie.set_same_site(SameSite::None);
-	//cookie.set_domain("localhost:3400");
+fn client_info(
+	client_ip: Option<&SecureClientIp>,
+	headers: &HeaderMap,
+) -> ClientInfo {
+	let user_agent = headers
+		.get(USER_AGENT)
+		.and_then(|user_agent| user_agent.to_str().ok())
+		.map(|user_agent| user_agent.chars().take(USER_AGENT_MAX_LEN).collect());
 
-	cookies.add(cookie);
+	ClientInfo {
+		ip: client_ip.map(|SecureClientIp(ip)| ip.to_string()),
+		user_agent,
+	}
+}
 
-	Ok(())
+/// The clients without a resolved ip share the same key (rather than
+/// escaping the per-ip throttling).
+fn attempt_ip_key(client_ip: Option<&SecureClientIp>) -> String {
+	client_ip.map_or_else(
+		|| UNKNOWN_IP_KEY.to_string(),
+		|SecureClientIp(ip)| ip.to_string(),
+	)
 }
 
-fn set_privileges_cookie(cookies: &Cookies, privileges: &Vec<i64>) -> Result<()> {
-	let privileges_string = privileges
-		.iter()
-		.map(|id| id.to_string())
-		.collect::<Vec<_>>()
-		.join(",");
+fn set_token_cookie(cookies: &Cookies, ident: &str, salt: Uuid) -> Result<()> {
+	let token = generate_web_token(ident, salt)?;
 
-	let mut cookie = Cookie::new(PRIVILEGES, privileges_string.to_string());
+	let mut cookie = Cookie::new(AUTH_TOKEN, token.to_string());
 	cookie.set_http_only(true);
 	cookie.set_path("/");
 	cookie.set_secure(true);
@@ -53,11 +72,9 @@ fn set_privileges_cookie(cookies: &Cookies, privileges: &Vec<i64>) -> Result<()>
 	Ok(())
 }
 
-fn remove_token_cookie(cookies: &Cookies) -> Result<()> {
+fn remove_token_cookie(cookies: &Cookies) {
 	let mut cookie = Cookie::from(AUTH_TOKEN);
 	cookie.set_path("/");
 
 	cookies.remove(cookie);
-
-	Ok(())
 }
@@ -1,20 +1,28 @@
 use crate::auth::token::{validate_web_token, Token};
 use crate::core::ctx::Ctx;
-use crate::core::model::associated_privilege::AssociatedPrivilegeBmc;
+use crate::core::model::api_key::ApiKeyBmc;
+use crate::core::model::ip_allowlist::IpAllowlistBmc;
+use crate::core::model::session::{SessionBmc, SessionForAuth, SessionTokenKind};
 use crate::core::model::user::{UserBmc, UserForAuth};
-use crate::core::model::ModelManager;
-use crate::web::{set_privileges_cookie, set_token_cookie, AUTH_TOKEN, PRIVILEGES};
+use crate::core::model::{self, ModelManager};
+use crate::web::mw_csrf::remove_csrf_cookie;
+use crate::web::{client_info, set_token_cookie, AUTH_TOKEN, PRIVILEGES};
 use crate::web::{Error, Result};
 use async_trait::async_trait;
 use axum::body::Body;
 use axum::extract::{FromRequestParts, State};
+use axum::http::header::AUTHORIZATION;
 use axum::http::request::Parts;
-use axum::http::Request;
+use axum::http::{HeaderMap, Request};
 use axum::middleware::Next;
 use axum::response::Response;
+use axum_client_ip::SecureClientIp;
 use serde::Serialize;
 use tower_cookies::{Cookie, Cookies};
 use tracing::debug;
+use uuid::Uuid;
+
+pub(super) const X_API_KEY: &str = "x-api-key";
 
 pub async fn mw_ctx_require(
 	ctx: Result<CtxW>,
@@ -30,21 +38,42 @@ pub async fn mw_ctx_require(
 
 pub async fn mw_ctx_resolve(
 	mm: State<ModelManager>,
+	client_ip: Option<SecureClientIp>,
 	cookies: Cookies,
 	mut req: Request<Body>,
 	next: Next,
 ) -> Result<Response> {
 	debug!(" {:<12} - mw_ctx_resolve", "MIDDLEWARE");
 
-	let ctx_ext_result = _ctx_resolve(mm, &cookies).await;
+	// The header token (or api key), when given, replaces the cookie one.
+	let headers = req.headers();
+	let ctx_ext_result = match (bearer_token(headers), api_key(headers)) {
+		(Err(ex), _) => Err(ex),
+		(Ok(Some(token)), _) => _ctx_resolve_header(&mm, token).await,
+		(Ok(None), Some(key)) => _ctx_resolve_api_key(&mm, key).await,
+		(Ok(None), None) => {
+			let ctx_ext_result = _ctx_resolve(&mm, &cookies).await;
+
+			// Remove the cookie if something went wrong other than NoAuthTokenCookie.
+			if ctx_ext_result.is_err()
+				&& !matches!(ctx_ext_result, Err(CtxExtError::TokenNotInCookie))
+			{
+				cookies.remove(Cookie::from(AUTH_TOKEN));
+				cookies.remove(Cookie::from(PRIVILEGES));
+				remove_csrf_cookie(&cookies);
+			}
+
+			ctx_ext_result
+		}
+	};
 
-	// Remove the cookie if something went wrong other than NoAuthTokenCookie.
-	if ctx_ext_result.is_err()
-		&& !matches!(ctx_ext_result, Err(CtxExtError::TokenNotInCookie))
-	{
-		cookies.remove(Cookie::from(AUTH_TOKEN));
-		cookies.remove(Cookie::from(PRIVILEGES));
+	// The user (or its role) may be restricted to some ip ranges.
+	let client = client_info(client_ip.as_ref(), req.headers());
+	let ctx_ext_result = match ctx_ext_result {
+		Ok(ctx_w) => check_client_ip(&mm, ctx_w, client_ip).await,
+		Err(ex) => Err(ex),
 	}
+	.map(|CtxW(ctx)| CtxW(ctx.with_client(client)));
 
 	// Store the ctx_result in the request extension.
 	req.extensions_mut().insert(ctx_ext_result);
@@ -52,36 +81,138 @@ pub async fn mw_ctx_resolve(
 	Ok(next.run(req).await)
 }
 
-async fn _ctx_resolve(mm: State<ModelManager>, cookies: &Cookies) -> CtxExtResult {
+async fn _ctx_resolve(mm: &ModelManager, cookies: &Cookies) -> CtxExtResult {
 	// -- Get Token String
 	let token = cookies
 		.get(AUTH_TOKEN)
 		.map(|c| c.value().to_string())
 		.ok_or(CtxExtError::TokenNotInCookie)?;
 
+	let (user, session) =
+		validate_token_session(mm, &token, SessionTokenKind::Cookie).await?;
+
+	// -- Update Token
+	set_token_cookie(cookies, &session.token_id.to_string(), user.token_salt)
+		.map_err(|_| CtxExtError::CannotSetTokenCookie)?;
+
+	// -- Create CtxExtResult
+	Ctx::new(user.id)
+		.map(|ctx| CtxW(ctx.with_session_id(session.id)))
+		.map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))
+}
+
+/// Same validation as the cookie token, without the sliding refresh
+/// (the header token expires at its own `exp`).
+async fn _ctx_resolve_header(mm: &ModelManager, token: &str) -> CtxExtResult {
+	let (user, session) =
+		validate_token_session(mm, token, SessionTokenKind::Header).await?;
+
+	Ctx::new(user.id)
+		.map(|ctx| CtxW(ctx.with_session_id(session.id)))
+		.map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))
+}
+
+/// The service account of a valid api key, restricted to the key scope.
+async fn _ctx_resolve_api_key(mm: &ModelManager, key: &str) -> CtxExtResult {
+	let (user_id, key_scope) = ApiKeyBmc::authenticate(mm, key)
+		.await
+		.map_err(|ex| CtxExtError::ModelAccessError(ex.to_string()))?
+		.ok_or(CtxExtError::ApiKeyNotValid)?;
+
+	Ctx::new(user_id)
+		.map(|ctx| CtxW(ctx.with_key_scope(key_scope)))
+		.map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))
+}
+
+/// The ctx, if its user is allowed from the client ip.
+async fn check_client_ip(
+	mm: &ModelManager,
+	ctx_w: CtxW,
+	client_ip: Option<SecureClientIp>,
+) -> CtxExtResult {
+	let ctx = &ctx_w.0;
+	let key_role_name = ctx
+		.key_scope()
+		.map(|key_scope| key_scope.role_name.as_str());
+	let ip = client_ip.map(|SecureClientIp(ip)| ip);
+
+	let allowed =
+		IpAllowlistBmc::check_ip(mm, ctx.user_id(), key_role_name, ip, "request")
+			.await
+			.map_err(|ex| CtxExtError::ModelAccessError(ex.to_string()))?;
+
+	if allowed {
+		Ok(ctx_w)
+	} else {
+		Err(CtxExtError::IpNotAllowed)
+	}
+}
+
+/// The user and session of a valid (signed, not expired) token, the token
+/// ident being the session `token_id`. A revoked session fails, as a token
+/// of another `token_kind` (e.g., a header token sent as the cookie).
+async fn validate_token_session(
+	mm: &ModelManager,
+	token: &str,
+	token_kind: SessionTokenKind,
+) -> core::result::Result<(UserForAuth, SessionForAuth), CtxExtError> {
 	// -- Parse Token
 	let token: Token = token.parse().map_err(|_| CtxExtError::TokenWrongFormat)?;
+	let token_id: Uuid = token
+		.ident
+		.parse()
+		.map_err(|_| CtxExtError::TokenWrongFormat)?;
 
-	let tmp_ctx = Ctx::root_ctx();
-
-	// -- Get UserForAuth
-	let user: UserForAuth = UserBmc::first_by_username(&tmp_ctx, &mm, &token.ident)
+	// -- Get the session and its UserForAuth
+	let session = SessionBmc::first_active_by_token_id(mm, token_id)
 		.await
 		.map_err(|ex| CtxExtError::ModelAccessError(ex.to_string()))?
-		.ok_or(CtxExtError::UserNotFound)?;
+		.ok_or(CtxExtError::SessionNotFound)?;
+	if session.token_kind != token_kind.as_str() {
+		return Err(CtxExtError::TokenWrongKind);
+	}
+
+	let tmp_ctx = Ctx::root_ctx();
+	let user: UserForAuth = UserBmc::get(&tmp_ctx, mm, session.user_id)
+		.await
+		.map_err(|ex| match ex {
+			model::Error::EntityNotFound { .. } => CtxExtError::UserNotFound,
+			ex => CtxExtError::ModelAccessError(ex.to_string()),
+		})?;
 
 	// -- Validate Token
 	validate_web_token(&token, user.token_salt)
 		.map_err(|_| CtxExtError::FailValidate)?;
 
-	// -- Update Token
-	set_token_cookie(cookies, &user.username, user.token_salt)
-		.map_err(|_| CtxExtError::CannotSetTokenCookie)?;
+	SessionBmc::touch_last_seen(mm, &session)
+		.await
+		.map_err(|ex| CtxExtError::ModelAccessError(ex.to_string()))?;
 
-	// -- Create CtxExtResult
-	Ctx::new(user.id)
-		.map(CtxW)
-		.map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))
+	Ok((user, session))
+}
+
+/// The token of an `Authorization: Bearer <token>` header, if any.
+fn bearer_token(
+	headers: &HeaderMap,
+) -> core::result::Result<Option<&str>, CtxExtError> {
+	let Some(authorization) = headers.get(AUTHORIZATION) else {
+		return Ok(None);
+	};
+
+	authorization
+		.to_str()
+		.ok()
+		.and_then(|authorization| authorization.strip_prefix("Bearer "))
+		.map(|token| Some(token.trim()))
+		.ok_or(CtxExtError::AuthHeaderNotBearer)
+}
+
+/// The key of an `X-Api-Key` header, if any.
+fn api_key(headers: &HeaderMap) -> Option<&str> {
+	headers
+		.get(X_API_KEY)
+		.and_then(|key| key.to_str().ok())
+		.map(str::trim)
 }
 
 #[derive(Debug, Clone)]
@@ -111,16 +242,162 @@ type CtxExtResult = core::result::Result<CtxW, CtxExtError>;
 #[derive(Clone, Serialize, Debug)]
 pub enum CtxExtError {
 	TokenNotInCookie,
+	AuthHeaderNotBearer,
 
 	TokenWrongFormat,
+	TokenWrongKind,
+	ApiKeyNotValid,
+	IpNotAllowed,
 
+	SessionNotFound,
 	UserNotFound,
 	ModelAccessError(String),
 	FailValidate,
 	CannotSetTokenCookie,
-	CannotSetPrivilegesCookie,
 
 	CtxNotInRequestExt,
 	CtxCreateFail(String),
 }
 // endregion: --- Ctx Extractor Result/Error
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::auth::token::generate_header_token;
+	use crate::core::_dev_utils;
+	use crate::core::model::session::SessionForCreate;
+	use anyhow::Result;
+	use axum::body::to_bytes;
+	use axum::extract::ConnectInfo;
+	use axum::http::HeaderValue;
+	use axum::routing::get;
+	use axum::Router;
+	use axum_client_ip::SecureClientIpSource;
+	use serial_test::serial;
+	use std::net::SocketAddr;
+	use tower::ServiceExt;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_validate_token_session_err_wrong_kind() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let demo1: UserForAuth = UserBmc::first_by_username(&root_ctx, &mm, "demo1")
+			.await?
+			.unwrap();
+		let fx_session = SessionBmc::create(
+			&mm,
+			SessionForCreate {
+				token_kind: SessionTokenKind::Header.as_str().to_string(),
+				user_id: demo1.id,
+				ip: None,
+				user_agent: Some("test_validate_token_session".to_string()),
+			},
+		)
+		.await?;
+		let fx_token = generate_header_token(
+			&fx_session.token_id.to_string(),
+			demo1.token_salt,
+		)?
+		.to_string();
+
+		// -- Exec
+		let res_header =
+			validate_token_session(&mm, &fx_token, SessionTokenKind::Header).await;
+		// A header token put in the `auth-token` cookie.
+		let res_cookie =
+			validate_token_session(&mm, &fx_token, SessionTokenKind::Cookie).await;
+
+		// -- Check
+		assert!(
+			matches!(&res_header, Ok((_, session)) if session.id == fx_session.id),
+			"Should have matched `Ok(..)` but was `{res_header:?}`"
+		);
+		assert!(
+			matches!(res_cookie, Err(CtxExtError::TokenWrongKind)),
+			"Should have matched `Err(CtxExtError::TokenWrongKind)` but was `{res_cookie:?}`"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_bearer_token_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut fx_headers = HeaderMap::new();
+		fx_headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer a.b.c"));
+
+		// -- Exec
+		let token = bearer_token(&fx_headers);
+
+		// -- Check
+		assert!(
+			matches!(token, Ok(Some("a.b.c"))),
+			"Should have matched `Ok(Some(\"a.b.c\"))` but was `{token:?}`"
+		);
+		assert!(matches!(bearer_token(&HeaderMap::new()), Ok(None)));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_bearer_token_err_scheme() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut fx_headers = HeaderMap::new();
+		fx_headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic dXNlcg=="));
+
+		// -- Exec
+		let res = bearer_token(&fx_headers);
+
+		// -- Check
+		assert!(
+			matches!(res, Err(CtxExtError::AuthHeaderNotBearer)),
+			"Should have matched `Err(CtxExtError::AuthHeaderNotBearer)` but was `{res:?}`"
+		);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_client_ip_forged_header_ignored() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_peer: SocketAddr = "10.0.0.7:4321".parse()?;
+		let app = Router::new()
+			.route(
+				"/",
+				get(|client_ip: Option<SecureClientIp>| async move {
+					client_ip
+						.map(|SecureClientIp(ip)| ip.to_string())
+						.unwrap_or_default()
+				}),
+			)
+			.layer(SecureClientIpSource::ConnectInfo.into_extension());
+		let fx_req = || {
+			Request::builder()
+				.uri("/")
+				.header("x-forwarded-for", "203.0.113.9")
+				.header("x-real-ip", "203.0.113.9")
+				.body(Body::empty())
+		};
+
+		// -- Exec
+		let mut req_peer = fx_req()?;
+		req_peer.extensions_mut().insert(ConnectInfo(fx_peer));
+		let res_peer = app.clone().oneshot(req_peer).await?;
+		let res_no_peer = app.oneshot(fx_req()?).await?;
+
+		// -- Check
+		let ip_peer = to_bytes(res_peer.into_body(), usize::MAX).await?;
+		assert_eq!(&ip_peer[..], b"10.0.0.7");
+		// Without the connect info, no ip (the allowlists then reject).
+		let ip_no_peer = to_bytes(res_no_peer.into_body(), usize::MAX).await?;
+		assert!(ip_no_peer.is_empty());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
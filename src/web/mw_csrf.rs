@@ -0,0 +1,154 @@
+//! Double-submit CSRF tokens. The token is issued at login (also returned by
+//! `/api/csrf`) as the `csrf-token` cookie and in the response body, and the
+//! state-changing requests authenticated by the auth cookie must send it back
+//! as the `X-CSRF-Token` header (which a cross-site form cannot set).
+
+use crate::utils::b64::b64u_encode;
+use crate::web::mw_auth::X_API_KEY;
+use crate::web::{Error, Result, AUTH_TOKEN};
+use axum::body::Body;
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, Method, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use rand::RngCore;
+use tower_cookies::cookie::SameSite;
+use tower_cookies::{Cookie, Cookies};
+use tracing::debug;
+
+pub const CSRF_TOKEN: &str = "csrf-token";
+pub const X_CSRF_TOKEN: &str = "x-csrf-token";
+
+const CSRF_TOKEN_LEN: usize = 32;
+
+pub async fn mw_csrf_require(
+	cookies: Cookies,
+	req: Request<Body>,
+	next: Next,
+) -> Result<Response> {
+	debug!(" {:<12} - mw_csrf_require", "MIDDLEWARE");
+
+	validate_csrf(
+		req.method(),
+		req.headers(),
+		cookies.get(AUTH_TOKEN).is_some(),
+		cookies.get(CSRF_TOKEN).as_ref().map(Cookie::value),
+	)?;
+
+	Ok(next.run(req).await)
+}
+
+/// Sets a new token as the cookie, returned for the response body.
+pub fn set_csrf_cookie(cookies: &Cookies) -> String {
+	let mut random = [0u8; CSRF_TOKEN_LEN];
+	rand::thread_rng().fill_bytes(&mut random);
+	let csrf_token = b64u_encode(random);
+
+	let mut cookie = Cookie::new(CSRF_TOKEN, csrf_token.clone());
+	cookie.set_http_only(true);
+	cookie.set_path("/");
+	cookie.set_secure(true);
+	cookie.set_same_site(SameSite::None);
+
+	cookies.add(cookie);
+
+	csrf_token
+}
+
+pub fn remove_csrf_cookie(cookies: &Cookies) {
+	let mut cookie = Cookie::from(CSRF_TOKEN);
+	cookie.set_path("/");
+
+	cookies.remove(cookie);
+}
+
+// region:    --- Privates
+
+/// Only the unsafe methods of the requests authenticated by the auth cookie
+/// (the browser does not send the header tokens and api keys by itself).
+fn validate_csrf(
+	method: &Method,
+	headers: &HeaderMap,
+	has_auth_cookie: bool,
+	csrf_cookie: Option<&str>,
+) -> Result<()> {
+	let is_safe = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+	let is_header_auth =
+		headers.contains_key(AUTHORIZATION) || headers.contains_key(X_API_KEY);
+	if is_safe || is_header_auth || !has_auth_cookie {
+		return Ok(());
+	}
+
+	let csrf_header = headers
+		.get(X_CSRF_TOKEN)
+		.and_then(|csrf_header| csrf_header.to_str().ok());
+
+	match (csrf_header, csrf_cookie) {
+		(Some(csrf_header), Some(csrf_cookie))
+			if eq_constant_time(csrf_header.as_bytes(), csrf_cookie.as_bytes()) =>
+		{
+			Ok(())
+		}
+		_ => Err(Error::CsrfTokenNotValid),
+	}
+}
+
+fn eq_constant_time(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// endregion: --- Privates
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use anyhow::Result;
+	use axum::http::HeaderValue;
+
+	#[test]
+	fn test_validate_csrf_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut fx_headers = HeaderMap::new();
+		fx_headers.insert(X_CSRF_TOKEN, HeaderValue::from_static("fx-csrf-01"));
+
+		// -- Exec & Check
+		validate_csrf(&Method::POST, &fx_headers, true, Some("fx-csrf-01"))?;
+		// Nothing to forge without the auth cookie, or on a safe method.
+		validate_csrf(&Method::POST, &HeaderMap::new(), false, None)?;
+		validate_csrf(&Method::GET, &HeaderMap::new(), true, Some("fx-csrf-01"))?;
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_validate_csrf_err_not_matching() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut fx_headers = HeaderMap::new();
+		fx_headers.insert(X_CSRF_TOKEN, HeaderValue::from_static("fx-csrf-02"));
+
+		// -- Exec
+		let res_other =
+			validate_csrf(&Method::POST, &fx_headers, true, Some("fx-csrf-01"));
+		let res_no_header = validate_csrf(
+			&Method::POST,
+			&HeaderMap::new(),
+			true,
+			Some("fx-csrf-01"),
+		);
+		let res_no_cookie = validate_csrf(&Method::POST, &fx_headers, true, None);
+
+		// -- Check
+		for res in [res_other, res_no_header, res_no_cookie] {
+			assert!(
+				matches!(res, Err(Error::CsrfTokenNotValid)),
+				"Should have matched `Err(Error::CsrfTokenNotValid)` but was `{res:?}`"
+			);
+		}
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
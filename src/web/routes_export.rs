@@ -0,0 +1,325 @@
+use super::error::{Error, Result};
+use super::mw_auth::CtxW;
+use super::routes_rpc::RpcInfo;
+use crate::core::ctx::Ctx;
+use crate::core::model::export::Export;
+use crate::core::model::ModelManager;
+use crate::rpc::{exec_export, RpcRequest};
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use futures::channel::mpsc;
+use futures::{SinkExt, TryStreamExt};
+use rust_xlsxwriter::{Format, Workbook, Worksheet};
+use serde::Deserialize;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::{io, mem};
+use tracing::debug;
+
+/// Bytes of csv rows sent at once.
+const CSV_CHUNK_SIZE: usize = 64 * 1024;
+/// Chunks waiting for the client, before pausing the database reads.
+const CSV_CHUNKS_BUFFER: usize = 8;
+/// The first chars of the texts that the spreadsheets read as formulas.
+const CSV_FORMULA_CHARS: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+pub fn routes(mm: ModelManager) -> Router {
+	Router::new()
+		.route("/export", post(export_handler))
+		.with_state(mm)
+}
+
+/// A listing rpc request (e.g., `search_archives`, `list_documents`),
+/// exporting all its rows as a file instead of a json page.
+#[derive(Deserialize)]
+struct ExportRequest {
+	method: String,
+	params: Option<Value>,
+	format: ExportFormat,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+	Csv,
+	Xlsx,
+}
+
+impl ExportFormat {
+	fn extension(self) -> &'static str {
+		match self {
+			Self::Csv => "csv",
+			Self::Xlsx => "xlsx",
+		}
+	}
+
+	fn content_type(self) -> &'static str {
+		match self {
+			Self::Csv => "text/csv; charset=utf-8",
+			Self::Xlsx => {
+				"application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+			}
+		}
+	}
+}
+
+async fn export_handler(
+	State(mm): State<ModelManager>,
+	ctx: CtxW,
+	body: String,
+) -> Response {
+	let export_req: ExportRequest = match serde_json::from_str(&body) {
+		Ok(req) => req,
+		Err(_) => return Error::InvalidJson.into_response(),
+	};
+
+	let rpc_info = RpcInfo {
+		id: None,
+		method: export_req.method.clone(),
+	};
+
+	let mut res = _export_handler(ctx.0, mm, export_req).await.into_response();
+	res.extensions_mut().insert(Arc::new(rpc_info));
+	res
+}
+
+async fn _export_handler(
+	ctx: Ctx,
+	mm: ModelManager,
+	export_req: ExportRequest,
+) -> Result<Response> {
+	let ExportRequest {
+		method,
+		params,
+		format,
+	} = export_req;
+
+	debug!(
+		"{:<12} - _export_handler - method: {method} {format:?}",
+		"HANDLER"
+	);
+
+	let file_name = format!("{method}.{}", format.extension());
+	let rpc_req = RpcRequest {
+		id: None,
+		method,
+		params,
+	};
+	let export = exec_export(ctx, mm.clone(), rpc_req).await?;
+
+	let body = match format {
+		ExportFormat::Csv => csv_body(mm, export),
+		ExportFormat::Xlsx => Body::from(xlsx_bytes(&mm, &export).await?),
+	};
+
+	let headers = [
+		(CONTENT_TYPE, format.content_type().to_string()),
+		(
+			CONTENT_DISPOSITION,
+			format!("attachment; filename=\"{file_name}\""),
+		),
+	];
+
+	Ok((headers, body).into_response())
+}
+
+/// The text of a cell, `None` when empty.
+fn cell_text(cell: &Value) -> Option<Cow<'_, str>> {
+	match cell {
+		Value::Null => None,
+		Value::String(text) => Some(Cow::Borrowed(text)),
+		other => Some(Cow::Owned(other.to_string())),
+	}
+}
+
+// region:    --- Csv
+
+/// Streams the csv while reading the rows, so that the rows are never all
+/// in memory. An error past the headers can only abort the response.
+fn csv_body(mm: ModelManager, export: Export) -> Body {
+	let (mut tx, rx) = mpsc::channel(CSV_CHUNKS_BUFFER);
+
+	tokio::spawn(async move {
+		if let Err(err) = write_csv(&mm, &export, &mut tx).await {
+			debug!("{:<12} - csv_body - {err:?}", "EXPORT");
+			let err = io::Error::new(io::ErrorKind::Other, err.to_string());
+			let _ = tx.send(Err(err)).await;
+		}
+	});
+
+	Body::from_stream(rx)
+}
+
+async fn write_csv(
+	mm: &ModelManager,
+	export: &Export,
+	tx: &mut mpsc::Sender<io::Result<Bytes>>,
+) -> Result<()> {
+	let mut writer = csv_writer();
+	writer.write_record(
+		export
+			.columns
+			.iter()
+			.map(|column| escape_csv_formula(&column.name).into_owned()),
+	)?;
+
+	let mut rows = export.rows(mm);
+	while let Some(cells) = rows.try_next().await? {
+		writer.write_record(cells.iter().zip(&export.columns).map(
+			|(cell, column)| csv_cell_text(cell, column.numeric).into_owned(),
+		))?;
+
+		if writer.get_ref().len() >= CSV_CHUNK_SIZE {
+			let chunk = take_csv_chunk(&mut writer)?;
+			// The client went away, nothing left to do.
+			if tx.send(Ok(chunk)).await.is_err() {
+				return Ok(());
+			}
+		}
+	}
+
+	let chunk = take_csv_chunk(&mut writer)?;
+	let _ = tx.send(Ok(chunk)).await;
+
+	Ok(())
+}
+
+/// The text of a csv cell, escaped from the formulas unless a number (or
+/// the parsable text of a `numeric` column).
+fn csv_cell_text(cell: &Value, numeric: bool) -> Cow<'_, str> {
+	match cell {
+		Value::String(text) if !(numeric && text.parse::<f64>().is_ok()) => {
+			escape_csv_formula(text)
+		}
+		cell => cell_text(cell).unwrap_or_default(),
+	}
+}
+
+/// A text that a spreadsheet would run as a formula (e.g.,
+/// `=HYPERLINK(..)`), prefixed with `'` to be shown as text.
+fn escape_csv_formula(text: &str) -> Cow<'_, str> {
+	if text.starts_with(CSV_FORMULA_CHARS) {
+		Cow::Owned(format!("'{text}"))
+	} else {
+		Cow::Borrowed(text)
+	}
+}
+
+fn csv_writer() -> csv::Writer<Vec<u8>> {
+	csv::Writer::from_writer(Vec::with_capacity(CSV_CHUNK_SIZE))
+}
+
+/// All the csv written so far, the writer starting over empty.
+fn take_csv_chunk(writer: &mut csv::Writer<Vec<u8>>) -> Result<Bytes> {
+	let chunk = mem::replace(writer, csv_writer())
+		.into_inner()
+		.map_err(|err| csv::Error::from(err.into_error()))?;
+
+	Ok(chunk.into())
+}
+
+// endregion: --- Csv
+
+// region:    --- Xlsx
+
+/// The rows are written to a constant memory worksheet (one row in memory
+/// at a time), the zipped file is only built at the end.
+async fn xlsx_bytes(mm: &ModelManager, export: &Export) -> Result<Vec<u8>> {
+	let mut workbook = Workbook::new();
+	let worksheet = workbook.add_worksheet_with_constant_memory();
+
+	let header_format = Format::new().set_bold();
+	for (col, column) in export.columns.iter().enumerate() {
+		worksheet.write_string_with_format(
+			0,
+			col as u16,
+			&column.name,
+			&header_format,
+		)?;
+	}
+
+	let mut rows = export.rows(mm);
+	let mut row = 0;
+	while let Some(cells) = rows.try_next().await? {
+		row += 1;
+		for (col, (cell, column)) in cells.iter().zip(&export.columns).enumerate() {
+			write_xlsx_cell(worksheet, row, col as u16, cell, column.numeric)?;
+		}
+	}
+
+	Ok(workbook.save_to_buffer()?)
+}
+
+/// Json numbers (and the parsable text of `numeric` columns) as numbers,
+/// the rest as text.
+fn write_xlsx_cell(
+	worksheet: &mut Worksheet,
+	row: u32,
+	col: u16,
+	cell: &Value,
+	numeric: bool,
+) -> Result<()> {
+	let number = match cell {
+		Value::Number(number) => number.as_f64(),
+		Value::String(text) if numeric => text.parse::<f64>().ok(),
+		_ => None,
+	};
+
+	match (number, cell_text(cell)) {
+		(Some(number), _) => {
+			worksheet.write_number(row, col, number)?;
+		}
+		(None, Some(text)) => {
+			worksheet.write_string(row, col, text)?;
+		}
+		(None, None) => {}
+	}
+
+	Ok(())
+}
+
+// endregion: --- Xlsx
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use anyhow::Result;
+	use serde_json::json;
+
+	#[test]
+	fn test_csv_cell_text_formula_escaped() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_formulas = [
+			"=HYPERLINK(\"http://example.org\")",
+			"+1+2",
+			"-2+3+cmd|' /C calc'!A0",
+			"@SUM(A1:A2)",
+			"\t=1",
+			"\r=1",
+		];
+
+		// -- Exec & Check
+		for formula in fx_formulas {
+			let cell = json!(formula);
+			assert_eq!(csv_cell_text(&cell, false), format!("'{formula}"));
+			// Not a number, even in a `numeric` column.
+			assert_eq!(csv_cell_text(&cell, true), format!("'{formula}"));
+		}
+		assert_eq!(csv_cell_text(&json!("a = b"), false), "a = b");
+		assert_eq!(csv_cell_text(&json!("-12.5"), true), "-12.5");
+		assert_eq!(csv_cell_text(&json!(-12.5), false), "-12.5");
+		assert_eq!(csv_cell_text(&Value::Null, false), "");
+		assert_eq!(escape_csv_formula("=index"), "'=index");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
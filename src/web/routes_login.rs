@@ -1,70 +1,172 @@
-use crate::auth::pwd::{self, ContentToHash, SchemeStatus};
-use crate::core::ctx::Ctx;
-use crate::core::model::associated_privilege::AssociatedPrivilegeBmc;
+use crate::auth::token::{
+	generate_header_token, generate_login_challenge_token,
+	validate_login_challenge_token, Token,
+};
+use crate::core::ctx::{ClientInfo, Ctx};
+use crate::core::model::event::{EventBmc, EventForCreate};
+use crate::core::model::ip_allowlist::IpAllowlistBmc;
+use crate::core::model::login_attempt::{AttemptScope, LoginAttemptBmc, Throttle};
+use crate::core::model::login_history::{LoginHistoryBmc, LoginHistoryKind};
+use crate::core::model::session::{
+	SessionBmc, SessionForAuth, SessionForCreate, SessionTokenKind,
+};
 use crate::core::model::user::{UserBmc, UserForLogin};
-use crate::core::model::ModelManager;
-use crate::web::{remove_token_cookie, set_token_cookie, Error, Result};
+use crate::core::model::user_totp::UserTotpBmc;
+use crate::core::model::{self, ModelManager};
+use crate::web::authenticator;
+use crate::web::mw_auth::CtxW;
+use crate::web::mw_csrf::{
+	mw_csrf_require, remove_csrf_cookie, set_csrf_cookie, CSRF_TOKEN,
+};
+use crate::web::{
+	attempt_ip_key, client_info, remove_token_cookie, set_token_cookie, Error,
+	Result,
+};
 use axum::extract::State;
-use axum::routing::post;
-use axum::{Json, Router};
+use axum::http::HeaderMap;
+use axum::routing::{get, post};
+use axum::{middleware, Json, Router};
+use axum_client_ip::SecureClientIp;
 use serde::Deserialize;
 use serde_json::{json, Value};
 use tower_cookies::Cookies;
 use tracing::debug;
 
+/// Of the challenge token idents, followed by the user id.
+const CHALLENGE_IDENT_PREFIX: &str = "totp-challenge-";
+
 pub fn routes(mm: ModelManager) -> Router {
 	Router::new()
 		.route("/api/login", post(api_login_handler))
-		.route("/api/logoff", post(api_logoff_handler))
+		.route("/api/login/totp", post(api_login_totp_handler))
+		.route("/api/token", post(api_token_handler))
+		.route("/api/token/totp", post(api_token_totp_handler))
+		.route(
+			"/api/logoff",
+			post(api_logoff_handler)
+				.route_layer(middleware::from_fn(mw_csrf_require)),
+		)
+		.route("/api/csrf", get(api_csrf_handler))
 		.with_state(mm)
 }
 
+/// With a TOTP enabled, no session is created yet: the `challenge` of the
+/// response is sent with the code to `/api/login/totp`.
 async fn api_login_handler(
 	State(mm): State<ModelManager>,
 	cookies: Cookies,
+	client_ip: Option<SecureClientIp>,
+	headers: HeaderMap,
 	Json(payload): Json<LoginPayload>,
 ) -> Result<Json<Value>> {
 	debug!(" {:<12} - api_login_handler", "HANDLER");
 
-	let LoginPayload {
-		username,
-		pwd: pwd_clear,
-	} = payload;
-	let root_ctx = Ctx::root_ctx();
+	let client = client_info(client_ip.as_ref(), &headers);
+	let user = login_user(&mm, payload, client_ip.as_ref(), &client).await?;
+	if let Some(body) = totp_challenge(&mm, &user).await? {
+		return Ok(body);
+	}
 
-	let user: UserForLogin = UserBmc::first_by_username(&root_ctx, &mm, &username)
-		.await?
-		.ok_or(Error::LoginFailUsernameNotFound)?;
+	cookie_login(&mm, &cookies, &user, client_ip, &headers).await
+}
 
-	let user_id = user.id;
+/// Second step of the login of a user with a TOTP.
+async fn api_login_totp_handler(
+	State(mm): State<ModelManager>,
+	cookies: Cookies,
+	client_ip: Option<SecureClientIp>,
+	headers: HeaderMap,
+	Json(payload): Json<LoginTotpPayload>,
+) -> Result<Json<Value>> {
+	debug!(" {:<12} - api_login_totp_handler", "HANDLER");
 
-	let Some(pwd): Option<String> = user.pwd else {
-		return Err(Error::LoginFailUserHasNoPwd { user_id });
-	};
+	let client = client_info(client_ip.as_ref(), &headers);
+	let user = login_user_totp(&mm, payload, client_ip.as_ref(), &client).await?;
 
-	let scheme_status = pwd::validate_pwd(
-		ContentToHash {
-			salt: user.pwd_salt,
-			content: pwd_clear.clone(),
-		},
-		pwd,
-	)
-	.await
-	.map_err(|_| Error::LoginFailPwdNotMatching { user_id })?;
+	cookie_login(&mm, &cookies, &user, client_ip, &headers).await
+}
+
+/// Login of the non-browser clients, the token is returned (to send as
+/// `Authorization: Bearer <token>`) instead of set as a cookie.
+async fn api_token_handler(
+	State(mm): State<ModelManager>,
+	client_ip: Option<SecureClientIp>,
+	headers: HeaderMap,
+	Json(payload): Json<LoginPayload>,
+) -> Result<Json<Value>> {
+	debug!(" {:<12} - api_token_handler", "HANDLER");
 
-	if let SchemeStatus::Outdated = scheme_status {
-		debug!("pwd encrypt scheme outdated, upgrading");
-		UserBmc::update_pwd(&root_ctx, &mm, user.id, &pwd_clear).await?;
+	let client = client_info(client_ip.as_ref(), &headers);
+	let user = login_user(&mm, payload, client_ip.as_ref(), &client).await?;
+	if let Some(body) = totp_challenge(&mm, &user).await? {
+		return Ok(body);
 	}
 
+	token_login(&mm, &user, client_ip, &headers).await
+}
+
+/// Second step of the token login of a user with a TOTP.
+async fn api_token_totp_handler(
+	State(mm): State<ModelManager>,
+	client_ip: Option<SecureClientIp>,
+	headers: HeaderMap,
+	Json(payload): Json<LoginTotpPayload>,
+) -> Result<Json<Value>> {
+	debug!(" {:<12} - api_token_totp_handler", "HANDLER");
+
+	let client = client_info(client_ip.as_ref(), &headers);
+	let user = login_user_totp(&mm, payload, client_ip.as_ref(), &client).await?;
+
+	token_login(&mm, &user, client_ip, &headers).await
+}
+
+async fn cookie_login(
+	mm: &ModelManager,
+	cookies: &Cookies,
+	user: &UserForLogin,
+	client_ip: Option<SecureClientIp>,
+	headers: &HeaderMap,
+) -> Result<Json<Value>> {
+	let session =
+		create_session(mm, user, SessionTokenKind::Cookie, client_ip, headers)
+			.await?;
+
 	// Set web token
-	set_token_cookie(&cookies, &user.username, user.token_salt)?;
+	set_token_cookie(cookies, &session.token_id.to_string(), user.token_salt)?;
+	let csrf_token = set_csrf_cookie(cookies);
 
 	// Create the success body.
 	let body = Json(json!({
 		"result": {
 			"success": true,
 			"username": user.username,
+			"role": user.assigned_role,
+			"csrf_token": csrf_token
+		}
+	}));
+
+	Ok(body)
+}
+
+async fn token_login(
+	mm: &ModelManager,
+	user: &UserForLogin,
+	client_ip: Option<SecureClientIp>,
+	headers: &HeaderMap,
+) -> Result<Json<Value>> {
+	let session =
+		create_session(mm, user, SessionTokenKind::Header, client_ip, headers)
+			.await?;
+
+	let token =
+		generate_header_token(&session.token_id.to_string(), user.token_salt)?;
+
+	// Create the success body.
+	let body = Json(json!({
+		"result": {
+			"token": token.to_string(),
+			"expires": token.exp,
+			"username": user.username,
 			"role": user.assigned_role
 		}
 	}));
@@ -72,34 +174,527 @@ async fn api_login_handler(
 	Ok(body)
 }
 
+/// The user of the credentials, throttled per username and per client ip.
+/// All failures (throttling included) are the same `LOGIN_FAIL` to the
+/// client, and are in the login history.
+async fn login_user(
+	mm: &ModelManager,
+	payload: LoginPayload,
+	client_ip: Option<&SecureClientIp>,
+	client: &ClientInfo,
+) -> Result<UserForLogin> {
+	let username = payload.username.clone();
+	let res = login_user_throttled(mm, payload, client_ip, client).await;
+
+	if let Err(err) = &res {
+		record_login_fail(mm, &username, client, err).await?;
+	}
+
+	res
+}
+
+async fn login_user_throttled(
+	mm: &ModelManager,
+	payload: LoginPayload,
+	client_ip: Option<&SecureClientIp>,
+	client: &ClientInfo,
+) -> Result<UserForLogin> {
+	let throttle = Throttle::from_config();
+	let keys = attempt_keys(&payload.username, client_ip);
+
+	check_not_throttled(mm, &payload.username, &keys, &throttle).await?;
+
+	match authenticator::authenticate(mm, &payload).await {
+		Ok(user) => {
+			// With a TOTP, the login only succeeds with the code.
+			if !UserTotpBmc::is_enabled(mm, user.id).await? {
+				LoginAttemptBmc::clear(
+					mm,
+					AttemptScope::Username,
+					&payload.username,
+				)
+				.await?;
+			}
+			Ok(user)
+		}
+		Err(
+			err @ (Error::LoginFailUsernameNotFound
+			| Error::LoginFailUserHasNoPwd { .. }
+			| Error::LoginFailPwdNotMatching { .. }
+			| Error::LoginFailLdapPwdNotMatching { .. }
+			| Error::LoginFailServiceAccount { .. }),
+		) => {
+			record_failures(mm, &payload.username, &keys, &throttle, client).await?;
+			Err(err)
+		}
+		Err(err) => Err(err),
+	}
+}
+
+/// The challenge response of a user with a TOTP, none otherwise.
+async fn totp_challenge(
+	mm: &ModelManager,
+	user: &UserForLogin,
+) -> Result<Option<Json<Value>>> {
+	if !UserTotpBmc::is_enabled(mm, user.id).await? {
+		return Ok(None);
+	}
+
+	let challenge = generate_login_challenge_token(
+		&format!("{CHALLENGE_IDENT_PREFIX}{}", user.id),
+		user.token_salt,
+	)?;
+
+	let body = Json(json!({
+		"result": {
+			"success": false,
+			"totp_required": true,
+			"challenge": challenge.to_string(),
+			"expires": challenge.exp
+		}
+	}));
+
+	Ok(Some(body))
+}
+
+/// The user of the challenge, once its TOTP (or recovery) code verified. The
+/// codes are throttled (and in the login history) as the pwds.
+async fn login_user_totp(
+	mm: &ModelManager,
+	payload: LoginTotpPayload,
+	client_ip: Option<&SecureClientIp>,
+	client: &ClientInfo,
+) -> Result<UserForLogin> {
+	let LoginTotpPayload { challenge, code } = payload;
+
+	let challenge: Token = challenge
+		.parse()
+		.map_err(|_| Error::LoginFailTotpChallengeNotValid)?;
+	let user_id: i64 = challenge
+		.ident
+		.strip_prefix(CHALLENGE_IDENT_PREFIX)
+		.and_then(|user_id| user_id.parse().ok())
+		.ok_or(Error::LoginFailTotpChallengeNotValid)?;
+
+	let user: UserForLogin = UserBmc::get(&Ctx::root_ctx(), mm, user_id)
+		.await
+		.map_err(|_| Error::LoginFailTotpChallengeNotValid)?;
+	validate_login_challenge_token(&challenge, user.token_salt)
+		.map_err(|_| Error::LoginFailTotpChallengeNotValid)?;
+
+	let throttle = Throttle::from_config();
+	let keys = attempt_keys(&user.username, client_ip);
+
+	if let Err(err) = check_not_throttled(mm, &user.username, &keys, &throttle).await
+	{
+		record_login_fail(mm, &user.username, client, &err).await?;
+		return Err(err);
+	}
+
+	match UserTotpBmc::verify(mm, user_id, &code).await {
+		Ok(()) => {
+			LoginAttemptBmc::clear(mm, AttemptScope::Username, &user.username)
+				.await?;
+			Ok(user)
+		}
+		Err(
+			model::Error::TotpCodeNotValid { .. }
+			| model::Error::TotpNotEnrolled { .. },
+		) => {
+			let err = Error::LoginFailTotpCodeNotValid { user_id };
+			record_login_fail(mm, &user.username, client, &err).await?;
+			record_failures(mm, &user.username, &keys, &throttle, client).await?;
+			Err(err)
+		}
+		Err(err) => Err(err.into()),
+	}
+}
+
+fn attempt_keys(
+	username: &str,
+	client_ip: Option<&SecureClientIp>,
+) -> Vec<(AttemptScope, String)> {
+	vec![
+		(AttemptScope::Username, username.to_string()),
+		(AttemptScope::Ip, attempt_ip_key(client_ip)),
+	]
+}
+
+/// Of the throttling keys, a lockout being in the login history.
+async fn record_failures(
+	mm: &ModelManager,
+	username: &str,
+	keys: &[(AttemptScope, String)],
+	throttle: &Throttle,
+	client: &ClientInfo,
+) -> Result<()> {
+	for (scope, key) in keys {
+		if LoginAttemptBmc::record_failure(mm, *scope, key, throttle).await? {
+			LoginHistoryBmc::record_for_username(
+				mm,
+				username,
+				LoginHistoryKind::Lockout,
+				client,
+				Some(json!({ "scope": scope.as_str() })),
+			)
+			.await?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Only the login failures (not e.g., a db error).
+async fn record_login_fail(
+	mm: &ModelManager,
+	username: &str,
+	client: &ClientInfo,
+	err: &Error,
+) -> Result<()> {
+	if matches!(
+		err,
+		Error::LoginFailUsernameNotFound
+			| Error::LoginFailUserHasNoPwd { .. }
+			| Error::LoginFailPwdNotMatching { .. }
+			| Error::LoginFailServiceAccount { .. }
+			| Error::LoginPwdExpired { .. }
+			| Error::LoginFailThrottled { .. }
+			| Error::LoginFailTotpCodeNotValid { .. }
+			| Error::LoginFailLdapPwdNotMatching { .. }
+			| Error::LoginFailLdapNoRole { .. }
+			| Error::LoginFailLdapNoEmail { .. }
+	) {
+		LoginHistoryBmc::record_for_username(
+			mm,
+			username,
+			LoginHistoryKind::LoginFail,
+			client,
+			Some(json!({ "reason": err.as_ref() })),
+		)
+		.await?;
+	}
+
+	Ok(())
+}
+
+async fn check_not_throttled(
+	mm: &ModelManager,
+	username: &str,
+	keys: &[(AttemptScope, String)],
+	throttle: &Throttle,
+) -> Result<()> {
+	if let Some(retry_after) =
+		LoginAttemptBmc::retry_after(mm, keys, throttle).await?
+	{
+		return Err(Error::LoginFailThrottled {
+			username: username.to_string(),
+			retry_after_sec: retry_after.whole_seconds().max(1),
+		});
+	}
+
+	Ok(())
+}
+
+/// The session of a login, its `token_id` being the ident of the tokens
+/// sent as `token_kind`.
+pub(super) async fn create_session(
+	mm: &ModelManager,
+	user: &UserForLogin,
+	token_kind: SessionTokenKind,
+	client_ip: Option<SecureClientIp>,
+	headers: &HeaderMap,
+) -> Result<SessionForAuth> {
+	let client = client_info(client_ip.as_ref(), headers);
+
+	let ip = client_ip.map(|SecureClientIp(ip)| ip);
+	if !IpAllowlistBmc::check_ip(mm, user.id, None, ip, "login").await? {
+		let err = Error::LoginFailIpNotAllowed { user_id: user.id };
+		LoginHistoryBmc::record(
+			mm,
+			Some(user.id),
+			&user.username,
+			LoginHistoryKind::LoginFail,
+			Some(&client),
+			Some(json!({ "reason": err.as_ref() })),
+		)
+		.await?;
+		return Err(err);
+	}
+
+	let session = SessionBmc::create(
+		mm,
+		SessionForCreate {
+			token_kind: token_kind.as_str().to_string(),
+			user_id: user.id,
+			ip: client.ip.clone(),
+			user_agent: client.user_agent.clone(),
+		},
+	)
+	.await?;
+
+	LoginHistoryBmc::record(
+		mm,
+		Some(user.id),
+		&user.username,
+		LoginHistoryKind::LoginOk,
+		Some(&client),
+		Some(json!({ "session_id": session.id })),
+	)
+	.await?;
+
+	Ok(session)
+}
+
 async fn api_logoff_handler(
+	State(mm): State<ModelManager>,
 	cookies: Cookies,
+	ctx: Option<CtxW>,
 	Json(payload): Json<LogoffPayload>,
 ) -> Result<Json<Value>> {
 	debug!("{:<12} - api_logoff_handler", "HANDLER");
-	let should_logoff = payload.logoff;
+	let LogoffPayload { logoff, everywhere } = payload;
 
-	if should_logoff {
-		remove_token_cookie(&cookies)?;
+	if logoff {
+		if let Some(CtxW(ctx)) = ctx {
+			logoff_session(&mm, &ctx, everywhere).await?;
+		}
+		remove_token_cookie(&cookies);
+		remove_csrf_cookie(&cookies);
 	}
 
 	// Create the success body.
 	let body = Json(json!({
 		"result": {
-			"logged_off": should_logoff
+			"logged_off": logoff,
+			"everywhere": logoff && everywhere
+		}
+	}));
+
+	Ok(body)
+}
+
+/// The csrf token of the cookie session (a new one if none, e.g., after an
+/// OpenID Connect login), to send as the `X-CSRF-Token` header.
+async fn api_csrf_handler(cookies: Cookies, _ctx: CtxW) -> Result<Json<Value>> {
+	debug!("{:<12} - api_csrf_handler", "HANDLER");
+
+	let csrf_token = match cookies.get(CSRF_TOKEN) {
+		Some(cookie) => cookie.value().to_string(),
+		None => set_csrf_cookie(&cookies),
+	};
+
+	let body = Json(json!({
+		"result": {
+			"csrf_token": csrf_token
 		}
 	}));
 
 	Ok(body)
 }
 
+/// Revokes the session of the request, or (`everywhere`) all the sessions of
+/// the user, also rotating its `token_salt` so that no token of the user
+/// stays valid. Both are written to the `event` log and the login history.
+async fn logoff_session(
+	mm: &ModelManager,
+	ctx: &Ctx,
+	everywhere: bool,
+) -> Result<()> {
+	// Api keys have no session to log off.
+	let Some(session_id) = ctx.session_id() else {
+		return Ok(());
+	};
+
+	let (event_c, kind) = if everywhere {
+		UserBmc::rotate_token_salt(ctx, mm, ctx.user_id()).await?;
+		let revoked = SessionBmc::revoke_all(ctx, mm, false).await?;
+
+		let event_c = EventForCreate {
+			action: "LOGOFF_EVERYWHERE",
+			object: "user",
+			object_id: ctx.user_id(),
+			additional_info: Some(json!({
+				"session_id": session_id,
+				"revoked_sessions": revoked
+			})),
+		};
+		(event_c, LoginHistoryKind::LogoffEverywhere)
+	} else {
+		SessionBmc::revoke(ctx, mm, session_id).await?;
+
+		let event_c = EventForCreate {
+			action: "LOGOFF",
+			object: "session",
+			object_id: session_id,
+			additional_info: None,
+		};
+		(event_c, LoginHistoryKind::Logoff)
+	};
+	EventBmc::create(ctx, mm, event_c).await?;
+	LoginHistoryBmc::record_for_user(
+		ctx,
+		mm,
+		ctx.user_id(),
+		kind,
+		Some(json!({ "session_id": session_id })),
+	)
+	.await?;
+
+	Ok(())
+}
+
 #[derive(Debug, Deserialize)]
 struct LogoffPayload {
 	logoff: bool,
+	/// Also logs off all the other sessions of the user.
+	#[serde(default)]
+	everywhere: bool,
 }
 
 #[derive(Debug, Deserialize)]
-struct LoginPayload {
-	username: String,
-	pwd: String,
+pub(super) struct LoginPayload {
+	pub(super) username: String,
+	pub(super) pwd: String,
+	/// The new pwd, when the pwd expired.
+	pub(super) new_pwd: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginTotpPayload {
+	challenge: String,
+	/// A TOTP code of the authenticator app, or a recovery code.
+	code: String,
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::auth::token::{generate_web_token, validate_web_token};
+	use crate::core::_dev_utils;
+	use crate::core::model::event::EventFilter;
+	use crate::core::model::user::{UserForAuth, UserForCreate};
+	use anyhow::Result;
+	use serial_test::serial;
+
+	async fn fx_user(mm: &ModelManager, username: &str) -> Result<UserForAuth> {
+		let root_ctx = Ctx::root_ctx();
+		let user_id = UserBmc::create(
+			&root_ctx,
+			mm,
+			UserForCreate {
+				username: username.to_string(),
+				email: format!("{username}@example.org"),
+				pwd_clear: "welcome-01".to_string(),
+				assigned_role: "demo".to_string(),
+			},
+		)
+		.await?;
+
+		Ok(UserBmc::get(&root_ctx, mm, user_id).await?)
+	}
+
+	/// A cookie session of the user, and its web token.
+	async fn fx_session(
+		mm: &ModelManager,
+		user: &UserForAuth,
+	) -> Result<(SessionForAuth, Token)> {
+		let session = SessionBmc::create(
+			mm,
+			SessionForCreate {
+				token_kind: SessionTokenKind::Cookie.as_str().to_string(),
+				user_id: user.id,
+				ip: None,
+				user_agent: Some(user.username.clone()),
+			},
+		)
+		.await?;
+		let token =
+			generate_web_token(&session.token_id.to_string(), user.token_salt)?;
+
+		Ok((session, token))
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_logoff_session_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_user = fx_user(&mm, "fx-lo-user-01").await?;
+		let (current, current_token) = fx_session(&mm, &fx_user).await?;
+		let (other, other_token) = fx_session(&mm, &fx_user).await?;
+		let user_id = fx_user.id;
+		let ctx = Ctx::new(user_id)?.with_session_id(current.id);
+
+		// -- Exec
+		logoff_session(&mm, &ctx, false).await?;
+
+		// -- Check
+		let user: UserForAuth = UserBmc::get(&root_ctx, &mm, user_id).await?;
+		assert!(SessionBmc::first_active_by_token_id(&mm, current.token_id)
+			.await?
+			.is_none());
+		// Only the current session, the other one and its token still valid.
+		assert!(SessionBmc::first_active_by_token_id(&mm, other.token_id)
+			.await?
+			.is_some());
+		validate_web_token(&current_token, user.token_salt)?;
+		validate_web_token(&other_token, user.token_salt)?;
+
+		// -- Clean
+		UserBmc::delete(&root_ctx, &mm, user_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_logoff_session_everywhere_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_username = "fx-lo-user-02";
+		let fx_user = fx_user(&mm, fx_username).await?;
+		let (current, current_token) = fx_session(&mm, &fx_user).await?;
+		let (other, other_token) = fx_session(&mm, &fx_user).await?;
+		let user_id = fx_user.id;
+		let ctx = Ctx::new(user_id)?.with_session_id(current.id);
+		let fx_event_filter: EventFilter = serde_json::from_value(json!({
+			"username": fx_username,
+			"action": "LOGOFF_EVERYWHERE"
+		}))?;
+
+		// -- Exec
+		logoff_session(&mm, &ctx, true).await?;
+
+		// -- Check
+		for session in [&current, &other] {
+			let active =
+				SessionBmc::first_active_by_token_id(&mm, session.token_id).await?;
+			assert!(active.is_none(), "Should be revoked but was `{active:?}`");
+		}
+		// The rotated salt invalidates the tokens, revoked or not.
+		let user: UserForAuth = UserBmc::get(&root_ctx, &mm, user_id).await?;
+		for token in [&current_token, &other_token] {
+			let res = validate_web_token(token, user.token_salt);
+			assert!(res.is_err(), "Should have failed but was `{res:?}`");
+		}
+		let events =
+			EventBmc::list(&root_ctx, &mm, Some(vec![fx_event_filter]), None)
+				.await?;
+		assert_eq!(events.items.len(), 1);
+		assert_eq!(
+			events.items[0].additional_info,
+			Some(json!({"session_id": current.id, "revoked_sessions": 2}))
+		);
+
+		// -- Clean
+		UserBmc::delete(&root_ctx, &mm, user_id).await?;
+
+		Ok(())
+	}
 }
+
+// endregion: --- Tests
@@ -0,0 +1,345 @@
+use crate::auth::oidc::{oidc_client, IdClaims, OidcClient};
+use crate::core::ctx::Ctx;
+use crate::core::model::event::{EventBmc, EventForCreate};
+use crate::core::model::oidc_login::{OidcLogin, OidcLoginBmc};
+use crate::core::model::session::SessionTokenKind;
+use crate::core::model::user::{ExternalUserForCreate, UserBmc, UserForLogin};
+use crate::core::model::user_identity::UserIdentityBmc;
+use crate::core::model::user_totp::UserTotpBmc;
+use crate::core::model::ModelManager;
+use crate::web::mw_auth::CtxExtError;
+use crate::web::mw_csrf::set_csrf_cookie;
+use crate::web::routes_login::create_session;
+use crate::web::{set_token_cookie, Error, Result};
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::response::Redirect;
+use axum::routing::get;
+use axum::Router;
+use axum_client_ip::SecureClientIp;
+use serde::Deserialize;
+use serde_json::json;
+use time::Duration;
+use tower_cookies::cookie::SameSite;
+use tower_cookies::{Cookie, Cookies};
+use tracing::debug;
+
+/// The state of the login of the browser, so that the callback of another
+/// login (e.g., of an attacker's account) is rejected.
+const OIDC_STATE: &str = "oidc-state";
+
+/// No routes when no IdP is configured.
+pub fn routes(mm: ModelManager) -> Router {
+	if oidc_client().is_none() {
+		return Router::new();
+	}
+
+	Router::new()
+		.route("/api/oidc/login", get(api_oidc_login_handler))
+		.route("/api/oidc/callback", get(api_oidc_callback_handler))
+		.with_state(mm)
+}
+
+/// Redirects the browser to the IdP.
+async fn api_oidc_login_handler(
+	State(mm): State<ModelManager>,
+	cookies: Cookies,
+) -> Result<Redirect> {
+	debug!(" {:<12} - api_oidc_login_handler", "HANDLER");
+
+	let client = client()?;
+	let login_duration = Duration::seconds(client.config().LOGIN_DURATION_SEC);
+
+	let request = client.authorization_request().await?;
+	OidcLoginBmc::create(
+		&mm,
+		OidcLogin {
+			state: request.state.clone(),
+			nonce: request.nonce,
+			code_verifier: request.code_verifier,
+		},
+		login_duration,
+	)
+	.await?;
+
+	let mut cookie = Cookie::new(OIDC_STATE, request.state);
+	cookie.set_http_only(true);
+	cookie.set_path("/api/oidc");
+	cookie.set_secure(true);
+	// Sent on the (top level) redirect of the IdP back to the callback.
+	cookie.set_same_site(SameSite::Lax);
+	cookie.set_max_age(login_duration);
+	cookies.add(cookie);
+
+	Ok(Redirect::to(&request.url))
+}
+
+/// The redirect of the IdP: logs the user of the id token in, with the same
+/// `auth-token` cookie as the pwd login, and sends the browser to the
+/// `POST_LOGIN_URL`. The second factor is the IdP's own (no TOTP here).
+async fn api_oidc_callback_handler(
+	State(mm): State<ModelManager>,
+	cookies: Cookies,
+	client_ip: Option<SecureClientIp>,
+	headers: HeaderMap,
+	Query(params): Query<CallbackParams>,
+) -> Result<Redirect> {
+	debug!(" {:<12} - api_oidc_callback_handler", "HANDLER");
+
+	let client = client()?;
+	let login_duration = Duration::seconds(client.config().LOGIN_DURATION_SEC);
+
+	let browser_state = cookies.get(OIDC_STATE).map(|c| c.value().to_string());
+	let mut cookie = Cookie::from(OIDC_STATE);
+	cookie.set_path("/api/oidc");
+	cookies.remove(cookie);
+
+	// Taken even on an error of the IdP, so that the state cannot be reused.
+	let oidc_login = OidcLoginBmc::take(&mm, &params.state, login_duration)
+		.await?
+		.filter(|_| browser_state.as_deref() == Some(params.state.as_str()))
+		.ok_or(Error::OidcFailStateNotValid)?;
+
+	if let Some(error) = params.error {
+		return Err(Error::OidcFailIdp { error });
+	}
+	let code = params.code.ok_or(Error::OidcFailStateNotValid)?;
+
+	let claims = client
+		.exchange_code(&code, &oidc_login.code_verifier, &oidc_login.nonce)
+		.await?;
+
+	let user = oidc_user(&mm, client, claims).await?;
+	if user.is_service {
+		return Err(Error::LoginFailServiceAccount { user_id: user.id });
+	}
+
+	let session =
+		create_session(&mm, &user, SessionTokenKind::Cookie, client_ip, &headers)
+			.await?;
+	set_token_cookie(&cookies, &session.token_id.to_string(), user.token_salt)?;
+	// Read by the app from `/api/csrf`, after the redirect.
+	set_csrf_cookie(&cookies);
+
+	Ok(Redirect::to(&client.config().POST_LOGIN_URL))
+}
+
+/// The user linked to the IdP account, otherwise the user of its (verified)
+/// email or a new user with the `DEFAULT_ROLE`, which are then linked.
+/// A user with a TOTP is never linked by email, its second factor being
+/// skipped by the IdP logins.
+async fn oidc_user(
+	mm: &ModelManager,
+	client: &OidcClient,
+	claims: IdClaims,
+) -> Result<UserForLogin> {
+	let root_ctx = Ctx::root_ctx();
+
+	if let Some(user_id) =
+		UserIdentityBmc::first_user_id(mm, &claims.iss, &claims.sub).await?
+	{
+		return Ok(UserBmc::get(&root_ctx, mm, user_id).await?);
+	}
+
+	// An unverified email could be anyone's.
+	let email = claims
+		.email
+		.clone()
+		.filter(|_| claims.email_verified == Some(true));
+
+	let existing_user: Option<UserForLogin> = match &email {
+		Some(email) => UserBmc::first_by_email(&root_ctx, mm, email).await?,
+		None => None,
+	};
+	if let Some(user) = &existing_user {
+		if UserTotpBmc::is_enabled(mm, user.id).await? {
+			return Err(Error::OidcFailUserHasTotp { user_id: user.id });
+		}
+	}
+
+	let (user_id, provisioned) = match (existing_user, email) {
+		(Some(user), _) => (user.id, false),
+		(None, Some(email)) => {
+			let assigned_role = client.config().DEFAULT_ROLE.clone().ok_or(
+				Error::OidcFailUserNotFound {
+					subject: claims.sub.clone(),
+				},
+			)?;
+			let username = provisioned_username(
+				mm,
+				claims.preferred_username.as_deref(),
+				&email,
+			)
+			.await?;
+
+			let user_id = UserBmc::create_external(
+				&root_ctx,
+				mm,
+				ExternalUserForCreate {
+					username,
+					email,
+					assigned_role,
+				},
+			)
+			.await?;
+
+			(user_id, true)
+		}
+		(None, None) => {
+			return Err(Error::OidcFailUserNotFound {
+				subject: claims.sub.clone(),
+			})
+		}
+	};
+
+	UserIdentityBmc::link(mm, user_id, &claims.iss, &claims.sub).await?;
+
+	let ctx = Ctx::new(user_id)
+		.map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))?;
+	EventBmc::create(
+		&ctx,
+		mm,
+		EventForCreate {
+			action: "OIDC_LINK",
+			object: "user",
+			object_id: user_id,
+			additional_info: Some(json!({
+				"issuer": claims.iss,
+				"subject": claims.sub,
+				"provisioned": provisioned
+			})),
+		},
+	)
+	.await?;
+
+	Ok(UserBmc::get(&root_ctx, mm, user_id).await?)
+}
+
+/// The `preferred_username` of the IdP when free, the email otherwise.
+async fn provisioned_username(
+	mm: &ModelManager,
+	preferred_username: Option<&str>,
+	email: &str,
+) -> Result<String> {
+	if let Some(username) = preferred_username {
+		let taken: Option<UserForLogin> =
+			UserBmc::first_by_username(&Ctx::root_ctx(), mm, username).await?;
+		if taken.is_none() {
+			return Ok(username.to_string());
+		}
+	}
+
+	Ok(email.to_string())
+}
+
+/// Always some, the routes only exist with an IdP.
+fn client() -> Result<&'static OidcClient> {
+	oidc_client().ok_or(Error::OidcFailStateNotValid)
+}
+
+#[derive(Debug, Deserialize)]
+struct CallbackParams {
+	state: String,
+	code: Option<String>,
+	/// The error of the IdP (e.g., `access_denied`), instead of the code.
+	error: Option<String>,
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::auth::oidc::OidcConfig;
+	use crate::auth::totp;
+	use crate::core::_dev_utils;
+	use crate::core::model::user::UserForCreate;
+	use crate::utils::time::now_utc;
+	use anyhow::Result;
+	use data_encoding::BASE32_NOPAD;
+	use serial_test::serial;
+
+	const FX_ISSUER: &str = "https://idp.example.org";
+
+	fn fx_client() -> OidcClient {
+		OidcClient::new(OidcConfig {
+			ISSUER: FX_ISSUER.to_string(),
+			CLIENT_ID: "fx-client".to_string(),
+			CLIENT_SECRET: None,
+			REDIRECT_URL: "http://localhost:8080/api/oidc/callback".to_string(),
+			POST_LOGIN_URL: "/".to_string(),
+			LOGIN_DURATION_SEC: 300,
+			DEFAULT_ROLE: None,
+		})
+	}
+
+	async fn fx_user(mm: &ModelManager, username: &str) -> Result<i64> {
+		let user_id = UserBmc::create(
+			&Ctx::root_ctx(),
+			mm,
+			UserForCreate {
+				username: username.to_string(),
+				email: format!("{username}@example.org"),
+				pwd_clear: "welcome-01".to_string(),
+				assigned_role: "demo".to_string(),
+			},
+		)
+		.await?;
+
+		Ok(user_id)
+	}
+
+	/// The claims of the verified email of the user.
+	fn fx_claims(username: &str) -> Result<IdClaims> {
+		Ok(serde_json::from_value(json!({
+			"iss": FX_ISSUER,
+			"sub": format!("{username}-sub"),
+			"email": format!("{username}@example.org"),
+			"email_verified": true,
+		}))?)
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_oidc_user_err_email_link_totp() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let client = fx_client();
+		let totp_user_id = fx_user(&mm, "fx-oi-user-01").await?;
+		let user_id = fx_user(&mm, "fx-oi-user-02").await?;
+		let totp_ctx = Ctx::new(totp_user_id)?;
+		let enrollment = UserTotpBmc::begin_enrollment(&totp_ctx, &mm).await?;
+		let secret = BASE32_NOPAD.decode(enrollment.secret.as_bytes())?;
+		let code = totp::generate_code(&secret, now_utc());
+		UserTotpBmc::confirm_enrollment(&totp_ctx, &mm, &code).await?;
+
+		// -- Exec
+		let res_totp = oidc_user(&mm, &client, fx_claims("fx-oi-user-01")?).await;
+		let user = oidc_user(&mm, &client, fx_claims("fx-oi-user-02")?).await?;
+
+		// -- Check
+		assert!(
+			matches!(res_totp, Err(Error::OidcFailUserHasTotp { user_id }) if user_id == totp_user_id),
+			"Should have matched `Err(Error::OidcFailUserHasTotp)` but was `{res_totp:?}`"
+		);
+		let totp_identity =
+			UserIdentityBmc::first_user_id(&mm, FX_ISSUER, "fx-oi-user-01-sub")
+				.await?;
+		assert!(totp_identity.is_none());
+		// Without a TOTP, linked by the email.
+		assert_eq!(user.id, user_id);
+		let identity =
+			UserIdentityBmc::first_user_id(&mm, FX_ISSUER, "fx-oi-user-02-sub")
+				.await?;
+		assert_eq!(identity, Some(user_id));
+
+		// -- Clean
+		UserBmc::delete(&root_ctx, &mm, totp_user_id).await?;
+		UserBmc::delete(&root_ctx, &mm, user_id).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,190 @@
+use crate::auth::token::{
+	generate_pwd_reset_token, validate_pwd_reset_token, Token,
+};
+use crate::core::ctx::Ctx;
+use crate::core::model::email_outbox::{EmailForCreate, EmailOutboxBmc};
+use crate::core::model::event::{EventBmc, EventForCreate};
+use crate::core::model::login_attempt::{AttemptScope, LoginAttemptBmc, Throttle};
+use crate::core::model::password_reset::PasswordResetBmc;
+use crate::core::model::session::SessionBmc;
+use crate::core::model::user::{UserBmc, UserForAuth, UserForLogin};
+use crate::core::model::ModelManager;
+use crate::web::mw_auth::CtxExtError;
+use crate::web::{attempt_ip_key, client_info, Error, Result};
+use crate::web_config;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::routing::post;
+use axum::{Json, Router};
+use axum_client_ip::SecureClientIp;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::debug;
+use uuid::Uuid;
+
+pub fn routes(mm: ModelManager) -> Router {
+	Router::new()
+		.route(
+			"/api/request_password_reset",
+			post(api_request_password_reset_handler),
+		)
+		.route(
+			"/api/confirm_password_reset",
+			post(api_confirm_password_reset_handler),
+		)
+		.with_state(mm)
+}
+
+/// Queues the reset email of the user of the email, if any. The response is
+/// the same either way, so that it does not tell which emails exist. The
+/// requests are throttled per email and per client ip, as the logins.
+async fn api_request_password_reset_handler(
+	State(mm): State<ModelManager>,
+	client_ip: Option<SecureClientIp>,
+	Json(payload): Json<RequestPwdResetPayload>,
+) -> Result<Json<Value>> {
+	debug!("{:<12} - api_request_password_reset_handler", "HANDLER");
+
+	let throttle = Throttle::from_config();
+	let keys = [
+		(AttemptScope::PwdResetEmail, payload.email.to_lowercase()),
+		(AttemptScope::PwdResetIp, attempt_ip_key(client_ip.as_ref())),
+	];
+	if let Some(retry_after) =
+		LoginAttemptBmc::retry_after(&mm, &keys, &throttle).await?
+	{
+		return Err(Error::PwdResetFailThrottled {
+			retry_after_sec: retry_after.whole_seconds().max(1),
+		});
+	}
+	for (scope, key) in &keys {
+		LoginAttemptBmc::record_failure(&mm, *scope, key, &throttle).await?;
+	}
+
+	let user: Option<UserForLogin> =
+		UserBmc::first_by_email(&Ctx::root_ctx(), &mm, &payload.email).await?;
+
+	match user {
+		// Service accounts have no pwd to reset.
+		Some(user) if !user.is_service => {
+			let reset = PasswordResetBmc::create(&mm, user.id).await?;
+			let token = generate_pwd_reset_token(
+				&reset.token_id.to_string(),
+				user.token_salt,
+			)?;
+
+			EmailOutboxBmc::enqueue(&mm, pwd_reset_email(&user, &token)).await?;
+		}
+		_ => debug!("{:<12} - no user to reset", "PWD_RESET"),
+	}
+
+	// Create the success body.
+	let body = Json(json!({
+		"result": {
+			"success": true
+		}
+	}));
+
+	Ok(body)
+}
+
+/// Sets the new pwd, consuming the reset. All the sessions (and tokens,
+/// other reset tokens included) of the user end with the `token_salt`
+/// rotation.
+async fn api_confirm_password_reset_handler(
+	State(mm): State<ModelManager>,
+	client_ip: Option<SecureClientIp>,
+	headers: HeaderMap,
+	Json(payload): Json<ConfirmPwdResetPayload>,
+) -> Result<Json<Value>> {
+	debug!("{:<12} - api_confirm_password_reset_handler", "HANDLER");
+	let ConfirmPwdResetPayload {
+		token,
+		pwd: pwd_clear,
+	} = payload;
+
+	let token: Token = token
+		.parse()
+		.map_err(|_| Error::PwdResetFailTokenNotFound)?;
+	let token_id: Uuid = token
+		.ident
+		.parse()
+		.map_err(|_| Error::PwdResetFailTokenNotFound)?;
+
+	let reset = PasswordResetBmc::first_unused_by_token_id(&mm, token_id)
+		.await?
+		.ok_or(Error::PwdResetFailTokenNotFound)?;
+	let user_id = reset.user_id;
+
+	let user: UserForAuth = UserBmc::get(&Ctx::root_ctx(), &mm, user_id).await?;
+	validate_pwd_reset_token(&token, user.token_salt)
+		.map_err(|_| Error::PwdResetFailTokenNotValid { user_id })?;
+
+	// Before consuming the token, so that a rejected pwd can be retried.
+	UserBmc::validate_new_pwd(&Ctx::root_ctx(), &mm, user_id, &pwd_clear).await?;
+
+	PasswordResetBmc::consume(&mm, reset.id)
+		.await
+		.map_err(|_| Error::PwdResetFailTokenNotValid { user_id })?;
+
+	let ctx = Ctx::new(user_id)
+		.map_err(|ex| CtxExtError::CtxCreateFail(ex.to_string()))?
+		.with_client(client_info(client_ip.as_ref(), &headers));
+
+	UserBmc::update_pwd(&ctx, &mm, user_id, &pwd_clear).await?;
+	UserBmc::rotate_token_salt(&ctx, &mm, user_id).await?;
+	let revoked = SessionBmc::revoke_all(&ctx, &mm, false).await?;
+	LoginAttemptBmc::clear(&mm, AttemptScope::Username, &user.username).await?;
+
+	EventBmc::create(
+		&ctx,
+		&mm,
+		EventForCreate {
+			action: "PWD_RESET",
+			object: "user",
+			object_id: user_id,
+			additional_info: Some(json!({
+				"password_reset_id": reset.id,
+				"revoked_sessions": revoked
+			})),
+		},
+	)
+	.await?;
+
+	// Create the success body.
+	let body = Json(json!({
+		"result": {
+			"success": true
+		}
+	}));
+
+	Ok(body)
+}
+
+fn pwd_reset_email(user: &UserForLogin, token: &Token) -> EmailForCreate {
+	let link = format!("{}?token={token}", web_config().PWD_RESET_URL);
+
+	EmailForCreate {
+		recipient: user.email.clone(),
+		subject: "Password reset".to_string(),
+		body: format!(
+			"Hello {},\n\n\
+			A password reset was requested for your account. To choose a new \
+			password, open:\n\n{link}\n\n\
+			The link can be used once and expires on {}. If you did not request \
+			it, ignore this email.\n",
+			user.username, token.exp
+		),
+	}
+}
+
+#[derive(Debug, Deserialize)]
+struct RequestPwdResetPayload {
+	email: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfirmPwdResetPayload {
+	token: String,
+	pwd: String,
+}